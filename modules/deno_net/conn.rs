@@ -0,0 +1,659 @@
+//! Sockets behind Deno.connect, Deno.listen and their TLS variants. Connections and
+//! listeners are resources with numeric ids, shared with the UDP sockets of `udp`.
+//! Their operations run on the smol executor, and JS takes the results with
+//! `net.poll`.
+//!
+//! Errors are thrown as "Name: message", where Name is the class of `Deno.errors`
+//! the JS side turns them into.
+
+use crate::tls::{self, TlsOptions};
+use crate::udp::Datagram;
+use futures_io::{AsyncRead, AsyncWrite};
+use futures_util::io::{AsyncReadExt, AsyncWriteExt, ReadHalf, WriteHalf};
+use once_cell::sync::Lazy;
+use rquickjs::{Ctx, TypedArray, Value};
+use smol::lock::Mutex as AsyncMutex;
+use smol::net::{TcpListener, TcpStream};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, mpsc};
+use std::task::{Context, Poll};
+use utils::{add_internal_function, poll_receiver};
+
+#[cfg(unix)]
+use smol::net::unix::{UnixListener, UnixStream};
+
+// Largest read done at once, whatever the size of the buffer
+const MAX_READ_SIZE: usize = 64 * 1024;
+
+pub(crate) const BAD_RESOURCE: &str = "BadResource: Bad resource ID";
+
+enum Stream {
+    Tcp(Box<tls::IoStream>),
+    #[cfg(unix)]
+    Unix(UnixStream),
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match &mut *self {
+            Stream::Tcp(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match &mut *self {
+            Stream::Tcp(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match &mut *self {
+            Stream::Tcp(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match &mut *self {
+            Stream::Tcp(s) => Pin::new(s).poll_close(cx),
+            #[cfg(unix)]
+            Stream::Unix(s) => Pin::new(s).poll_close(cx),
+        }
+    }
+}
+
+type Reader = Arc<AsyncMutex<ReadHalf<Stream>>>;
+type Writer = Arc<AsyncMutex<WriteHalf<Stream>>>;
+
+struct Connection {
+    reader: Reader,
+    writer: Writer,
+    // The TCP socket, for socket options. None for Unix sockets.
+    tcp: Option<TcpStream>,
+    // Whether this is TCP without TLS, which can be upgraded
+    plain: bool,
+    // Dropped when the connection is closed, which interrupts its operations
+    _closed: smol::channel::Sender<()>,
+    closing: smol::channel::Receiver<()>,
+}
+
+enum Listener {
+    Tcp {
+        listener: TcpListener,
+        acceptor: Option<tls::TlsAcceptor>,
+    },
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+struct ListenerResource {
+    listener: Arc<Listener>,
+    _closed: smol::channel::Sender<()>,
+    closing: smol::channel::Receiver<()>,
+}
+
+/// Result of an operation, handed to JS by `net.poll`.
+pub(crate) enum Output {
+    // JSON, like the id and the addresses of a new connection
+    Json(String),
+    // Bytes read, or None at the end of the stream
+    Read(Option<Vec<u8>>),
+    // A datagram and the JSON address of its sender
+    Datagram(Vec<u8>, String),
+    Written(usize),
+    Done,
+}
+
+pub(crate) type OpResult = Result<Output, String>;
+
+#[derive(Default)]
+pub(crate) struct Resources {
+    next_id: u32,
+    connections: HashMap<u32, Connection>,
+    listeners: HashMap<u32, ListenerResource>,
+    pub(crate) datagrams: HashMap<u32, Datagram>,
+    ops: HashMap<u32, mpsc::Receiver<OpResult>>,
+}
+
+impl Resources {
+    pub(crate) fn next_id(&mut self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+pub(crate) static RESOURCES: Lazy<Mutex<Resources>> =
+    Lazy::new(|| Mutex::new(Resources::default()));
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].net = {};")?;
+
+    add_internal_function!(ctx, "net.connect", |options: String| {
+        spawn_op(connect(options))
+    });
+
+    add_internal_function!(ctx, "net.connectTls", |options: String| {
+        spawn_op(connect_tls(options))
+    });
+
+    add_internal_function!(ctx, "net.startTls", |rid: u32, options: String| {
+        start_tls(rid, options)
+    });
+
+    add_internal_function!(ctx, "net.listen", |ctx: Ctx<'_>, options: String| {
+        listen(&options, false).map_err(|e| throw(&ctx, &e))
+    });
+
+    add_internal_function!(ctx, "net.listenTls", |ctx: Ctx<'_>, options: String| {
+        listen(&options, true).map_err(|e| throw(&ctx, &e))
+    });
+
+    add_internal_function!(ctx, "net.accept", |rid: u32| { accept(rid) });
+
+    add_internal_function!(ctx, "net.read", |rid: u32, size: usize| { read(rid, size) });
+
+    add_internal_function!(ctx, "net.write", |rid: u32, data: TypedArray<'_, u8>| {
+        write(rid, data.as_bytes().unwrap_or_default().to_vec())
+    });
+
+    add_internal_function!(ctx, "net.closeWrite", |rid: u32| { close_write(rid) });
+
+    add_internal_function!(ctx, "net.close", |rid: u32| -> bool {
+        let mut resources = RESOURCES.lock().unwrap();
+        resources.connections.remove(&rid).is_some()
+            || resources.listeners.remove(&rid).is_some()
+            || resources.datagrams.remove(&rid).is_some()
+    });
+
+    add_internal_function!(
+        ctx,
+        "net.setNoDelay",
+        |ctx: Ctx<'_>, rid: u32, enable: bool| {
+            with_tcp(rid, |tcp| tcp.set_nodelay(enable)).map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    add_internal_function!(
+        ctx,
+        "net.setKeepAlive",
+        |ctx: Ctx<'_>, rid: u32, enable: bool| {
+            with_tcp(rid, |tcp| socket2::SockRef::from(tcp).set_keepalive(enable))
+                .map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    add_internal_function!(ctx, "net.poll", net_poll);
+
+    // The operation still runs, but its result is dropped
+    add_internal_function!(ctx, "net.cancel", |id: u32| {
+        RESOURCES.lock().unwrap().ops.remove(&id);
+    });
+
+    Ok(())
+}
+
+pub(crate) fn throw(ctx: &Ctx<'_>, message: &str) -> rquickjs::Error {
+    rquickjs::Exception::throw_message(ctx, message)
+}
+
+/// The `Deno.errors` class matching an I/O error.
+fn error_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::NotFound => "NotFound",
+        ErrorKind::PermissionDenied => "PermissionDenied",
+        ErrorKind::ConnectionRefused => "ConnectionRefused",
+        ErrorKind::ConnectionReset => "ConnectionReset",
+        ErrorKind::ConnectionAborted => "ConnectionAborted",
+        ErrorKind::NotConnected => "NotConnected",
+        ErrorKind::AddrInUse => "AddrInUse",
+        ErrorKind::AddrNotAvailable => "AddrNotAvailable",
+        ErrorKind::BrokenPipe => "BrokenPipe",
+        ErrorKind::AlreadyExists => "AlreadyExists",
+        ErrorKind::InvalidData => "InvalidData",
+        ErrorKind::TimedOut => "TimedOut",
+        ErrorKind::Interrupted => "Interrupted",
+        ErrorKind::WriteZero => "WriteZero",
+        ErrorKind::UnexpectedEof => "UnexpectedEof",
+        ErrorKind::Unsupported => "NotSupported",
+        _ => "Error",
+    }
+}
+
+pub(crate) fn io_error(error: std::io::Error) -> String {
+    format!("{}: {}", error_name(error.kind()), error)
+}
+
+/// Run `op` on the executor. Returns the id to poll its result with.
+pub(crate) fn spawn_op(op: impl Future<Output = OpResult> + Send + 'static) -> u32 {
+    let (sender, receiver) = mpsc::channel();
+    smol::spawn(async move {
+        let _ = sender.send(op.await);
+    })
+    .detach();
+
+    let mut resources = RESOURCES.lock().unwrap();
+    let id = resources.next_id();
+    resources.ops.insert(id, receiver);
+    id
+}
+
+/// Run `future` until the resource behind `closing` is closed.
+pub(crate) async fn interruptible<T>(
+    closing: &smol::channel::Receiver<()>,
+    future: impl Future<Output = std::io::Result<T>>,
+) -> Result<T, String> {
+    let interrupted = async {
+        let _ = closing.recv().await;
+        Err("Interrupted: Operation canceled".to_string())
+    };
+    smol::future::or(async { future.await.map_err(io_error) }, interrupted).await
+}
+
+/// Take the result of operation `id`. Returns undefined while it runs, and throws
+/// if it failed.
+fn net_poll<'js>(ctx: Ctx<'js>, id: u32) -> rquickjs::Result<Value<'js>> {
+    let result = {
+        let mut resources = RESOURCES.lock().unwrap();
+        let Some(receiver) = resources.ops.get(&id) else {
+            return Err(throw(&ctx, BAD_RESOURCE));
+        };
+        match poll_receiver(&ctx, receiver) {
+            Some(result) => {
+                resources.ops.remove(&id);
+                result
+            }
+            None => return Ok(Value::new_undefined(ctx)),
+        }
+    };
+
+    match result.map_err(|e| throw(&ctx, &e))? {
+        Output::Json(json) => rquickjs::String::from_str(ctx, &json).map(Value::from),
+        Output::Read(Some(bytes)) => {
+            TypedArray::<u8>::new(ctx, bytes).map(|array| array.into_value())
+        }
+        Output::Datagram(bytes, addr) => {
+            let pair = rquickjs::Array::new(ctx.clone())?;
+            pair.set(0, TypedArray::<u8>::new(ctx, bytes)?)?;
+            pair.set(1, addr)?;
+            Ok(pair.into_value())
+        }
+        Output::Read(None) | Output::Done => Ok(Value::new_null(ctx)),
+        Output::Written(n) => Ok(Value::new_number(ctx, n as f64)),
+    }
+}
+
+pub(crate) fn parse_options(options: &str) -> serde_json::Value {
+    serde_json::from_str(options).unwrap_or_default()
+}
+
+pub(crate) fn string_option(options: &serde_json::Value, name: &str) -> Option<String> {
+    options.get(name)?.as_str().map(str::to_string)
+}
+
+pub(crate) fn port_option(options: &serde_json::Value) -> Result<u16, String> {
+    let port = options.get("port").and_then(|port| port.as_u64());
+    port.and_then(|port| u16::try_from(port).ok())
+        .ok_or_else(|| "TypeError: Invalid port".to_string())
+}
+
+fn string_list(options: &serde_json::Value, name: &str) -> Vec<String> {
+    let list = options.get(name).and_then(|list| list.as_array());
+    list.into_iter()
+        .flatten()
+        .filter_map(|item| item.as_str().map(str::to_string))
+        .collect()
+}
+
+fn tls_options(options: &serde_json::Value) -> TlsOptions {
+    TlsOptions {
+        ca_certs: string_list(options, "caCerts"),
+        cert: string_option(options, "cert"),
+        key: string_option(options, "key"),
+        alpn_protocols: string_list(options, "alpnProtocols"),
+    }
+}
+
+pub(crate) fn socket_addr(
+    transport: &str,
+    addr: std::io::Result<std::net::SocketAddr>,
+) -> serde_json::Value {
+    match addr {
+        Ok(addr) => serde_json::json!({
+            "transport": transport,
+            "hostname": addr.ip().to_string(),
+            "port": addr.port(),
+        }),
+        Err(_) => serde_json::Value::Null,
+    }
+}
+
+#[cfg(unix)]
+fn unix_addr(addr: std::io::Result<std::os::unix::net::SocketAddr>) -> serde_json::Value {
+    let path = addr.ok().and_then(|addr| {
+        addr.as_pathname()
+            .map(|path| path.to_string_lossy().into_owned())
+    });
+    serde_json::json!({ "transport": "unix", "path": path })
+}
+
+/// Add a connection to the resources. Returns JSON with its id, its addresses and
+/// the protocol negotiated with ALPN.
+fn register(stream: Stream) -> String {
+    let (local_addr, remote_addr, tcp, alpn_protocol, plain) = match &stream {
+        Stream::Tcp(io) => {
+            let tcp = io.tcp_stream();
+            let alpn_protocol = io
+                .alpn_protocol()
+                .map(|protocol| String::from_utf8_lossy(&protocol).into_owned());
+            (
+                socket_addr("tcp", tcp.local_addr()),
+                socket_addr("tcp", tcp.peer_addr()),
+                Some(tcp.clone()),
+                alpn_protocol,
+                matches!(**io, tls::IoStream::Plain(_)),
+            )
+        }
+        #[cfg(unix)]
+        Stream::Unix(unix) => (
+            unix_addr(unix.local_addr()),
+            unix_addr(unix.peer_addr()),
+            None,
+            None,
+            false,
+        ),
+    };
+
+    let (reader, writer) = stream.split();
+    let (closed, closing) = smol::channel::bounded(1);
+    let mut resources = RESOURCES.lock().unwrap();
+    let rid = resources.next_id();
+    resources.connections.insert(
+        rid,
+        Connection {
+            reader: Arc::new(AsyncMutex::new(reader)),
+            writer: Arc::new(AsyncMutex::new(writer)),
+            tcp,
+            plain,
+            _closed: closed,
+            closing,
+        },
+    );
+
+    serde_json::json!({
+        "rid": rid,
+        "localAddr": local_addr,
+        "remoteAddr": remote_addr,
+        "alpnProtocol": alpn_protocol,
+    })
+    .to_string()
+}
+
+/// Connect with JSON options `{transport, hostname, port}` or `{transport: "unix",
+/// path}`.
+async fn connect(options: String) -> OpResult {
+    let options = parse_options(&options);
+    let stream = match options.get("transport").and_then(|t| t.as_str()) {
+        Some("unix") => connect_unix(string_option(&options, "path").unwrap_or_default()).await?,
+        _ => {
+            let hostname = string_option(&options, "hostname").unwrap_or("127.0.0.1".into());
+            let tcp = crate::tcp::connect(&hostname, port_option(&options)?)
+                .await
+                .map_err(io_error)?;
+            Stream::Tcp(Box::new(tls::IoStream::Plain(tcp)))
+        }
+    };
+    Ok(Output::Json(register(stream)))
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: String) -> Result<Stream, String> {
+    let stream = UnixStream::connect(path).await.map_err(io_error)?;
+    Ok(Stream::Unix(stream))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: String) -> Result<Stream, String> {
+    Err(UNIX_UNSUPPORTED.to_string())
+}
+
+#[cfg(not(unix))]
+const UNIX_UNSUPPORTED: &str = "NotSupported: Unix sockets are not supported on this platform";
+
+/// Connect and run the TLS handshake, with JSON options `{hostname, port, caCerts,
+/// cert, key, alpnProtocols}`.
+async fn connect_tls(options: String) -> OpResult {
+    let options = parse_options(&options);
+    let hostname = string_option(&options, "hostname").unwrap_or("127.0.0.1".into());
+    let port = port_option(&options)?;
+    let config = tls::TlsConfig::new(&tls_options(&options)).map_err(tls_error)?;
+
+    let tcp = crate::tcp::connect(&hostname, port)
+        .await
+        .map_err(io_error)?;
+    handshake(&config, tcp, &hostname).await
+}
+
+fn tls_error(message: String) -> String {
+    format!("InvalidData: {}", message)
+}
+
+async fn handshake(config: &tls::TlsConfig, tcp: TcpStream, hostname: &str) -> OpResult {
+    // IPv6 addresses may be in brackets, but not in server names
+    let server_name = hostname.trim_start_matches('[').trim_end_matches(']');
+    let io = tls::connect_tls(config, tcp, server_name)
+        .await
+        .map_err(tls_error)?;
+    Ok(Output::Json(register(Stream::Tcp(Box::new(io)))))
+}
+
+/// Upgrade plain TCP connection `rid` to TLS, with JSON options `{hostname,
+/// caCerts, alpnProtocols}`. The connection is replaced by the one returned.
+fn start_tls(rid: u32, options: String) -> u32 {
+    let stream = take_plain(rid);
+    spawn_op(async move {
+        let tcp = stream?;
+        let options = parse_options(&options);
+        let hostname = string_option(&options, "hostname").unwrap_or("127.0.0.1".into());
+        let config = tls::TlsConfig::new(&tls_options(&options)).map_err(tls_error)?;
+        handshake(&config, tcp, &hostname).await
+    })
+}
+
+/// Remove plain TCP connection `rid` to upgrade it. A connection that cannot be
+/// upgraded is left in place.
+fn take_plain(rid: u32) -> Result<TcpStream, String> {
+    let mut resources = RESOURCES.lock().unwrap();
+    let connection = resources.connections.get(&rid).ok_or(BAD_RESOURCE)?;
+    if !connection.plain {
+        return Err("InvalidData: Only plain TCP connections can be upgraded".to_string());
+    }
+    // Operations in flight hold the halves
+    if Arc::strong_count(&connection.reader) > 1 || Arc::strong_count(&connection.writer) > 1 {
+        return Err("Busy: The connection has pending operations".to_string());
+    }
+
+    // The halves own nothing but the socket, which the clone keeps open
+    let connection = resources.connections.remove(&rid).ok_or(BAD_RESOURCE)?;
+    connection.tcp.ok_or(BAD_RESOURCE.to_string())
+}
+
+/// Listen with JSON options `{transport, hostname, port}` or `{transport: "unix",
+/// path}`, adding `{cert, key, alpnProtocols}` for TLS. Returns JSON with the id of
+/// the listener and its address.
+fn listen(options: &str, tls: bool) -> Result<String, String> {
+    let options = parse_options(options);
+    let (listener, addr) = match options.get("transport").and_then(|t| t.as_str()) {
+        Some("unix") if !tls => listen_unix(string_option(&options, "path").unwrap_or_default())?,
+        _ => {
+            let hostname = string_option(&options, "hostname").unwrap_or("0.0.0.0".into());
+            let port = port_option(&options)?;
+            let acceptor = if tls {
+                let cert = string_option(&options, "cert");
+                let key = string_option(&options, "key");
+                let (Some(cert), Some(key)) = (cert, key) else {
+                    return Err("TypeError: Both cert and key are required".to_string());
+                };
+                let protocols = string_list(&options, "alpnProtocols");
+                Some(tls::TlsAcceptor::new(&cert, &key, &protocols).map_err(tls_error)?)
+            } else {
+                None
+            };
+
+            let listener = std::net::TcpListener::bind((hostname.as_str(), port))
+                .and_then(TcpListener::try_from)
+                .map_err(io_error)?;
+            let addr = socket_addr("tcp", listener.local_addr());
+            (Listener::Tcp { listener, acceptor }, addr)
+        }
+    };
+
+    let (closed, closing) = smol::channel::bounded(1);
+    let mut resources = RESOURCES.lock().unwrap();
+    let rid = resources.next_id();
+    resources.listeners.insert(
+        rid,
+        ListenerResource {
+            listener: Arc::new(listener),
+            _closed: closed,
+            closing,
+        },
+    );
+    Ok(serde_json::json!({ "rid": rid, "addr": addr }).to_string())
+}
+
+#[cfg(unix)]
+fn listen_unix(path: String) -> Result<(Listener, serde_json::Value), String> {
+    let listener = UnixListener::bind(path).map_err(io_error)?;
+    let addr = unix_addr(listener.local_addr());
+    Ok((Listener::Unix(listener), addr))
+}
+
+#[cfg(not(unix))]
+fn listen_unix(_path: String) -> Result<(Listener, serde_json::Value), String> {
+    Err(UNIX_UNSUPPORTED.to_string())
+}
+
+/// Wait for the next connection to listener `rid`. Clients failing the TLS
+/// handshake are skipped.
+fn accept(rid: u32) -> u32 {
+    let listener = {
+        let resources = RESOURCES.lock().unwrap();
+        let listener = resources.listeners.get(&rid);
+        listener.map(|listener| (listener.listener.clone(), listener.closing.clone()))
+    };
+
+    spawn_op(async move {
+        let (listener, closing) = listener.ok_or(BAD_RESOURCE.to_string())?;
+        loop {
+            let stream = match &*listener {
+                Listener::Tcp { listener, acceptor } => {
+                    let (tcp, _) = interruptible(&closing, listener.accept()).await?;
+                    match acceptor {
+                        Some(acceptor) => match tls::accept_tls(acceptor, tcp).await {
+                            Ok(io) => Stream::Tcp(Box::new(io)),
+                            Err(_) => continue,
+                        },
+                        None => Stream::Tcp(Box::new(tls::IoStream::Plain(tcp))),
+                    }
+                }
+                #[cfg(unix)]
+                Listener::Unix(listener) => {
+                    let (unix, _) = interruptible(&closing, listener.accept()).await?;
+                    Stream::Unix(unix)
+                }
+            };
+            return Ok(Output::Json(register(stream)));
+        }
+    })
+}
+
+/// The read half of connection `rid`, and the signal of its closing.
+fn reader(rid: u32) -> Result<(Reader, smol::channel::Receiver<()>), String> {
+    let resources = RESOURCES.lock().unwrap();
+    let connection = resources.connections.get(&rid).ok_or(BAD_RESOURCE)?;
+    Ok((connection.reader.clone(), connection.closing.clone()))
+}
+
+/// The write half of connection `rid`, and the signal of its closing.
+fn writer(rid: u32) -> Result<(Writer, smol::channel::Receiver<()>), String> {
+    let resources = RESOURCES.lock().unwrap();
+    let connection = resources.connections.get(&rid).ok_or(BAD_RESOURCE)?;
+    Ok((connection.writer.clone(), connection.closing.clone()))
+}
+
+/// Read up to `size` bytes from connection `rid`.
+fn read(rid: u32, size: usize) -> u32 {
+    let reader = reader(rid);
+    spawn_op(async move {
+        let (reader, closing) = reader?;
+        let mut buffer = vec![0; size.min(MAX_READ_SIZE)];
+        if buffer.is_empty() {
+            return Ok(Output::Read(Some(buffer)));
+        }
+        let n = interruptible(&closing, async {
+            reader.lock().await.read(&mut buffer).await
+        })
+        .await?;
+        if n == 0 {
+            return Ok(Output::Read(None));
+        }
+        buffer.truncate(n);
+        Ok(Output::Read(Some(buffer)))
+    })
+}
+
+/// Write `data` to connection `rid`. The result is the number of bytes written.
+fn write(rid: u32, data: Vec<u8>) -> u32 {
+    let writer = writer(rid);
+    spawn_op(async move {
+        let (writer, closing) = writer?;
+        let n = interruptible(&closing, async {
+            let mut writer = writer.lock().await;
+            let n = writer.write(&data).await?;
+            // TLS streams buffer records until flushed
+            writer.flush().await?;
+            Ok(n)
+        })
+        .await?;
+        Ok(Output::Written(n))
+    })
+}
+
+/// Shut down the writing side of connection `rid`.
+fn close_write(rid: u32) -> u32 {
+    let writer = writer(rid);
+    spawn_op(async move {
+        let (writer, closing) = writer?;
+        interruptible(&closing, async { writer.lock().await.close().await }).await?;
+        Ok(Output::Done)
+    })
+}
+
+/// Run `f` on the socket of TCP connection `rid`.
+fn with_tcp(rid: u32, f: impl FnOnce(&TcpStream) -> std::io::Result<()>) -> Result<(), String> {
+    let resources = RESOURCES.lock().unwrap();
+    let connection = resources.connections.get(&rid).ok_or(BAD_RESOURCE)?;
+    let tcp = connection
+        .tcp
+        .as_ref()
+        .ok_or("NotSupported: Not a TCP connection")?;
+    f(tcp).map_err(io_error)
+}
@@ -0,0 +1,510 @@
+// Copyright 2018-2025 the Deno authors. MIT license.
+// Register socket APIs under __mdeno__.net
+// https://docs.deno.com/api/deno/network
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+// Size of the chunks read for Conn.readable
+const READ_CHUNK_SIZE = 16 * 1024;
+
+// Largest datagram received when no buffer is given
+const MAX_DATAGRAM_SIZE = 64 * 1024;
+
+// https://docs.deno.com/api/deno/~/Deno.errors
+const errors = {};
+for (
+  const name of [
+    "NotFound",
+    "PermissionDenied",
+    "ConnectionRefused",
+    "ConnectionReset",
+    "ConnectionAborted",
+    "NotConnected",
+    "AddrInUse",
+    "AddrNotAvailable",
+    "BrokenPipe",
+    "AlreadyExists",
+    "InvalidData",
+    "TimedOut",
+    "Interrupted",
+    "WriteZero",
+    "UnexpectedEof",
+    "BadResource",
+    "Busy",
+    "NotSupported",
+  ]
+) {
+  errors[name] = class extends Error {
+    constructor(message, options) {
+      super(message, options);
+      this.name = name;
+    }
+  };
+  Object.defineProperty(errors[name], "name", { value: name });
+}
+
+// Native errors come as "Name: message", Name being the class to throw
+function netError(error) {
+  const match = /^(\w+): ([^]*)$/.exec(error?.message ?? "");
+  if (match === null) return error;
+  const [, name, message] = match;
+  if (name === "TypeError") return new TypeError(message);
+  if (name === "Error") return new Error(message);
+  return errors[name] ? new errors[name](message) : error;
+}
+
+function call(f) {
+  try {
+    return f();
+  } catch (error) {
+    throw netError(error);
+  }
+}
+
+// Wait for the result of operation `id`, or until `signal` aborts
+async function wait(id, signal = undefined) {
+  while (true) {
+    if (signal?.aborted) {
+      __internal.net.cancel(id);
+      throw signal.reason;
+    }
+
+    // Waits briefly for the result when there is nothing else to run
+    const result = call(() => __internal.net.poll(id));
+    if (result !== undefined) return result;
+
+    // Yield to event loop
+    await Promise.resolve();
+  }
+}
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+let connRid;
+
+// https://docs.deno.com/api/deno/~/Deno.Conn
+class Conn {
+  #rid;
+  #localAddr;
+  #remoteAddr;
+  #readable = null;
+  #writable = null;
+
+  constructor(key = null, info = undefined) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#rid = info.rid;
+    this.#localAddr = info.localAddr;
+    this.#remoteAddr = info.remoteAddr;
+  }
+
+  get localAddr() {
+    return this.#localAddr;
+  }
+
+  get remoteAddr() {
+    return this.#remoteAddr;
+  }
+
+  // Resolves with the number of bytes read into `buffer`, or null at the end
+  // of the stream
+  async read(buffer) {
+    const chunk = await this.#readChunk(buffer.byteLength);
+    if (chunk === null) return null;
+    buffer.set(chunk);
+    return chunk.byteLength;
+  }
+
+  // Resolves with the number of bytes written, which may be less than the
+  // length of `data`
+  async write(data) {
+    if (!(data instanceof Uint8Array)) {
+      throw new TypeError("Data must be a Uint8Array");
+    }
+    return await wait(__internal.net.write(this.#rid, data));
+  }
+
+  // Shut down the writing side, the peer then reads the end of the stream
+  async closeWrite() {
+    await wait(__internal.net.closeWrite(this.#rid));
+  }
+
+  close() {
+    if (!__internal.net.close(this.#rid)) {
+      throw new errors.BadResource("Bad resource ID");
+    }
+  }
+
+  get readable() {
+    this.#readable ??= new ReadableStream({
+      pull: async (controller) => {
+        const chunk = await this.#readChunk(READ_CHUNK_SIZE);
+        if (chunk === null) {
+          controller.close();
+        } else {
+          controller.enqueue(chunk);
+        }
+      },
+      cancel: () => {
+        __internal.net.close(this.#rid);
+      },
+    });
+    return this.#readable;
+  }
+
+  // Closing the stream shuts down the writing side, aborting it closes the
+  // connection
+  get writable() {
+    this.#writable ??= new WritableStream({
+      write: async (chunk) => {
+        let written = 0;
+        while (written < chunk.byteLength) {
+          written += await this.write(chunk.subarray(written));
+        }
+      },
+      close: () => this.closeWrite(),
+      abort: () => {
+        __internal.net.close(this.#rid);
+      },
+    });
+    return this.#writable;
+  }
+
+  #readChunk(size) {
+    return wait(__internal.net.read(this.#rid, size));
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Conn";
+  }
+
+  static {
+    connRid = (conn) => conn.#rid;
+  }
+}
+
+// https://docs.deno.com/api/deno/~/Deno.TcpConn
+class TcpConn extends Conn {
+  setNoDelay(noDelay = true) {
+    call(() => __internal.net.setNoDelay(connRid(this), Boolean(noDelay)));
+  }
+
+  setKeepAlive(keepAlive = true) {
+    call(() => __internal.net.setKeepAlive(connRid(this), Boolean(keepAlive)));
+  }
+
+  get [Symbol.toStringTag]() {
+    return "TcpConn";
+  }
+}
+
+// https://docs.deno.com/api/deno/~/Deno.UnixConn
+class UnixConn extends Conn {
+  get [Symbol.toStringTag]() {
+    return "UnixConn";
+  }
+}
+
+// https://docs.deno.com/api/deno/~/Deno.TlsConn
+class TlsConn extends Conn {
+  #alpnProtocol;
+
+  constructor(key = null, info = undefined) {
+    super(key, info);
+    this.#alpnProtocol = info.alpnProtocol;
+  }
+
+  // The handshake is done by the time the connection is handed out
+  async handshake() {
+    return { alpnProtocol: this.#alpnProtocol };
+  }
+
+  get [Symbol.toStringTag]() {
+    return "TlsConn";
+  }
+}
+
+// https://docs.deno.com/api/deno/~/Deno.Listener
+class Listener {
+  #rid;
+  #addr;
+  #ConnClass;
+
+  constructor(key = null, rid = undefined, addr = undefined, ConnClass = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#rid = rid;
+    this.#addr = addr;
+    this.#ConnClass = ConnClass;
+  }
+
+  get addr() {
+    return this.#addr;
+  }
+
+  async accept() {
+    const info = JSON.parse(await wait(__internal.net.accept(this.#rid)));
+    return new this.#ConnClass(illegalConstructorKey, info);
+  }
+
+  close() {
+    if (!__internal.net.close(this.#rid)) {
+      throw new errors.BadResource("Bad resource ID");
+    }
+  }
+
+  // Yields connections until the listener is closed
+  async *[Symbol.asyncIterator]() {
+    while (true) {
+      let conn;
+      try {
+        conn = await this.accept();
+      } catch (error) {
+        if (
+          error instanceof errors.BadResource ||
+          error instanceof errors.Interrupted
+        ) {
+          return;
+        }
+        throw error;
+      }
+      yield conn;
+    }
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Listener";
+  }
+}
+
+// https://docs.deno.com/api/deno/~/Deno.DatagramConn
+class DatagramConn {
+  #rid;
+  #addr;
+
+  constructor(key = null, rid = undefined, addr = undefined) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#rid = rid;
+    this.#addr = addr;
+  }
+
+  get addr() {
+    return this.#addr;
+  }
+
+  // Resolves with the datagram, in `buffer` when given, and the address of its
+  // sender
+  async receive(buffer = undefined) {
+    const size = buffer?.byteLength ?? MAX_DATAGRAM_SIZE;
+    const [data, addr] = await wait(__internal.net.receive(this.#rid, size));
+    if (buffer === undefined) return [data, JSON.parse(addr)];
+    buffer.set(data);
+    return [buffer.subarray(0, data.byteLength), JSON.parse(addr)];
+  }
+
+  // Resolves with the number of bytes sent
+  async send(data, addr) {
+    if (!(data instanceof Uint8Array)) {
+      throw new TypeError("Data must be a Uint8Array");
+    }
+    const target = JSON.stringify({ hostname: addr.hostname, port: addr.port });
+    return await wait(__internal.net.send(this.#rid, data, target));
+  }
+
+  // https://docs.deno.com/api/deno/~/Deno.MulticastV4Membership
+  async joinMulticastV4(address, networkInterface) {
+    const membership = this.#join(false, address, networkInterface);
+    const rid = this.#rid;
+    membership.setTTL = async (ttl) => {
+      call(() => __internal.net.setMulticastTtl(rid, Number(ttl)));
+    };
+    return membership;
+  }
+
+  // https://docs.deno.com/api/deno/~/Deno.MulticastV6Membership
+  async joinMulticastV6(address, networkInterface) {
+    return this.#join(true, address, networkInterface);
+  }
+
+  // `networkInterface` is an IPv4 address for IPv4 groups, and an interface
+  // index for IPv6 ones
+  #join(ipv6, address, networkInterface) {
+    const rid = this.#rid;
+    const group = [String(address), String(networkInterface)];
+    call(() => __internal.net.multicast(rid, true, ...group));
+    return {
+      leave: async () => {
+        call(() => __internal.net.multicast(rid, false, ...group));
+      },
+      setLoopback: async (loopback) => {
+        call(() => {
+          __internal.net.setMulticastLoopback(rid, ipv6, Boolean(loopback));
+        });
+      },
+    };
+  }
+
+  close() {
+    if (!__internal.net.close(this.#rid)) {
+      throw new errors.BadResource("Bad resource ID");
+    }
+  }
+
+  // Yields datagrams and their senders until the socket is closed
+  async *[Symbol.asyncIterator]() {
+    while (true) {
+      let datagram;
+      try {
+        datagram = await this.receive();
+      } catch (error) {
+        if (
+          error instanceof errors.BadResource ||
+          error instanceof errors.Interrupted
+        ) {
+          return;
+        }
+        throw error;
+      }
+      yield datagram;
+    }
+  }
+
+  get [Symbol.toStringTag]() {
+    return "DatagramConn";
+  }
+}
+
+function addressOptions(options) {
+  const transport = options.transport ?? "tcp";
+  if (transport === "unix") {
+    return { transport, path: String(options.path) };
+  }
+  if (transport !== "tcp") {
+    throw new TypeError(`Unsupported transport: '${transport}'`);
+  }
+  return { transport, hostname: options.hostname, port: options.port };
+}
+
+// https://docs.deno.com/api/deno/~/Deno.connect
+async function connect(options) {
+  const address = addressOptions(options);
+  const info = JSON.parse(
+    await wait(__internal.net.connect(JSON.stringify(address))),
+  );
+  const ConnClass = address.transport === "unix" ? UnixConn : TcpConn;
+  return new ConnClass(illegalConstructorKey, info);
+}
+
+// https://docs.deno.com/api/deno/~/Deno.connectTls
+async function connectTls(options) {
+  const info = JSON.parse(
+    await wait(__internal.net.connectTls(JSON.stringify({
+      hostname: options.hostname,
+      port: options.port,
+      caCerts: options.caCerts,
+      cert: options.cert,
+      key: options.key,
+      alpnProtocols: options.alpnProtocols,
+    }))),
+  );
+  return new TlsConn(illegalConstructorKey, info);
+}
+
+// https://docs.deno.com/api/deno/~/Deno.startTls
+async function startTls(conn, options = {}) {
+  if (!(conn instanceof TcpConn)) {
+    throw new TypeError("Only TCP connections can be upgraded to TLS");
+  }
+  const id = __internal.net.startTls(
+    connRid(conn),
+    JSON.stringify({
+      hostname: options.hostname,
+      caCerts: options.caCerts,
+      alpnProtocols: options.alpnProtocols,
+    }),
+  );
+  const info = JSON.parse(await wait(id));
+  return new TlsConn(illegalConstructorKey, info);
+}
+
+// https://docs.deno.com/api/deno/~/Deno.listen
+function listen(options) {
+  const address = addressOptions(options);
+  const { rid, addr } = JSON.parse(
+    call(() => __internal.net.listen(JSON.stringify(address))),
+  );
+  const ConnClass = address.transport === "unix" ? UnixConn : TcpConn;
+  return new Listener(illegalConstructorKey, rid, addr, ConnClass);
+}
+
+// https://docs.deno.com/api/deno/~/Deno.listenTls
+function listenTls(options) {
+  const { rid, addr } = JSON.parse(
+    call(() =>
+      __internal.net.listenTls(JSON.stringify({
+        hostname: options.hostname,
+        port: options.port,
+        cert: options.cert,
+        key: options.key,
+        alpnProtocols: options.alpnProtocols,
+      }))
+    ),
+  );
+  return new Listener(illegalConstructorKey, rid, addr, TlsConn);
+}
+
+// https://docs.deno.com/api/deno/~/Deno.listenDatagram
+function listenDatagram(options) {
+  if (options.transport !== "udp") {
+    throw new TypeError(`Unsupported transport: '${options.transport}'`);
+  }
+  const { rid, addr } = JSON.parse(
+    call(() =>
+      __internal.net.listenDatagram(JSON.stringify({
+        hostname: options.hostname,
+        port: options.port,
+        reuseAddress: options.reuseAddress,
+        broadcast: options.broadcast,
+        loopback: options.loopback,
+      }))
+    ),
+  );
+  return new DatagramConn(illegalConstructorKey, rid, addr);
+}
+
+// https://docs.deno.com/api/deno/~/Deno.resolveDns
+async function resolveDns(query, recordType, options = {}) {
+  const signal = options?.signal;
+  signal?.throwIfAborted();
+  const nameServer = options?.nameServer;
+  const server = nameServer === undefined ? null : {
+    ipAddr: nameServer.ipAddr,
+    port: nameServer.port ?? 53,
+  };
+  const id = __internal.net.resolveDns(
+    String(query),
+    String(recordType),
+    JSON.stringify(server),
+  );
+  return JSON.parse(await wait(id, signal));
+}
+
+Object.assign(globalThis.__mdeno__.net, {
+  errors,
+  Conn,
+  TcpConn,
+  UnixConn,
+  TlsConn,
+  Listener,
+  DatagramConn,
+  connect,
+  connectTls,
+  startTls,
+  listen,
+  listenTls,
+  listenDatagram,
+  resolveDns,
+});
@@ -0,0 +1,252 @@
+//! DNS lookups behind Deno.resolveDns. Queries go over UDP to the name servers of
+//! /etc/resolv.conf, or to the one given, and are retried over TCP when the answer
+//! is truncated. Results are JSON in the shapes of the Deno API.
+
+use crate::conn::{OpResult, Output, io_error, spawn_op};
+use futures_util::io::{AsyncReadExt, AsyncWriteExt};
+use hickory_proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use hickory_proto::rr::{Name, RData, RecordType};
+use serde_json::{Value, json};
+use smol::Timer;
+use smol::net::{TcpStream, UdpSocket};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use utils::add_internal_function;
+
+const RESOLV_CONF: &str = "/etc/resolv.conf";
+
+// Largest answer received over UDP, with EDNS or not
+const MAX_UDP_SIZE: usize = 4096;
+
+/// Where and how persistently to send queries.
+struct NameServers {
+    addrs: Vec<SocketAddr>,
+    timeout: Duration,
+    attempts: u32,
+}
+
+impl NameServers {
+    fn system() -> Result<NameServers, String> {
+        let conf = std::fs::read(RESOLV_CONF).map_err(|e| {
+            format!(
+                "NotFound: Failed to read {}: {}. Pass a nameServer instead",
+                RESOLV_CONF, e
+            )
+        })?;
+        let config = resolv_conf::Config::parse(conf)
+            .map_err(|e| format!("InvalidData: Invalid {}: {}", RESOLV_CONF, e))?;
+        let addrs = config
+            .get_nameservers_or_local()
+            .into_iter()
+            .map(|ip| SocketAddr::new(IpAddr::from(ip), 53))
+            .collect();
+        Ok(NameServers {
+            addrs,
+            timeout: Duration::from_secs(config.timeout.max(1).into()),
+            attempts: config.attempts.max(1),
+        })
+    }
+}
+
+pub fn setup_internal(ctx: &rquickjs::Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(
+        ctx,
+        "net.resolveDns",
+        |query: String, record_type: String, name_server: String| {
+            spawn_op(resolve(query, record_type, name_server))
+        }
+    );
+
+    Ok(())
+}
+
+/// Look up the `record_type` records of `query`. `name_server` is JSON with the
+/// `ipAddr` and `port` of the server to ask, or null for the system ones.
+async fn resolve(query: String, record_type: String, name_server: String) -> OpResult {
+    let record_type = match record_type.as_str() {
+        "A" => RecordType::A,
+        "AAAA" => RecordType::AAAA,
+        "CNAME" => RecordType::CNAME,
+        "MX" => RecordType::MX,
+        "TXT" => RecordType::TXT,
+        "SRV" => RecordType::SRV,
+        "NS" => RecordType::NS,
+        "PTR" => RecordType::PTR,
+        "SOA" => RecordType::SOA,
+        "CAA" => RecordType::CAA,
+        other => return Err(format!("TypeError: Unsupported record type: {}", other)),
+    };
+    let mut name =
+        Name::from_utf8(&query).map_err(|e| format!("TypeError: Invalid name {}: {}", query, e))?;
+    name.set_fqdn(true);
+
+    let name_servers = match serde_json::from_str::<Value>(&name_server).unwrap_or_default() {
+        Value::Null => NameServers::system()?,
+        server => {
+            let ip = server
+                .get("ipAddr")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let ip: IpAddr = ip
+                .parse()
+                .map_err(|_| format!("TypeError: Invalid name server address: {}", ip))?;
+            let port = server.get("port").and_then(Value::as_u64).unwrap_or(53);
+            let port = u16::try_from(port).map_err(|_| "TypeError: Invalid port".to_string())?;
+            NameServers {
+                addrs: vec![SocketAddr::new(ip, port)],
+                timeout: Duration::from_secs(5),
+                attempts: 2,
+            }
+        }
+    };
+
+    let mut request = Message::new();
+    request
+        .set_id(rand_id())
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name, record_type));
+    let response = exchange(&request, &name_servers).await?;
+
+    match response.response_code() {
+        ResponseCode::NoError => {}
+        ResponseCode::NXDomain => {
+            return Err(format!("NotFound: No such domain: {}", query));
+        }
+        code => return Err(format!("Error: DNS server answered {}", code)),
+    }
+
+    // Answers to an address query may also hold the CNAME records leading to it
+    let records: Vec<Value> = response
+        .answers()
+        .iter()
+        .filter(|record| record.record_type() == record_type)
+        .map(|record| record_json(record.data()))
+        .collect();
+    if records.is_empty() {
+        return Err(format!(
+            "NotFound: No {} records found for {}",
+            record_type, query
+        ));
+    }
+    Ok(Output::Json(Value::from(records).to_string()))
+}
+
+// Query ids only need to tell apart the answers arriving on a socket
+fn rand_id() -> u16 {
+    use std::hash::{BuildHasher, RandomState};
+    RandomState::new().hash_one(std::time::SystemTime::now()) as u16
+}
+
+/// Send `request` to each server in turn until one answers, for the configured
+/// number of rounds.
+async fn exchange(request: &Message, name_servers: &NameServers) -> Result<Message, String> {
+    let bytes = request.to_vec().map_err(|e| format!("Error: {}", e))?;
+    let mut last_error = "TimedOut: DNS query timed out".to_string();
+    for _ in 0..name_servers.attempts {
+        for &server in &name_servers.addrs {
+            let attempt = async {
+                let response = query_udp(&bytes, request.id(), server).await?;
+                if response.truncated() {
+                    return query_tcp(&bytes, request.id(), server).await;
+                }
+                Ok(response)
+            };
+            let timeout = async {
+                Timer::after(name_servers.timeout).await;
+                Err("TimedOut: DNS query timed out".to_string())
+            };
+            match smol::future::or(attempt, timeout).await {
+                Ok(response) => return Ok(response),
+                Err(e) => last_error = e,
+            }
+        }
+    }
+    Err(last_error)
+}
+
+async fn query_udp(bytes: &[u8], id: u16, server: SocketAddr) -> Result<Message, String> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+        SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+    };
+    let socket = UdpSocket::bind(local).await.map_err(io_error)?;
+    socket.connect(server).await.map_err(io_error)?;
+    socket.send(bytes).await.map_err(io_error)?;
+
+    let mut buffer = vec![0; MAX_UDP_SIZE];
+    loop {
+        let n = socket.recv(&mut buffer).await.map_err(io_error)?;
+        // Stray datagrams are dropped
+        if let Ok(response) = Message::from_vec(&buffer[..n])
+            && response.id() == id
+            && response.message_type() == MessageType::Response
+        {
+            return Ok(response);
+        }
+    }
+}
+
+async fn query_tcp(bytes: &[u8], id: u16, server: SocketAddr) -> Result<Message, String> {
+    let mut stream = TcpStream::connect(server).await.map_err(io_error)?;
+    // Messages over TCP are prefixed with their length
+    let length = u16::try_from(bytes.len()).map_err(|_| "Error: DNS query too long")?;
+    stream
+        .write_all(&length.to_be_bytes())
+        .await
+        .map_err(io_error)?;
+    stream.write_all(bytes).await.map_err(io_error)?;
+
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).await.map_err(io_error)?;
+    let mut buffer = vec![0; u16::from_be_bytes(length).into()];
+    stream.read_exact(&mut buffer).await.map_err(io_error)?;
+    let response = Message::from_vec(&buffer)
+        .map_err(|e| format!("InvalidData: Invalid DNS answer: {}", e))?;
+    if response.id() != id {
+        return Err("InvalidData: DNS answer does not match the query".to_string());
+    }
+    Ok(response)
+}
+
+fn record_json(data: &RData) -> Value {
+    match data {
+        RData::A(a) => json!(a.to_string()),
+        RData::AAAA(aaaa) => json!(aaaa.to_string()),
+        RData::CNAME(name) => json!(name.to_string()),
+        RData::NS(name) => json!(name.to_string()),
+        RData::PTR(name) => json!(name.to_string()),
+        RData::MX(mx) => json!({
+            "preference": mx.preference(),
+            "exchange": mx.exchange().to_string(),
+        }),
+        // Each record is a list of strings
+        RData::TXT(txt) => json!(
+            txt.iter()
+                .map(|part| String::from_utf8_lossy(part).into_owned())
+                .collect::<Vec<_>>()
+        ),
+        RData::SRV(srv) => json!({
+            "priority": srv.priority(),
+            "weight": srv.weight(),
+            "port": srv.port(),
+            "target": srv.target().to_string(),
+        }),
+        RData::SOA(soa) => json!({
+            "mname": soa.mname().to_string(),
+            "rname": soa.rname().to_string(),
+            "serial": soa.serial(),
+            "refresh": soa.refresh(),
+            "retry": soa.retry(),
+            "expire": soa.expire(),
+            "minimum": soa.minimum(),
+        }),
+        RData::CAA(caa) => json!({
+            "critical": caa.issuer_critical(),
+            "tag": caa.tag().as_str(),
+            "value": String::from_utf8_lossy(caa.raw_value()),
+        }),
+        _ => Value::Null,
+    }
+}
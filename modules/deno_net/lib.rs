@@ -0,0 +1,22 @@
+// Copyright 2018-2025 the Deno authors. MIT license.
+//! Networking shared by the runtime: TCP connection setup and TLS streams, used by
+//! fetch and by the socket APIs of the Deno namespace.
+
+use rquickjs::{Ctx, Module};
+
+mod conn;
+mod dns;
+pub mod tcp;
+pub mod tls;
+mod udp;
+
+pub fn init(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    tls::install_provider();
+
+    conn::setup_internal(ctx)?;
+    udp::setup_internal(ctx)?;
+    dns::setup_internal(ctx)?;
+    let module = Module::evaluate(ctx.clone(), "deno_net", include_str!("deno_net.js"))?;
+    module.finish::<()>()?;
+    Ok(())
+}
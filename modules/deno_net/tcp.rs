@@ -0,0 +1,191 @@
+//! Host name resolution and TCP connection setup.
+//!
+//! Connections race the resolved addresses as described by Happy Eyeballs (RFC 8305),
+//! so a host whose IPv6 or IPv4 addresses are unreachable does not stall requests.
+
+use futures_util::StreamExt;
+use futures_util::stream::FuturesUnordered;
+use smol::Timer;
+use smol::net::TcpStream;
+use std::io::{Error, ErrorKind};
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+// Time to wait for an attempt before starting the next one in parallel
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// Time allowed for resolving a host and establishing the connection
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Open a TCP connection to `host`, a host name or an IP address. IPv6 addresses
+/// may be in brackets as in URLs.
+pub async fn connect(host: &str, port: u16) -> std::io::Result<TcpStream> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let attempt = async {
+        let addrs = resolve(host, port).await?;
+        connect_any(addrs).await
+    };
+    let timeout = async {
+        Timer::after(CONNECT_TIMEOUT).await;
+        Err(Error::new(
+            ErrorKind::TimedOut,
+            format!("Connection to {} timed out", host),
+        ))
+    };
+    smol::future::or(attempt, timeout).await
+}
+
+/// Resolve `host`, ordering the addresses so that families alternate, starting
+/// with the family the resolver listed first.
+async fn resolve(host: &str, port: u16) -> std::io::Result<Vec<SocketAddr>> {
+    // IP literals need no lookup
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![SocketAddr::new(ip, port)]);
+    }
+
+    let addrs = smol::net::resolve((host, port))
+        .await
+        .map_err(|e| Error::new(e.kind(), format!("DNS lookup of {} failed: {}", host, e)))?;
+    let Some(first) = addrs.first() else {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("DNS lookup of {} returned no addresses", host),
+        ));
+    };
+
+    let first_is_ipv6 = first.is_ipv6();
+    let (preferred, other): (Vec<_>, Vec<_>) = addrs
+        .into_iter()
+        .partition(|addr| addr.is_ipv6() == first_is_ipv6);
+    let mut preferred = preferred.into_iter();
+    let mut other = other.into_iter();
+
+    let mut ordered = Vec::new();
+    loop {
+        match (preferred.next(), other.next()) {
+            (None, None) => return Ok(ordered),
+            (first, second) => ordered.extend(first.into_iter().chain(second)),
+        }
+    }
+}
+
+enum Event {
+    Connected(std::io::Result<TcpStream>),
+    Delay,
+}
+
+/// Connect to the first address that answers. Each attempt gets `ATTEMPT_DELAY`
+/// before the next address is tried alongside it; a failed attempt moves on to the
+/// next address right away.
+async fn connect_any(addrs: Vec<SocketAddr>) -> std::io::Result<TcpStream> {
+    let mut addrs = addrs.into_iter();
+    let mut attempts = FuturesUnordered::new();
+    let mut last_error: Option<Error> = None;
+
+    loop {
+        if attempts.is_empty() {
+            match addrs.next() {
+                Some(addr) => attempts.push(TcpStream::connect(addr)),
+                None => {
+                    let error =
+                        last_error.unwrap_or_else(|| Error::new(ErrorKind::NotFound, "no address"));
+                    return Err(Error::new(
+                        error.kind(),
+                        format!("Connection failed: {}", error),
+                    ));
+                }
+            }
+        }
+
+        let remaining = addrs.len();
+        let connected = async { Event::Connected(attempts.next().await.unwrap()) };
+        let delay = async {
+            if remaining == 0 {
+                std::future::pending::<()>().await;
+            }
+            Timer::after(ATTEMPT_DELAY).await;
+            Event::Delay
+        };
+
+        match smol::future::or(connected, delay).await {
+            Event::Connected(Ok(stream)) => return Ok(stream),
+            Event::Connected(Err(e)) => {
+                last_error = Some(e);
+                if let Some(addr) = addrs.next() {
+                    attempts.push(TcpStream::connect(addr));
+                }
+            }
+            Event::Delay => {
+                if let Some(addr) = addrs.next() {
+                    attempts.push(TcpStream::connect(addr));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    fn listener() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        (listener, addr)
+    }
+
+    /// An address nothing listens on once its listener is dropped.
+    fn closed() -> SocketAddr {
+        listener().1
+    }
+
+    #[test]
+    fn refused_addresses_move_on_right_away() {
+        let (_listener, addr) = listener();
+
+        let started = Instant::now();
+        let stream = smol::block_on(connect_any(vec![closed(), addr])).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+        assert!(started.elapsed() < ATTEMPT_DELAY);
+    }
+
+    #[test]
+    fn stalled_addresses_race_the_next_one() {
+        let (_listener, addr) = listener();
+        // TEST-NET-1 is never routed, so the attempt stalls or fails
+        let stalled = SocketAddr::from(([192, 0, 2, 1], addr.port()));
+
+        let started = Instant::now();
+        let stream = smol::block_on(connect_any(vec![stalled, addr])).unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), addr);
+        assert!(started.elapsed() < CONNECT_TIMEOUT);
+    }
+
+    #[test]
+    fn failure_reports_the_last_error() {
+        let error = smol::block_on(connect_any(vec![closed()])).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::ConnectionRefused);
+        assert!(error.to_string().starts_with("Connection failed: "));
+    }
+
+    #[test]
+    fn ip_literals_are_not_looked_up() {
+        let addrs = smol::block_on(resolve("::1", 80)).unwrap();
+        assert_eq!(addrs, [SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 1], 80))]);
+    }
+
+    #[test]
+    fn bracketed_ipv6_hosts() {
+        let Ok(listener) = TcpListener::bind("[::1]:0") else {
+            // No IPv6 loopback
+            return;
+        };
+        let port = listener.local_addr().unwrap().port();
+
+        let stream = smol::block_on(connect("[::1]", port)).unwrap();
+        assert!(stream.peer_addr().unwrap().is_ipv6());
+    }
+}
@@ -0,0 +1,668 @@
+//! TLS streams over TCP connections, backed by native-tls or rustls depending on
+//! the enabled feature. Without either, plain connections still work and TLS
+//! setup fails with an error.
+
+/// TLS settings of a client connection.
+#[derive(Default)]
+pub struct TlsOptions {
+    // PEM certificates trusted in addition to the default roots
+    pub ca_certs: Vec<String>,
+    // PEM client certificate chain and private key
+    pub cert: Option<String>,
+    pub key: Option<String>,
+    // Protocols offered through ALPN, most preferred first
+    pub alpn_protocols: Vec<String>,
+}
+
+/// Certificates from the PEM files named by DENO_CERT and SSL_CERT_FILE, trusted by
+/// every client.
+#[cfg(any(feature = "native-tls", feature = "rustls"))]
+fn env_ca_certs() -> Result<Vec<String>, String> {
+    ["DENO_CERT", "SSL_CERT_FILE"]
+        .into_iter()
+        .filter_map(|name| std::env::var_os(name).filter(|path| !path.is_empty()))
+        .map(|path| {
+            std::fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {}", path.to_string_lossy(), e))
+        })
+        .collect()
+}
+
+pub use backend::*;
+
+#[cfg(feature = "native-tls")]
+mod backend {
+    use super::{TlsOptions, env_ca_certs};
+    use futures_io::{AsyncRead, AsyncWrite};
+    use native_tls::HandshakeError;
+    use smol::net::TcpStream;
+    use std::io::{Read, Write};
+    use std::pin::Pin;
+    use std::task::{Context, Poll, Waker};
+
+    pub enum IoStream {
+        Plain(TcpStream),
+        Tls(native_tls::TlsStream<SyncStream>),
+    }
+
+    /// Blocking-style view of a TCP stream for native-tls. Operations that would
+    /// block return `WouldBlock` after registering the waker of the current poll.
+    pub struct SyncStream {
+        stream: TcpStream,
+        waker: Waker,
+    }
+
+    impl SyncStream {
+        fn poll<T>(
+            &mut self,
+            f: impl FnOnce(Pin<&mut TcpStream>, &mut Context<'_>) -> Poll<std::io::Result<T>>,
+        ) -> std::io::Result<T> {
+            let waker = self.waker.clone();
+            match f(Pin::new(&mut self.stream), &mut Context::from_waker(&waker)) {
+                Poll::Ready(result) => result,
+                Poll::Pending => Err(std::io::ErrorKind::WouldBlock.into()),
+            }
+        }
+    }
+
+    impl Read for SyncStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.poll(|stream, cx| stream.poll_read(cx, buf))
+        }
+    }
+
+    impl Write for SyncStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.poll(|stream, cx| stream.poll_write(cx, buf))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.poll(|stream, cx| stream.poll_flush(cx))
+        }
+    }
+
+    /// Run a native-tls operation on the stream with the waker of `cx`.
+    fn poll_tls<T>(
+        stream: &mut native_tls::TlsStream<SyncStream>,
+        cx: &mut Context<'_>,
+        f: impl FnOnce(&mut native_tls::TlsStream<SyncStream>) -> std::io::Result<T>,
+    ) -> Poll<std::io::Result<T>> {
+        stream.get_mut().waker = cx.waker().clone();
+        match f(stream) {
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => Poll::Pending,
+            result => Poll::Ready(result),
+        }
+    }
+
+    impl IoStream {
+        /// The protocol the peer selected through ALPN.
+        pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+            match self {
+                IoStream::Plain(_) => None,
+                IoStream::Tls(s) => s.negotiated_alpn().ok().flatten(),
+            }
+        }
+
+        /// The TCP connection the stream runs over.
+        pub fn tcp_stream(&self) -> &TcpStream {
+            match self {
+                IoStream::Plain(s) => s,
+                IoStream::Tls(s) => &s.get_ref().stream,
+            }
+        }
+    }
+
+    impl AsyncRead for IoStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_read(cx, buf),
+                IoStream::Tls(s) => poll_tls(s, cx, |s| s.read(buf)),
+            }
+        }
+    }
+
+    impl AsyncWrite for IoStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
+                IoStream::Tls(s) => poll_tls(s, cx, |s| s.write(buf)),
+            }
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_flush(cx),
+                IoStream::Tls(s) => poll_tls(s, cx, |s| s.flush()),
+            }
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_close(cx),
+                IoStream::Tls(s) => match poll_tls(s, cx, |s| s.shutdown()) {
+                    Poll::Ready(Ok(())) => Pin::new(&mut s.get_mut().stream).poll_close(cx),
+                    poll => poll,
+                },
+            }
+        }
+    }
+
+    pub fn install_provider() {}
+
+    pub struct TlsConfig {
+        connector: native_tls::TlsConnector,
+    }
+
+    impl TlsConfig {
+        pub fn new(options: &TlsOptions) -> Result<TlsConfig, String> {
+            let mut builder = native_tls::TlsConnector::builder();
+            if !options.alpn_protocols.is_empty() {
+                let protocols: Vec<&str> =
+                    options.alpn_protocols.iter().map(String::as_str).collect();
+                builder.request_alpns(&protocols);
+            }
+
+            for pem in options.ca_certs.iter().chain(&env_ca_certs()?) {
+                let blocks = pem_certificates(pem);
+                if blocks.is_empty() {
+                    return Err("Invalid CA certificate: no PEM certificate found".to_string());
+                }
+                for block in blocks {
+                    let cert = native_tls::Certificate::from_pem(block.as_bytes())
+                        .map_err(|e| format!("Invalid CA certificate: {}", e))?;
+                    builder.add_root_certificate(cert);
+                }
+            }
+
+            match (&options.cert, &options.key) {
+                (Some(cert), Some(key)) => {
+                    // native-tls only reads PKCS#8 keys ("BEGIN PRIVATE KEY")
+                    let identity =
+                        native_tls::Identity::from_pkcs8(cert.as_bytes(), key.as_bytes())
+                            .map_err(|e| format!("Invalid client certificate or key: {}", e))?;
+                    builder.identity(identity);
+                }
+                (None, None) => {}
+                _ => {
+                    return Err(
+                        "Both cert and key are required for a client certificate".to_string()
+                    );
+                }
+            }
+
+            let connector = builder
+                .build()
+                .map_err(|e| format!("TLS setup failed: {}", e))?;
+            Ok(TlsConfig { connector })
+        }
+    }
+
+    // The CERTIFICATE blocks of a PEM bundle, which native-tls reads one at a time
+    fn pem_certificates(pem: &str) -> Vec<&str> {
+        const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+        const END: &str = "-----END CERTIFICATE-----";
+
+        let mut blocks = Vec::new();
+        let mut rest = pem;
+        while let Some(start) = rest.find(BEGIN) {
+            let Some(length) = rest[start..].find(END) else {
+                break;
+            };
+            let end = start + length + END.len();
+            blocks.push(&rest[start..end]);
+            rest = &rest[end..];
+        }
+        blocks
+    }
+
+    pub async fn create_tls_stream(
+        config: &TlsConfig,
+        host: &str,
+        port: u16,
+    ) -> Result<IoStream, String> {
+        let stream = crate::tcp::connect(host, port)
+            .await
+            .map_err(|e| e.to_string())?;
+        connect_tls(config, stream, host).await
+    }
+
+    /// Run the TLS handshake for `host` over an established connection.
+    pub async fn connect_tls(
+        config: &TlsConfig,
+        stream: TcpStream,
+        host: &str,
+    ) -> Result<IoStream, String> {
+        handshake(stream, |stream| config.connector.connect(host, stream)).await
+    }
+
+    /// Certificate and key presented by a server. native-tls cannot negotiate
+    /// ALPN as a server, so `alpn_protocols` is ignored and HTTP clients are
+    /// served HTTP/1.1.
+    pub struct TlsAcceptor {
+        acceptor: native_tls::TlsAcceptor,
+    }
+
+    impl TlsAcceptor {
+        pub fn new(
+            cert: &str,
+            key: &str,
+            _alpn_protocols: &[String],
+        ) -> Result<TlsAcceptor, String> {
+            // native-tls only reads PKCS#8 keys ("BEGIN PRIVATE KEY")
+            let identity = native_tls::Identity::from_pkcs8(cert.as_bytes(), key.as_bytes())
+                .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+            let acceptor = native_tls::TlsAcceptor::new(identity)
+                .map_err(|e| format!("TLS setup failed: {}", e))?;
+            Ok(TlsAcceptor { acceptor })
+        }
+    }
+
+    /// Run the server side of the TLS handshake over an accepted connection.
+    pub async fn accept_tls(acceptor: &TlsAcceptor, stream: TcpStream) -> Result<IoStream, String> {
+        handshake(stream, |stream| acceptor.acceptor.accept(stream)).await
+    }
+
+    async fn handshake(
+        stream: TcpStream,
+        start: impl FnOnce(
+            SyncStream,
+        )
+            -> Result<native_tls::TlsStream<SyncStream>, HandshakeError<SyncStream>>,
+    ) -> Result<IoStream, String> {
+        // Drive the handshake until it no longer waits for the socket
+        let mut start = Some(start);
+        let mut stream = Some(SyncStream {
+            stream,
+            waker: Waker::noop().clone(),
+        });
+        let mut handshake: Option<native_tls::MidHandshakeTlsStream<SyncStream>> = None;
+        let tls_stream = std::future::poll_fn(|cx| {
+            let result = match handshake.take() {
+                Some(mut mid) => {
+                    mid.get_mut().waker = cx.waker().clone();
+                    mid.handshake()
+                }
+                None => {
+                    let mut stream = stream.take().unwrap();
+                    stream.waker = cx.waker().clone();
+                    (start.take().unwrap())(stream)
+                }
+            };
+            match result {
+                Ok(tls_stream) => Poll::Ready(Ok(tls_stream)),
+                Err(HandshakeError::WouldBlock(mid)) => {
+                    handshake = Some(mid);
+                    Poll::Pending
+                }
+                Err(HandshakeError::Failure(e)) => {
+                    Poll::Ready(Err(format!("TLS handshake failed: {}", e)))
+                }
+            }
+        })
+        .await?;
+
+        Ok(IoStream::Tls(tls_stream))
+    }
+
+    pub async fn create_plain_stream(host: &str, port: u16) -> Result<IoStream, String> {
+        let stream = crate::tcp::connect(host, port)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(IoStream::Plain(stream))
+    }
+}
+
+#[cfg(feature = "rustls")]
+mod backend {
+    use super::{TlsOptions, env_ca_certs};
+    use futures_io::{AsyncRead, AsyncWrite};
+    use futures_rustls::TlsConnector;
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+    use smol::net::TcpStream;
+    use std::sync::Arc;
+
+    pub enum IoStream {
+        Plain(TcpStream),
+        Tls(futures_rustls::client::TlsStream<TcpStream>),
+        ServerTls(futures_rustls::server::TlsStream<TcpStream>),
+    }
+
+    impl IoStream {
+        /// The protocol the peer selected through ALPN.
+        pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+            let protocol = match self {
+                IoStream::Plain(_) => None,
+                IoStream::Tls(s) => s.get_ref().1.alpn_protocol(),
+                IoStream::ServerTls(s) => s.get_ref().1.alpn_protocol(),
+            };
+            protocol.map(<[u8]>::to_vec)
+        }
+
+        /// The TCP connection the stream runs over.
+        pub fn tcp_stream(&self) -> &TcpStream {
+            match self {
+                IoStream::Plain(s) => s,
+                IoStream::Tls(s) => s.get_ref().0,
+                IoStream::ServerTls(s) => s.get_ref().0,
+            }
+        }
+    }
+
+    impl AsyncRead for IoStream {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            match &mut *self {
+                IoStream::Plain(s) => std::pin::Pin::new(s).poll_read(cx, buf),
+                IoStream::Tls(s) => std::pin::Pin::new(s).poll_read(cx, buf),
+                IoStream::ServerTls(s) => std::pin::Pin::new(s).poll_read(cx, buf),
+            }
+        }
+    }
+
+    impl AsyncWrite for IoStream {
+        fn poll_write(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+            buf: &[u8],
+        ) -> std::task::Poll<std::io::Result<usize>> {
+            match &mut *self {
+                IoStream::Plain(s) => std::pin::Pin::new(s).poll_write(cx, buf),
+                IoStream::Tls(s) => std::pin::Pin::new(s).poll_write(cx, buf),
+                IoStream::ServerTls(s) => std::pin::Pin::new(s).poll_write(cx, buf),
+            }
+        }
+
+        fn poll_flush(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            match &mut *self {
+                IoStream::Plain(s) => std::pin::Pin::new(s).poll_flush(cx),
+                IoStream::Tls(s) => std::pin::Pin::new(s).poll_flush(cx),
+                IoStream::ServerTls(s) => std::pin::Pin::new(s).poll_flush(cx),
+            }
+        }
+
+        fn poll_close(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut std::task::Context<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            match &mut *self {
+                IoStream::Plain(s) => std::pin::Pin::new(s).poll_close(cx),
+                IoStream::Tls(s) => std::pin::Pin::new(s).poll_close(cx),
+                IoStream::ServerTls(s) => std::pin::Pin::new(s).poll_close(cx),
+            }
+        }
+    }
+
+    /// Make ring the crypto provider of rustls. Runs before any TLS setup.
+    pub fn install_provider() {
+        // Fails when a provider is installed already
+        let _ = rustls::crypto::ring::default_provider().install_default();
+    }
+
+    pub struct TlsConfig {
+        config: Arc<rustls::ClientConfig>,
+    }
+
+    impl TlsConfig {
+        pub fn new(options: &TlsOptions) -> Result<TlsConfig, String> {
+            let mut root_store = rustls::RootCertStore {
+                roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+            };
+
+            // System roots are trusted as well, skipping the ones rustls rejects
+            let native = rustls_native_certs::load_native_certs();
+            root_store.add_parsable_certificates(native.certs);
+
+            for pem in options.ca_certs.iter().chain(&env_ca_certs()?) {
+                let certs = certificates(pem, "CA certificate")?;
+                for cert in certs {
+                    root_store
+                        .add(cert)
+                        .map_err(|e| format!("Invalid CA certificate: {}", e))?;
+                }
+            }
+
+            let builder = rustls::ClientConfig::builder().with_root_certificates(root_store);
+            let mut config = match (&options.cert, &options.key) {
+                (Some(cert), Some(key)) => {
+                    let chain = certificates(cert, "client certificate")?;
+                    let key = PrivateKeyDer::from_pem_slice(key.as_bytes())
+                        .map_err(|e| format!("Invalid client key: {}", e))?;
+                    builder
+                        .with_client_auth_cert(chain, key)
+                        .map_err(|e| format!("Invalid client certificate or key: {}", e))?
+                }
+                (None, None) => builder.with_no_client_auth(),
+                _ => {
+                    return Err(
+                        "Both cert and key are required for a client certificate".to_string()
+                    );
+                }
+            };
+
+            config.alpn_protocols = alpn_protocols(&options.alpn_protocols);
+            Ok(TlsConfig {
+                config: Arc::new(config),
+            })
+        }
+    }
+
+    fn alpn_protocols(protocols: &[String]) -> Vec<Vec<u8>> {
+        protocols
+            .iter()
+            .map(|protocol| protocol.as_bytes().to_vec())
+            .collect()
+    }
+
+    fn certificates(pem: &str, what: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+        let certs = CertificateDer::pem_slice_iter(pem.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid {}: {}", what, e))?;
+        if certs.is_empty() {
+            return Err(format!("Invalid {}: no PEM certificate found", what));
+        }
+        Ok(certs)
+    }
+
+    pub async fn create_tls_stream(
+        config: &TlsConfig,
+        host: &str,
+        port: u16,
+    ) -> Result<IoStream, String> {
+        let stream = crate::tcp::connect(host, port)
+            .await
+            .map_err(|e| e.to_string())?;
+        connect_tls(config, stream, host).await
+    }
+
+    /// Run the TLS handshake for `host` over an established connection.
+    pub async fn connect_tls(
+        config: &TlsConfig,
+        stream: TcpStream,
+        host: &str,
+    ) -> Result<IoStream, String> {
+        use rustls::pki_types::ServerName;
+
+        let connector = TlsConnector::from(config.config.clone());
+        let server_name = ServerName::try_from(host.to_string())
+            .map_err(|e| format!("Invalid server name: {}", e))?;
+
+        let tls_stream = connector
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+
+        Ok(IoStream::Tls(tls_stream))
+    }
+
+    /// Certificate and key presented by a server.
+    pub struct TlsAcceptor {
+        acceptor: futures_rustls::TlsAcceptor,
+    }
+
+    impl TlsAcceptor {
+        pub fn new(
+            cert: &str,
+            key: &str,
+            alpn_protocols: &[String],
+        ) -> Result<TlsAcceptor, String> {
+            let chain = certificates(cert, "certificate")?;
+            let key = PrivateKeyDer::from_pem_slice(key.as_bytes())
+                .map_err(|e| format!("Invalid key: {}", e))?;
+            let mut config = rustls::ServerConfig::builder()
+                .with_no_client_auth()
+                .with_single_cert(chain, key)
+                .map_err(|e| format!("Invalid certificate or key: {}", e))?;
+            config.alpn_protocols = self::alpn_protocols(alpn_protocols);
+            Ok(TlsAcceptor {
+                acceptor: futures_rustls::TlsAcceptor::from(Arc::new(config)),
+            })
+        }
+    }
+
+    /// Run the server side of the TLS handshake over an accepted connection.
+    pub async fn accept_tls(acceptor: &TlsAcceptor, stream: TcpStream) -> Result<IoStream, String> {
+        let tls_stream = acceptor
+            .acceptor
+            .accept(stream)
+            .await
+            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+        Ok(IoStream::ServerTls(tls_stream))
+    }
+
+    pub async fn create_plain_stream(host: &str, port: u16) -> Result<IoStream, String> {
+        let stream = crate::tcp::connect(host, port)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(IoStream::Plain(stream))
+    }
+}
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+mod backend {
+    use super::TlsOptions;
+    use futures_io::{AsyncRead, AsyncWrite};
+    use smol::net::TcpStream;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    const UNSUPPORTED: &str = "TLS is not supported in this build";
+
+    pub enum IoStream {
+        Plain(TcpStream),
+    }
+
+    impl IoStream {
+        pub fn alpn_protocol(&self) -> Option<Vec<u8>> {
+            None
+        }
+
+        pub fn tcp_stream(&self) -> &TcpStream {
+            match self {
+                IoStream::Plain(s) => s,
+            }
+        }
+    }
+
+    impl AsyncRead for IoStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            }
+        }
+    }
+
+    impl AsyncWrite for IoStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            }
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_flush(cx),
+            }
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            match &mut *self {
+                IoStream::Plain(s) => Pin::new(s).poll_close(cx),
+            }
+        }
+    }
+
+    pub fn install_provider() {}
+
+    pub struct TlsConfig;
+
+    impl TlsConfig {
+        pub fn new(_options: &TlsOptions) -> Result<TlsConfig, String> {
+            Err(UNSUPPORTED.to_string())
+        }
+    }
+
+    pub async fn create_tls_stream(
+        _config: &TlsConfig,
+        _host: &str,
+        _port: u16,
+    ) -> Result<IoStream, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn connect_tls(
+        _config: &TlsConfig,
+        _stream: TcpStream,
+        _host: &str,
+    ) -> Result<IoStream, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub struct TlsAcceptor;
+
+    impl TlsAcceptor {
+        pub fn new(
+            _cert: &str,
+            _key: &str,
+            _alpn_protocols: &[String],
+        ) -> Result<TlsAcceptor, String> {
+            Err(UNSUPPORTED.to_string())
+        }
+    }
+
+    pub async fn accept_tls(
+        _acceptor: &TlsAcceptor,
+        _stream: TcpStream,
+    ) -> Result<IoStream, String> {
+        Err(UNSUPPORTED.to_string())
+    }
+
+    pub async fn create_plain_stream(host: &str, port: u16) -> Result<IoStream, String> {
+        let stream = crate::tcp::connect(host, port)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(IoStream::Plain(stream))
+    }
+}
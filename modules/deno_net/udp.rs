@@ -0,0 +1,193 @@
+//! UDP sockets behind Deno.listenDatagram. They are resources next to the
+//! connections of `conn`, and their operations are polled the same way.
+
+use crate::conn::{
+    BAD_RESOURCE, Output, RESOURCES, interruptible, io_error, parse_options, port_option,
+    socket_addr, spawn_op, string_option, throw,
+};
+use rquickjs::{Ctx, TypedArray};
+use smol::net::UdpSocket;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs};
+use std::sync::Arc;
+use utils::add_internal_function;
+
+// Largest datagram received, whatever the size of the buffer
+const MAX_DATAGRAM_SIZE: usize = 64 * 1024;
+
+pub(crate) struct Datagram {
+    socket: Arc<UdpSocket>,
+    // Dropped when the socket is closed, which interrupts its operations
+    _closed: smol::channel::Sender<()>,
+    closing: smol::channel::Receiver<()>,
+}
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(
+        ctx,
+        "net.listenDatagram",
+        |ctx: Ctx<'_>, options: String| { listen_datagram(&options).map_err(|e| throw(&ctx, &e)) }
+    );
+
+    add_internal_function!(ctx, "net.receive", |rid: u32, size: usize| {
+        receive(rid, size)
+    });
+
+    add_internal_function!(
+        ctx,
+        "net.send",
+        |rid: u32, data: TypedArray<'_, u8>, addr: String| {
+            send(rid, data.as_bytes().unwrap_or_default().to_vec(), addr)
+        }
+    );
+
+    add_internal_function!(
+        ctx,
+        "net.multicast",
+        |ctx: Ctx<'_>, rid: u32, join: bool, address: String, interface: String| {
+            multicast(rid, join, &address, &interface).map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    add_internal_function!(
+        ctx,
+        "net.setMulticastLoopback",
+        |ctx: Ctx<'_>, rid: u32, ipv6: bool, loopback: bool| {
+            with_socket(rid, |socket| {
+                if ipv6 {
+                    socket.set_multicast_loop_v6(loopback)
+                } else {
+                    socket.set_multicast_loop_v4(loopback)
+                }
+            })
+            .map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    add_internal_function!(
+        ctx,
+        "net.setMulticastTtl",
+        |ctx: Ctx<'_>, rid: u32, ttl: u32| {
+            with_socket(rid, |socket| socket.set_multicast_ttl_v4(ttl)).map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    Ok(())
+}
+
+/// Bind a socket with JSON options `{hostname, port, reuseAddress, broadcast,
+/// loopback}`. Returns JSON with the id of the socket and its address.
+fn listen_datagram(options: &str) -> Result<String, String> {
+    let options = parse_options(options);
+    let hostname = string_option(&options, "hostname").unwrap_or("0.0.0.0".into());
+    let port = port_option(&options)?;
+    let flag = |name| options.get(name).and_then(|value| value.as_bool());
+
+    let addr = (hostname.as_str(), port)
+        .to_socket_addrs()
+        .map_err(io_error)?
+        .next()
+        .ok_or_else(|| format!("NotFound: No address found for {}", hostname))?;
+
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))
+        .map_err(io_error)?;
+    if flag("reuseAddress") == Some(true) {
+        // Lets several processes receive the same multicast group
+        socket.set_reuse_address(true).map_err(io_error)?;
+        #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+        socket.set_reuse_port(true).map_err(io_error)?;
+    }
+    if let Some(broadcast) = flag("broadcast") {
+        socket.set_broadcast(broadcast).map_err(io_error)?;
+    }
+    if let Some(loopback) = flag("loopback") {
+        let result = if addr.is_ipv6() {
+            socket.set_multicast_loop_v6(loopback)
+        } else {
+            socket.set_multicast_loop_v4(loopback)
+        };
+        result.map_err(io_error)?;
+    }
+    socket.bind(&SockAddr::from(addr)).map_err(io_error)?;
+    let socket = UdpSocket::try_from(std::net::UdpSocket::from(socket)).map_err(io_error)?;
+    let addr = socket_addr("udp", socket.local_addr());
+
+    let (closed, closing) = smol::channel::bounded(1);
+    let mut resources = RESOURCES.lock().unwrap();
+    let rid = resources.next_id();
+    resources.datagrams.insert(
+        rid,
+        Datagram {
+            socket: Arc::new(socket),
+            _closed: closed,
+            closing,
+        },
+    );
+    Ok(serde_json::json!({ "rid": rid, "addr": addr }).to_string())
+}
+
+fn datagram(rid: u32) -> Result<(Arc<UdpSocket>, smol::channel::Receiver<()>), String> {
+    let resources = RESOURCES.lock().unwrap();
+    let datagram = resources.datagrams.get(&rid).ok_or(BAD_RESOURCE)?;
+    Ok((datagram.socket.clone(), datagram.closing.clone()))
+}
+
+/// Receive a datagram of up to `size` bytes on socket `rid`.
+fn receive(rid: u32, size: usize) -> u32 {
+    let datagram = datagram(rid);
+    spawn_op(async move {
+        let (socket, closing) = datagram?;
+        let mut buffer = vec![0; size.min(MAX_DATAGRAM_SIZE)];
+        let (n, sender) = interruptible(&closing, socket.recv_from(&mut buffer)).await?;
+        buffer.truncate(n);
+        let sender = socket_addr("udp", Ok(sender)).to_string();
+        Ok(Output::Datagram(buffer, sender))
+    })
+}
+
+/// Send `data` from socket `rid` to the JSON address `{hostname, port}`.
+fn send(rid: u32, data: Vec<u8>, addr: String) -> u32 {
+    let datagram = datagram(rid);
+    spawn_op(async move {
+        let (socket, closing) = datagram?;
+        let addr = parse_options(&addr);
+        let hostname = string_option(&addr, "hostname").unwrap_or("127.0.0.1".into());
+        let port = port_option(&addr)?;
+        let target = (hostname.as_str(), port);
+        let n = interruptible(&closing, socket.send_to(&data, target)).await?;
+        Ok(Output::Written(n))
+    })
+}
+
+/// Join or leave multicast group `address` on socket `rid`. The interface is an
+/// IPv4 address for IPv4 groups, and an interface index for IPv6 ones.
+fn multicast(rid: u32, join: bool, address: &str, interface: &str) -> Result<(), String> {
+    let group: IpAddr = address
+        .parse()
+        .map_err(|_| "TypeError: Invalid multicast address".to_string())?;
+    with_socket(rid, |socket| match group {
+        IpAddr::V4(group) => {
+            let interface: Ipv4Addr = interface.parse().unwrap_or(Ipv4Addr::UNSPECIFIED);
+            if join {
+                socket.join_multicast_v4(group, interface)
+            } else {
+                socket.leave_multicast_v4(group, interface)
+            }
+        }
+        IpAddr::V6(group) => {
+            let interface: u32 = interface.parse().unwrap_or(0);
+            if join {
+                socket.join_multicast_v6(&group, interface)
+            } else {
+                socket.leave_multicast_v6(&group, interface)
+            }
+        }
+    })
+}
+
+/// Run `f` on UDP socket `rid`.
+fn with_socket(rid: u32, f: impl FnOnce(&UdpSocket) -> std::io::Result<()>) -> Result<(), String> {
+    let resources = RESOURCES.lock().unwrap();
+    let datagram = resources.datagrams.get(&rid).ok_or(BAD_RESOURCE)?;
+    f(&datagram.socket).map_err(io_error)
+}
@@ -1,8 +1,11 @@
 // Copyright 2018-2025 the Deno authors. MIT license.
 // Deno namespace binding point - individual modules define their own APIs
 
+const __internal = globalThis[Symbol.for("mdeno.internal")];
 const fs = globalThis.__mdeno__.fs;
 const os = globalThis.__mdeno__.os;
+const fetchNs = globalThis.__mdeno__.fetch;
+const net = globalThis.__mdeno__.net;
 
 const denoNs = {
   // File System APIs
@@ -18,6 +21,30 @@ const denoNs = {
   // OS APIs
   exit: os.exit,
   env: os.env,
+
+  // HTTP APIs
+  HttpClient: fetchNs.HttpClient,
+  createHttpClient: fetchNs.createHttpClient,
+  serve: fetchNs.serve,
+
+  // Network APIs
+  connect: net.connect,
+  connectTls: net.connectTls,
+  startTls: net.startTls,
+  listen: net.listen,
+  listenTls: net.listenTls,
+  listenDatagram: net.listenDatagram,
+  resolveDns: net.resolveDns,
+  Conn: net.Conn,
+  TcpConn: net.TcpConn,
+  UnixConn: net.UnixConn,
+  TlsConn: net.TlsConn,
+  Listener: net.Listener,
+  DatagramConn: net.DatagramConn,
+  errors: net.errors,
+
+  // Runtime metadata
+  version: Object.freeze({ ...__internal.version }),
 };
 
 // Add noColor as a getter
@@ -34,6 +61,13 @@ Object.defineProperty(denoNs, "build", {
   },
 });
 
+// Add mainModule as a getter
+Object.defineProperty(denoNs, "mainModule", {
+  get() {
+    return __internal.mainModule;
+  },
+});
+
 // Define globalThis.Deno
 Object.defineProperty(globalThis, "Deno", {
   value: denoNs,
@@ -6,6 +6,7 @@ const noColorValue = __internal.noColor ?? false;
 
 Object.assign(globalThis.__mdeno__.os, {
   exit: function (code) {
+    __internal.lifecycle?.dispatchUnload();
     __internal.exit(code);
   },
 
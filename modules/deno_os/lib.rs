@@ -151,10 +151,17 @@ fn is_standalone_binary() -> bool {
         Err(_) => return false,
     };
 
+    // On Linux the payload is appended after the magic string, and libsui's
+    // trailer records how far back the payload starts
+    #[cfg(target_os = "linux")]
+    if let Some(found) = magic_before_elf_payload(&mut file, file_size) {
+        return found;
+    }
+
     let search_size = std::cmp::min(1024 * 1024, file_size);
     let offset = file_size.saturating_sub(search_size);
 
-    if let Err(_) = file.seek(std::io::SeekFrom::Start(offset as u64)) {
+    if file.seek(std::io::SeekFrom::Start(offset as u64)).is_err() {
         return false;
     }
 
@@ -167,3 +174,26 @@ fn is_standalone_binary() -> bool {
     // Search for the magic string "md3n04cl1"
     buffer[..bytes_read].windows(9).any(|w| w == b"md3n04cl1")
 }
+
+/// Check for the magic string right before the libsui ELF payload, or `None`
+/// if the file has no libsui trailer.
+#[cfg(target_os = "linux")]
+fn magic_before_elf_payload(file: &mut fs::File, file_size: usize) -> Option<bool> {
+    // Trailer: magic (u32), section name hash (u32), payload length + 16 (u64)
+    let mut trailer = [0u8; 16];
+    file.seek(std::io::SeekFrom::End(-16)).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    if u32::from_le_bytes(trailer[..4].try_into().unwrap()) != 0x501e {
+        return None;
+    }
+
+    let offset = u64::from_le_bytes(trailer[8..].try_into().unwrap());
+    let Some(start) = (file_size as u64).checked_sub(offset + 9) else {
+        return Some(false);
+    };
+    let mut magic = [0u8; 9];
+    if file.seek(std::io::SeekFrom::Start(start)).is_err() || file.read_exact(&mut magic).is_err() {
+        return Some(false);
+    }
+    Some(&magic == b"md3n04cl1")
+}
@@ -42,23 +42,22 @@ pub fn get_argv() -> Result<String, String> {
     let mut args: Vec<String> = std::env::args().collect();
 
     // Convert the first argument (executable path) to absolute path
-    if !args.is_empty() {
-        if let Ok(exe_path) = std::env::current_exe() {
-            args[0] = exe_path.to_string_lossy().to_string();
-        }
+    if !args.is_empty()
+        && let Ok(exe_path) = std::env::current_exe()
+    {
+        args[0] = exe_path.to_string_lossy().to_string();
     }
 
     // Convert argv[1] (script path) to absolute canonical path
-    if let Some(script_path) = args.get_mut(1) {
-        if !script_path.is_empty() {
-            if let Ok(canonical) = Path::new(script_path).canonicalize() {
-                let path_str = canonical.display().to_string();
-                *script_path = path_str
-                    .strip_prefix(r"\\?\")
-                    .unwrap_or(&path_str)
-                    .to_string();
-            }
-        }
+    if let Some(script_path) = args.get_mut(1)
+        && !script_path.is_empty()
+        && let Ok(canonical) = Path::new(script_path).canonicalize()
+    {
+        let path_str = canonical.display().to_string();
+        *script_path = path_str
+            .strip_prefix(r"\\?\")
+            .unwrap_or(&path_str)
+            .to_string();
     }
 
     Ok(serde_json::to_string(&args).unwrap())
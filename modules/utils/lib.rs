@@ -1,4 +1,6 @@
 use rquickjs::{Ctx, Result};
+use std::sync::mpsc;
+use std::time::Duration;
 
 pub trait ModuleDef {
     fn init(ctx: &Ctx<'_>) -> Result<()>;
@@ -21,3 +23,21 @@ macro_rules! add_internal_function {
         ))?
     }};
 }
+
+// Longest a poll from JS blocks when JS has nothing else to do, so an idle server
+// or socket does not keep a CPU busy
+const IDLE_WAIT: Duration = Duration::from_millis(10);
+
+/// Take the next item of `receiver` for a poll from JS, blocking for up to
+/// `IDLE_WAIT` when no promise job is pending.
+pub fn poll_receiver<T>(ctx: &Ctx<'_>, receiver: &mpsc::Receiver<T>) -> Option<T> {
+    // SAFETY: the runtime of a live context is valid
+    let busy = unsafe {
+        rquickjs::qjs::JS_IsJobPending(rquickjs::qjs::JS_GetRuntime(ctx.as_raw().as_ptr()))
+    };
+    if busy {
+        receiver.try_recv().ok()
+    } else {
+        receiver.recv_timeout(IDLE_WAIT).ok()
+    }
+}
@@ -0,0 +1,230 @@
+//! AES in the GCM, CBC and CTR modes, with 128, 192 and 256-bit keys.
+
+use crate::{Error, Result, bytes_of, throw};
+use ::aes::cipher::{Block, BlockEncrypt, KeyInit};
+use ::aes::{Aes128, Aes192, Aes256};
+use rquickjs::{Ctx, TypedArray};
+use utils::add_internal_function;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.aesGcm", aes_gcm);
+
+    add_internal_function!(ctx, "crypto.aesCbc", aes_cbc);
+
+    add_internal_function!(ctx, "crypto.aesCtr", aes_ctr);
+
+    Ok(())
+}
+
+fn aes_gcm<'js>(
+    ctx: Ctx<'js>,
+    encrypt: bool,
+    key: TypedArray<'js, u8>,
+    iv: TypedArray<'js, u8>,
+    additional_data: TypedArray<'js, u8>,
+    tag_length: usize,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let result = gcm(
+        encrypt,
+        &bytes_of(&key),
+        &bytes_of(&iv),
+        &bytes_of(&additional_data),
+        tag_length,
+        &bytes_of(&data),
+    );
+    TypedArray::new(ctx.clone(), result.map_err(|e| throw(&ctx, e))?)
+}
+
+fn aes_cbc<'js>(
+    ctx: Ctx<'js>,
+    encrypt: bool,
+    key: TypedArray<'js, u8>,
+    iv: TypedArray<'js, u8>,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let result = cbc(encrypt, &bytes_of(&key), &bytes_of(&iv), &bytes_of(&data));
+    TypedArray::new(ctx.clone(), result.map_err(|e| throw(&ctx, e))?)
+}
+
+fn aes_ctr<'js>(
+    ctx: Ctx<'js>,
+    key: TypedArray<'js, u8>,
+    counter: TypedArray<'js, u8>,
+    length: u32,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let result = ctr(
+        &bytes_of(&key),
+        &bytes_of(&counter),
+        length,
+        &bytes_of(&data),
+    );
+    TypedArray::new(ctx.clone(), result.map_err(|e| throw(&ctx, e))?)
+}
+
+fn invalid_key_length() -> Error {
+    Error::data("AES key must be 128, 192 or 256 bits long")
+}
+
+fn gcm(
+    encrypt: bool,
+    key: &[u8],
+    iv: &[u8],
+    additional_data: &[u8],
+    tag_length: usize,
+    data: &[u8],
+) -> Result<Vec<u8>> {
+    use aes_gcm::aead::consts::{U12, U13, U14, U15, U16};
+    use aes_gcm::aead::{Aead, Payload};
+    use aes_gcm::{AesGcm, Nonce};
+
+    let payload = Payload {
+        msg: data,
+        aad: additional_data,
+    };
+    let failed = |_| {
+        Error::operation(if encrypt {
+            "Encryption failed"
+        } else {
+            "Decryption failed"
+        })
+    };
+
+    macro_rules! run {
+        ($aes:ty, $nonce:ty, $tag:ty) => {{
+            let cipher = AesGcm::<$aes, $nonce, $tag>::new_from_slice(key)
+                .map_err(|_| invalid_key_length())?;
+            let nonce: &Nonce<$nonce> = iv.into();
+            if encrypt {
+                cipher.encrypt(nonce, payload).map_err(failed)
+            } else {
+                cipher.decrypt(nonce, payload).map_err(failed)
+            }
+        }};
+    }
+    macro_rules! with_tag {
+        ($aes:ty, $nonce:ty) => {
+            match tag_length {
+                96 => run!($aes, $nonce, U12),
+                104 => run!($aes, $nonce, U13),
+                112 => run!($aes, $nonce, U14),
+                120 => run!($aes, $nonce, U15),
+                128 => run!($aes, $nonce, U16),
+                _ => Err(Error::not_supported(format!(
+                    "Unsupported AES-GCM tag length: {}",
+                    tag_length
+                ))),
+            }
+        };
+    }
+    macro_rules! with_nonce {
+        ($aes:ty) => {
+            match iv.len() {
+                12 => with_tag!($aes, U12),
+                16 => with_tag!($aes, U16),
+                _ => Err(Error::not_supported(
+                    "AES-GCM initialization vector must be 12 or 16 bytes long",
+                )),
+            }
+        };
+    }
+
+    match key.len() {
+        16 => with_nonce!(Aes128),
+        24 => with_nonce!(Aes192),
+        32 => with_nonce!(Aes256),
+        _ => Err(invalid_key_length()),
+    }
+}
+
+/// CBC with PKCS#7 padding.
+fn cbc(encrypt: bool, key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    use ::cbc::cipher::block_padding::Pkcs7;
+    use ::cbc::cipher::{BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+
+    if iv.len() != 16 {
+        return Err(Error::operation(
+            "AES-CBC initialization vector must be 16 bytes long",
+        ));
+    }
+    macro_rules! run {
+        ($aes:ty) => {
+            if encrypt {
+                let cipher = ::cbc::Encryptor::<$aes>::new_from_slices(key, iv)
+                    .map_err(|_| invalid_key_length())?;
+                Ok(cipher.encrypt_padded_vec_mut::<Pkcs7>(data))
+            } else {
+                let cipher = ::cbc::Decryptor::<$aes>::new_from_slices(key, iv)
+                    .map_err(|_| invalid_key_length())?;
+                cipher
+                    .decrypt_padded_vec_mut::<Pkcs7>(data)
+                    .map_err(|_| Error::operation("Decryption failed"))
+            }
+        };
+    }
+    match key.len() {
+        16 => run!(Aes128),
+        24 => run!(Aes192),
+        32 => run!(Aes256),
+        _ => Err(invalid_key_length()),
+    }
+}
+
+/// CTR, where the rightmost `length` bits of the counter block are incremented and
+/// wrap around. Encryption and decryption are the same.
+fn ctr(key: &[u8], counter: &[u8], length: u32, data: &[u8]) -> Result<Vec<u8>> {
+    let counter: [u8; 16] = counter
+        .try_into()
+        .map_err(|_| Error::operation("AES-CTR counter must be 16 bytes long"))?;
+    if !(1..=128).contains(&length) {
+        return Err(Error::operation(
+            "AES-CTR counter length must be between 1 and 128",
+        ));
+    }
+    // The counter must not repeat within a message
+    let blocks = data.len().div_ceil(16) as u128;
+    if length < 128 && blocks > 1u128 << length {
+        return Err(Error::operation("AES-CTR counter would wrap around"));
+    }
+
+    match key.len() {
+        16 => Ok(ctr_with(
+            &Aes128::new_from_slice(key).unwrap(),
+            counter,
+            length,
+            data,
+        )),
+        24 => Ok(ctr_with(
+            &Aes192::new_from_slice(key).unwrap(),
+            counter,
+            length,
+            data,
+        )),
+        32 => Ok(ctr_with(
+            &Aes256::new_from_slice(key).unwrap(),
+            counter,
+            length,
+            data,
+        )),
+        _ => Err(invalid_key_length()),
+    }
+}
+
+fn ctr_with<C: BlockEncrypt>(cipher: &C, counter: [u8; 16], length: u32, data: &[u8]) -> Vec<u8> {
+    let mask = if length == 128 {
+        u128::MAX
+    } else {
+        (1u128 << length) - 1
+    };
+    let mut block = u128::from_be_bytes(counter);
+    let mut output = Vec::with_capacity(data.len());
+    for chunk in data.chunks(16) {
+        let mut keystream = Block::<C>::default();
+        keystream.copy_from_slice(&block.to_be_bytes());
+        cipher.encrypt_block(&mut keystream);
+        output.extend(chunk.iter().zip(keystream.iter()).map(|(a, b)| a ^ b));
+        block = (block & !mask) | (block.wrapping_add(1) & mask);
+    }
+    output
+}
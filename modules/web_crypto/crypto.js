@@ -0,0 +1,921 @@
+// https://w3c.github.io/webcrypto/
+// https://wicg.github.io/webcrypto-secure-curves/
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+// Algorithms compiled into this build
+const AVAILABLE = JSON.parse(__internal.crypto.algorithms());
+
+const HASHES = ["SHA-1", "SHA-256", "SHA-384", "SHA-512"];
+const AES = ["AES-GCM", "AES-CBC", "AES-CTR"];
+const SIGNATURES = ["HMAC", "ECDSA", "Ed25519", "RSA-PSS"];
+const ENCRYPTIONS = [...AES, "RSA-OAEP"];
+const KEY_GENERATIONS = [
+  "HMAC",
+  ...AES,
+  "ECDSA",
+  "Ed25519",
+  "X25519",
+  "RSA-PSS",
+  "RSA-OAEP",
+];
+
+// Algorithms of each operation
+// https://w3c.github.io/webcrypto/#algorithm-normalization-internal-object
+const OPERATIONS = {
+  digest: HASHES,
+  sign: SIGNATURES,
+  verify: SIGNATURES,
+  encrypt: ENCRYPTIONS,
+  decrypt: ENCRYPTIONS,
+  wrapKey: ENCRYPTIONS,
+  unwrapKey: ENCRYPTIONS,
+  generateKey: KEY_GENERATIONS,
+  importKey: [...KEY_GENERATIONS, "PBKDF2", "HKDF"],
+  deriveBits: ["X25519", "PBKDF2", "HKDF"],
+  getKeyLength: ["HMAC", ...AES],
+};
+
+const KEY_USAGES = [
+  "encrypt",
+  "decrypt",
+  "sign",
+  "verify",
+  "deriveKey",
+  "deriveBits",
+  "wrapKey",
+  "unwrapKey",
+];
+
+const SIGN_USAGES = { private: ["sign"], public: ["verify"] };
+const AES_USAGES = { secret: ["encrypt", "decrypt", "wrapKey", "unwrapKey"] };
+const DERIVE_USAGES = { secret: ["deriveKey", "deriveBits"] };
+
+// Usages allowed for each type of key of an algorithm
+const USAGES = {
+  "HMAC": { secret: ["sign", "verify"] },
+  "AES-GCM": AES_USAGES,
+  "AES-CBC": AES_USAGES,
+  "AES-CTR": AES_USAGES,
+  "ECDSA": SIGN_USAGES,
+  "Ed25519": SIGN_USAGES,
+  "RSA-PSS": SIGN_USAGES,
+  "RSA-OAEP": {
+    private: ["decrypt", "unwrapKey"],
+    public: ["encrypt", "wrapKey"],
+  },
+  "X25519": { private: ["deriveKey", "deriveBits"], public: [] },
+  "PBKDF2": DERIVE_USAGES,
+  "HKDF": DERIVE_USAGES,
+};
+
+// Block sizes in bits, the default length of HMAC keys
+const BLOCK_SIZES = {
+  "SHA-1": 512,
+  "SHA-256": 512,
+  "SHA-384": 1024,
+  "SHA-512": 1024,
+};
+
+function cryptoError(error) {
+  const match = /^(\w+): ([^]*)$/.exec(error?.message ?? "");
+  if (match === null) return error;
+  const [, name, message] = match;
+  if (name === "TypeError") return new TypeError(message);
+  return new DOMException(message, name);
+}
+
+function call(f) {
+  try {
+    return f();
+  } catch (error) {
+    throw cryptoError(error);
+  }
+}
+
+// Copy of the bytes of a BufferSource
+function bufferSource(data, name) {
+  if (data instanceof ArrayBuffer) return new Uint8Array(data.slice(0));
+  if (ArrayBuffer.isView(data)) {
+    return new Uint8Array(
+      data.buffer.slice(data.byteOffset, data.byteOffset + data.byteLength),
+    );
+  }
+  throw new TypeError(`${name} must be an ArrayBuffer or an ArrayBufferView`);
+}
+
+function arrayBuffer(bytes) {
+  return bytes.buffer.slice(bytes.byteOffset, bytes.byteOffset + bytes.length);
+}
+
+function required(algorithm, member) {
+  const value = algorithm[member];
+  if (value === undefined) {
+    throw new TypeError(`${algorithm.name}: '${member}' is required`);
+  }
+  return value;
+}
+
+// https://w3c.github.io/webcrypto/#algorithm-normalization-normalize-an-algorithm
+function normalizeAlgorithm(algorithm, operation) {
+  if (typeof algorithm === "string") algorithm = { name: algorithm };
+  if (typeof algorithm !== "object" || algorithm === null) {
+    throw new TypeError("Algorithm must be a string or an object");
+  }
+  if (algorithm.name === undefined) {
+    throw new TypeError("Algorithm name is missing");
+  }
+  const name = String(algorithm.name).toUpperCase();
+  const canonical = OPERATIONS[operation].find((candidate) =>
+    candidate.toUpperCase() === name && AVAILABLE.includes(candidate)
+  );
+  if (canonical === undefined) {
+    throw new DOMException(
+      `Unrecognized algorithm name '${algorithm.name}'`,
+      "NotSupportedError",
+    );
+  }
+  const normalized = { ...algorithm, name: canonical };
+  if (algorithm.hash !== undefined) {
+    normalized.hash = normalizeAlgorithm(algorithm.hash, "digest");
+  }
+  return normalized;
+}
+
+function normalizeUsages(usages) {
+  usages = Array.from(usages ?? [], String);
+  for (const usage of usages) {
+    if (!KEY_USAGES.includes(usage)) {
+      throw new TypeError(`'${usage}' is not a valid key usage`);
+    }
+  }
+  return KEY_USAGES.filter((usage) => usages.includes(usage));
+}
+
+function checkUsages(usages, allowed) {
+  for (const usage of usages) {
+    if (!allowed.includes(usage)) {
+      throw new DOMException(
+        `Unsupported key usage '${usage}'`,
+        "SyntaxError",
+      );
+    }
+  }
+}
+
+// The key data of each CryptoKey: secret keys raw, private keys as PKCS#8 and
+// public keys as SPKI
+const keyMaterial = new WeakMap();
+
+// https://w3c.github.io/webcrypto/#cryptokey-interface
+class CryptoKey {
+  #type;
+  #extractable;
+  #algorithm;
+  #usages;
+
+  constructor(
+    key = null,
+    type = undefined,
+    extractable = undefined,
+    algorithm = undefined,
+    usages = undefined,
+    data = undefined,
+  ) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#type = type;
+    this.#extractable = extractable;
+    this.#algorithm = algorithm;
+    this.#usages = usages;
+    keyMaterial.set(this, data);
+  }
+
+  get type() {
+    return this.#type;
+  }
+
+  get extractable() {
+    return this.#extractable;
+  }
+
+  get algorithm() {
+    return this.#algorithm;
+  }
+
+  get usages() {
+    return this.#usages;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "CryptoKey";
+  }
+}
+
+function createKey(type, extractable, algorithm, usages, data) {
+  return new CryptoKey(
+    illegalConstructorKey,
+    type,
+    Boolean(extractable),
+    algorithm,
+    usages,
+    data,
+  );
+}
+
+// Check that `key` is for `algorithm` and allows `usage`
+function checkKey(key, algorithm, usage) {
+  if (!(key instanceof CryptoKey)) {
+    throw new TypeError("Key must be a CryptoKey");
+  }
+  if (key.algorithm.name !== algorithm.name) {
+    throw new DOMException(
+      `Key is not for ${algorithm.name}`,
+      "InvalidAccessError",
+    );
+  }
+  if (!key.usages.includes(usage)) {
+    throw new DOMException(
+      `Key does not allow '${usage}'`,
+      "InvalidAccessError",
+    );
+  }
+}
+
+// What the Rust side calls the keys of an algorithm
+function keyKind(algorithm) {
+  switch (algorithm.name) {
+    case "ECDSA":
+      return String(required(algorithm, "namedCurve"));
+    case "Ed25519":
+    case "X25519":
+      return algorithm.name;
+    case "RSA-PSS":
+    case "RSA-OAEP":
+      return "RSA";
+    default:
+      return "secret";
+  }
+}
+
+// The "alg" of JWKs for keys of `algorithm`, when it has one
+function jwkAlg(algorithm) {
+  const bits = algorithm.hash?.name.slice(4);
+  switch (algorithm.name) {
+    case "HMAC":
+      return `HS${bits}`;
+    case "AES-GCM":
+    case "AES-CBC":
+    case "AES-CTR":
+      return `A${algorithm.length}${algorithm.name.slice(4)}`;
+    case "RSA-PSS":
+      return `PS${bits}`;
+    case "RSA-OAEP":
+      return bits === "1" ? "RSA-OAEP" : `RSA-OAEP-${bits}`;
+  }
+}
+
+function hashAlgorithm(algorithm) {
+  return { name: required(algorithm, "hash").name };
+}
+
+// Length in bits of the keys `algorithm` derives or generates
+// https://w3c.github.io/webcrypto/#dfn-get-key-length
+function keyLength(algorithm) {
+  if (algorithm.name === "HMAC") {
+    return algorithm.length === undefined
+      ? BLOCK_SIZES[hashAlgorithm(algorithm).name]
+      : Number(algorithm.length);
+  }
+  const length = Number(required(algorithm, "length"));
+  if (![128, 192, 256].includes(length)) {
+    throw new DOMException(
+      "AES key length must be 128, 192 or 256 bits",
+      "OperationError",
+    );
+  }
+  return length;
+}
+
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-importKey
+function importKey(format, keyData, algorithm, extractable, usages) {
+  if (!["raw", "pkcs8", "spki", "jwk"].includes(format)) {
+    throw new TypeError(`'${format}' is not a valid key format`);
+  }
+  let data;
+  if (format === "jwk") {
+    if (typeof keyData !== "object" || keyData === null) {
+      throw new TypeError("JWK must be an object");
+    }
+    checkJwk(keyData, algorithm, extractable, usages);
+    data = JSON.stringify(keyData);
+  } else {
+    data = bufferSource(keyData, "Key data");
+  }
+  if (
+    ["PBKDF2", "HKDF"].includes(algorithm.name) &&
+    (format !== "raw" || extractable)
+  ) {
+    throw new DOMException(
+      `${algorithm.name} keys must be raw and not extractable`,
+      format === "raw" ? "SyntaxError" : "NotSupportedError",
+    );
+  }
+  if (keyKind(algorithm) === "secret" && !["raw", "jwk"].includes(format)) {
+    throw new DOMException(
+      `Unsupported key format '${format}'`,
+      "NotSupportedError",
+    );
+  }
+
+  const kind = keyKind(algorithm);
+  const imported = call(() =>
+    __internal.crypto.importKey(kind, format, data)
+  );
+  const allowed = USAGES[algorithm.name][imported.type];
+  checkUsages(usages, allowed);
+  if (imported.type !== "public" && usages.length === 0) {
+    throw new DOMException("Key usages must not be empty", "SyntaxError");
+  }
+
+  let keyAlgorithm;
+  switch (algorithm.name) {
+    case "HMAC":
+      if (imported.length === 0) {
+        throw new DOMException("HMAC key must not be empty", "DataError");
+      }
+      if (
+        algorithm.length !== undefined &&
+        Number(algorithm.length) !== imported.length
+      ) {
+        throw new DOMException(
+          "HMAC key length does not match the key",
+          "DataError",
+        );
+      }
+      keyAlgorithm = {
+        name: "HMAC",
+        hash: hashAlgorithm(algorithm),
+        length: imported.length,
+      };
+      break;
+    case "AES-GCM":
+    case "AES-CBC":
+    case "AES-CTR":
+      if (![128, 192, 256].includes(imported.length)) {
+        throw new DOMException(
+          "AES key must be 128, 192 or 256 bits long",
+          "DataError",
+        );
+      }
+      keyAlgorithm = { name: algorithm.name, length: imported.length };
+      break;
+    case "ECDSA":
+      keyAlgorithm = { name: "ECDSA", namedCurve: kind };
+      break;
+    case "RSA-PSS":
+    case "RSA-OAEP":
+      keyAlgorithm = {
+        name: algorithm.name,
+        modulusLength: imported.length,
+        publicExponent: imported.publicExponent,
+        hash: hashAlgorithm(algorithm),
+      };
+      break;
+    default:
+      keyAlgorithm = { name: algorithm.name };
+  }
+  return createKey(
+    imported.type,
+    extractable,
+    keyAlgorithm,
+    usages,
+    imported.data,
+  );
+}
+
+// Members of a JWK that are checked against the import
+// https://w3c.github.io/webcrypto/#concept-parse-a-jwk
+function checkJwk(jwk, algorithm, extractable, usages) {
+  const dataError = (message) => new DOMException(message, "DataError");
+  if (jwk.ext === false && extractable) {
+    throw dataError("JWK is not extractable");
+  }
+  if (
+    jwk.key_ops !== undefined &&
+    !usages.every((usage) => jwk.key_ops.includes(usage))
+  ) {
+    throw dataError("JWK \"key_ops\" does not allow the key usages");
+  }
+  if (jwk.use !== undefined && usages.length > 0) {
+    const use = ["sign", "verify"].includes(usages[0]) ? "sig" : "enc";
+    if (jwk.use !== use) throw dataError(`JWK "use" must be "${use}"`);
+  }
+  if (jwk.alg === undefined) return;
+  let expected;
+  if (algorithm.name === "HMAC") {
+    expected = jwkAlg({ ...algorithm, hash: hashAlgorithm(algorithm) });
+  } else if (AES.includes(algorithm.name)) {
+    const bytes = typeof jwk.k === "string" ? jwk.k.replace(/=+$/, "") : "";
+    expected = jwkAlg({ ...algorithm, length: (bytes.length * 6) & ~63 });
+  } else if (["RSA-PSS", "RSA-OAEP"].includes(algorithm.name)) {
+    expected = jwkAlg({ ...algorithm, hash: hashAlgorithm(algorithm) });
+  }
+  if (expected !== undefined && jwk.alg !== expected) {
+    throw dataError(`JWK "alg" must be "${expected}"`);
+  }
+}
+
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-exportKey
+function exportKey(format, key) {
+  if (!(key instanceof CryptoKey)) {
+    throw new TypeError("Key must be a CryptoKey");
+  }
+  if (!["raw", "pkcs8", "spki", "jwk"].includes(format)) {
+    throw new TypeError(`'${format}' is not a valid key format`);
+  }
+  if (!key.extractable) {
+    throw new DOMException("Key is not extractable", "InvalidAccessError");
+  }
+  const invalidAccess = (message) =>
+    new DOMException(message, "InvalidAccessError");
+  if (format === "raw" && key.type === "private") {
+    throw invalidAccess("Private keys cannot be exported as raw");
+  }
+  if (format === "spki" && key.type !== "public") {
+    throw invalidAccess("Only public keys can be exported as SPKI");
+  }
+  if (format === "pkcs8" && key.type !== "private") {
+    throw invalidAccess("Only private keys can be exported as PKCS#8");
+  }
+  if (["PBKDF2", "HKDF"].includes(key.algorithm.name)) {
+    throw new DOMException(
+      `${key.algorithm.name} keys cannot be exported`,
+      "NotSupportedError",
+    );
+  }
+
+  const exported = call(() =>
+    __internal.crypto.exportKey(
+      keyKind(key.algorithm),
+      format,
+      key.type,
+      keyMaterial.get(key),
+    )
+  );
+  if (format !== "jwk") return arrayBuffer(exported);
+  const jwk = JSON.parse(exported);
+  const alg = jwkAlg(key.algorithm);
+  if (alg !== undefined) jwk.alg = alg;
+  jwk.key_ops = [...key.usages];
+  jwk.ext = key.extractable;
+  return jwk;
+}
+
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-generateKey
+function generateKey(algorithm, extractable, usages) {
+  const { name } = algorithm;
+  if (name === "HMAC" || AES.includes(name)) {
+    checkUsages(usages, USAGES[name].secret);
+    if (usages.length === 0) {
+      throw new DOMException("Key usages must not be empty", "SyntaxError");
+    }
+    const length = keyLength(algorithm);
+    if (length === 0 || length % 8 !== 0) {
+      throw new DOMException(
+        "Key length must be a non-zero multiple of 8",
+        "OperationError",
+      );
+    }
+    const data = call(() => __internal.crypto.randomBytes(length / 8));
+    const keyAlgorithm = name === "HMAC"
+      ? { name, hash: hashAlgorithm(algorithm), length }
+      : { name, length };
+    return createKey("secret", extractable, keyAlgorithm, usages, data);
+  }
+
+  const allowed = USAGES[name];
+  checkUsages(usages, [...allowed.private, ...allowed.public]);
+  const privateUsages = usages.filter((usage) =>
+    allowed.private.includes(usage)
+  );
+  if (privateUsages.length === 0) {
+    throw new DOMException(
+      "Private key usages must not be empty",
+      "SyntaxError",
+    );
+  }
+
+  const kind = keyKind(algorithm);
+  let keyAlgorithm = { name };
+  let pair;
+  if (kind === "RSA") {
+    const modulusLength = Number(required(algorithm, "modulusLength"));
+    const publicExponent = bufferSource(
+      required(algorithm, "publicExponent"),
+      "Public exponent",
+    );
+    keyAlgorithm = {
+      name,
+      modulusLength,
+      publicExponent,
+      hash: hashAlgorithm(algorithm),
+    };
+    pair = call(() =>
+      __internal.crypto.generateKeyPair(kind, modulusLength, publicExponent)
+    );
+  } else {
+    if (name === "ECDSA") keyAlgorithm = { name, namedCurve: kind };
+    pair = call(() => __internal.crypto.generateKeyPair(kind));
+  }
+  return {
+    // Public keys are always extractable
+    publicKey: createKey(
+      "public",
+      true,
+      keyAlgorithm,
+      usages.filter((usage) => allowed.public.includes(usage)),
+      pair.publicKey,
+    ),
+    privateKey: createKey(
+      "private",
+      extractable,
+      keyAlgorithm,
+      privateUsages,
+      pair.privateKey,
+    ),
+  };
+}
+
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-sign
+function sign(algorithm, key, data) {
+  const bytes = keyMaterial.get(key);
+  switch (algorithm.name) {
+    case "HMAC":
+      return __internal.crypto.hmacSign(key.algorithm.hash.name, bytes, data);
+    case "ECDSA":
+      return __internal.crypto.ecdsaSign(
+        key.algorithm.namedCurve,
+        hashAlgorithm(algorithm).name,
+        bytes,
+        data,
+      );
+    case "Ed25519":
+      return __internal.crypto.ed25519Sign(bytes, data);
+    case "RSA-PSS":
+      return __internal.crypto.rsaPssSign(
+        key.algorithm.hash.name,
+        bytes,
+        Number(required(algorithm, "saltLength")),
+        data,
+      );
+  }
+}
+
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-verify
+function verify(algorithm, key, signature, data) {
+  const bytes = keyMaterial.get(key);
+  switch (algorithm.name) {
+    case "HMAC":
+      return __internal.crypto.hmacVerify(
+        key.algorithm.hash.name,
+        bytes,
+        data,
+        signature,
+      );
+    case "ECDSA":
+      return __internal.crypto.ecdsaVerify(
+        key.algorithm.namedCurve,
+        hashAlgorithm(algorithm).name,
+        bytes,
+        data,
+        signature,
+      );
+    case "Ed25519":
+      return __internal.crypto.ed25519Verify(bytes, data, signature);
+    case "RSA-PSS":
+      return __internal.crypto.rsaPssVerify(
+        key.algorithm.hash.name,
+        bytes,
+        Number(required(algorithm, "saltLength")),
+        data,
+        signature,
+      );
+  }
+}
+
+// Encrypt or decrypt `data`
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-encrypt
+function cipher(encrypt, algorithm, key, data) {
+  const bytes = keyMaterial.get(key);
+  switch (algorithm.name) {
+    case "AES-GCM":
+      return __internal.crypto.aesGcm(
+        encrypt,
+        bytes,
+        bufferSource(required(algorithm, "iv"), "iv"),
+        bufferSource(algorithm.additionalData ?? new Uint8Array(), "Data"),
+        Number(algorithm.tagLength ?? 128),
+        data,
+      );
+    case "AES-CBC":
+      return __internal.crypto.aesCbc(
+        encrypt,
+        bytes,
+        bufferSource(required(algorithm, "iv"), "iv"),
+        data,
+      );
+    case "AES-CTR":
+      return __internal.crypto.aesCtr(
+        bytes,
+        bufferSource(required(algorithm, "counter"), "Counter"),
+        Number(required(algorithm, "length")),
+        data,
+      );
+    case "RSA-OAEP":
+      return __internal.crypto.rsaOaep(
+        encrypt,
+        key.algorithm.hash.name,
+        bytes,
+        bufferSource(algorithm.label ?? new Uint8Array(), "Label"),
+        data,
+      );
+  }
+}
+
+// https://w3c.github.io/webcrypto/#SubtleCrypto-method-deriveBits
+function deriveBits(algorithm, baseKey, length) {
+  const bytes = keyMaterial.get(baseKey);
+  switch (algorithm.name) {
+    case "X25519": {
+      const publicKey = required(algorithm, "public");
+      if (
+        !(publicKey instanceof CryptoKey) ||
+        publicKey.type !== "public" ||
+        publicKey.algorithm.name !== "X25519"
+      ) {
+        throw new DOMException(
+          "'public' must be an X25519 public key",
+          "InvalidAccessError",
+        );
+      }
+      const bits = __internal.crypto.x25519DeriveBits(
+        bytes,
+        keyMaterial.get(publicKey),
+      );
+      if (length === null) return bits;
+      if (length > bits.length * 8) {
+        throw new DOMException(
+          `X25519 derives at most ${bits.length * 8} bits`,
+          "OperationError",
+        );
+      }
+      // Bits past the length are zeroed in the last byte
+      const derived = bits.slice(0, Math.ceil(length / 8));
+      if (length % 8 !== 0) {
+        derived[derived.length - 1] &= 0xff << (8 - length % 8);
+      }
+      return derived;
+    }
+    case "PBKDF2":
+      if (length === null) {
+        throw new DOMException("Length is required", "OperationError");
+      }
+      return __internal.crypto.pbkdf2(
+        hashAlgorithm(algorithm).name,
+        bytes,
+        bufferSource(required(algorithm, "salt"), "Salt"),
+        Number(required(algorithm, "iterations")),
+        length,
+      );
+    case "HKDF":
+      if (length === null) {
+        throw new DOMException("Length is required", "OperationError");
+      }
+      return __internal.crypto.hkdf(
+        hashAlgorithm(algorithm).name,
+        bytes,
+        bufferSource(required(algorithm, "salt"), "Salt"),
+        bufferSource(required(algorithm, "info"), "Info"),
+        length,
+      );
+  }
+}
+
+// https://w3c.github.io/webcrypto/#subtlecrypto-interface
+class SubtleCrypto {
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+  }
+
+  async digest(algorithm, data) {
+    algorithm = normalizeAlgorithm(algorithm, "digest");
+    data = bufferSource(data, "Data");
+    return arrayBuffer(
+      call(() => __internal.crypto.digest(algorithm.name, data)),
+    );
+  }
+
+  async encrypt(algorithm, key, data) {
+    algorithm = normalizeAlgorithm(algorithm, "encrypt");
+    data = bufferSource(data, "Data");
+    checkKey(key, algorithm, "encrypt");
+    return arrayBuffer(call(() => cipher(true, algorithm, key, data)));
+  }
+
+  async decrypt(algorithm, key, data) {
+    algorithm = normalizeAlgorithm(algorithm, "decrypt");
+    data = bufferSource(data, "Data");
+    checkKey(key, algorithm, "decrypt");
+    return arrayBuffer(call(() => cipher(false, algorithm, key, data)));
+  }
+
+  async sign(algorithm, key, data) {
+    algorithm = normalizeAlgorithm(algorithm, "sign");
+    data = bufferSource(data, "Data");
+    checkKey(key, algorithm, "sign");
+    return arrayBuffer(call(() => sign(algorithm, key, data)));
+  }
+
+  async verify(algorithm, key, signature, data) {
+    algorithm = normalizeAlgorithm(algorithm, "verify");
+    signature = bufferSource(signature, "Signature");
+    data = bufferSource(data, "Data");
+    checkKey(key, algorithm, "verify");
+    return call(() => verify(algorithm, key, signature, data));
+  }
+
+  async generateKey(algorithm, extractable, keyUsages) {
+    algorithm = normalizeAlgorithm(algorithm, "generateKey");
+    return generateKey(algorithm, extractable, normalizeUsages(keyUsages));
+  }
+
+  async importKey(format, keyData, algorithm, extractable, keyUsages) {
+    algorithm = normalizeAlgorithm(algorithm, "importKey");
+    return importKey(
+      String(format),
+      keyData,
+      algorithm,
+      Boolean(extractable),
+      normalizeUsages(keyUsages),
+    );
+  }
+
+  async exportKey(format, key) {
+    return exportKey(String(format), key);
+  }
+
+  async deriveBits(algorithm, baseKey, length = null) {
+    algorithm = normalizeAlgorithm(algorithm, "deriveBits");
+    checkKey(baseKey, algorithm, "deriveBits");
+    length = length === null ? null : Number(length);
+    return arrayBuffer(call(() => deriveBits(algorithm, baseKey, length)));
+  }
+
+  async deriveKey(
+    algorithm,
+    baseKey,
+    derivedKeyType,
+    extractable,
+    keyUsages,
+  ) {
+    algorithm = normalizeAlgorithm(algorithm, "deriveBits");
+    derivedKeyType = normalizeAlgorithm(derivedKeyType, "importKey");
+    const length = keyLength(
+      normalizeAlgorithm(derivedKeyType, "getKeyLength"),
+    );
+    checkKey(baseKey, algorithm, "deriveKey");
+    const bits = call(() => deriveBits(algorithm, baseKey, length));
+    return importKey(
+      "raw",
+      bits,
+      derivedKeyType,
+      Boolean(extractable),
+      normalizeUsages(keyUsages),
+    );
+  }
+
+  async wrapKey(format, key, wrappingKey, wrapAlgorithm) {
+    wrapAlgorithm = normalizeAlgorithm(wrapAlgorithm, "wrapKey");
+    checkKey(wrappingKey, wrapAlgorithm, "wrapKey");
+    if (!(key instanceof CryptoKey)) {
+      throw new TypeError("Key must be a CryptoKey");
+    }
+    let exported = exportKey(String(format), key);
+    exported = format === "jwk"
+      ? new TextEncoder().encode(JSON.stringify(exported))
+      : new Uint8Array(exported);
+    return arrayBuffer(
+      call(() => cipher(true, wrapAlgorithm, wrappingKey, exported)),
+    );
+  }
+
+  async unwrapKey(
+    format,
+    wrappedKey,
+    unwrappingKey,
+    unwrapAlgorithm,
+    unwrappedKeyAlgorithm,
+    extractable,
+    keyUsages,
+  ) {
+    unwrapAlgorithm = normalizeAlgorithm(unwrapAlgorithm, "unwrapKey");
+    unwrappedKeyAlgorithm = normalizeAlgorithm(
+      unwrappedKeyAlgorithm,
+      "importKey",
+    );
+    wrappedKey = bufferSource(wrappedKey, "Wrapped key");
+    checkKey(unwrappingKey, unwrapAlgorithm, "unwrapKey");
+    let key = call(() =>
+      cipher(false, unwrapAlgorithm, unwrappingKey, wrappedKey)
+    );
+    if (format === "jwk") {
+      try {
+        key = JSON.parse(new TextDecoder().decode(key));
+      } catch {
+        throw new DOMException("Unwrapped key is not a JWK", "DataError");
+      }
+    }
+    return importKey(
+      String(format),
+      key,
+      unwrappedKeyAlgorithm,
+      Boolean(extractable),
+      normalizeUsages(keyUsages),
+    );
+  }
+
+  get [Symbol.toStringTag]() {
+    return "SubtleCrypto";
+  }
+}
+
+const INTEGER_ARRAYS = [
+  Int8Array,
+  Uint8Array,
+  Uint8ClampedArray,
+  Int16Array,
+  Uint16Array,
+  Int32Array,
+  Uint32Array,
+  BigInt64Array,
+  BigUint64Array,
+];
+
+// https://w3c.github.io/webcrypto/#crypto-interface
+class Crypto {
+  #subtle = new SubtleCrypto(illegalConstructorKey);
+
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+  }
+
+  get subtle() {
+    return this.#subtle;
+  }
+
+  getRandomValues(array) {
+    if (!INTEGER_ARRAYS.some((type) => array instanceof type)) {
+      throw new DOMException(
+        "Array must be an integer typed array",
+        "TypeMismatchError",
+      );
+    }
+    if (array.byteLength > 65536) {
+      throw new DOMException(
+        `The array of ${array.byteLength} bytes exceeds the maximum of 65536`,
+        "QuotaExceededError",
+      );
+    }
+    const bytes = call(() => __internal.crypto.randomBytes(array.byteLength));
+    new Uint8Array(array.buffer, array.byteOffset, array.byteLength).set(bytes);
+    return array;
+  }
+
+  // https://www.rfc-editor.org/rfc/rfc9562#name-uuid-version-4
+  randomUUID() {
+    const bytes = call(() => __internal.crypto.randomBytes(16));
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    const hex = Array.from(bytes, (byte) => byte.toString(16).padStart(2, "0"))
+      .join("");
+    return `${hex.slice(0, 8)}-${hex.slice(8, 12)}-${hex.slice(12, 16)}-${
+      hex.slice(16, 20)
+    }-${hex.slice(20)}`;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Crypto";
+  }
+}
+
+globalThis.Crypto = Crypto;
+globalThis.CryptoKey = CryptoKey;
+globalThis.SubtleCrypto = SubtleCrypto;
+globalThis.crypto = new Crypto(illegalConstructorKey);
@@ -0,0 +1,82 @@
+//! SHA digests, and HMAC signatures over them.
+
+use crate::{Hash, bytes_of, throw, with_hash};
+use rquickjs::{Ctx, TypedArray};
+use sha2::Digest;
+use utils::add_internal_function;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.digest", digest_bytes);
+
+    #[cfg(feature = "hmac")]
+    add_internal_function!(ctx, "crypto.hmacSign", hmac::hmac_sign);
+
+    #[cfg(feature = "hmac")]
+    add_internal_function!(
+        ctx,
+        "crypto.hmacVerify",
+        |ctx: Ctx<'_>,
+         hash: String,
+         key: TypedArray<'_, u8>,
+         data: TypedArray<'_, u8>,
+         signature: TypedArray<'_, u8>| {
+            let hash = Hash::parse(&hash).map_err(|e| throw(&ctx, e))?;
+            Ok::<_, rquickjs::Error>(hmac::verify(
+                hash,
+                &bytes_of(&key),
+                &bytes_of(&data),
+                &bytes_of(&signature),
+            ))
+        }
+    );
+
+    Ok(())
+}
+
+fn digest_bytes<'js>(
+    ctx: Ctx<'js>,
+    hash: String,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let hash = Hash::parse(&hash).map_err(|e| throw(&ctx, e))?;
+    TypedArray::new(ctx, digest(hash, &bytes_of(&data)))
+}
+
+pub(crate) fn digest(hash: Hash, data: &[u8]) -> Vec<u8> {
+    with_hash!(hash, D => D::digest(data).to_vec())
+}
+
+#[cfg(feature = "hmac")]
+pub(crate) mod hmac {
+    use crate::{Hash, bytes_of, throw, with_hash};
+    use ::hmac::{Hmac, Mac};
+    use rquickjs::{Ctx, TypedArray};
+
+    pub(super) fn hmac_sign<'js>(
+        ctx: Ctx<'js>,
+        hash: String,
+        key: TypedArray<'js, u8>,
+        data: TypedArray<'js, u8>,
+    ) -> rquickjs::Result<TypedArray<'js, u8>> {
+        let hash = Hash::parse(&hash).map_err(|e| throw(&ctx, e))?;
+        TypedArray::new(ctx, sign(hash, &bytes_of(&key), &bytes_of(&data)))
+    }
+
+    pub(crate) fn sign(hash: Hash, key: &[u8], data: &[u8]) -> Vec<u8> {
+        with_hash!(hash, D => {
+            // HMAC takes keys of any length
+            let mut mac = Hmac::<D>::new_from_slice(key).expect("HMAC key");
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        })
+    }
+
+    /// Check `signature` in constant time.
+    pub(crate) fn verify(hash: Hash, key: &[u8], data: &[u8], signature: &[u8]) -> bool {
+        with_hash!(hash, D => {
+            let mut mac = Hmac::<D>::new_from_slice(key).expect("HMAC key");
+            mac.update(data);
+            mac.verify_slice(signature).is_ok()
+        })
+    }
+}
@@ -0,0 +1,226 @@
+//! ECDSA over the P-256 and P-384 curves, and their keys.
+
+use crate::digest::digest;
+use crate::{
+    Error, Exported, Hash, ImportedKey, KeyData, Result, base64url, bytes_of, jwk_member,
+    required_jwk_member, throw,
+};
+use rquickjs::{Ctx, TypedArray};
+use utils::add_internal_function;
+
+/// Evaluate `$body` with `$curve` standing for the crate of the curve `$kind`.
+macro_rules! with_curve {
+    ($kind:expr, $curve:ident => $body:expr) => {
+        match $kind {
+            "P-256" => {
+                use p256 as $curve;
+                $body
+            }
+            "P-384" => {
+                use p384 as $curve;
+                $body
+            }
+            other => Err(Error::not_supported(format!(
+                "Unsupported curve: {}",
+                other
+            ))),
+        }
+    };
+}
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.ecdsaSign", ecdsa_sign);
+
+    add_internal_function!(ctx, "crypto.ecdsaVerify", |ctx: Ctx<'_>,
+                                                       curve: String,
+                                                       hash: String,
+                                                       key: TypedArray<'_, u8>,
+                                                       data: TypedArray<'_, u8>,
+                                                       signature: TypedArray<
+        '_,
+        u8,
+    >| {
+        Hash::parse(&hash)
+            .and_then(|hash| {
+                verify(
+                    &curve,
+                    hash,
+                    &bytes_of(&key),
+                    &bytes_of(&data),
+                    &bytes_of(&signature),
+                )
+            })
+            .map_err(|e| throw(&ctx, e))
+    });
+
+    Ok(())
+}
+
+fn ecdsa_sign<'js>(
+    ctx: Ctx<'js>,
+    curve: String,
+    hash: String,
+    key: TypedArray<'js, u8>,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let signature =
+        Hash::parse(&hash).and_then(|hash| sign(&curve, hash, &bytes_of(&key), &bytes_of(&data)));
+    TypedArray::new(ctx.clone(), signature.map_err(|e| throw(&ctx, e))?)
+}
+
+fn invalid_key() -> Error {
+    Error::data("Invalid EC key")
+}
+
+/// Digest of `data` as ECDSA signs it. Digests shorter than the curve order are
+/// padded in front, which keeps their value.
+fn prehash(hash: Hash, data: &[u8], field_size: usize) -> Vec<u8> {
+    let mut prehash = digest(hash, data);
+    if prehash.len() < field_size {
+        prehash.splice(0..0, std::iter::repeat_n(0, field_size - prehash.len()));
+    }
+    prehash
+}
+
+/// Sign with PKCS#8 `key`. Signatures are the concatenated r and s.
+fn sign(kind: &str, hash: Hash, key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    with_curve!(kind, curve => {
+        use curve::ecdsa::signature::hazmat::PrehashSigner;
+        use curve::ecdsa::{Signature, SigningKey};
+        use curve::pkcs8::DecodePrivateKey;
+
+        let key = SigningKey::from_pkcs8_der(key).map_err(|_| invalid_key())?;
+        let prehash = prehash(hash, data, curve::FieldBytes::default().len());
+        let signature: Signature = key
+            .sign_prehash(&prehash)
+            .map_err(|_| Error::operation("Signing failed"))?;
+        Ok(signature.to_bytes().to_vec())
+    })
+}
+
+fn verify(kind: &str, hash: Hash, key: &[u8], data: &[u8], signature: &[u8]) -> Result<bool> {
+    with_curve!(kind, curve => {
+        use curve::ecdsa::signature::hazmat::PrehashVerifier;
+        use curve::ecdsa::{Signature, VerifyingKey};
+        use curve::pkcs8::DecodePublicKey;
+
+        let key = VerifyingKey::from_public_key_der(key).map_err(|_| invalid_key())?;
+        let Ok(signature) = Signature::from_slice(signature) else {
+            return Ok(false);
+        };
+        let prehash = prehash(hash, data, curve::FieldBytes::default().len());
+        Ok(key.verify_prehash(&prehash, &signature).is_ok())
+    })
+}
+
+pub(crate) fn generate(kind: &str) -> Result<(Vec<u8>, Vec<u8>)> {
+    with_curve!(kind, curve => {
+        use curve::pkcs8::{EncodePrivateKey, EncodePublicKey};
+
+        let secret = curve::SecretKey::random(&mut rand_core::OsRng);
+        let private_key = secret.to_pkcs8_der().map_err(|_| invalid_key())?;
+        let public_key = secret.public_key().to_public_key_der().map_err(|_| invalid_key())?;
+        Ok((private_key.as_bytes().to_vec(), public_key.as_bytes().to_vec()))
+    })
+}
+
+pub(crate) fn import(kind: &str, format: &str, data: KeyData) -> Result<ImportedKey> {
+    with_curve!(kind, curve => {
+        use curve::elliptic_curve::sec1::FromEncodedPoint;
+        use curve::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey};
+        use curve::{PublicKey, SecretKey};
+
+        let spki = |key: PublicKey| {
+            let der = key.to_public_key_der().map_err(|_| invalid_key())?;
+            Ok(ImportedKey::new("public", der.as_bytes().to_vec()))
+        };
+        let pkcs8 = |key: SecretKey| {
+            let der = key.to_pkcs8_der().map_err(|_| invalid_key())?;
+            Ok(ImportedKey::new("private", der.as_bytes().to_vec()))
+        };
+
+        match (format, data) {
+            ("raw", KeyData::Bytes(bytes)) => {
+                spki(PublicKey::from_sec1_bytes(&bytes).map_err(|_| invalid_key())?)
+            }
+            // Keys are decoded and encoded again, which checks them
+            ("spki", KeyData::Bytes(bytes)) => {
+                spki(PublicKey::from_public_key_der(&bytes).map_err(|_| invalid_key())?)
+            }
+            ("pkcs8", KeyData::Bytes(bytes)) => {
+                pkcs8(SecretKey::from_pkcs8_der(&bytes).map_err(|_| invalid_key())?)
+            }
+            ("jwk", KeyData::Jwk(jwk)) => {
+                if jwk["kty"] != "EC" {
+                    return Err(Error::data("JWK \"kty\" must be \"EC\""));
+                }
+                if jwk["crv"] != kind {
+                    return Err(Error::data(format!("JWK \"crv\" must be \"{}\"", kind)));
+                }
+                let x = required_jwk_member(&jwk, "x")?;
+                let y = required_jwk_member(&jwk, "y")?;
+                let size = curve::FieldBytes::default().len();
+                if x.len() != size || y.len() != size {
+                    return Err(invalid_key());
+                }
+                let point = curve::EncodedPoint::from_affine_coordinates(
+                    x.as_slice().into(),
+                    y.as_slice().into(),
+                    false,
+                );
+                let public = Option::from(PublicKey::from_encoded_point(&point))
+                    .ok_or_else(invalid_key)?;
+                match jwk_member(&jwk, "d")? {
+                    Some(d) => {
+                        let secret = SecretKey::from_slice(&d).map_err(|_| invalid_key())?;
+                        if secret.public_key() != public {
+                            return Err(Error::data("JWK public and private keys do not match"));
+                        }
+                        pkcs8(secret)
+                    }
+                    None => spki(public),
+                }
+            }
+            _ => Err(Error::not_supported(format!("Unsupported key format: {}", format))),
+        }
+    })
+}
+
+pub(crate) fn export(kind: &str, format: &str, private: bool, data: &[u8]) -> Result<Exported> {
+    with_curve!(kind, curve => {
+        use curve::elliptic_curve::sec1::ToEncodedPoint;
+        use curve::pkcs8::{DecodePrivateKey, DecodePublicKey};
+        use curve::{PublicKey, SecretKey};
+
+        let (secret, public) = if private {
+            let secret = SecretKey::from_pkcs8_der(data).map_err(|_| invalid_key())?;
+            let public = secret.public_key();
+            (Some(secret), public)
+        } else {
+            (None, PublicKey::from_public_key_der(data).map_err(|_| invalid_key())?)
+        };
+
+        match format {
+            "raw" => Ok(Exported::Bytes(public.to_encoded_point(false).as_bytes().to_vec())),
+            // Keys are kept in these formats
+            "spki" | "pkcs8" => Ok(Exported::Bytes(data.to_vec())),
+            "jwk" => {
+                let point = public.to_encoded_point(false);
+                let (Some(x), Some(y)) = (point.x(), point.y()) else {
+                    return Err(invalid_key());
+                };
+                let mut jwk = serde_json::json!({
+                    "kty": "EC",
+                    "crv": kind,
+                    "x": base64url(x),
+                    "y": base64url(y),
+                });
+                if let Some(secret) = secret {
+                    jwk["d"] = base64url(&secret.to_bytes()).into();
+                }
+                Ok(Exported::Jwk(jwk))
+            }
+            _ => Err(Error::not_supported(format!("Unsupported key format: {}", format))),
+        }
+    })
+}
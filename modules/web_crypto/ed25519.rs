@@ -0,0 +1,134 @@
+//! Ed25519 signatures, and their keys.
+
+use crate::{
+    Error, Exported, ImportedKey, KeyData, Result, base64url, bytes_of, jwk_member, random_bytes,
+    required_jwk_member, rfc8410, throw,
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rquickjs::{Ctx, TypedArray};
+use utils::add_internal_function;
+
+// Last byte of the OID of Ed25519, 1.3.101.112
+const OID_END: u8 = 0x70;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.ed25519Sign", ed25519_sign);
+
+    add_internal_function!(ctx, "crypto.ed25519Verify", |ctx: Ctx<'_>,
+                                                         key: TypedArray<'_, u8>,
+                                                         data: TypedArray<'_, u8>,
+                                                         signature: TypedArray<
+        '_,
+        u8,
+    >| {
+        let key = rfc8410::parse_spki(OID_END, &bytes_of(&key)).map_err(|e| throw(&ctx, e))?;
+        Ok::<_, rquickjs::Error>(verify(&key, &bytes_of(&data), &bytes_of(&signature)))
+    });
+
+    Ok(())
+}
+
+fn ed25519_sign<'js>(
+    ctx: Ctx<'js>,
+    key: TypedArray<'js, u8>,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let key = rfc8410::parse_pkcs8(OID_END, &bytes_of(&key)).map_err(|e| throw(&ctx, e))?;
+    let signature = SigningKey::from_bytes(&key).sign(&bytes_of(&data));
+    TypedArray::new(ctx, signature.to_bytes().to_vec())
+}
+
+fn verify(key: &[u8; 32], data: &[u8], signature: &[u8]) -> bool {
+    let Ok(key) = VerifyingKey::from_bytes(key) else {
+        return false;
+    };
+    let Ok(signature) = Signature::from_slice(signature) else {
+        return false;
+    };
+    key.verify(data, &signature).is_ok()
+}
+
+fn invalid_key() -> Error {
+    Error::data("Invalid Ed25519 key")
+}
+
+fn public_key(key: &[u8]) -> Result<[u8; 32]> {
+    let key: [u8; 32] = key.try_into().map_err(|_| invalid_key())?;
+    // Keys must be points of the curve
+    VerifyingKey::from_bytes(&key).map_err(|_| invalid_key())?;
+    Ok(key)
+}
+
+pub(crate) fn generate() -> Result<(Vec<u8>, Vec<u8>)> {
+    let seed: [u8; 32] = random_bytes(32)?.try_into().unwrap();
+    let key = SigningKey::from_bytes(&seed);
+    Ok((
+        rfc8410::pkcs8(OID_END, &seed),
+        rfc8410::spki(OID_END, key.verifying_key().as_bytes()),
+    ))
+}
+
+pub(crate) fn import(format: &str, data: KeyData) -> Result<ImportedKey> {
+    let public = |key: [u8; 32]| ImportedKey::new("public", rfc8410::spki(OID_END, &key));
+    match (format, data) {
+        ("raw", KeyData::Bytes(bytes)) => Ok(public(public_key(&bytes)?)),
+        ("spki", KeyData::Bytes(bytes)) => {
+            Ok(public(public_key(&rfc8410::parse_spki(OID_END, &bytes)?)?))
+        }
+        ("pkcs8", KeyData::Bytes(bytes)) => {
+            rfc8410::parse_pkcs8(OID_END, &bytes)?;
+            Ok(ImportedKey::new("private", bytes))
+        }
+        ("jwk", KeyData::Jwk(jwk)) => {
+            if jwk["kty"] != "OKP" || jwk["crv"] != "Ed25519" {
+                return Err(Error::data(
+                    "JWK \"kty\" must be \"OKP\" and \"crv\" \"Ed25519\"",
+                ));
+            }
+            let x = public_key(&required_jwk_member(&jwk, "x")?)?;
+            match jwk_member(&jwk, "d")? {
+                Some(d) => {
+                    let seed: [u8; 32] = d.try_into().map_err(|_| invalid_key())?;
+                    if SigningKey::from_bytes(&seed).verifying_key().as_bytes() != &x {
+                        return Err(Error::data("JWK public and private keys do not match"));
+                    }
+                    Ok(ImportedKey::new("private", rfc8410::pkcs8(OID_END, &seed)))
+                }
+                None => Ok(public(x)),
+            }
+        }
+        _ => Err(Error::not_supported(format!(
+            "Unsupported key format: {}",
+            format
+        ))),
+    }
+}
+
+pub(crate) fn export(format: &str, private: bool, data: &[u8]) -> Result<Exported> {
+    let (seed, public) = if private {
+        let seed = rfc8410::parse_pkcs8(OID_END, data)?;
+        let public = SigningKey::from_bytes(&seed).verifying_key().to_bytes();
+        (Some(seed), public)
+    } else {
+        (None, rfc8410::parse_spki(OID_END, data)?)
+    };
+    match format {
+        "raw" => Ok(Exported::Bytes(public.to_vec())),
+        "spki" | "pkcs8" => Ok(Exported::Bytes(data.to_vec())),
+        "jwk" => {
+            let mut jwk = serde_json::json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "x": base64url(&public),
+            });
+            if let Some(seed) = seed {
+                jwk["d"] = base64url(&seed).into();
+            }
+            Ok(Exported::Jwk(jwk))
+        }
+        _ => Err(Error::not_supported(format!(
+            "Unsupported key format: {}",
+            format
+        ))),
+    }
+}
@@ -0,0 +1,88 @@
+//! PBKDF2 and HKDF key derivation.
+
+use crate::{Error, Hash, Result, bytes_of, throw, with_hash};
+use rquickjs::{Ctx, TypedArray};
+use utils::add_internal_function;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.pbkdf2", pbkdf2_bits);
+
+    add_internal_function!(ctx, "crypto.hkdf", hkdf_bits);
+
+    Ok(())
+}
+
+fn pbkdf2_bits<'js>(
+    ctx: Ctx<'js>,
+    hash: String,
+    password: TypedArray<'js, u8>,
+    salt: TypedArray<'js, u8>,
+    iterations: u32,
+    length: usize,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let bits = Hash::parse(&hash).and_then(|hash| {
+        pbkdf2(
+            hash,
+            &bytes_of(&password),
+            &bytes_of(&salt),
+            iterations,
+            length,
+        )
+    });
+    TypedArray::new(ctx.clone(), bits.map_err(|e| throw(&ctx, e))?)
+}
+
+fn hkdf_bits<'js>(
+    ctx: Ctx<'js>,
+    hash: String,
+    key: TypedArray<'js, u8>,
+    salt: TypedArray<'js, u8>,
+    info: TypedArray<'js, u8>,
+    length: usize,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let bits = Hash::parse(&hash).and_then(|hash| {
+        hkdf(
+            hash,
+            &bytes_of(&key),
+            &bytes_of(&salt),
+            &bytes_of(&info),
+            length,
+        )
+    });
+    TypedArray::new(ctx.clone(), bits.map_err(|e| throw(&ctx, e))?)
+}
+
+/// Derive `length` bits, a multiple of 8.
+fn pbkdf2(
+    hash: Hash,
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    length: usize,
+) -> Result<Vec<u8>> {
+    if length == 0 || !length.is_multiple_of(8) {
+        return Err(Error::operation("Length must be a non-zero multiple of 8"));
+    }
+    if iterations == 0 {
+        return Err(Error::operation("Iterations must not be zero"));
+    }
+    let mut bits = vec![0; length / 8];
+    with_hash!(hash, D => {
+        ::pbkdf2::pbkdf2_hmac::<D>(password, salt, iterations, &mut bits);
+    });
+    Ok(bits)
+}
+
+/// Derive `length` bits, a multiple of 8.
+fn hkdf(hash: Hash, key: &[u8], salt: &[u8], info: &[u8], length: usize) -> Result<Vec<u8>> {
+    if !length.is_multiple_of(8) {
+        return Err(Error::operation("Length must be a multiple of 8"));
+    }
+    let mut bits = vec![0; length / 8];
+    with_hash!(hash, D => {
+        ::hkdf::Hkdf::<D>::new(Some(salt), key)
+            .expand(info, &mut bits)
+            .map_err(|_| Error::operation("Length is too large for the hash"))?;
+    });
+    Ok(bits)
+}
@@ -0,0 +1,467 @@
+//! Web Crypto. JS keeps keys as bytes: secret keys raw, private keys as PKCS#8 and
+//! public keys as SPKI, which the operations here parse on each call. Algorithms
+//! are compiled in through cargo features, and the ones left out are reported to
+//! JS as not supported.
+
+use base64::Engine;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use rquickjs::prelude::Opt;
+use rquickjs::{Ctx, Module, Object, TypedArray, Value};
+use utils::add_internal_function;
+
+#[cfg(feature = "aes")]
+mod aes;
+#[cfg(feature = "digest")]
+mod digest;
+#[cfg(feature = "ecdsa")]
+mod ec;
+#[cfg(feature = "ed25519")]
+mod ed25519;
+#[cfg(feature = "kdf")]
+mod kdf;
+#[cfg(feature = "rsa")]
+mod rsa;
+#[cfg(feature = "x25519")]
+mod x25519;
+
+pub fn init(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    setup_internal(ctx)?;
+    let module = Module::evaluate(ctx.clone(), "web_crypto", include_str!("crypto.js"))?;
+    module.finish::<()>()?;
+    Ok(())
+}
+
+fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].crypto = {};")?;
+
+    add_internal_function!(ctx, "crypto.randomBytes", random_values);
+
+    add_internal_function!(ctx, "crypto.algorithms", || -> String {
+        serde_json::Value::from(algorithms()).to_string()
+    });
+
+    add_internal_function!(ctx, "crypto.importKey", import_key);
+
+    add_internal_function!(ctx, "crypto.exportKey", export_key);
+
+    add_internal_function!(ctx, "crypto.generateKeyPair", generate_key_pair);
+
+    #[cfg(feature = "digest")]
+    digest::setup_internal(ctx)?;
+    #[cfg(feature = "aes")]
+    aes::setup_internal(ctx)?;
+    #[cfg(feature = "ecdsa")]
+    ec::setup_internal(ctx)?;
+    #[cfg(feature = "ed25519")]
+    ed25519::setup_internal(ctx)?;
+    #[cfg(feature = "x25519")]
+    x25519::setup_internal(ctx)?;
+    #[cfg(feature = "rsa")]
+    rsa::setup_internal(ctx)?;
+    #[cfg(feature = "kdf")]
+    kdf::setup_internal(ctx)?;
+
+    Ok(())
+}
+
+fn random_values<'js>(ctx: Ctx<'js>, length: usize) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let bytes = random_bytes(length).map_err(|e| throw(&ctx, e))?;
+    TypedArray::new(ctx, bytes)
+}
+
+/// Names of the algorithms of this build.
+fn algorithms() -> Vec<&'static str> {
+    let mut names = Vec::new();
+    if cfg!(feature = "digest") {
+        names.extend(["SHA-1", "SHA-256", "SHA-384", "SHA-512"]);
+    }
+    if cfg!(feature = "hmac") {
+        names.push("HMAC");
+    }
+    if cfg!(feature = "aes") {
+        names.extend(["AES-GCM", "AES-CBC", "AES-CTR"]);
+    }
+    if cfg!(feature = "ecdsa") {
+        names.push("ECDSA");
+    }
+    if cfg!(feature = "ed25519") {
+        names.push("Ed25519");
+    }
+    if cfg!(feature = "x25519") {
+        names.push("X25519");
+    }
+    if cfg!(feature = "rsa") {
+        names.extend(["RSA-PSS", "RSA-OAEP"]);
+    }
+    if cfg!(feature = "kdf") {
+        names.extend(["PBKDF2", "HKDF"]);
+    }
+    names
+}
+
+/// Failure of an operation, thrown to JS as a DOMException called `name`.
+pub(crate) struct Error {
+    name: &'static str,
+    message: String,
+}
+
+impl Error {
+    pub(crate) fn data(message: impl Into<String>) -> Error {
+        Error {
+            name: "DataError",
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn operation(message: impl Into<String>) -> Error {
+        Error {
+            name: "OperationError",
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn not_supported(message: impl Into<String>) -> Error {
+        Error {
+            name: "NotSupportedError",
+            message: message.into(),
+        }
+    }
+}
+
+pub(crate) type Result<T> = std::result::Result<T, Error>;
+
+/// Throw `error` as "Name: message", which JS turns into a DOMException.
+pub(crate) fn throw(ctx: &Ctx<'_>, error: Error) -> rquickjs::Error {
+    rquickjs::Exception::throw_message(ctx, &format!("{}: {}", error.name, error.message))
+}
+
+pub(crate) fn bytes_of(array: &TypedArray<'_, u8>) -> Vec<u8> {
+    array.as_bytes().unwrap_or_default().to_vec()
+}
+
+pub(crate) fn random_bytes(length: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0; length];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| Error::operation(format!("Failed to get random values: {}", e)))?;
+    Ok(bytes)
+}
+
+/// Hash functions taken by HMAC, ECDSA, RSA and the key derivations.
+#[cfg(feature = "digest")]
+#[derive(Clone, Copy)]
+pub(crate) enum Hash {
+    Sha1,
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+#[cfg(feature = "digest")]
+impl Hash {
+    pub(crate) fn parse(name: &str) -> Result<Hash> {
+        match name {
+            "SHA-1" => Ok(Hash::Sha1),
+            "SHA-256" => Ok(Hash::Sha256),
+            "SHA-384" => Ok(Hash::Sha384),
+            "SHA-512" => Ok(Hash::Sha512),
+            _ => Err(Error::not_supported(format!("Unsupported hash: {}", name))),
+        }
+    }
+}
+
+/// Evaluate `$body` with `$D` standing for the digest type of `$hash`.
+#[cfg(feature = "digest")]
+macro_rules! with_hash {
+    ($hash:expr, $D:ident => $body:expr) => {
+        match $hash {
+            $crate::Hash::Sha1 => {
+                type $D = sha1::Sha1;
+                $body
+            }
+            $crate::Hash::Sha256 => {
+                type $D = sha2::Sha256;
+                $body
+            }
+            $crate::Hash::Sha384 => {
+                type $D = sha2::Sha384;
+                $body
+            }
+            $crate::Hash::Sha512 => {
+                type $D = sha2::Sha512;
+                $body
+            }
+        }
+    };
+}
+#[cfg(feature = "digest")]
+pub(crate) use with_hash;
+
+// JWK members are base64url, which some producers pad
+const BASE64URL: GeneralPurpose = GeneralPurpose::new(
+    &base64::alphabet::URL_SAFE,
+    GeneralPurposeConfig::new()
+        .with_encode_padding(false)
+        .with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+pub(crate) fn base64url(bytes: &[u8]) -> String {
+    BASE64URL.encode(bytes)
+}
+
+/// Bytes of member `name` of a JWK, or None when it is missing.
+pub(crate) fn jwk_member(jwk: &serde_json::Value, name: &str) -> Result<Option<Vec<u8>>> {
+    match jwk.get(name) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(value)) => BASE64URL
+            .decode(value)
+            .map(Some)
+            .map_err(|_| Error::data(format!("Invalid JWK member \"{}\"", name))),
+        Some(_) => Err(Error::data(format!("Invalid JWK member \"{}\"", name))),
+    }
+}
+
+pub(crate) fn required_jwk_member(jwk: &serde_json::Value, name: &str) -> Result<Vec<u8>> {
+    jwk_member(jwk, name)?.ok_or_else(|| Error::data(format!("JWK member \"{}\" is missing", name)))
+}
+
+/// Key material handed over by JS: bytes, or a JWK as JSON.
+pub(crate) enum KeyData {
+    Bytes(Vec<u8>),
+    Jwk(serde_json::Value),
+}
+
+impl KeyData {
+    fn from_js(format: &str, data: Value<'_>) -> Result<KeyData> {
+        if format == "jwk" {
+            let jwk = data.as_string().and_then(|jwk| jwk.to_string().ok());
+            let jwk = jwk.ok_or_else(|| Error::data("Invalid JWK"))?;
+            serde_json::from_str(&jwk)
+                .map(KeyData::Jwk)
+                .map_err(|_| Error::data("Invalid JWK"))
+        } else {
+            let bytes =
+                TypedArray::<u8>::from_value(data).map_err(|_| Error::data("Invalid key data"))?;
+            Ok(KeyData::Bytes(bytes_of(&bytes)))
+        }
+    }
+}
+
+/// A key as JS keeps it.
+pub(crate) struct ImportedKey {
+    pub(crate) key_type: &'static str,
+    pub(crate) data: Vec<u8>,
+    // Length in bits of secret keys and of RSA moduli
+    pub(crate) length: Option<usize>,
+    pub(crate) public_exponent: Option<Vec<u8>>,
+}
+
+impl ImportedKey {
+    pub(crate) fn new(key_type: &'static str, data: Vec<u8>) -> ImportedKey {
+        ImportedKey {
+            key_type,
+            data,
+            length: None,
+            public_exponent: None,
+        }
+    }
+}
+
+/// A key exported in the format asked for.
+pub(crate) enum Exported {
+    Bytes(Vec<u8>),
+    Jwk(serde_json::Value),
+}
+
+/// Import key material of `kind`: "secret", a named curve, or "RSA". Returns an
+/// object with the `type` of the key, its `data` and what it tells about the
+/// algorithm.
+fn import_key<'js>(
+    ctx: Ctx<'js>,
+    kind: String,
+    format: String,
+    data: Value<'js>,
+) -> rquickjs::Result<Object<'js>> {
+    let key = KeyData::from_js(&format, data)
+        .and_then(|data| import(&kind, &format, data))
+        .map_err(|e| throw(&ctx, e))?;
+
+    let object = Object::new(ctx.clone())?;
+    object.set("type", key.key_type)?;
+    object.set("data", TypedArray::<u8>::new(ctx.clone(), key.data)?)?;
+    if let Some(length) = key.length {
+        object.set("length", length)?;
+    }
+    if let Some(exponent) = key.public_exponent {
+        object.set("publicExponent", TypedArray::<u8>::new(ctx, exponent)?)?;
+    }
+    Ok(object)
+}
+
+fn import(kind: &str, format: &str, data: KeyData) -> Result<ImportedKey> {
+    match kind {
+        "secret" => import_secret(format, data),
+        #[cfg(feature = "ecdsa")]
+        "P-256" | "P-384" => ec::import(kind, format, data),
+        #[cfg(feature = "ed25519")]
+        "Ed25519" => ed25519::import(format, data),
+        #[cfg(feature = "x25519")]
+        "X25519" => x25519::import(format, data),
+        #[cfg(feature = "rsa")]
+        "RSA" => rsa::import(format, data),
+        _ => Err(Error::not_supported(format!("Unsupported key: {}", kind))),
+    }
+}
+
+fn import_secret(format: &str, data: KeyData) -> Result<ImportedKey> {
+    let bytes = match data {
+        KeyData::Bytes(bytes) if format == "raw" => bytes,
+        KeyData::Jwk(jwk) => {
+            if jwk["kty"] != "oct" {
+                return Err(Error::data("JWK \"kty\" must be \"oct\""));
+            }
+            required_jwk_member(&jwk, "k")?
+        }
+        _ => {
+            return Err(Error::not_supported(format!(
+                "Unsupported key format: {}",
+                format
+            )));
+        }
+    };
+    let mut key = ImportedKey::new("secret", bytes);
+    key.length = Some(key.data.len() * 8);
+    Ok(key)
+}
+
+/// Export key `data` of `kind` and `key_type` in `format`. Returns the bytes, or
+/// the JWK as JSON.
+fn export_key<'js>(
+    ctx: Ctx<'js>,
+    kind: String,
+    format: String,
+    key_type: String,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<Value<'js>> {
+    let exported =
+        export(&kind, &format, &key_type, &bytes_of(&data)).map_err(|e| throw(&ctx, e))?;
+    match exported {
+        Exported::Bytes(bytes) => Ok(TypedArray::<u8>::new(ctx, bytes)?.into_value()),
+        Exported::Jwk(jwk) => {
+            rquickjs::String::from_str(ctx, &jwk.to_string()).map(|jwk| jwk.into_value())
+        }
+    }
+}
+
+#[cfg_attr(
+    not(any(
+        feature = "ecdsa",
+        feature = "ed25519",
+        feature = "x25519",
+        feature = "rsa"
+    )),
+    allow(unused_variables)
+)]
+fn export(kind: &str, format: &str, key_type: &str, data: &[u8]) -> Result<Exported> {
+    match kind {
+        "secret" => match format {
+            "raw" => Ok(Exported::Bytes(data.to_vec())),
+            "jwk" => Ok(Exported::Jwk(serde_json::json!({
+                "kty": "oct",
+                "k": base64url(data),
+            }))),
+            _ => Err(Error::not_supported(format!(
+                "Unsupported key format: {}",
+                format
+            ))),
+        },
+        #[cfg(feature = "ecdsa")]
+        "P-256" | "P-384" => ec::export(kind, format, key_type == "private", data),
+        #[cfg(feature = "ed25519")]
+        "Ed25519" => ed25519::export(format, key_type == "private", data),
+        #[cfg(feature = "x25519")]
+        "X25519" => x25519::export(format, key_type == "private", data),
+        #[cfg(feature = "rsa")]
+        "RSA" => rsa::export(format, key_type == "private", data),
+        _ => Err(Error::not_supported(format!("Unsupported key: {}", kind))),
+    }
+}
+
+/// Generate a key pair of `kind`. `modulus_length` and `public_exponent` only apply
+/// to RSA. Returns an object with the `privateKey` and `publicKey` data.
+#[cfg_attr(not(feature = "rsa"), allow(unused_variables))]
+fn generate_key_pair<'js>(
+    ctx: Ctx<'js>,
+    kind: String,
+    modulus_length: Opt<usize>,
+    public_exponent: Opt<TypedArray<'js, u8>>,
+) -> rquickjs::Result<Object<'js>> {
+    let pair: Result<(Vec<u8>, Vec<u8>)> = match kind.as_str() {
+        #[cfg(feature = "ecdsa")]
+        "P-256" | "P-384" => ec::generate(&kind),
+        #[cfg(feature = "ed25519")]
+        "Ed25519" => ed25519::generate(),
+        #[cfg(feature = "x25519")]
+        "X25519" => x25519::generate(),
+        #[cfg(feature = "rsa")]
+        "RSA" => rsa::generate(
+            modulus_length.0.unwrap_or_default(),
+            &public_exponent.0.as_ref().map(bytes_of).unwrap_or_default(),
+        ),
+        _ => Err(Error::not_supported(format!("Unsupported key: {}", kind))),
+    };
+    let (private_key, public_key) = pair.map_err(|e| throw(&ctx, e))?;
+
+    let object = Object::new(ctx.clone())?;
+    object.set(
+        "privateKey",
+        TypedArray::<u8>::new(ctx.clone(), private_key)?,
+    )?;
+    object.set("publicKey", TypedArray::<u8>::new(ctx, public_key)?)?;
+    Ok(object)
+}
+
+/// DER of the PKCS#8 and SPKI structures of the RFC 8410 curves, which only differ
+/// by the OID of the curve and the 32 bytes of the key that follow.
+#[cfg(any(feature = "ed25519", feature = "x25519"))]
+pub(crate) mod rfc8410 {
+    use crate::{Error, Result};
+
+    const PKCS8_PREFIX: [u8; 16] = [
+        0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x00, 0x04, 0x22, 0x04,
+        0x20,
+    ];
+    const SPKI_PREFIX: [u8; 12] = [
+        0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x00, 0x03, 0x21, 0x00,
+    ];
+    // Offsets of the last byte of the OID in the prefixes
+    const PKCS8_OID_END: usize = 11;
+    const SPKI_OID_END: usize = 8;
+
+    fn prefix<const N: usize>(mut prefix: [u8; N], at: usize, oid_end: u8) -> [u8; N] {
+        prefix[at] = oid_end;
+        prefix
+    }
+
+    pub(crate) fn pkcs8(oid_end: u8, key: &[u8; 32]) -> Vec<u8> {
+        [&prefix(PKCS8_PREFIX, PKCS8_OID_END, oid_end)[..], key].concat()
+    }
+
+    pub(crate) fn spki(oid_end: u8, key: &[u8; 32]) -> Vec<u8> {
+        [&prefix(SPKI_PREFIX, SPKI_OID_END, oid_end)[..], key].concat()
+    }
+
+    pub(crate) fn parse_pkcs8(oid_end: u8, der: &[u8]) -> Result<[u8; 32]> {
+        let key = der
+            .strip_prefix(&prefix(PKCS8_PREFIX, PKCS8_OID_END, oid_end)[..])
+            .ok_or_else(|| Error::data("Invalid PKCS#8 key"))?;
+        key.try_into()
+            .map_err(|_| Error::data("Invalid PKCS#8 key"))
+    }
+
+    pub(crate) fn parse_spki(oid_end: u8, der: &[u8]) -> Result<[u8; 32]> {
+        let key = der
+            .strip_prefix(&prefix(SPKI_PREFIX, SPKI_OID_END, oid_end)[..])
+            .ok_or_else(|| Error::data("Invalid SPKI key"))?;
+        key.try_into().map_err(|_| Error::data("Invalid SPKI key"))
+    }
+}
@@ -0,0 +1,265 @@
+//! RSA-PSS signatures and RSA-OAEP encryption, and RSA keys.
+//!
+//! The rsa crate is not constant time (RUSTSEC-2023-0071, the Marvin attack):
+//! timing of operations with a private key may leak it to an attacker who can have
+//! many chosen ciphertexts decrypted and measure how long it takes. RSA-OAEP
+//! decryption of ciphertexts from untrusted parties is exposed to it, so keys used
+//! that way are best kept out of reach of remote timing, or replaced by ECDH.
+
+use crate::digest::digest;
+use crate::{
+    Error, Exported, Hash, ImportedKey, KeyData, Result, base64url, bytes_of, jwk_member,
+    required_jwk_member, throw, with_hash,
+};
+use ::rsa::pkcs8::{DecodePrivateKey, DecodePublicKey, EncodePrivateKey, EncodePublicKey};
+use ::rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use ::rsa::{BigUint, Oaep, Pss, RsaPrivateKey, RsaPublicKey};
+use rand_core::OsRng;
+use rquickjs::{Ctx, TypedArray};
+use utils::add_internal_function;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.rsaPssSign", rsa_pss_sign);
+
+    add_internal_function!(ctx, "crypto.rsaPssVerify", |ctx: Ctx<'_>,
+                                                        hash: String,
+                                                        key: TypedArray<'_, u8>,
+                                                        salt_length: usize,
+                                                        data: TypedArray<'_, u8>,
+                                                        signature: TypedArray<
+        '_,
+        u8,
+    >| {
+        Hash::parse(&hash)
+            .and_then(|hash| {
+                pss_verify(
+                    hash,
+                    &bytes_of(&key),
+                    salt_length,
+                    &bytes_of(&data),
+                    &bytes_of(&signature),
+                )
+            })
+            .map_err(|e| throw(&ctx, e))
+    });
+
+    add_internal_function!(ctx, "crypto.rsaOaep", rsa_oaep);
+
+    Ok(())
+}
+
+fn rsa_pss_sign<'js>(
+    ctx: Ctx<'js>,
+    hash: String,
+    key: TypedArray<'js, u8>,
+    salt_length: usize,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let signature = Hash::parse(&hash)
+        .and_then(|hash| pss_sign(hash, &bytes_of(&key), salt_length, &bytes_of(&data)));
+    TypedArray::new(ctx.clone(), signature.map_err(|e| throw(&ctx, e))?)
+}
+
+fn rsa_oaep<'js>(
+    ctx: Ctx<'js>,
+    encrypt: bool,
+    hash: String,
+    key: TypedArray<'js, u8>,
+    label: TypedArray<'js, u8>,
+    data: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let result = Hash::parse(&hash).and_then(|hash| {
+        oaep(
+            encrypt,
+            hash,
+            &bytes_of(&key),
+            &bytes_of(&label),
+            &bytes_of(&data),
+        )
+    });
+    TypedArray::new(ctx.clone(), result.map_err(|e| throw(&ctx, e))?)
+}
+
+fn invalid_key() -> Error {
+    Error::data("Invalid RSA key")
+}
+
+fn private_key(der: &[u8]) -> Result<RsaPrivateKey> {
+    RsaPrivateKey::from_pkcs8_der(der).map_err(|_| invalid_key())
+}
+
+fn public_key(der: &[u8]) -> Result<RsaPublicKey> {
+    RsaPublicKey::from_public_key_der(der).map_err(|_| invalid_key())
+}
+
+fn pss_sign(hash: Hash, key: &[u8], salt_length: usize, data: &[u8]) -> Result<Vec<u8>> {
+    let key = private_key(key)?;
+    let hashed = digest(hash, data);
+    with_hash!(hash, D => {
+        key.sign_with_rng(&mut OsRng, Pss::new_with_salt::<D>(salt_length), &hashed)
+            .map_err(|e| Error::operation(format!("Signing failed: {}", e)))
+    })
+}
+
+fn pss_verify(
+    hash: Hash,
+    key: &[u8],
+    salt_length: usize,
+    data: &[u8],
+    signature: &[u8],
+) -> Result<bool> {
+    let key = public_key(key)?;
+    let hashed = digest(hash, data);
+    with_hash!(hash, D => {
+        Ok(key
+            .verify(Pss::new_with_salt::<D>(salt_length), &hashed, signature)
+            .is_ok())
+    })
+}
+
+/// Encrypt with SPKI `key`, or decrypt with PKCS#8 `key`.
+fn oaep(encrypt: bool, hash: Hash, key: &[u8], label: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    // Labels are strings to the rsa crate
+    let label = std::str::from_utf8(label)
+        .map_err(|_| Error::not_supported("RSA-OAEP labels must be UTF-8"))?;
+    let padding = with_hash!(hash, D => {
+        if label.is_empty() {
+            Oaep::new::<D>()
+        } else {
+            Oaep::new_with_label::<D, _>(label)
+        }
+    });
+    if encrypt {
+        public_key(key)?
+            .encrypt(&mut OsRng, padding, data)
+            .map_err(|e| Error::operation(format!("Encryption failed: {}", e)))
+    } else {
+        // Not constant time, see the module documentation
+        private_key(key)?
+            .decrypt(padding, data)
+            .map_err(|_| Error::operation("Decryption failed"))
+    }
+}
+
+// Shortest modulus of generated keys in bits, shorter ones being insecure
+// (NIST SP 800-131A)
+const MIN_MODULUS_LENGTH: usize = 2048;
+
+pub(crate) fn generate(
+    modulus_length: usize,
+    public_exponent: &[u8],
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    if modulus_length < MIN_MODULUS_LENGTH || !modulus_length.is_multiple_of(8) {
+        return Err(Error::operation(format!(
+            "RSA modulus length must be a multiple of 8 of at least {}",
+            MIN_MODULUS_LENGTH
+        )));
+    }
+    let exponent = BigUint::from_bytes_be(public_exponent);
+    let key = RsaPrivateKey::new_with_exp(&mut OsRng, modulus_length, &exponent)
+        .map_err(|e| Error::operation(format!("Failed to generate RSA key: {}", e)))?;
+    Ok((pkcs8(&key)?, spki(&key.to_public_key())?))
+}
+
+fn pkcs8(key: &RsaPrivateKey) -> Result<Vec<u8>> {
+    let der = key.to_pkcs8_der().map_err(|_| invalid_key())?;
+    Ok(der.as_bytes().to_vec())
+}
+
+fn spki(key: &RsaPublicKey) -> Result<Vec<u8>> {
+    let der = key.to_public_key_der().map_err(|_| invalid_key())?;
+    Ok(der.as_bytes().to_vec())
+}
+
+/// Imported key with the modulus length and public exponent of `key`.
+fn imported(key_type: &'static str, data: Vec<u8>, key: &impl PublicKeyParts) -> ImportedKey {
+    let mut imported = ImportedKey::new(key_type, data);
+    imported.length = Some(key.n().bits());
+    imported.public_exponent = Some(key.e().to_bytes_be());
+    imported
+}
+
+pub(crate) fn import(format: &str, data: KeyData) -> Result<ImportedKey> {
+    match (format, data) {
+        // Keys are decoded and encoded again, which checks them
+        ("spki", KeyData::Bytes(bytes)) => {
+            let key = public_key(&bytes)?;
+            Ok(imported("public", spki(&key)?, &key))
+        }
+        ("pkcs8", KeyData::Bytes(bytes)) => {
+            let key = private_key(&bytes)?;
+            Ok(imported("private", pkcs8(&key)?, &key))
+        }
+        ("jwk", KeyData::Jwk(jwk)) => {
+            if jwk["kty"] != "RSA" {
+                return Err(Error::data("JWK \"kty\" must be \"RSA\""));
+            }
+            let member = |name| Ok(BigUint::from_bytes_be(&required_jwk_member(&jwk, name)?));
+            let n = member("n")?;
+            let e = member("e")?;
+            if jwk_member(&jwk, "d")?.is_none() {
+                let key = RsaPublicKey::new(n, e).map_err(|_| invalid_key())?;
+                return Ok(imported("public", spki(&key)?, &key));
+            }
+            if jwk.get("oth").is_some() {
+                return Err(Error::not_supported(
+                    "Multi-prime RSA keys are not supported",
+                ));
+            }
+            let primes = vec![member("p")?, member("q")?];
+            let key = RsaPrivateKey::from_components(n, e, member("d")?, primes)
+                .map_err(|_| invalid_key())?;
+            key.validate().map_err(|_| invalid_key())?;
+            Ok(imported("private", pkcs8(&key)?, &key))
+        }
+        _ => Err(Error::not_supported(format!(
+            "Unsupported key format: {}",
+            format
+        ))),
+    }
+}
+
+pub(crate) fn export(format: &str, private: bool, data: &[u8]) -> Result<Exported> {
+    match format {
+        "spki" | "pkcs8" => Ok(Exported::Bytes(data.to_vec())),
+        "jwk" => {
+            let uint = |value: &BigUint| base64url(&value.to_bytes_be());
+            if !private {
+                let key = public_key(data)?;
+                return Ok(Exported::Jwk(serde_json::json!({
+                    "kty": "RSA",
+                    "n": uint(key.n()),
+                    "e": uint(key.e()),
+                })));
+            }
+            let key = private_key(data)?;
+            let [p, q] = key.primes() else {
+                return Err(Error::not_supported(
+                    "Multi-prime RSA keys are not supported",
+                ));
+            };
+            let (Some(dp), Some(dq), Some(qi)) = (
+                key.dp(),
+                key.dq(),
+                key.qinv().and_then(|qi| qi.to_biguint()),
+            ) else {
+                return Err(invalid_key());
+            };
+            Ok(Exported::Jwk(serde_json::json!({
+                "kty": "RSA",
+                "n": uint(key.n()),
+                "e": uint(key.e()),
+                "d": uint(key.d()),
+                "p": uint(p),
+                "q": uint(q),
+                "dp": uint(dp),
+                "dq": uint(dq),
+                "qi": uint(&qi),
+            })))
+        }
+        _ => Err(Error::not_supported(format!(
+            "Unsupported key format: {}",
+            format
+        ))),
+    }
+}
@@ -0,0 +1,125 @@
+//! X25519 key agreement, and its keys.
+
+use crate::{
+    Error, Exported, ImportedKey, KeyData, Result, base64url, bytes_of, jwk_member, random_bytes,
+    required_jwk_member, rfc8410, throw,
+};
+use rquickjs::{Ctx, TypedArray};
+use utils::add_internal_function;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+// Last byte of the OID of X25519, 1.3.101.110
+const OID_END: u8 = 0x6e;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    add_internal_function!(ctx, "crypto.x25519DeriveBits", x25519_derive_bits);
+
+    Ok(())
+}
+
+fn x25519_derive_bits<'js>(
+    ctx: Ctx<'js>,
+    private_key: TypedArray<'js, u8>,
+    public_key: TypedArray<'js, u8>,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let bits = derive_bits(&bytes_of(&private_key), &bytes_of(&public_key));
+    TypedArray::new(ctx.clone(), bits.map_err(|e| throw(&ctx, e))?)
+}
+
+/// Shared secret of PKCS#8 `private_key` and SPKI `public_key`.
+fn derive_bits(private_key: &[u8], public_key: &[u8]) -> Result<Vec<u8>> {
+    let secret = StaticSecret::from(rfc8410::parse_pkcs8(OID_END, private_key)?);
+    let public = PublicKey::from(rfc8410::parse_spki(OID_END, public_key)?);
+    let shared = secret.diffie_hellman(&public);
+    // Public keys of small order give a secret of zeros
+    if !shared.was_contributory() {
+        return Err(Error::operation("Invalid X25519 public key"));
+    }
+    Ok(shared.as_bytes().to_vec())
+}
+
+fn invalid_key() -> Error {
+    Error::data("Invalid X25519 key")
+}
+
+fn public_of(secret: [u8; 32]) -> [u8; 32] {
+    PublicKey::from(&StaticSecret::from(secret)).to_bytes()
+}
+
+pub(crate) fn generate() -> Result<(Vec<u8>, Vec<u8>)> {
+    let secret: [u8; 32] = random_bytes(32)?.try_into().unwrap();
+    Ok((
+        rfc8410::pkcs8(OID_END, &secret),
+        rfc8410::spki(OID_END, &public_of(secret)),
+    ))
+}
+
+pub(crate) fn import(format: &str, data: KeyData) -> Result<ImportedKey> {
+    let public = |key: &[u8; 32]| ImportedKey::new("public", rfc8410::spki(OID_END, key));
+    match (format, data) {
+        ("raw", KeyData::Bytes(bytes)) => {
+            let key: [u8; 32] = bytes.try_into().map_err(|_| invalid_key())?;
+            Ok(public(&key))
+        }
+        ("spki", KeyData::Bytes(bytes)) => Ok(public(&rfc8410::parse_spki(OID_END, &bytes)?)),
+        ("pkcs8", KeyData::Bytes(bytes)) => {
+            rfc8410::parse_pkcs8(OID_END, &bytes)?;
+            Ok(ImportedKey::new("private", bytes))
+        }
+        ("jwk", KeyData::Jwk(jwk)) => {
+            if jwk["kty"] != "OKP" || jwk["crv"] != "X25519" {
+                return Err(Error::data(
+                    "JWK \"kty\" must be \"OKP\" and \"crv\" \"X25519\"",
+                ));
+            }
+            let x: [u8; 32] = required_jwk_member(&jwk, "x")?
+                .try_into()
+                .map_err(|_| invalid_key())?;
+            match jwk_member(&jwk, "d")? {
+                Some(d) => {
+                    let secret: [u8; 32] = d.try_into().map_err(|_| invalid_key())?;
+                    if public_of(secret) != x {
+                        return Err(Error::data("JWK public and private keys do not match"));
+                    }
+                    Ok(ImportedKey::new(
+                        "private",
+                        rfc8410::pkcs8(OID_END, &secret),
+                    ))
+                }
+                None => Ok(public(&x)),
+            }
+        }
+        _ => Err(Error::not_supported(format!(
+            "Unsupported key format: {}",
+            format
+        ))),
+    }
+}
+
+pub(crate) fn export(format: &str, private: bool, data: &[u8]) -> Result<Exported> {
+    let (secret, public) = if private {
+        let secret = rfc8410::parse_pkcs8(OID_END, data)?;
+        (Some(secret), public_of(secret))
+    } else {
+        (None, rfc8410::parse_spki(OID_END, data)?)
+    };
+    match format {
+        "raw" => Ok(Exported::Bytes(public.to_vec())),
+        "spki" | "pkcs8" => Ok(Exported::Bytes(data.to_vec())),
+        "jwk" => {
+            let mut jwk = serde_json::json!({
+                "kty": "OKP",
+                "crv": "X25519",
+                "x": base64url(&public),
+            });
+            if let Some(secret) = secret {
+                jwk["d"] = base64url(&secret).into();
+            }
+            Ok(Exported::Jwk(jwk))
+        }
+        _ => Err(Error::not_supported(format!(
+            "Unsupported key format: {}",
+            format
+        ))),
+    }
+}
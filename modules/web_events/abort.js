@@ -0,0 +1,178 @@
+// https://dom.spec.whatwg.org/#aborting-ongoing-activities
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+// Abort algorithm and abort listener check, assigned inside AbortSignal to
+// reach its private state
+let signalAbort;
+let isObserved;
+
+// Signals created by AbortSignal.timeout() by the id of their host timer
+const timeoutSignals = new Map();
+
+class AbortSignal extends EventTarget {
+  #aborted = false;
+  #reason = undefined;
+  // Signals this one follows (AbortSignal.any) and signals following this one
+  #dependent = false;
+  #sources = [];
+  #dependents = [];
+
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    super();
+  }
+
+  static abort(reason) {
+    const signal = new AbortSignal(illegalConstructorKey);
+    signal.#aborted = true;
+    signal.#reason = reason === undefined ? abortError() : reason;
+    return signal;
+  }
+
+  // Like Deno's unref'd timers, the timer only keeps the process alive while
+  // something listens for the abort.
+  static timeout(milliseconds) {
+    milliseconds = Number(milliseconds);
+    if (!Number.isFinite(milliseconds) || milliseconds < 0) {
+      throw new TypeError(
+        "Failed to execute 'timeout' on 'AbortSignal': Value is outside the 'unsigned long long' value range.",
+      );
+    }
+    const signal = new AbortSignal(illegalConstructorKey);
+    const id = __internal.events.setTimer(Math.trunc(milliseconds));
+    timeoutSignals.set(id, signal);
+    return signal;
+  }
+
+  static any(signals) {
+    const dependent = new AbortSignal(illegalConstructorKey);
+    signals = [...signals];
+    for (const signal of signals) {
+      if (!(signal instanceof AbortSignal)) {
+        throw new TypeError(
+          "Failed to execute 'any' on 'AbortSignal': Expected AbortSignal.",
+        );
+      }
+    }
+    for (const signal of signals) {
+      if (signal.aborted) {
+        dependent.#aborted = true;
+        dependent.#reason = signal.reason;
+        return dependent;
+      }
+    }
+    dependent.#dependent = true;
+    for (const signal of signals) {
+      // Follow the sources of dependent signals directly
+      const sources = signal.#dependent ? signal.#sources : [signal];
+      for (const source of sources) {
+        if (!dependent.#sources.includes(source)) {
+          dependent.#sources.push(source);
+          source.#dependents.push(dependent);
+        }
+      }
+    }
+    return dependent;
+  }
+
+  get aborted() {
+    return this.#aborted;
+  }
+
+  get reason() {
+    return this.#reason;
+  }
+
+  throwIfAborted() {
+    if (this.aborted) {
+      throw this.#reason;
+    }
+  }
+
+  static #signalAbort(signal, reason) {
+    if (signal.#aborted) return;
+    signal.#aborted = true;
+    signal.#reason = reason;
+
+    // Dependents are marked aborted before any abort event runs
+    const dependents = signal.#dependents.filter((dependent) =>
+      !dependent.#aborted
+    );
+    for (const dependent of dependents) {
+      dependent.#aborted = true;
+      dependent.#reason = reason;
+    }
+    signal.#dependents = [];
+    signal.#sources = [];
+
+    __internal.events.dispatchTrusted(signal, new Event("abort"));
+    for (const dependent of dependents) {
+      dependent.#sources = [];
+      __internal.events.dispatchTrusted(dependent, new Event("abort"));
+    }
+  }
+
+  static {
+    signalAbort = (signal, reason) => {
+      AbortSignal.#signalAbort(
+        signal,
+        reason === undefined ? abortError() : reason,
+      );
+    };
+    isObserved = (signal) =>
+      [signal, ...signal.#dependents].some((target) =>
+        __internal.events.hasListeners(target, "abort")
+      );
+  }
+
+  get [Symbol.toStringTag]() {
+    return "AbortSignal";
+  }
+}
+
+__internal.events.defineEventHandler(AbortSignal.prototype, "abort");
+
+// Called by the event loop of the runtime
+__internal.events.timers = {
+  fire(id) {
+    const signal = timeoutSignals.get(id);
+    timeoutSignals.delete(id);
+    signalAbort(
+      signal,
+      new DOMException("Signal timed out.", "TimeoutError"),
+    );
+  },
+  keepAlive() {
+    for (const signal of timeoutSignals.values()) {
+      if (isObserved(signal)) return true;
+    }
+    return false;
+  },
+};
+
+class AbortController {
+  #signal = new AbortSignal(illegalConstructorKey);
+
+  get signal() {
+    return this.#signal;
+  }
+
+  abort(reason) {
+    signalAbort(this.#signal, reason);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "AbortController";
+  }
+}
+
+function abortError() {
+  return new DOMException("The signal has been aborted", "AbortError");
+}
+
+globalThis.AbortSignal = AbortSignal;
+globalThis.AbortController = AbortController;
@@ -0,0 +1,622 @@
+// https://dom.spec.whatwg.org/#events
+// https://webidl.spec.whatwg.org/#idl-DOMException
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+// DOMException legacy error codes
+const DOM_EXCEPTION_CODES = {
+  IndexSizeError: 1,
+  HierarchyRequestError: 3,
+  WrongDocumentError: 4,
+  InvalidCharacterError: 5,
+  NoModificationAllowedError: 7,
+  NotFoundError: 8,
+  NotSupportedError: 9,
+  InUseAttributeError: 10,
+  InvalidStateError: 11,
+  SyntaxError: 12,
+  InvalidModificationError: 13,
+  NamespaceError: 14,
+  InvalidAccessError: 15,
+  TypeMismatchError: 17,
+  SecurityError: 18,
+  NetworkError: 19,
+  AbortError: 20,
+  URLMismatchError: 21,
+  QuotaExceededError: 22,
+  TimeoutError: 23,
+  InvalidNodeTypeError: 24,
+  DataCloneError: 25,
+};
+
+class DOMException extends Error {
+  #message;
+  #name;
+  #code;
+
+  constructor(message = "", options = "Error") {
+    super();
+    this.#message = String(message);
+    if (typeof options === "object" && options !== null) {
+      this.#name = "name" in options ? String(options.name) : "Error";
+      if ("cause" in options) {
+        Object.defineProperty(this, "cause", {
+          value: options.cause,
+          writable: true,
+          configurable: true,
+        });
+      }
+    } else {
+      this.#name = String(options);
+    }
+    this.#code = DOM_EXCEPTION_CODES[this.#name] ?? 0;
+  }
+
+  get message() {
+    return this.#message;
+  }
+
+  get name() {
+    return this.#name;
+  }
+
+  get code() {
+    return this.#code;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "DOMException";
+  }
+}
+
+const DOM_EXCEPTION_CONSTANTS = {
+  INDEX_SIZE_ERR: 1,
+  DOMSTRING_SIZE_ERR: 2,
+  HIERARCHY_REQUEST_ERR: 3,
+  WRONG_DOCUMENT_ERR: 4,
+  INVALID_CHARACTER_ERR: 5,
+  NO_DATA_ALLOWED_ERR: 6,
+  NO_MODIFICATION_ALLOWED_ERR: 7,
+  NOT_FOUND_ERR: 8,
+  NOT_SUPPORTED_ERR: 9,
+  INUSE_ATTRIBUTE_ERR: 10,
+  INVALID_STATE_ERR: 11,
+  SYNTAX_ERR: 12,
+  INVALID_MODIFICATION_ERR: 13,
+  NAMESPACE_ERR: 14,
+  INVALID_ACCESS_ERR: 15,
+  VALIDATION_ERR: 16,
+  TYPE_MISMATCH_ERR: 17,
+  SECURITY_ERR: 18,
+  NETWORK_ERR: 19,
+  ABORT_ERR: 20,
+  URL_MISMATCH_ERR: 21,
+  QUOTA_EXCEEDED_ERR: 22,
+  TIMEOUT_ERR: 23,
+  INVALID_NODE_TYPE_ERR: 24,
+  DATA_CLONE_ERR: 25,
+};
+
+for (const [key, value] of Object.entries(DOM_EXCEPTION_CONSTANTS)) {
+  const descriptor = { value, enumerable: true };
+  Object.defineProperty(DOMException, key, descriptor);
+  Object.defineProperty(DOMException.prototype, key, descriptor);
+}
+
+// Event phases
+const NONE = 0;
+const CAPTURING_PHASE = 1;
+const AT_TARGET = 2;
+const BUBBLING_PHASE = 3;
+
+// Internal event state, shared with EventTarget dispatch
+const eventState = new WeakMap();
+
+function getEventState(event) {
+  const state = eventState.get(event);
+  if (!state) {
+    throw new TypeError("Illegal invocation");
+  }
+  return state;
+}
+
+class Event {
+  constructor(type, eventInitDict = {}) {
+    if (arguments.length === 0) {
+      throw new TypeError(
+        "Failed to construct 'Event': 1 argument required, but only 0 present.",
+      );
+    }
+    eventInitDict ??= {};
+    eventState.set(this, {
+      type: String(type),
+      bubbles: Boolean(eventInitDict.bubbles),
+      cancelable: Boolean(eventInitDict.cancelable),
+      composed: Boolean(eventInitDict.composed),
+      target: null,
+      currentTarget: null,
+      eventPhase: NONE,
+      canceled: false,
+      initialized: true,
+      dispatching: false,
+      inPassiveListener: false,
+      stopPropagation: false,
+      stopImmediatePropagation: false,
+      isTrusted: false,
+      timeStamp: Date.now(),
+    });
+  }
+
+  get type() {
+    return getEventState(this).type;
+  }
+
+  get target() {
+    return getEventState(this).target;
+  }
+
+  get srcElement() {
+    return getEventState(this).target;
+  }
+
+  get currentTarget() {
+    return getEventState(this).currentTarget;
+  }
+
+  composedPath() {
+    const state = getEventState(this);
+    return state.dispatching && state.currentTarget ? [state.currentTarget] : [];
+  }
+
+  get eventPhase() {
+    return getEventState(this).eventPhase;
+  }
+
+  stopPropagation() {
+    getEventState(this).stopPropagation = true;
+  }
+
+  get cancelBubble() {
+    return getEventState(this).stopPropagation;
+  }
+
+  set cancelBubble(value) {
+    if (value) {
+      getEventState(this).stopPropagation = true;
+    }
+  }
+
+  stopImmediatePropagation() {
+    const state = getEventState(this);
+    state.stopPropagation = true;
+    state.stopImmediatePropagation = true;
+  }
+
+  get bubbles() {
+    return getEventState(this).bubbles;
+  }
+
+  get cancelable() {
+    return getEventState(this).cancelable;
+  }
+
+  get returnValue() {
+    return !getEventState(this).canceled;
+  }
+
+  set returnValue(value) {
+    if (!value) {
+      setCanceled(getEventState(this));
+    }
+  }
+
+  preventDefault() {
+    setCanceled(getEventState(this));
+  }
+
+  get defaultPrevented() {
+    return getEventState(this).canceled;
+  }
+
+  get composed() {
+    return getEventState(this).composed;
+  }
+
+  get isTrusted() {
+    return getEventState(this).isTrusted;
+  }
+
+  get timeStamp() {
+    return getEventState(this).timeStamp;
+  }
+
+  initEvent(type, bubbles = false, cancelable = false) {
+    const state = getEventState(this);
+    if (state.dispatching) return;
+    state.initialized = true;
+    state.stopPropagation = false;
+    state.stopImmediatePropagation = false;
+    state.canceled = false;
+    state.isTrusted = false;
+    state.target = null;
+    state.type = String(type);
+    state.bubbles = Boolean(bubbles);
+    state.cancelable = Boolean(cancelable);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Event";
+  }
+}
+
+function setCanceled(state) {
+  if (state.cancelable && !state.inPassiveListener) {
+    state.canceled = true;
+  }
+}
+
+for (
+  const [key, value] of Object.entries({
+    NONE,
+    CAPTURING_PHASE,
+    AT_TARGET,
+    BUBBLING_PHASE,
+  })
+) {
+  const descriptor = { value, enumerable: true };
+  Object.defineProperty(Event, key, descriptor);
+  Object.defineProperty(Event.prototype, key, descriptor);
+}
+
+class CustomEvent extends Event {
+  #detail;
+
+  constructor(type, eventInitDict = {}) {
+    super(type, eventInitDict);
+    this.#detail = eventInitDict?.detail ?? null;
+  }
+
+  get detail() {
+    return this.#detail;
+  }
+
+  initCustomEvent(type, bubbles = false, cancelable = false, detail = null) {
+    if (getEventState(this).dispatching) return;
+    this.initEvent(type, bubbles, cancelable);
+    this.#detail = detail;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "CustomEvent";
+  }
+}
+
+// https://html.spec.whatwg.org/multipage/webappapis.html#errorevent
+class ErrorEvent extends Event {
+  #message;
+  #filename;
+  #lineno;
+  #colno;
+  #error;
+
+  constructor(type, eventInitDict = {}) {
+    super(type, eventInitDict);
+    this.#message = String(eventInitDict?.message ?? "");
+    this.#filename = String(eventInitDict?.filename ?? "");
+    this.#lineno = Number(eventInitDict?.lineno ?? 0);
+    this.#colno = Number(eventInitDict?.colno ?? 0);
+    this.#error = eventInitDict?.error;
+  }
+
+  get message() {
+    return this.#message;
+  }
+
+  get filename() {
+    return this.#filename;
+  }
+
+  get lineno() {
+    return this.#lineno;
+  }
+
+  get colno() {
+    return this.#colno;
+  }
+
+  get error() {
+    return this.#error;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "ErrorEvent";
+  }
+}
+
+// https://html.spec.whatwg.org/multipage/comms.html#messageevent
+class MessageEvent extends Event {
+  #data;
+  #origin;
+  #lastEventId;
+  #source;
+  #ports;
+
+  constructor(type, eventInitDict = {}) {
+    super(type, eventInitDict);
+    this.#data = eventInitDict?.data ?? null;
+    this.#origin = String(eventInitDict?.origin ?? "");
+    this.#lastEventId = String(eventInitDict?.lastEventId ?? "");
+    this.#source = eventInitDict?.source ?? null;
+    this.#ports = Object.freeze([...(eventInitDict?.ports ?? [])]);
+  }
+
+  get data() {
+    return this.#data;
+  }
+
+  get origin() {
+    return this.#origin;
+  }
+
+  get lastEventId() {
+    return this.#lastEventId;
+  }
+
+  get source() {
+    return this.#source;
+  }
+
+  get ports() {
+    return this.#ports;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "MessageEvent";
+  }
+}
+
+// https://html.spec.whatwg.org/multipage/webappapis.html#promiserejectionevent
+class PromiseRejectionEvent extends Event {
+  #promise;
+  #reason;
+
+  constructor(type, eventInitDict) {
+    if (typeof eventInitDict !== "object" || eventInitDict === null) {
+      throw new TypeError(
+        "Failed to construct 'PromiseRejectionEvent': 2 arguments required.",
+      );
+    }
+    super(type, eventInitDict);
+    this.#promise = eventInitDict.promise;
+    this.#reason = eventInitDict.reason;
+  }
+
+  get promise() {
+    return this.#promise;
+  }
+
+  get reason() {
+    return this.#reason;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "PromiseRejectionEvent";
+  }
+}
+
+// Listener lists keyed by target so that any object (e.g. globalThis) can be an EventTarget
+const targetListeners = new WeakMap();
+
+function getListeners(target) {
+  const listeners = targetListeners.get(target);
+  if (!listeners) {
+    throw new TypeError("Illegal invocation");
+  }
+  return listeners;
+}
+
+function normalizeOptions(options) {
+  if (typeof options === "boolean" || options == null) {
+    return { capture: Boolean(options) };
+  }
+  return options;
+}
+
+class EventTarget {
+  constructor() {
+    targetListeners.set(this, new Map());
+  }
+
+  addEventListener(type, callback, options) {
+    const listeners = getListeners(this ?? globalThis);
+    if (callback === null || callback === undefined) return;
+
+    options = normalizeOptions(options);
+    const capture = Boolean(options.capture);
+    const signal = options.signal;
+    if (signal?.aborted) return;
+
+    type = String(type);
+    let list = listeners.get(type);
+    if (!list) {
+      list = [];
+      listeners.set(type, list);
+    }
+    if (
+      list.some((listener) =>
+        listener.callback === callback && listener.capture === capture
+      )
+    ) {
+      return;
+    }
+
+    const listener = {
+      callback,
+      capture,
+      once: Boolean(options.once),
+      passive: Boolean(options.passive),
+      removed: false,
+    };
+    list.push(listener);
+
+    if (signal) {
+      signal.addEventListener("abort", () => {
+        this.removeEventListener(type, callback, { capture });
+      });
+    }
+  }
+
+  removeEventListener(type, callback, options) {
+    const listeners = getListeners(this ?? globalThis);
+    const capture = Boolean(normalizeOptions(options).capture);
+    const list = listeners.get(String(type));
+    if (!list) return;
+
+    const index = list.findIndex((listener) =>
+      listener.callback === callback && listener.capture === capture
+    );
+    if (index !== -1) {
+      list[index].removed = true;
+      list.splice(index, 1);
+    }
+  }
+
+  dispatchEvent(event) {
+    const target = this ?? globalThis;
+    getListeners(target);
+    const state = eventState.get(event);
+    if (!state) {
+      throw new TypeError(
+        "Failed to execute 'dispatchEvent' on 'EventTarget': parameter 1 is not of type 'Event'.",
+      );
+    }
+    if (state.dispatching || !state.initialized) {
+      throw new DOMException(
+        "Failed to execute 'dispatchEvent' on 'EventTarget': The event is already being dispatched.",
+        "InvalidStateError",
+      );
+    }
+    state.isTrusted = false;
+    return dispatch(target, event, state);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "EventTarget";
+  }
+}
+
+function dispatch(target, event, state) {
+  state.dispatching = true;
+  state.target = target;
+  state.currentTarget = target;
+  state.eventPhase = AT_TARGET;
+
+  // There is no tree to walk, so capturing listeners run first at the target
+  invokeListeners(target, event, state, true);
+  invokeListeners(target, event, state, false);
+
+  state.dispatching = false;
+  state.eventPhase = NONE;
+  state.currentTarget = null;
+  state.stopPropagation = false;
+  state.stopImmediatePropagation = false;
+
+  return !state.canceled;
+}
+
+function invokeListeners(target, event, state, capture) {
+  const list = targetListeners.get(target)?.get(state.type);
+  if (!list) return;
+
+  for (const listener of [...list]) {
+    if (state.stopImmediatePropagation) return;
+    if (listener.removed || listener.capture !== capture) continue;
+
+    if (listener.once) {
+      target.removeEventListener(state.type, listener.callback, { capture });
+    }
+
+    state.inPassiveListener = listener.passive;
+    try {
+      const callback = listener.callback;
+      if (typeof callback === "function") {
+        callback.call(target, event);
+      } else if (typeof callback?.handleEvent === "function") {
+        callback.handleEvent(event);
+      }
+    } catch (error) {
+      reportException(error);
+    }
+    state.inPassiveListener = false;
+  }
+}
+
+function reportException(error) {
+  if (__internal.lifecycle) {
+    __internal.lifecycle.reportException(error);
+  } else {
+    throw error;
+  }
+}
+
+// Make an existing object (e.g. globalThis) behave as an EventTarget
+function setEventTargetData(target) {
+  targetListeners.set(target, new Map());
+}
+
+// Define an `on<name>` event handler attribute backed by a listener
+function defineEventHandler(target, name) {
+  const handlers = new WeakMap();
+  Object.defineProperty(target, `on${name}`, {
+    get() {
+      return handlers.get(this ?? globalThis)?.handler ?? null;
+    },
+    set(value) {
+      const self = this ?? globalThis;
+      let entry = handlers.get(self);
+      if (!entry) {
+        entry = {
+          handler: null,
+          listener(event) {
+            if (typeof entry.handler !== "function") return;
+            const result = entry.handler.call(self, event);
+            if (result === false) {
+              event.preventDefault();
+            }
+          },
+        };
+        handlers.set(self, entry);
+        self.addEventListener(name, entry.listener);
+      }
+      entry.handler = typeof value === "function" ? value : null;
+    },
+    configurable: true,
+    enumerable: true,
+  });
+}
+
+// Dispatch an event generated by the runtime itself
+function dispatchTrusted(target, event) {
+  const state = getEventState(event);
+  state.isTrusted = true;
+  return dispatch(target, event, state);
+}
+
+// Whether `target` has listeners for events of `type`
+function hasListeners(target, type) {
+  return (targetListeners.get(target)?.get(type)?.length ?? 0) > 0;
+}
+
+__internal.events = {
+  setEventTargetData,
+  defineEventHandler,
+  dispatchTrusted,
+  hasListeners,
+};
+
+globalThis.DOMException = DOMException;
+globalThis.Event = Event;
+globalThis.EventTarget = EventTarget;
+globalThis.CustomEvent = CustomEvent;
+globalThis.ErrorEvent = ErrorEvent;
+globalThis.MessageEvent = MessageEvent;
+globalThis.PromiseRejectionEvent = PromiseRejectionEvent;
@@ -0,0 +1,16 @@
+use rquickjs::{Ctx, Module};
+
+mod timers;
+
+pub use timers::{next_timer, run_timers};
+
+pub fn init(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    let module = Module::evaluate(ctx.clone(), "web_events", include_str!("events.js"))?;
+    module.finish::<()>()?;
+
+    timers::setup_internal(ctx)?;
+
+    let module = Module::evaluate(ctx.clone(), "web_events_abort", include_str!("abort.js"))?;
+    module.finish::<()>()?;
+    Ok(())
+}
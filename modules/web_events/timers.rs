@@ -0,0 +1,83 @@
+//! Host timers run by the event loop of the runtime. Callbacks stay on the JS
+//! side, keyed by timer id, so no JS value outlives the runtime.
+
+use rquickjs::{Ctx, Function, Object, Result};
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+use utils::add_internal_function;
+
+#[derive(Default)]
+struct Timers {
+    next_id: u32,
+    pending: Vec<(Instant, u32)>,
+}
+
+thread_local! {
+    static TIMERS: RefCell<Timers> = RefCell::default();
+}
+
+pub fn setup_internal(ctx: &Ctx) -> Result<()> {
+    add_internal_function!(ctx, "events.setTimer", |milliseconds: f64| -> u32 {
+        let deadline = Instant::now() + Duration::from_secs_f64(milliseconds.max(0.0) / 1000.0);
+        TIMERS.with_borrow_mut(|timers| {
+            timers.next_id += 1;
+            let id = timers.next_id;
+            timers.pending.push((deadline, id));
+            id
+        })
+    });
+
+    add_internal_function!(ctx, "events.clearTimer", |id: u32| {
+        TIMERS.with_borrow_mut(|timers| timers.pending.retain(|&(_, timer)| timer != id));
+    });
+
+    Ok(())
+}
+
+/// Fire the timers whose deadline has passed.
+pub fn run_timers(ctx: &Ctx<'_>) -> Result<()> {
+    let now = Instant::now();
+    let expired: Vec<u32> = TIMERS.with_borrow_mut(|timers| {
+        if timers.pending.iter().all(|&(deadline, _)| deadline > now) {
+            return Vec::new();
+        }
+        let mut expired: Vec<(Instant, u32)> = Vec::new();
+        timers.pending.retain(|&timer| {
+            let keep = timer.0 > now;
+            if !keep {
+                expired.push(timer);
+            }
+            keep
+        });
+        expired.sort();
+        expired.into_iter().map(|(_, id)| id).collect()
+    });
+
+    if expired.is_empty() {
+        return Ok(());
+    }
+    let fire: Function = internal_timers(ctx)?.get("fire")?;
+    for id in expired {
+        fire.call::<_, ()>((id,))?;
+    }
+    Ok(())
+}
+
+/// Time until the next timer fires, if a pending timer keeps the process alive.
+pub fn next_timer(ctx: &Ctx<'_>) -> Result<Option<Duration>> {
+    let Some(deadline) =
+        TIMERS.with_borrow(|timers| timers.pending.iter().map(|&(deadline, _)| deadline).min())
+    else {
+        return Ok(None);
+    };
+
+    let keep_alive: Function = internal_timers(ctx)?.get("keepAlive")?;
+    if !keep_alive.call::<_, bool>(())? {
+        return Ok(None);
+    }
+    Ok(Some(deadline.saturating_duration_since(Instant::now())))
+}
+
+fn internal_timers<'js>(ctx: &Ctx<'js>) -> Result<Object<'js>> {
+    ctx.eval("globalThis[Symbol.for('mdeno.internal')].events.timers")
+}
@@ -0,0 +1,144 @@
+// https://w3c.github.io/FileAPI/#blob-section
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+// Bytes of a Blob, assigned inside Blob to reach its private state
+let blobBytes;
+
+function toBytes(part) {
+  if (part instanceof Blob) {
+    return blobBytes(part);
+  }
+  if (part instanceof ArrayBuffer) {
+    return new Uint8Array(part.slice(0));
+  }
+  if (ArrayBuffer.isView(part)) {
+    return new Uint8Array(
+      part.buffer.slice(part.byteOffset, part.byteOffset + part.byteLength),
+    );
+  }
+  return __internal.fetch.encode(String(part));
+}
+
+// Type strings containing anything but printable ASCII are ignored
+function normalizeType(type) {
+  type = type === undefined ? "" : String(type);
+  return /^[\x20-\x7e]*$/.test(type) ? type.toLowerCase() : "";
+}
+
+function relativeIndex(index, size) {
+  if (index === undefined) return undefined;
+  index = Math.trunc(Number(index)) || 0;
+  return index < 0 ? Math.max(size + index, 0) : Math.min(index, size);
+}
+
+class Blob {
+  #bytes;
+  #type;
+
+  constructor(blobParts = [], options = {}) {
+    if (typeof blobParts?.[Symbol.iterator] !== "function") {
+      throw new TypeError(
+        "Failed to construct 'Blob': The provided value cannot be converted to a sequence.",
+      );
+    }
+    const parts = [...blobParts].map(toBytes);
+    const size = parts.reduce((total, part) => total + part.byteLength, 0);
+
+    this.#bytes = new Uint8Array(size);
+    let offset = 0;
+    for (const part of parts) {
+      this.#bytes.set(part, offset);
+      offset += part.byteLength;
+    }
+    this.#type = normalizeType(options?.type);
+  }
+
+  get size() {
+    return this.#bytes.byteLength;
+  }
+
+  get type() {
+    return this.#type;
+  }
+
+  slice(start, end, contentType) {
+    const size = this.size;
+    start = relativeIndex(start, size) ?? 0;
+    end = relativeIndex(end, size) ?? size;
+
+    const blob = new Blob([], { type: contentType });
+    blob.#bytes = this.#bytes.slice(start, Math.max(start, end));
+    return blob;
+  }
+
+  // deno-lint-ignore require-await
+  async text() {
+    return __internal.fetch.decode(this.#bytes, "utf-8");
+  }
+
+  // deno-lint-ignore require-await
+  async arrayBuffer() {
+    return this.#bytes.slice().buffer;
+  }
+
+  // deno-lint-ignore require-await
+  async bytes() {
+    return this.#bytes.slice();
+  }
+
+  stream() {
+    const bytes = this.#bytes.slice();
+    return __internal.streams.createReadableStream({
+      start(controller) {
+        if (bytes.byteLength > 0) {
+          controller.enqueue(bytes);
+        }
+        controller.close();
+      },
+    });
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Blob";
+  }
+
+  static {
+    blobBytes = (blob) => blob.#bytes;
+  }
+}
+
+// https://w3c.github.io/FileAPI/#file-section
+class File extends Blob {
+  #name;
+  #lastModified;
+
+  constructor(fileBits, fileName, options = {}) {
+    if (arguments.length < 2) {
+      throw new TypeError(
+        `Failed to construct 'File': 2 arguments required, but only ${arguments.length} present.`,
+      );
+    }
+    super(fileBits, options);
+    this.#name = String(fileName);
+    this.#lastModified = options?.lastModified === undefined
+      ? Date.now()
+      : Math.trunc(Number(options.lastModified)) || 0;
+  }
+
+  get name() {
+    return this.#name;
+  }
+
+  get lastModified() {
+    return this.#lastModified;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "File";
+  }
+}
+
+__internal.fetch.blobBytes = blobBytes;
+
+globalThis.Blob = Blob;
+globalThis.File = File;
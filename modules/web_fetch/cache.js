@@ -0,0 +1,308 @@
+// https://w3c.github.io/ServiceWorker/#cache-objects
+// https://httpwg.org/specs/rfc9111.html
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+// Statuses the HTTP cache stores, those cacheable by default
+// https://httpwg.org/specs/rfc9110.html#rfc.section.15.1
+const CACHEABLE_STATUSES = [
+  200,
+  203,
+  204,
+  300,
+  301,
+  308,
+  404,
+  405,
+  410,
+  414,
+  501,
+];
+
+// Headers of a 304 response that do not replace those of the stored response
+const UNUPDATED_HEADERS = ["content-length", "content-encoding"];
+
+function toRequest(request) {
+  return request instanceof Request ? request : new Request(request);
+}
+
+function withoutFragment(url) {
+  return url.split("#")[0];
+}
+
+// Lower-cased names of the request headers a response varies on
+function varyNames(headers) {
+  const vary = headers.get("vary");
+  if (vary === null) return [];
+  return vary.split(",").map((name) => name.trim().toLowerCase())
+    .filter((name) => name !== "");
+}
+
+// Metadata of a stored entry: the URL and varying headers of the request, and
+// the response without its body
+function entryMeta(request, response) {
+  return {
+    url: withoutFragment(request.url),
+    requestHeaders: varyNames(response.headers).map((name) => [
+      name,
+      request.headers.get(name),
+    ]),
+    status: response.status,
+    statusText: response.statusText,
+    headers: [...response.headers],
+    responseUrl: response.url,
+    redirected: response.redirected,
+  };
+}
+
+// https://w3c.github.io/ServiceWorker/#request-matches-cached-item-algorithm
+function queryCache(name, request, options) {
+  if (
+    request !== null && request.method !== "GET" && !options?.ignoreMethod
+  ) {
+    return [];
+  }
+  const entries = JSON.parse(__internal.cache.entries(
+    name,
+    request && withoutFragment(request.url),
+    Boolean(options?.ignoreSearch),
+  ));
+  if (request === null || options?.ignoreVary) return entries;
+  return entries.filter((entry) =>
+    entry.requestHeaders.every(([name, value]) =>
+      request.headers.get(name) === value
+    )
+  );
+}
+
+// Response of a stored entry, with its body
+function storedResponse(entry, body) {
+  const response = new Response(body, {
+    status: entry.status,
+    statusText: entry.statusText,
+    headers: entry.headers,
+  });
+  __internal.fetch.setHeadersGuard(response.headers, "immutable");
+  __internal.fetch.setResponseURL(
+    response,
+    entry.responseUrl,
+    entry.redirected,
+  );
+  return response;
+}
+
+// https://w3c.github.io/ServiceWorker/#cache-interface
+class Cache {
+  #name;
+
+  constructor(key = null, name = undefined) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#name = name;
+  }
+
+  async match(request, options = undefined) {
+    const [entry] = queryCache(this.#name, toRequest(request), options);
+    return entry &&
+      storedResponse(entry, __internal.cache.body(this.#name, entry.key));
+  }
+
+  async put(request, response) {
+    request = toRequest(request);
+    if (!/^https?:/.test(request.url)) {
+      throw new TypeError("Request url protocol must be 'http:' or 'https:'");
+    }
+    if (request.method !== "GET") {
+      throw new TypeError("Request method must be GET");
+    }
+    if (!(response instanceof Response)) {
+      throw new TypeError("Response must be a Response");
+    }
+    if (response.status === 206) {
+      throw new TypeError("Response status must not be 206");
+    }
+    if (varyNames(response.headers).includes("*")) {
+      throw new TypeError("Vary header must not contain '*'");
+    }
+    if (response.bodyUsed) {
+      throw new TypeError("Response body is already used");
+    }
+    const body = new Uint8Array(await response.arrayBuffer());
+    // Entries are stored per value of the headers the response varies on, and
+    // those the request matches are replaced whatever they vary on
+    for (const entry of queryCache(this.#name, request)) {
+      __internal.cache.remove(this.#name, entry.key);
+    }
+    __internal.cache.put(
+      this.#name,
+      JSON.stringify(entryMeta(request, response)),
+      body,
+    );
+  }
+
+  async delete(request, options = undefined) {
+    const entries = queryCache(this.#name, toRequest(request), options);
+    let deleted = false;
+    for (const entry of entries) {
+      deleted = __internal.cache.remove(this.#name, entry.key) || deleted;
+    }
+    return deleted;
+  }
+
+  async keys(request = undefined, options = undefined) {
+    request = request === undefined ? null : toRequest(request);
+    return queryCache(this.#name, request, options).map((entry) =>
+      new Request(entry.url, {
+        headers: entry.requestHeaders.filter(([, value]) => value !== null),
+      })
+    );
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Cache";
+  }
+}
+
+// https://w3c.github.io/ServiceWorker/#cachestorage-interface
+class CacheStorage {
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+  }
+
+  async open(cacheName) {
+    cacheName = String(cacheName);
+    __internal.cache.open(cacheName);
+    return new Cache(illegalConstructorKey, cacheName);
+  }
+
+  async has(cacheName) {
+    return __internal.cache.has(String(cacheName));
+  }
+
+  async delete(cacheName) {
+    return __internal.cache.delete(String(cacheName));
+  }
+
+  async keys() {
+    return JSON.parse(__internal.cache.names());
+  }
+
+  get [Symbol.toStringTag]() {
+    return "CacheStorage";
+  }
+}
+
+// Directives of a Cache-Control header, by lower-cased name
+function cacheControl(headers) {
+  const directives = new Map();
+  for (const part of (headers.get("cache-control") ?? "").split(",")) {
+    const [name, value = ""] = part.split("=", 2);
+    if (name.trim() !== "") {
+      directives.set(name.trim().toLowerCase(), value.trim().replace(/"/g, ""));
+    }
+  }
+  return directives;
+}
+
+// Whether a stored response can be used without revalidation
+// https://httpwg.org/specs/rfc9111.html#expiration.model
+function isFresh(entry) {
+  const headers = new Headers(entry.headers);
+  const directives = cacheControl(headers);
+  if (directives.has("no-cache")) return false;
+
+  const date = Date.parse(headers.get("date") ?? "") || entry.time;
+  let lifetime;
+  if (directives.has("max-age")) {
+    lifetime = Number(directives.get("max-age")) * 1000;
+  } else if (headers.has("expires")) {
+    lifetime = (Date.parse(headers.get("expires")) || 0) - date;
+  } else if (headers.has("last-modified")) {
+    // Heuristic freshness, a tenth of the time since the last modification
+    lifetime = (date - (Date.parse(headers.get("last-modified")) || date)) / 10;
+  } else {
+    return false;
+  }
+  const age = Number(headers.get("age") ?? 0) * 1000 + Date.now() - entry.time;
+  return age < lifetime;
+}
+
+// Whether a response may be stored by the HTTP cache
+function isStorable(response) {
+  const directives = cacheControl(response.headers);
+  return CACHEABLE_STATUSES.includes(response.status) &&
+    !directives.has("no-store") &&
+    !varyNames(response.headers).includes("*") &&
+    (directives.has("max-age") || directives.has("no-cache") ||
+      response.headers.has("expires") || response.headers.has("etag") ||
+      response.headers.has("last-modified"));
+}
+
+// Fetch a GET request through the HTTP cache, following its cache mode
+// https://fetch.spec.whatwg.org/#http-network-or-cache-fetch
+async function cachedFetch(request) {
+  const mode = request.cache;
+  const [stored] = mode === "reload" ? [] : queryCache(null, request);
+  if (
+    stored !== undefined &&
+    (mode === "force-cache" || mode === "only-if-cached" ||
+      (mode === "default" && isFresh(stored)))
+  ) {
+    return storedResponse(stored, __internal.cache.body(null, stored.key));
+  }
+  if (mode === "only-if-cached") {
+    throw new TypeError(
+      `Failed to fetch: ${request.url} is not in the HTTP cache`,
+    );
+  }
+
+  // Stale responses are revalidated, unless the request is conditional itself
+  const headers = new Headers(request.headers);
+  const storedHeaders = new Headers(stored?.headers);
+  const conditional = ["if-none-match", "if-modified-since"]
+    .some((name) => headers.has(name));
+  let revalidating = false;
+  if (stored !== undefined && !conditional) {
+    if (storedHeaders.has("etag")) {
+      headers.set("if-none-match", storedHeaders.get("etag"));
+      revalidating = true;
+    }
+    if (storedHeaders.has("last-modified")) {
+      headers.set("if-modified-since", storedHeaders.get("last-modified"));
+      revalidating = true;
+    }
+  }
+
+  const response = await fetch(
+    new Request(request, { cache: "no-store", headers }),
+  );
+  if (revalidating && response.status === 304) {
+    // The stored response is still valid, with the headers of the new one
+    for (const [name, value] of response.headers) {
+      if (!UNUPDATED_HEADERS.includes(name)) storedHeaders.set(name, value);
+    }
+    const updated = { ...stored, headers: [...storedHeaders] };
+    const body = __internal.cache.body(null, stored.key);
+    __internal.cache.put(null, JSON.stringify(updated), body);
+    return storedResponse(updated, body);
+  }
+
+  if (!isStorable(response)) return response;
+
+  // Stored responses are read whole, so the next request finds them
+  const body = new Uint8Array(await response.arrayBuffer());
+  const meta = entryMeta(request, response);
+  if (stored !== undefined) __internal.cache.remove(null, stored.key);
+  __internal.cache.put(null, JSON.stringify(meta), body);
+  return storedResponse(meta, body);
+}
+
+__internal.fetch.cachedFetch = cachedFetch;
+
+globalThis.Cache = Cache;
+globalThis.CacheStorage = CacheStorage;
+globalThis.caches = new CacheStorage(illegalConstructorKey);
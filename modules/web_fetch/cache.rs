@@ -0,0 +1,257 @@
+//! Disk storage behind the Cache Storage API and the HTTP cache of fetch. Each
+//! cache is a directory of entries, one per URL and values of the request headers
+//! the response varies on, kept as a JSON file with the request and response
+//! metadata next to a file with the response body. The caches live in `DENO_DIR`,
+//! or in the cache directory of the user.
+
+use rquickjs::{Ctx, TypedArray};
+use sha2::{Digest, Sha256};
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use utils::add_internal_function;
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].cache = {};")?;
+
+    add_internal_function!(ctx, "cache.open", |ctx: Ctx<'_>, name: String| {
+        open(&name).map_err(|e| throw(&ctx, &e))
+    });
+
+    add_internal_function!(ctx, "cache.has", |name: String| -> bool {
+        cache_dir(Some(&name)).is_ok_and(|dir| dir.is_dir())
+    });
+
+    add_internal_function!(ctx, "cache.delete", |ctx: Ctx<'_>, name: String| {
+        let dir = cache_dir(Some(&name)).map_err(|e| throw(&ctx, &e))?;
+        match std::fs::remove_dir_all(dir) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(throw(&ctx, &io_error(e))),
+        }
+    });
+
+    add_internal_function!(ctx, "cache.names", |ctx: Ctx<'_>| {
+        cache_names().map_err(|e| throw(&ctx, &e))
+    });
+
+    add_internal_function!(
+        ctx,
+        "cache.put",
+        |ctx: Ctx<'_>, name: Option<String>, meta: String, body: TypedArray<'_, u8>| {
+            let body = body.as_bytes().unwrap_or_default();
+            put(name.as_deref(), &meta, body).map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    add_internal_function!(
+        ctx,
+        "cache.entries",
+        |ctx: Ctx<'_>, name: Option<String>, url: Option<String>, ignore_search: bool| {
+            entries(name.as_deref(), url.as_deref(), ignore_search).map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    add_internal_function!(ctx, "cache.body", body);
+
+    add_internal_function!(
+        ctx,
+        "cache.remove",
+        |ctx: Ctx<'_>, name: Option<String>, key: String| {
+            remove(name.as_deref(), &key).map_err(|e| throw(&ctx, &e))
+        }
+    );
+
+    Ok(())
+}
+
+fn throw(ctx: &Ctx<'_>, message: &str) -> rquickjs::Error {
+    rquickjs::Exception::throw_type(ctx, message)
+}
+
+fn io_error(e: std::io::Error) -> String {
+    format!("Cache storage failed: {}", e)
+}
+
+fn root_dir() -> Result<PathBuf, String> {
+    match std::env::var_os("DENO_DIR").filter(|dir| !dir.is_empty()) {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => dirs::cache_dir()
+            .map(|dir| dir.join("mdeno"))
+            .ok_or_else(|| "Cache storage failed: No cache directory".to_string()),
+    }
+}
+
+// File of a cache directory holding the name of the cache
+const NAME_FILE: &str = "name";
+
+/// Directory of cache `name` of Cache Storage, or of the HTTP cache when there is
+/// no name. Directories are named after the hash of the name, so that names of any
+/// length and content make valid file names.
+fn cache_dir(name: Option<&str>) -> Result<PathBuf, String> {
+    let root = root_dir()?;
+    Ok(match name {
+        Some(name) => root.join("caches").join(hash(name.as_bytes())),
+        None => root.join("http_cache"),
+    })
+}
+
+fn hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Create cache `name` unless it exists, recording its name for `cache_names`.
+fn open(name: &str) -> Result<(), String> {
+    let dir = cache_dir(Some(name))?;
+    std::fs::create_dir_all(&dir).map_err(io_error)?;
+    std::fs::write(dir.join(NAME_FILE), name).map_err(io_error)
+}
+
+/// Names of the caches of Cache Storage, as a JSON array.
+fn cache_names() -> Result<String, String> {
+    let dir = root_dir()?.join("caches");
+    let mut names = Vec::new();
+    match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries {
+                let path = entry.map_err(io_error)?.path().join(NAME_FILE);
+                // Caches deleted meanwhile are skipped
+                if let Ok(name) = std::fs::read_to_string(path) {
+                    names.push(name);
+                }
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(io_error(e)),
+    }
+    names.sort();
+    Ok(serde_json::Value::from(names).to_string())
+}
+
+/// Path of a file of the entry `key`. Keys are hashes, anything else is rejected so
+/// that paths stay within the cache.
+fn entry_path(name: Option<&str>, key: &str, extension: &str) -> Result<PathBuf, String> {
+    if key.is_empty() || !key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err("Invalid cache entry".to_string());
+    }
+    Ok(cache_dir(name)?.join(format!("{}.{}", key, extension)))
+}
+
+/// Key of the entry of `url`: the hash of the URL, by which the entries of a URL are
+/// found, followed by the hash of the `request_headers` the response varies on.
+fn entry_key(url: &str, request_headers: &serde_json::Value) -> String {
+    let mut headers: Vec<_> = request_headers.as_array().into_iter().flatten().collect();
+    headers.sort_by_key(|pair| pair[0].as_str());
+    let headers = serde_json::Value::from(headers.into_iter().cloned().collect::<Vec<_>>());
+    hash(url.as_bytes()) + &hash(headers.to_string().as_bytes())
+}
+
+/// Store a response, replacing the entry of the same URL and varying request
+/// headers. `meta` is JSON with the `url` and `requestHeaders` of the request and
+/// what is needed to rebuild the request and response.
+fn put(name: Option<&str>, meta: &str, body: &[u8]) -> Result<(), String> {
+    let mut meta: serde_json::Value =
+        serde_json::from_str(meta).map_err(|e| format!("Invalid cache entry: {}", e))?;
+    let url = meta["url"].as_str().ok_or("Invalid cache entry")?;
+    let key = entry_key(url, &meta["requestHeaders"]);
+    meta["key"] = key.clone().into();
+    // Milliseconds with their fraction, so that entries put in the same millisecond
+    // keep their order
+    meta["time"] = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0.0, |time| time.as_secs_f64() * 1000.0)
+        .into();
+
+    let dir = cache_dir(name)?;
+    if name.is_none() {
+        std::fs::create_dir_all(&dir).map_err(io_error)?;
+    } else if !dir.is_dir() {
+        return Err("Cache was deleted".to_string());
+    }
+
+    // Files are written aside and renamed, so readers never see a partial entry.
+    // The body goes first, as entries are found by their metadata.
+    let write = |extension, contents: &[u8]| {
+        let path = entry_path(name, &key, extension)?;
+        let temp = path.with_extension(format!("{}.{}", extension, std::process::id()));
+        std::fs::write(&temp, contents).map_err(io_error)?;
+        std::fs::rename(&temp, &path).map_err(io_error)
+    };
+    write("body", body)?;
+    write("json", meta.to_string().as_bytes())
+}
+
+/// Metadata of the entries of a cache as a JSON array, oldest first. With a `url`,
+/// only its entries are listed, or the entries of the URLs differing from it in
+/// their query when `ignore_search` is set.
+fn entries(name: Option<&str>, url: Option<&str>, ignore_search: bool) -> Result<String, String> {
+    let dir = cache_dir(name)?;
+    let mut found = Vec::new();
+
+    // Entries are named after their URL, so the metadata of others is not read
+    let prefix = url
+        .filter(|_| !ignore_search)
+        .map(|url| hash(url.as_bytes()));
+    let without_search = |url: &str| url.split(['?', '#']).next().unwrap_or_default().to_string();
+    let wanted = |entry_url: &str| match url {
+        None => true,
+        Some(url) if ignore_search => without_search(url) == without_search(entry_url),
+        Some(url) => url == entry_url,
+    };
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok("[]".to_string()),
+        Err(e) => return Err(io_error(e)),
+    };
+    for entry in entries {
+        let path = entry.map_err(io_error)?.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        if let Some(prefix) = &prefix
+            && !path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(prefix.as_str()))
+        {
+            continue;
+        }
+        // Entries removed meanwhile are skipped
+        let Ok(meta) = std::fs::read(&path) else {
+            continue;
+        };
+        let Ok(meta) = serde_json::from_slice::<serde_json::Value>(&meta) else {
+            continue;
+        };
+        if wanted(meta["url"].as_str().unwrap_or_default()) {
+            found.push(meta);
+        }
+    }
+    let time = |meta: &serde_json::Value| meta["time"].as_f64().unwrap_or_default();
+    found.sort_by(|a, b| time(a).total_cmp(&time(b)));
+    Ok(serde_json::Value::from(found).to_string())
+}
+
+/// Body of the entry `key`.
+fn body<'js>(
+    ctx: Ctx<'js>,
+    name: Option<String>,
+    key: String,
+) -> rquickjs::Result<TypedArray<'js, u8>> {
+    let body = entry_path(name.as_deref(), &key, "body")
+        .and_then(|path| std::fs::read(path).map_err(io_error));
+    let body = body.map_err(|e| throw(&ctx, &e))?;
+    TypedArray::new(ctx, body)
+}
+
+fn remove(name: Option<&str>, key: &str) -> Result<bool, String> {
+    match std::fs::remove_file(entry_path(name, key, "json")?) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(io_error(e)),
+    }
+    let _ = std::fs::remove_file(entry_path(name, key, "body")?);
+    Ok(true)
+}
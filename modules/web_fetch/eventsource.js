@@ -0,0 +1,115 @@
+// https://html.spec.whatwg.org/multipage/server-sent-events.html
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const CONNECTING = 0;
+const OPEN = 1;
+const CLOSED = 2;
+
+class EventSource extends EventTarget {
+  #id;
+  #url;
+  #withCredentials;
+  #readyState = CONNECTING;
+
+  constructor(url, eventSourceInitDict = {}) {
+    if (arguments.length < 1) {
+      throw new TypeError(
+        "Failed to construct 'EventSource': 1 argument required, but only 0 present.",
+      );
+    }
+    super();
+    let parsed;
+    try {
+      parsed = new URL(String(url));
+    } catch {
+      throw new DOMException(
+        `Failed to construct 'EventSource': Cannot open an EventSource to '${url}'. The URL is invalid.`,
+        "SyntaxError",
+      );
+    }
+    if (parsed.protocol !== "http:" && parsed.protocol !== "https:") {
+      throw new DOMException(
+        `Failed to construct 'EventSource': The URL's scheme must be either 'http' or 'https'. '${parsed.protocol}' is not allowed.`,
+        "SyntaxError",
+      );
+    }
+
+    this.#url = parsed.href;
+    this.#withCredentials = Boolean(eventSourceInitDict?.withCredentials);
+    this.#id = __internal.eventsource.connect(this.#url);
+    this.#receive(parsed.origin);
+  }
+
+  get url() {
+    return this.#url;
+  }
+
+  get withCredentials() {
+    return this.#withCredentials;
+  }
+
+  get readyState() {
+    return this.#readyState;
+  }
+
+  close() {
+    this.#readyState = CLOSED;
+    __internal.eventsource.close(this.#id);
+  }
+
+  // Dispatch the events of the stream until the source closes
+  async #receive(origin) {
+    // Events are dispatched after the constructor returns and the caller had a
+    // chance to add listeners
+    await Promise.resolve();
+    while (this.#readyState !== CLOSED) {
+      const event = __internal.eventsource.next(this.#id);
+      if (event === undefined) {
+        // Yield to event loop
+        await Promise.resolve();
+        continue;
+      }
+      if (event === null) return;
+
+      switch (event.type) {
+        case "open":
+          this.#readyState = OPEN;
+          this.#dispatch(new Event("open"));
+          break;
+        case "message":
+          this.#dispatch(
+            new MessageEvent(event.eventType, {
+              data: event.data,
+              origin,
+              lastEventId: event.lastEventId,
+            }),
+          );
+          break;
+        case "error":
+          this.#readyState = event.reconnecting ? CONNECTING : CLOSED;
+          this.#dispatch(new Event("error"));
+          break;
+      }
+    }
+  }
+
+  #dispatch(event) {
+    __internal.events.dispatchTrusted(this, event);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "EventSource";
+  }
+}
+
+for (const [key, value] of Object.entries({ CONNECTING, OPEN, CLOSED })) {
+  const descriptor = { value, enumerable: true };
+  Object.defineProperty(EventSource, key, descriptor);
+  Object.defineProperty(EventSource.prototype, key, descriptor);
+}
+
+for (const name of ["open", "message", "error"]) {
+  __internal.events.defineEventHandler(EventSource.prototype, name);
+}
+
+globalThis.EventSource = EventSource;
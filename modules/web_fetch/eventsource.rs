@@ -0,0 +1,436 @@
+//! EventSource connections. The event stream is requested like fetch does and parsed
+//! as its chunks arrive, and the events are queued for JS, which takes them with
+//! `eventsource.next`. Lost connections are reestablished after the retry delay.
+
+use crate::{RedirectMode, RequestSource, fetch_request, next_id, pool, read_chunk};
+use bytes::Bytes;
+use once_cell::sync::Lazy;
+use rquickjs::{Ctx, Object, Value};
+use smol::Timer;
+use std::collections::HashMap;
+use std::sync::{Mutex, mpsc};
+use std::time::Duration;
+use utils::{add_internal_function, poll_receiver};
+
+// Delay before reconnecting until the server sets one with a retry field
+const DEFAULT_RETRY: Duration = Duration::from_secs(3);
+
+struct Source {
+    events: mpsc::Receiver<Event>,
+    // Dropping the task closes the connection
+    _task: smol::Task<()>,
+}
+
+enum Event {
+    Open,
+    Message {
+        event_type: String,
+        data: String,
+        last_event_id: String,
+    },
+    // The connection is reestablished after the error when `reconnecting`, and
+    // closed for good otherwise
+    Error {
+        message: String,
+        reconnecting: bool,
+    },
+}
+
+static SOURCES: Lazy<Mutex<HashMap<u64, Source>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].eventsource = {};")?;
+
+    add_internal_function!(ctx, "eventsource.connect", |url: String| {
+        eventsource_connect(url)
+    });
+
+    add_internal_function!(ctx, "eventsource.next", eventsource_next);
+
+    add_internal_function!(ctx, "eventsource.close", |id: u64| {
+        SOURCES.lock().unwrap().remove(&id);
+    });
+
+    Ok(())
+}
+
+/// Open an event stream from `url`. Returns the id of the source.
+fn eventsource_connect(url: String) -> u64 {
+    let id = next_id();
+    let (events, receiver) = mpsc::channel();
+    let task = smol::spawn(run(url, events));
+    SOURCES.lock().unwrap().insert(
+        id,
+        Source {
+            events: receiver,
+            _task: task,
+        },
+    );
+    id
+}
+
+async fn run(url: String, events: mpsc::Sender<Event>) {
+    let mut parser = Parser::new();
+    loop {
+        let message = match stream(&url, &mut parser, &events).await {
+            Ok(()) => "Event stream ended".to_string(),
+            Err(Failure::Network(message)) => message,
+            Err(Failure::Fatal(message)) => {
+                let _ = events.send(Event::Error {
+                    message,
+                    reconnecting: false,
+                });
+                return;
+            }
+        };
+        let _ = events.send(Event::Error {
+            message,
+            reconnecting: true,
+        });
+        Timer::after(parser.retry).await;
+    }
+}
+
+enum Failure {
+    // The connection can be tried again
+    Network(String),
+    // The response is not an event stream
+    Fatal(String),
+}
+
+/// Request the event stream and queue its events until it ends.
+async fn stream(
+    url: &str,
+    parser: &mut Parser,
+    events: &mpsc::Sender<Event>,
+) -> Result<(), Failure> {
+    let client = pool::DEFAULT_CLIENT.clone().map_err(Failure::Fatal)?;
+    let mut headers = vec![
+        ("accept".to_string(), "text/event-stream".to_string()),
+        ("cache-control".to_string(), "no-cache".to_string()),
+    ];
+    if !parser.last_event_id.is_empty() {
+        headers.push(("last-event-id".to_string(), parser.last_event_id.clone()));
+    }
+    let headers = serde_json::to_string(&headers).unwrap_or_default();
+    let source = RequestSource::Buffered(Bytes::new());
+    let response = fetch_request(
+        &client,
+        url.to_string(),
+        "GET".to_string(),
+        headers,
+        source,
+        RedirectMode::Follow,
+    )
+    .await
+    .map_err(Failure::Network)?;
+
+    let meta: serde_json::Value = serde_json::from_str(&response.meta).unwrap_or_default();
+    let status = meta["status"].as_u64().unwrap_or_default();
+    if status != 200 {
+        return Err(Failure::Fatal(format!(
+            "EventSource's response has a status ({}) that is not 200",
+            status
+        )));
+    }
+    let content_type = meta["headers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|pair| pair[0].as_str() == Some("content-type"))
+        .and_then(|pair| pair[1].as_str())
+        .unwrap_or_default();
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    if !essence.eq_ignore_ascii_case("text/event-stream") {
+        return Err(Failure::Fatal(format!(
+            "EventSource's response has a MIME type (\"{}\") that is not \"text/event-stream\"",
+            essence
+        )));
+    }
+
+    let _ = events.send(Event::Open);
+    parser.reset();
+    let mut body = response.body;
+    while let Some(chunk) = read_chunk(&mut body).await.map_err(Failure::Network)? {
+        parser.feed(&chunk, events);
+    }
+    Ok(())
+}
+
+/// Incremental parser of the event stream format.
+/// https://html.spec.whatwg.org/multipage/server-sent-events.html#event-stream-interpretation
+struct Parser {
+    // Bytes of the line being received
+    line: Vec<u8>,
+    // Whether the previous chunk ended with CR, so a leading LF ends no line
+    after_cr: bool,
+    // Whether no line was completed yet, which may start with a BOM
+    first_line: bool,
+    data: String,
+    event_type: String,
+    last_event_id: String,
+    retry: Duration,
+}
+
+impl Parser {
+    fn new() -> Parser {
+        Parser {
+            line: Vec::new(),
+            after_cr: false,
+            first_line: true,
+            data: String::new(),
+            event_type: String::new(),
+            last_event_id: String::new(),
+            retry: DEFAULT_RETRY,
+        }
+    }
+
+    /// Discard what is left of the previous connection, keeping the last event id
+    /// and the retry delay.
+    fn reset(&mut self) {
+        self.line.clear();
+        self.after_cr = false;
+        self.first_line = true;
+        self.data.clear();
+        self.event_type.clear();
+    }
+
+    fn feed(&mut self, chunk: &[u8], events: &mpsc::Sender<Event>) {
+        // Lines end with CRLF, LF or CR. These are ASCII, so lines are split before
+        // decoding and characters are never split.
+        for &byte in chunk {
+            let after_cr = std::mem::replace(&mut self.after_cr, false);
+            match byte {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    self.after_cr = byte == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    self.process_line(&line, events);
+                }
+                _ => self.line.push(byte),
+            }
+        }
+    }
+
+    fn process_line(&mut self, line: &[u8], events: &mpsc::Sender<Event>) {
+        let mut line = String::from_utf8_lossy(line);
+        if std::mem::replace(&mut self.first_line, false)
+            && let Some(rest) = line.strip_prefix('\u{feff}')
+        {
+            line = rest.to_string().into();
+        }
+
+        if line.is_empty() {
+            self.dispatch(events);
+            return;
+        }
+        // Comments
+        if line.starts_with(':') {
+            return;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (&*line, ""),
+        };
+        match field {
+            "event" => self.event_type = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_event_id = value.to_string(),
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Duration::from_millis(millis);
+                }
+            }
+            // Other fields are ignored
+            _ => {}
+        }
+    }
+
+    fn dispatch(&mut self, events: &mpsc::Sender<Event>) {
+        let mut data = std::mem::take(&mut self.data);
+        let event_type = std::mem::take(&mut self.event_type);
+        if data.is_empty() {
+            return;
+        }
+        data.pop();
+        let event_type = if event_type.is_empty() {
+            "message".to_string()
+        } else {
+            event_type
+        };
+        let _ = events.send(Event::Message {
+            event_type,
+            data,
+            last_event_id: self.last_event_id.clone(),
+        });
+    }
+}
+
+/// Take the next event of source `id`. Returns an object with the event `type` and
+/// its fields, undefined when there is none yet, or null once the source closed.
+fn eventsource_next<'js>(ctx: Ctx<'js>, id: u64) -> rquickjs::Result<Value<'js>> {
+    let mut sources = SOURCES.lock().unwrap();
+    let Some(source) = sources.get(&id) else {
+        return Ok(Value::new_null(ctx));
+    };
+    let Some(event) = poll_receiver(&ctx, &source.events) else {
+        return Ok(Value::new_undefined(ctx));
+    };
+
+    let object = Object::new(ctx.clone())?;
+    match event {
+        Event::Open => object.set("type", "open")?,
+        Event::Message {
+            event_type,
+            data,
+            last_event_id,
+        } => {
+            object.set("type", "message")?;
+            object.set("eventType", event_type)?;
+            object.set("data", data)?;
+            object.set("lastEventId", last_event_id)?;
+        }
+        Event::Error {
+            message,
+            reconnecting,
+        } => {
+            if !reconnecting {
+                sources.remove(&id);
+            }
+            object.set("type", "error")?;
+            object.set("message", message)?;
+            object.set("reconnecting", reconnecting)?;
+        }
+    }
+    Ok(object.into_value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed `chunks` to `parser` and return the messages as (type, data, id).
+    fn feed(parser: &mut Parser, chunks: &[&[u8]]) -> Vec<(String, String, String)> {
+        let (events, receiver) = mpsc::channel();
+        for chunk in chunks {
+            parser.feed(chunk, &events);
+        }
+        drop(events);
+        receiver
+            .into_iter()
+            .map(|event| match event {
+                Event::Message {
+                    event_type,
+                    data,
+                    last_event_id,
+                } => (event_type, data, last_event_id),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    fn message(event_type: &str, data: &str, id: &str) -> (String, String, String) {
+        (event_type.into(), data.into(), id.into())
+    }
+
+    #[test]
+    fn line_endings() {
+        for stream in [
+            "data: a\n\ndata: b\n\n",
+            "data: a\r\n\r\ndata: b\r\n\r\n",
+            "data: a\r\rdata: b\r\r",
+        ] {
+            let messages = feed(&mut Parser::new(), &[stream.as_bytes()]);
+            assert_eq!(
+                messages,
+                [message("message", "a", ""), message("message", "b", "")],
+                "{:?}",
+                stream
+            );
+        }
+    }
+
+    #[test]
+    fn line_endings_split_across_chunks() {
+        // The LF of a CRLF in the next chunk ends no second line
+        let messages = feed(
+            &mut Parser::new(),
+            &[b"data: a\r", b"\ndata: b\r", b"\n\r", b"\n"],
+        );
+        assert_eq!(messages, [message("message", "a\nb", "")]);
+
+        // Characters may be split across chunks
+        let messages = feed(&mut Parser::new(), &[b"data: \xc3", b"\xa9\n\n"]);
+        assert_eq!(messages, [message("message", "é", "")]);
+    }
+
+    #[test]
+    fn fields() {
+        let mut parser = Parser::new();
+        let messages = feed(
+            &mut parser,
+            &[b"event: update\ndata: first\ndata\ndata:last\nid: 7\n\n: comment\n\ndata: x\n\n"],
+        );
+        assert_eq!(
+            messages,
+            [
+                message("update", "first\n\nlast", "7"),
+                // The event type is reset, the id is kept
+                message("message", "x", "7"),
+            ]
+        );
+
+        // Events without data are not dispatched, an id with NUL is ignored
+        let messages = feed(&mut parser, &[b"event: empty\n\nid: a\0b\ndata: y\n\n"]);
+        assert_eq!(messages, [message("message", "y", "7")]);
+
+        // An empty id resets it, unknown fields are ignored
+        let messages = feed(&mut parser, &[b"id\nfoo: bar\ndata: z\n\n"]);
+        assert_eq!(messages, [message("message", "z", "")]);
+    }
+
+    #[test]
+    fn retry() {
+        let mut parser = Parser::new();
+        feed(&mut parser, &[b"retry: 1500\n"]);
+        assert_eq!(parser.retry, Duration::from_millis(1500));
+
+        // Values that are not all digits are ignored
+        feed(
+            &mut parser,
+            &[b"retry: 2s\nretry: -1\nretry:\nretry: 1 0\n"],
+        );
+        assert_eq!(parser.retry, Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        let mut parser = Parser::new();
+        let messages = feed(&mut parser, &["\u{feff}data: a\n\n".as_bytes()]);
+        assert_eq!(messages, [message("message", "a", "")]);
+
+        // Only a leading BOM is skipped, later ones make unknown fields
+        let messages = feed(&mut parser, &["\u{feff}data: b\n\n".as_bytes()]);
+        assert!(messages.is_empty());
+
+        // A new connection may start with a BOM again
+        parser.reset();
+        let messages = feed(&mut parser, &["\u{feff}data: c\n\n".as_bytes()]);
+        assert_eq!(messages, [message("message", "c", "")]);
+    }
+
+    #[test]
+    fn reset_discards_the_partial_event() {
+        let mut parser = Parser::new();
+        feed(
+            &mut parser,
+            &[b"id: 1\nevent: x\ndata: lost\ndata: partial"],
+        );
+        parser.reset();
+        let messages = feed(&mut parser, &[b"data: kept\n\n"]);
+        assert_eq!(messages, [message("message", "kept", "1")]);
+    }
+}
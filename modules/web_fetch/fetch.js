@@ -1,17 +1,200 @@
 const __internal = globalThis[Symbol.for("mdeno.internal")];
 
+// Body of a request or response
+class InnerBody {
+  // Uint8Array until the stream is requested, then a ReadableStream
+  #source;
+  #used = false;
+
+  constructor(source) {
+    this.#source = source;
+  }
+
+  get stream() {
+    if (this.#source instanceof Uint8Array) {
+      const bytes = this.#source;
+      this.#source = __internal.streams.createReadableStream({
+        start(controller) {
+          controller.enqueue(bytes);
+          controller.close();
+        },
+      });
+      if (this.#used) {
+        this.#source.cancel();
+      }
+    }
+    return this.#source;
+  }
+
+  // Bytes that can be sent at once, or null for a stream
+  get bytes() {
+    return this.#source instanceof Uint8Array ? this.#source : null;
+  }
+
+  get used() {
+    if (this.#used) {
+      return true;
+    }
+    return !(this.#source instanceof Uint8Array) &&
+      __internal.streams.isDisturbed(this.#source);
+  }
+
+  get locked() {
+    return !(this.#source instanceof Uint8Array) && this.#source.locked;
+  }
+
+  // Move the body to a new owner, leaving this one used
+  take() {
+    this.#used = true;
+    return new InnerBody(this.#source);
+  }
+
+  clone() {
+    if (this.#source instanceof Uint8Array) {
+      return new InnerBody(this.#source.slice());
+    }
+    const [source, clone] = this.#source.tee();
+    this.#source = source;
+    return new InnerBody(clone);
+  }
+
+  // Read the whole body into a Uint8Array backed by its own buffer
+  async consume() {
+    if (this.used) {
+      throw new TypeError("Body has already been consumed");
+    }
+    if (this.locked) {
+      throw new TypeError("Body is locked");
+    }
+    this.#used = true;
+
+    if (this.#source instanceof Uint8Array) {
+      return this.#source;
+    }
+
+    const chunks = [];
+    let size = 0;
+    for await (const chunk of readChunks(this.#source)) {
+      chunks.push(chunk);
+      size += chunk.byteLength;
+    }
+    const bytes = new Uint8Array(size);
+    let offset = 0;
+    for (const chunk of chunks) {
+      bytes.set(chunk, offset);
+      offset += chunk.byteLength;
+    }
+    return bytes;
+  }
+}
+
+// Body and default content type of a request or response
+function initBody(init) {
+  const { source, stream, type } = extractBody(init);
+  if (stream !== null) {
+    const readable = __internal.streams.isReadableStream(stream)
+      ? stream
+      : ReadableStream.from(stream);
+    return { body: new InnerBody(readable), type };
+  }
+  if (source === null) {
+    return { body: null, type };
+  }
+  const bytes = typeof source === "string"
+    ? __internal.fetch.encode(source)
+    : source.slice();
+  return { body: new InnerBody(bytes), type };
+}
+
+// Define the Body mixin on the prototype of Request or Response, reaching the
+// InnerBody of an instance through `getBody`
+// https://fetch.spec.whatwg.org/#body-mixin
+function mixinBody(prototype, getBody) {
+  const consume = (owner) =>
+    getBody(owner)?.consume() ?? Promise.resolve(new Uint8Array(0));
+
+  const members = {
+    get body() {
+      return getBody(this)?.stream ?? null;
+    },
+
+    get bodyUsed() {
+      return getBody(this)?.used ?? false;
+    },
+
+    async arrayBuffer() {
+      const bytes = await consume(this);
+      return bytes.buffer;
+    },
+
+    bytes() {
+      return consume(this);
+    },
+
+    async blob() {
+      const bytes = await consume(this);
+      return new Blob([bytes], {
+        type: this.headers.get("content-type") ?? "",
+      });
+    },
+
+    async formData() {
+      const bytes = await consume(this);
+      return __internal.fetch.parseFormData(
+        bytes,
+        this.headers.get("content-type"),
+      );
+    },
+
+    async text() {
+      const bytes = await consume(this);
+      return __internal.fetch.decode(
+        bytes,
+        charsetOf(this.headers.get("content-type")),
+      );
+    },
+
+    async json() {
+      const bytes = await consume(this);
+      return JSON.parse(__internal.fetch.decode(bytes, "utf-8"));
+    },
+  };
+
+  // Non-enumerable like class members
+  const descriptors = Object.getOwnPropertyDescriptors(members);
+  for (const [name, descriptor] of Object.entries(descriptors)) {
+    Object.defineProperty(prototype, name, {
+      ...descriptor,
+      enumerable: false,
+    });
+  }
+}
+
+// URL of a fetched response and the state read by Deno.serve, assigned inside
+// Response to reach private state
+let setResponseURL;
+let responseState;
+
 class Response {
   #status;
   #statusText;
+  // Final URL after redirects, empty for constructed responses
+  #url = "";
+  #redirected = false;
   #headers;
+  // InnerBody, or null
   #body;
-  #bodyUsed = false;
 
-  constructor(body, options = {}) {
+  constructor(body = null, options = {}) {
     this.#status = options.status || 200;
     this.#statusText = options.statusText || "";
-    this.#headers = new Headers(options.headers || {});
-    this.#body = body;
+    this.#headers = new Headers(options.headers);
+
+    const { body: inner, type } = initBody(body);
+    this.#body = inner;
+    if (type !== null && !this.#headers.has("content-type")) {
+      this.#headers.set("content-type", type);
+    }
   }
 
   get status() {
@@ -22,116 +205,669 @@ class Response {
     return this.#statusText;
   }
 
-  get ok() {
-    return this.#status >= 200 && this.#status < 300;
-  }
-
-  get headers() {
-    return this.#headers;
+  get url() {
+    return this.#url;
   }
 
-  get bodyUsed() {
-    return this.#bodyUsed;
+  get redirected() {
+    return this.#redirected;
   }
 
-  // deno-lint-ignore require-await
-  async text() {
-    if (this.#bodyUsed) {
-      throw new TypeError("Body has already been consumed");
-    }
-    this.#bodyUsed = true;
-    return this.#body;
+  get ok() {
+    return this.#status >= 200 && this.#status < 300;
   }
 
-  async json() {
-    const text = await this.text();
-    return JSON.parse(text);
+  get headers() {
+    return this.#headers;
   }
 
   clone() {
-    if (this.#bodyUsed) {
+    if (this.bodyUsed || this.#body?.locked) {
       throw new TypeError("Cannot clone a response that has been consumed");
     }
-    return new Response(this.#body, {
+    const response = new Response(null, {
       status: this.#status,
       statusText: this.#statusText,
       headers: this.#headers,
     });
+    response.#body = this.#body?.clone() ?? null;
+    setHeadersGuard(response.headers, getHeadersGuard(this.#headers));
+    setResponseURL(response, this.#url, this.#redirected);
+    return response;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Response";
+  }
+
+  static {
+    setResponseURL = (response, url, redirected) => {
+      response.#url = url;
+      response.#redirected = redirected;
+    };
+    responseState = (response) => ({
+      status: response.#status,
+      headers: response.#headers,
+      body: response.#body,
+    });
+    mixinBody(Response.prototype, (response) => response.#body);
+  }
+}
+
+// Charset parameter of a Content-Type, defaulting to UTF-8
+function charsetOf(contentType) {
+  const match = /;\s*charset\s*=\s*"?([^";\s]+)/i.exec(contentType ?? "");
+  return match ? match[1] : "utf-8";
+}
+
+// Guard of a Headers object, assigned inside Headers to reach its private state
+let setHeadersGuard;
+let getHeadersGuard;
+
+const HEADER_NAME = /^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/;
+// Leading and trailing HTTP whitespace is stripped from values
+const HTTP_WHITESPACE = /^[\t\n\r ]+|[\t\n\r ]+$/g;
+const INVALID_VALUE = /[\0\n\r]|[^\0-\xff]/;
+
+function normalizeName(name) {
+  name = String(name);
+  if (!HEADER_NAME.test(name)) {
+    throw new TypeError(`Header name is not valid: "${name}"`);
+  }
+  return name.toLowerCase();
+}
+
+function normalizeValue(value) {
+  value = String(value).replace(HTTP_WHITESPACE, "");
+  if (INVALID_VALUE.test(value)) {
+    throw new TypeError(`Header value is not valid: "${value}"`);
   }
+  return value;
 }
 
+// https://fetch.spec.whatwg.org/#headers-class
 class Headers {
-  #headers = {};
+  // Header list of [lowercased name, value] in insertion order
+  #list = [];
+  // "none" or "immutable"
+  #guard = "none";
 
-  constructor(init = {}) {
-    if (init) {
-      if (typeof init === "object") {
-        for (const [key, value] of Object.entries(init)) {
-          this.set(key, value);
+  constructor(init = undefined) {
+    if (init === undefined || init === null) {
+      return;
+    }
+    if (typeof init !== "object" && typeof init !== "function") {
+      throw new TypeError(
+        "Failed to construct 'Headers': The provided value is not of type '(record<ByteString, ByteString> or sequence<sequence<ByteString>>)'.",
+      );
+    }
+    if (typeof init[Symbol.iterator] === "function") {
+      for (const pair of init) {
+        const entry = [...pair];
+        if (entry.length !== 2) {
+          throw new TypeError("Failed to construct 'Headers': Invalid value");
         }
+        this.append(entry[0], entry[1]);
+      }
+    } else {
+      for (const [name, value] of Object.entries(init)) {
+        this.append(name, value);
       }
     }
   }
 
+  append(name, value) {
+    name = normalizeName(name);
+    value = normalizeValue(value);
+    this.#checkMutable();
+    this.#list.push([name, value]);
+  }
+
+  delete(name) {
+    name = normalizeName(name);
+    this.#checkMutable();
+    this.#list = this.#list.filter(([n]) => n !== name);
+  }
+
   get(name) {
-    return this.#headers[name.toLowerCase()] || null;
+    name = normalizeName(name);
+    const values = this.#list.filter(([n]) => n === name).map(([, v]) => v);
+    return values.length === 0 ? null : values.join(", ");
   }
 
-  set(name, value) {
-    this.#headers[name.toLowerCase()] = String(value);
+  getSetCookie() {
+    return this.#list.filter(([n]) => n === "set-cookie").map(([, v]) => v);
   }
 
   has(name) {
-    return name.toLowerCase() in this.#headers;
+    name = normalizeName(name);
+    return this.#list.some(([n]) => n === name);
   }
 
-  delete(name) {
-    delete this.#headers[name.toLowerCase()];
+  set(name, value) {
+    name = normalizeName(name);
+    value = normalizeValue(value);
+    this.#checkMutable();
+    const index = this.#list.findIndex(([n]) => n === name);
+    if (index === -1) {
+      this.#list.push([name, value]);
+    } else {
+      this.#list[index][1] = value;
+      this.#list = this.#list.filter(([n], i) => n !== name || i <= index);
+    }
+  }
+
+  forEach(callback, thisArg) {
+    for (const [name, value] of this.#sortAndCombine()) {
+      callback.call(thisArg, value, name, this);
+    }
+  }
+
+  *entries() {
+    yield* this.#sortAndCombine();
+  }
+
+  *keys() {
+    for (const [name] of this.#sortAndCombine()) {
+      yield name;
+    }
+  }
+
+  *values() {
+    for (const [, value] of this.#sortAndCombine()) {
+      yield value;
+    }
+  }
+
+  [Symbol.iterator]() {
+    return this.entries();
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Headers";
+  }
+
+  #checkMutable() {
+    if (this.#guard === "immutable") {
+      throw new TypeError("Headers are immutable.");
+    }
+  }
+
+  // Names sorted, values of repeated names combined except for Set-Cookie
+  // https://fetch.spec.whatwg.org/#concept-header-list-sort-and-combine
+  #sortAndCombine() {
+    const names = [...new Set(this.#list.map(([n]) => n))].sort();
+    const headers = [];
+    for (const name of names) {
+      if (name === "set-cookie") {
+        for (const value of this.getSetCookie()) {
+          headers.push([name, value]);
+        }
+      } else {
+        headers.push([name, this.get(name)]);
+      }
+    }
+    return headers;
+  }
+
+  static {
+    setHeadersGuard = (headers, guard) => {
+      headers.#guard = guard;
+    };
+    getHeadersGuard = (headers) => headers.#guard;
+  }
+}
+
+const NULL_BODY_METHODS = ["GET", "HEAD"];
+const NULL_BODY_STATUSES = [101, 103, 204, 205, 304];
+const REDIRECT_MODES = ["follow", "manual", "error"];
+const CACHE_MODES = [
+  "default",
+  "no-store",
+  "reload",
+  "no-cache",
+  "force-cache",
+  "only-if-cached",
+];
+
+// Normalize a request body into a string or bytes sent at once, or a stream
+// sent in chunks, along with its default content type
+// https://fetch.spec.whatwg.org/#concept-bodyinit-extract
+function extractBody(body) {
+  if (body === undefined || body === null) {
+    return { source: null, stream: null, type: null };
+  }
+  if (typeof body === "string") {
+    return { source: body, stream: null, type: "text/plain;charset=UTF-8" };
+  }
+  if (body instanceof ArrayBuffer) {
+    return { source: new Uint8Array(body), stream: null, type: null };
+  }
+  if (ArrayBuffer.isView(body)) {
+    const bytes = new Uint8Array(
+      body.buffer,
+      body.byteOffset,
+      body.byteLength,
+    );
+    return { source: bytes, stream: null, type: null };
+  }
+  if (body instanceof FormData) {
+    const { bytes, type } = __internal.fetch.encodeFormData(body);
+    return { source: bytes, stream: null, type };
+  }
+  if (body instanceof Blob) {
+    return {
+      source: __internal.fetch.blobBytes(body),
+      stream: null,
+      type: body.type || null,
+    };
+  }
+  if (globalThis.URLSearchParams && body instanceof URLSearchParams) {
+    return {
+      source: body.toString(),
+      stream: null,
+      type: "application/x-www-form-urlencoded;charset=UTF-8",
+    };
+  }
+  if (
+    typeof body.getReader === "function" ||
+    typeof body[Symbol.asyncIterator] === "function"
+  ) {
+    return { source: null, stream: body, type: null };
+  }
+  return {
+    source: String(body),
+    stream: null,
+    type: "text/plain;charset=UTF-8",
+  };
+}
+
+async function* readChunks(stream) {
+  const reader = stream.getReader();
+  try {
+    while (true) {
+      const { done, value } = await reader.read();
+      if (done) return;
+      yield value;
+    }
+  } finally {
+    reader.releaseLock();
+  }
+}
+
+// Feed a streamed request body to the native side until it is consumed or the
+// request finishes
+async function sendBody(taskId, stream, upload) {
+  const chunks = typeof stream.getReader === "function"
+    ? readChunks(stream)
+    : stream;
+  try {
+    for await (const chunk of chunks) {
+      if (upload.done) return;
+      if (!(chunk instanceof Uint8Array)) {
+        throw new TypeError("Request body stream chunks must be Uint8Array");
+      }
+      while (!__internal.fetch.write(taskId, chunk)) {
+        if (upload.done) return;
+        await Promise.resolve();
+      }
+    }
+    __internal.fetch.close(taskId, null);
+  } catch (error) {
+    __internal.fetch.close(taskId, String(error));
+  }
+}
+
+// Stream the body of response `taskId`, reading a chunk from the connection
+// only when the consumer asks for one
+function responseBody(taskId, signal) {
+  let readId = null;
+  return __internal.streams.createReadableStream({
+    async pull(controller) {
+      readId = __internal.fetch.read(taskId);
+      while (true) {
+        if (signal?.aborted) {
+          __internal.fetch.cancel(readId);
+          __internal.fetch.cancel(taskId);
+          controller.error(signal.reason);
+          return;
+        }
+
+        // Throws when the connection failed
+        const chunk = __internal.fetch.pollRead(readId);
+        if (chunk !== undefined) {
+          readId = null;
+          if (chunk === null) {
+            controller.close();
+          } else {
+            controller.enqueue(chunk);
+          }
+          return;
+        }
+
+        // Yield to event loop
+        await Promise.resolve();
+      }
+    },
+    cancel() {
+      if (readId !== null) {
+        __internal.fetch.cancel(readId);
+      }
+      __internal.fetch.cancel(taskId);
+    },
+  }, 0);
+}
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+// Id of a client, assigned inside HttpClient to reach its private state
+let clientId;
+
+// https://docs.deno.com/api/deno/~/Deno.HttpClient
+class HttpClient {
+  #id;
+
+  constructor(key = null, id = undefined) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#id = id;
+  }
+
+  close() {
+    __internal.fetch.closeClient(this.#id);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "HttpClient";
+  }
+
+  static {
+    clientId = (client) => client.#id;
+  }
+}
+
+// https://docs.deno.com/api/deno/~/Deno.createHttpClient
+function createHttpClient(options = {}) {
+  const proxy = options.proxy;
+  const id = __internal.fetch.createClient(JSON.stringify({
+    caCerts: options.caCerts && [...options.caCerts].map(String),
+    cert: options.cert && String(options.cert),
+    key: options.key && String(options.key),
+    http2: options.http2 ?? true,
+    proxy: proxy && {
+      url: String(proxy.url),
+      basicAuth: proxy.basicAuth && {
+        username: String(proxy.basicAuth.username),
+        password: String(proxy.basicAuth.password),
+      },
+    },
+  }));
+  return new HttpClient(illegalConstructorKey, id);
+}
+
+// https://fetch.spec.whatwg.org/#concept-method-normalize
+const NORMALIZED_METHODS = ["DELETE", "GET", "HEAD", "OPTIONS", "POST", "PUT"];
+const FORBIDDEN_METHODS = ["CONNECT", "TRACE", "TRACK"];
+
+function normalizeMethod(method) {
+  method = String(method);
+  if (!HEADER_NAME.test(method)) {
+    throw new TypeError(`'${method}' is not a valid HTTP method.`);
+  }
+  const upper = method.toUpperCase();
+  if (FORBIDDEN_METHODS.includes(upper)) {
+    throw new TypeError(`'${method}' HTTP method is unsupported.`);
+  }
+  return NORMALIZED_METHODS.includes(upper) ? upper : method;
+}
+
+function parseURL(input) {
+  input = String(input);
+  if (globalThis.URL === undefined) {
+    return input;
+  }
+  try {
+    return new URL(input).href;
+  } catch {
+    throw new TypeError(`Invalid URL: '${input}'`);
+  }
+}
+
+function checkEnum(value, values, name) {
+  value = String(value);
+  if (!values.includes(value)) {
+    throw new TypeError(
+      `'${value}' is not a valid value for enumeration ${name}.`,
+    );
+  }
+  return value;
+}
+
+// State read by fetch, assigned inside Request to reach private state
+let requestState;
+
+// https://fetch.spec.whatwg.org/#request-class
+class Request {
+  #url;
+  #method = "GET";
+  #headers;
+  // InnerBody, or null
+  #body = null;
+  #redirect = "follow";
+  // Cache mode, null unless one was given and the HTTP cache is used
+  #cache = null;
+  // AbortSignal, created on first access when none was given
+  #signal = null;
+  // Deno.HttpClient the request is sent with
+  #client = null;
+
+  constructor(input, init = {}) {
+    init ??= {};
+    const inputRequest = input instanceof Request ? input : null;
+    if (inputRequest !== null) {
+      this.#url = inputRequest.#url;
+      this.#method = inputRequest.#method;
+      this.#redirect = inputRequest.#redirect;
+      this.#cache = inputRequest.#cache;
+      this.#signal = inputRequest.#signal;
+      this.#client = inputRequest.#client;
+    } else {
+      this.#url = parseURL(input);
+    }
+
+    if (init.method !== undefined) {
+      this.#method = normalizeMethod(init.method);
+    }
+    if (init.redirect !== undefined) {
+      this.#redirect = checkEnum(
+        init.redirect,
+        REDIRECT_MODES,
+        "RequestRedirect",
+      );
+    }
+    if (init.cache !== undefined) {
+      this.#cache = checkEnum(init.cache, CACHE_MODES, "RequestCache");
+    }
+    if (init.signal !== undefined) {
+      this.#signal = init.signal;
+    }
+    if (init.client !== undefined) {
+      if (init.client !== null && !(init.client instanceof HttpClient)) {
+        throw new TypeError("client must be a Deno.HttpClient");
+      }
+      this.#client = init.client;
+    }
+    this.#headers = new Headers(init.headers ?? inputRequest?.#headers);
+
+    const inputBody = inputRequest?.#body ?? null;
+    const hasInitBody = init.body !== undefined && init.body !== null;
+    if (
+      (hasInitBody || inputBody !== null) &&
+      NULL_BODY_METHODS.includes(this.#method)
+    ) {
+      throw new TypeError(
+        `Request with ${this.#method} method cannot have a body.`,
+      );
+    }
+
+    if (hasInitBody) {
+      const { body, type } = initBody(init.body);
+      this.#body = body;
+      if (type !== null && !this.#headers.has("content-type")) {
+        this.#headers.set("content-type", type);
+      }
+    } else if (inputBody !== null) {
+      if (inputBody.used || inputBody.locked) {
+        throw new TypeError("Input request's body is unusable.");
+      }
+      this.#body = inputBody.take();
+    }
+  }
+
+  get method() {
+    return this.#method;
+  }
+
+  get url() {
+    return this.#url;
   }
 
-  forEach(callback) {
-    for (const [key, value] of Object.entries(this.#headers)) {
-      callback(value, key, this);
+  get headers() {
+    return this.#headers;
+  }
+
+  get redirect() {
+    return this.#redirect;
+  }
+
+  get cache() {
+    return this.#cache ?? "default";
+  }
+
+  get signal() {
+    if (this.#signal === null && globalThis.AbortController) {
+      this.#signal = new AbortController().signal;
     }
+    return this.#signal;
+  }
+
+  get destination() {
+    return "";
   }
 
-  entries() {
-    return Object.entries(this.#headers)[Symbol.iterator]();
+  get referrer() {
+    return "about:client";
   }
 
-  keys() {
-    return Object.keys(this.#headers)[Symbol.iterator]();
+  get referrerPolicy() {
+    return "";
   }
 
-  values() {
-    return Object.values(this.#headers)[Symbol.iterator]();
+  get mode() {
+    return "cors";
+  }
+
+  get credentials() {
+    return "same-origin";
+  }
+
+  get integrity() {
+    return "";
+  }
+
+  get keepalive() {
+    return false;
+  }
+
+  get duplex() {
+    return "half";
+  }
+
+  clone() {
+    if (this.bodyUsed || this.#body?.locked) {
+      throw new TypeError("Cannot clone a request that has been consumed");
+    }
+    const request = new Request(this.#url, {
+      method: this.#method,
+      headers: this.#headers,
+      redirect: this.#redirect,
+      cache: this.#cache ?? undefined,
+      signal: this.#signal ?? undefined,
+      client: this.#client,
+    });
+    request.#body = this.#body?.clone() ?? null;
+    return request;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "Request";
+  }
+
+  static {
+    requestState = (request) => ({
+      url: request.#url,
+      method: request.#method,
+      headers: request.#headers,
+      body: request.#body,
+      redirect: request.#redirect,
+      cache: request.#cache,
+      signal: request.#signal,
+      client: request.#client,
+    });
+    mixinBody(Request.prototype, (request) => request.#body);
   }
 }
 
-async function fetch(url, options = {}) {
-  const method = (options.method || "GET").toUpperCase();
-  const headers = new Headers(options.headers || {});
-  const body = options.body || "";
+async function fetch(input, init = undefined) {
+  const request = new Request(input, init);
+  const { url, method, headers, body, redirect, cache, signal, client } =
+    requestState(request);
+  signal?.throwIfAborted();
+
+  // Only requests given a cache mode go through the HTTP cache
+  if (cache !== null && cache !== "no-store" && method === "GET") {
+    return await __internal.fetch.cachedFetch(request);
+  }
+
+  // Bytes are sent at once, streams in chunks
+  const source = body?.bytes ?? null;
+  const stream = body !== null && source === null ? body.stream : null;
 
-  // Convert headers to JSON string
-  const headersObj = {};
+  // Convert headers to JSON name/value pairs
+  const headerPairs = [];
   headers.forEach((value, key) => {
-    headersObj[key] = value;
+    headerPairs.push([key, value]);
   });
-  const headersJson = JSON.stringify(headersObj);
+  const headersJson = JSON.stringify(headerPairs);
 
   // Start async fetch
   const taskId = __internal.fetch.start(
-    String(url),
+    url,
     method,
     headersJson,
-    String(body),
+    source,
+    stream !== null,
+    redirect,
+    client && clientId(client),
   );
 
+  const upload = { done: false };
+  if (stream !== null) {
+    sendBody(taskId, stream, upload);
+  }
+
   // Poll for result
   while (true) {
+    if (signal?.aborted) {
+      upload.done = true;
+      __internal.fetch.cancel(taskId);
+      throw signal.reason;
+    }
+
     const resultJson = __internal.fetch.poll(taskId);
     if (resultJson) {
+      upload.done = true;
       const result = JSON.parse(resultJson);
 
       if (result.error) {
@@ -139,11 +875,20 @@ async function fetch(url, options = {}) {
       }
 
       // Create response
-      return new Response(result.body, {
+      let body = null;
+      if (NULL_BODY_STATUSES.includes(result.status) || method === "HEAD") {
+        __internal.fetch.cancel(taskId);
+      } else {
+        body = responseBody(taskId, signal);
+      }
+      const response = new Response(body, {
         status: result.status,
-        statusText: "",
+        statusText: result.statusText,
         headers: result.headers,
       });
+      setHeadersGuard(response.headers, "immutable");
+      setResponseURL(response, result.url, result.redirected);
+      return response;
     }
 
     // Yield to event loop
@@ -151,6 +896,19 @@ async function fetch(url, options = {}) {
   }
 }
 
+// Used by Deno.serve to read request bodies and to send responses
+__internal.fetch.bodyStream = responseBody;
+__internal.fetch.responseState = responseState;
+// Used by the Cache Storage API and the HTTP cache to rebuild stored responses
+__internal.fetch.setResponseURL = setResponseURL;
+__internal.fetch.setHeadersGuard = setHeadersGuard;
+
 globalThis.fetch = fetch;
+globalThis.Request = Request;
 globalThis.Response = Response;
 globalThis.Headers = Headers;
+
+Object.assign(globalThis.__mdeno__.fetch, {
+  HttpClient,
+  createHttpClient,
+});
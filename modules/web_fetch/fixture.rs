@@ -0,0 +1,301 @@
+//! Recording and replay of fetch traffic for tests that must not touch the network.
+//! With MDENO_FETCH_RECORD set to a file, each request and its response are
+//! written to it in the HAR format. With MDENO_FETCH_REPLAY set to such a file,
+//! responses are served from it and requests without a recorded response fail.
+//! Credentials and cookies are redacted from recorded headers, and the values of
+//! query parameters known to carry credentials from recorded URLs. Other parameters
+//! and bodies are kept as sent, so review fixtures of services taking credentials
+//! there before committing them.
+
+use crate::{ReceivedBody, RequestBody, pool};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use once_cell::sync::Lazy;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Instant;
+
+pub enum Mode {
+    Network,
+    Record(Recorder),
+    Replay(Replayer),
+}
+
+pub static MODE: Lazy<Mode> = Lazy::new(|| {
+    let path = |name| std::env::var_os(name).filter(|path| !path.is_empty());
+    // Replay wins, so that a stray recording variable never reaches the network
+    if let Some(path) = path("MDENO_FETCH_REPLAY") {
+        Mode::Replay(Replayer::load(PathBuf::from(path)))
+    } else if let Some(path) = path("MDENO_FETCH_RECORD") {
+        Mode::Record(Recorder {
+            path: PathBuf::from(path),
+            entries: Mutex::new(Vec::new()),
+        })
+    } else {
+        Mode::Network
+    }
+});
+
+/// Writes the exchanges of this process to a fixture, replacing what it held.
+pub struct Recorder {
+    path: PathBuf,
+    entries: Mutex<Vec<Value>>,
+}
+
+impl Recorder {
+    /// Send `req` and record it with its response. Bodies are read whole, so
+    /// streamed requests and responses are buffered.
+    pub async fn send(
+        &self,
+        client: &pool::Client,
+        uri: &hyper::Uri,
+        req: hyper::Request<RequestBody>,
+    ) -> Result<hyper::Response<ReceivedBody>, String> {
+        let started = Instant::now();
+        let (parts, body) = req.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| format!("Failed to read request body: {}", e))?
+            .to_bytes();
+        let mut request = json!({
+            "method": parts.method.as_str(),
+            "url": redacted_url(uri),
+            "httpVersion": format!("{:?}", parts.version),
+            "headers": headers_json(&parts.headers),
+        });
+        if !body.is_empty() {
+            let mime_type = parts
+                .headers
+                .get(hyper::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default();
+            request["postData"] = content_json(mime_type, &body);
+        }
+
+        let body = Full::new(body).map_err(|never| match never {}).boxed();
+        let req = hyper::Request::from_parts(parts, body);
+        let response = pool::send_request(client, uri, req).await?;
+        let status_text = crate::status_text(&response);
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| format!("Failed to read body: {}", e))?
+            .to_bytes();
+        let mime_type = parts
+            .headers
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let response = json!({
+            "status": parts.status.as_u16(),
+            "statusText": status_text,
+            "httpVersion": format!("{:?}", parts.version),
+            "headers": headers_json(&parts.headers),
+            "content": content_json(mime_type, &body),
+        });
+
+        let entry = json!({
+            "time": started.elapsed().as_secs_f64() * 1000.0,
+            "request": request,
+            "response": response,
+        });
+        self.write(entry)?;
+        Ok(hyper::Response::from_parts(parts, full_body(body)))
+    }
+
+    fn write(&self, entry: Value) -> Result<(), String> {
+        let mut entries = self.entries.lock().unwrap();
+        entries.push(entry);
+        let har = json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "mdeno", "version": env!("CARGO_PKG_VERSION") },
+                "entries": *entries,
+            },
+        });
+        let har = serde_json::to_string_pretty(&har).unwrap_or_default();
+        std::fs::write(&self.path, har)
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))
+    }
+}
+
+/// Serves the responses of a fixture. Requests match entries by method and URL,
+/// redacted as when recorded; repeated requests get the matching entries in turn,
+/// then the last one again.
+pub struct Replayer {
+    entries: Result<Vec<Value>, String>,
+    // Responses served so far, by method and URL
+    served: Mutex<HashMap<(String, String), usize>>,
+}
+
+impl Replayer {
+    fn load(path: PathBuf) -> Replayer {
+        let entries = std::fs::read(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+            .and_then(|har| {
+                let har: Value = serde_json::from_slice(&har)
+                    .map_err(|e| format!("Invalid fixture {}: {}", path.display(), e))?;
+                match har["log"]["entries"].as_array() {
+                    Some(entries) => Ok(entries.clone()),
+                    None => Err(format!("Invalid fixture {}: no entries", path.display())),
+                }
+            });
+        Replayer {
+            entries,
+            served: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn send(
+        &self,
+        uri: &hyper::Uri,
+        req: hyper::Request<RequestBody>,
+    ) -> Result<hyper::Response<ReceivedBody>, String> {
+        let entries = self.entries.as_ref().map_err(|e| e.clone())?;
+        let method = req.method().as_str();
+        let url = redacted_url(uri);
+        let matching: Vec<&Value> = entries
+            .iter()
+            .filter(|entry| {
+                entry["request"]["method"].as_str() == Some(method)
+                    && entry["request"]["url"].as_str() == Some(&url)
+            })
+            .collect();
+        if matching.is_empty() {
+            return Err(format!("No recorded response for {} {}", method, url));
+        }
+
+        let mut served = self.served.lock().unwrap();
+        let count = served.entry((method.to_string(), url)).or_default();
+        let entry = matching[(*count).min(matching.len() - 1)];
+        *count += 1;
+        drop(served);
+
+        response(&entry["response"])
+    }
+}
+
+fn response(response: &Value) -> Result<hyper::Response<ReceivedBody>, String> {
+    let content = &response["content"];
+    let text = content["text"].as_str().unwrap_or_default();
+    let body = if content["encoding"].as_str() == Some("base64") {
+        STANDARD
+            .decode(text)
+            .map_err(|e| format!("Invalid recorded body: {}", e))?
+    } else {
+        text.as_bytes().to_vec()
+    };
+
+    let status = response["status"].as_u64().unwrap_or(200);
+    let mut builder = hyper::Response::builder()
+        .status(u16::try_from(status).unwrap_or(200))
+        .version(match response["httpVersion"].as_str() {
+            Some("HTTP/2.0") => hyper::Version::HTTP_2,
+            _ => hyper::Version::HTTP_11,
+        });
+    // Reason phrases other than the canonical one reach fetch as on the network
+    let status_text = response["statusText"].as_str().unwrap_or_default();
+    if let Ok(reason) = hyper::ext::ReasonPhrase::try_from(status_text.as_bytes().to_vec()) {
+        builder = builder.extension(reason);
+    }
+    for header in response["headers"].as_array().into_iter().flatten() {
+        if let (Some(name), Some(value)) = (header["name"].as_str(), header["value"].as_str()) {
+            builder = builder.header(name, value);
+        }
+    }
+    builder
+        .body(full_body(Bytes::from(body)))
+        .map_err(|e| format!("Invalid recorded response: {}", e))
+}
+
+fn full_body(bytes: Bytes) -> ReceivedBody {
+    Full::new(bytes)
+        .map_err(|never| match never {})
+        .boxed_unsync()
+}
+
+// Headers whose values are replaced in fixtures
+const REDACTED_HEADERS: [hyper::header::HeaderName; 4] = [
+    hyper::header::AUTHORIZATION,
+    hyper::header::PROXY_AUTHORIZATION,
+    hyper::header::COOKIE,
+    hyper::header::SET_COOKIE,
+];
+
+// Query parameters whose values are replaced in fixtures, compared ignoring case
+const REDACTED_PARAMETERS: [&str; 16] = [
+    "access_token",
+    "api_key",
+    "apikey",
+    "auth",
+    "client_secret",
+    "code",
+    "key",
+    "password",
+    "refresh_token",
+    "secret",
+    "sig",
+    "signature",
+    "token",
+    "x-amz-credential",
+    "x-amz-security-token",
+    "x-amz-signature",
+];
+
+fn redacted_url(uri: &hyper::Uri) -> String {
+    let url = uri.to_string();
+    let Some((path, query)) = url.split_once('?') else {
+        return url;
+    };
+    let query: Vec<_> = query
+        .split('&')
+        .map(|parameter| match parameter.split_once('=') {
+            Some((name, _))
+                if REDACTED_PARAMETERS
+                    .iter()
+                    .any(|redacted| redacted.eq_ignore_ascii_case(name)) =>
+            {
+                format!("{}=[REDACTED]", name)
+            }
+            _ => parameter.to_string(),
+        })
+        .collect();
+    format!("{}?{}", path, query.join("&"))
+}
+
+fn headers_json(headers: &hyper::HeaderMap) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if REDACTED_HEADERS.contains(name) {
+                "[REDACTED]".into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes())
+            };
+            json!({
+                "name": name.as_str(),
+                "value": value,
+            })
+        })
+        .collect()
+}
+
+/// Body in the form of HAR content: text when it is UTF-8, so fixtures can be read
+/// and edited, and base64 otherwise. Compressed bodies are kept as received.
+fn content_json(mime_type: &str, body: &[u8]) -> Value {
+    let mut content = json!({ "size": body.len(), "mimeType": mime_type });
+    match std::str::from_utf8(body) {
+        Ok(text) => content["text"] = text.into(),
+        Err(_) => {
+            content["text"] = STANDARD.encode(body).into();
+            content["encoding"] = "base64".into();
+        }
+    }
+    content
+}
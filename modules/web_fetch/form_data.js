@@ -0,0 +1,292 @@
+// https://xhr.spec.whatwg.org/#interface-formdata
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+// Blobs are stored as Files, named "blob" unless a file name is given
+function createEntry(method, name, value, filename) {
+  name = String(name);
+  if (!(value instanceof Blob)) {
+    if (filename !== undefined) {
+      throw new TypeError(
+        `Failed to execute '${method}' on 'FormData': parameter 2 is not of type 'Blob'.`,
+      );
+    }
+    return [name, String(value)];
+  }
+  if (!(value instanceof File) || filename !== undefined) {
+    value = new File([value], filename ?? value.name ?? "blob", {
+      type: value.type,
+      lastModified: value.lastModified,
+    });
+  }
+  return [name, value];
+}
+
+class FormData {
+  // Entry list of [name, string or File] in insertion order
+  #entries = [];
+
+  constructor(form = undefined) {
+    if (form !== undefined) {
+      throw new TypeError(
+        "Failed to construct 'FormData': parameter 1 is not of type 'HTMLFormElement'.",
+      );
+    }
+  }
+
+  append(name, value, filename = undefined) {
+    this.#entries.push(createEntry("append", name, value, filename));
+  }
+
+  delete(name) {
+    name = String(name);
+    this.#entries = this.#entries.filter(([n]) => n !== name);
+  }
+
+  get(name) {
+    name = String(name);
+    return this.#entries.find(([n]) => n === name)?.[1] ?? null;
+  }
+
+  getAll(name) {
+    name = String(name);
+    return this.#entries.filter(([n]) => n === name).map(([, v]) => v);
+  }
+
+  has(name) {
+    name = String(name);
+    return this.#entries.some(([n]) => n === name);
+  }
+
+  set(name, value, filename = undefined) {
+    const entry = createEntry("set", name, value, filename);
+    const index = this.#entries.findIndex(([n]) => n === entry[0]);
+    if (index === -1) {
+      this.#entries.push(entry);
+    } else {
+      this.#entries[index] = entry;
+      this.#entries = this.#entries.filter(([n], i) =>
+        n !== entry[0] || i <= index
+      );
+    }
+  }
+
+  forEach(callback, thisArg) {
+    for (const [name, value] of this.#entries) {
+      callback.call(thisArg, value, name, this);
+    }
+  }
+
+  *entries() {
+    for (const [name, value] of this.#entries) {
+      yield [name, value];
+    }
+  }
+
+  *keys() {
+    for (const [name] of this.#entries) {
+      yield name;
+    }
+  }
+
+  *values() {
+    for (const [, value] of this.#entries) {
+      yield value;
+    }
+  }
+
+  [Symbol.iterator]() {
+    return this.entries();
+  }
+
+  get [Symbol.toStringTag]() {
+    return "FormData";
+  }
+}
+
+const encode = (text) => __internal.fetch.encode(text);
+const decode = (bytes) => __internal.fetch.decode(bytes, "utf-8");
+
+// Names and file names are escaped, and line breaks in names and values are
+// normalized to CRLF
+// https://html.spec.whatwg.org/multipage/form-control-infrastructure.html#multipart-form-data
+function escapeName(name) {
+  return name.replace(/\n/g, "%0A").replace(/\r/g, "%0D").replace(/"/g, "%22");
+}
+
+function normalizeLineBreaks(text) {
+  return text.replace(/\r\n|\r|\n/g, "\r\n");
+}
+
+function randomBoundary() {
+  let boundary = "----mdenoFormBoundary";
+  for (let i = 0; i < 16; i++) {
+    boundary += Math.floor(Math.random() * 36).toString(36);
+  }
+  return boundary;
+}
+
+// Encode a FormData as a multipart/form-data body
+function encodeFormData(formData) {
+  const boundary = randomBoundary();
+  const parts = [];
+  for (const [name, value] of formData) {
+    let head = `--${boundary}\r\nContent-Disposition: form-data; name="${
+      escapeName(normalizeLineBreaks(name))
+    }"`;
+    if (typeof value === "string") {
+      head += "\r\n\r\n";
+      parts.push(encode(head + normalizeLineBreaks(value) + "\r\n"));
+    } else {
+      head += `; filename="${escapeName(value.name)}"\r\nContent-Type: ${
+        value.type || "application/octet-stream"
+      }\r\n\r\n`;
+      parts.push(
+        encode(head),
+        __internal.fetch.blobBytes(value),
+        encode("\r\n"),
+      );
+    }
+  }
+  parts.push(encode(`--${boundary}--\r\n`));
+
+  const size = parts.reduce((total, part) => total + part.byteLength, 0);
+  const bytes = new Uint8Array(size);
+  let offset = 0;
+  for (const part of parts) {
+    bytes.set(part, offset);
+    offset += part.byteLength;
+  }
+  return { bytes, type: `multipart/form-data; boundary=${boundary}` };
+}
+
+function indexOf(bytes, pattern, from) {
+  const last = bytes.byteLength - pattern.byteLength;
+  search: for (let i = Math.max(from, 0); i <= last; i++) {
+    for (let j = 0; j < pattern.byteLength; j++) {
+      if (bytes[i + j] !== pattern[j]) continue search;
+    }
+    return i;
+  }
+  return -1;
+}
+
+function parseError() {
+  return new TypeError("Failed to parse body as FormData.");
+}
+
+// Parameter of a header value, with a quoted or a plain value
+const HEADER_PARAMETER =
+  /;\s*([^=;\s]+)\s*=\s*(?:"((?:[^"\\]|\\.)*)"|([^;]*))/g;
+
+function unescapeName(name) {
+  return name.replace(/%0A/gi, "\n").replace(/%0D/gi, "\r")
+    .replace(/%22/g, '"');
+}
+
+// Headers of a body part, with the parameters of its Content-Disposition
+function parsePartHeaders(text) {
+  const headers = {};
+  for (const line of text.split("\r\n")) {
+    const colon = line.indexOf(":");
+    if (colon === -1) continue;
+    headers[line.slice(0, colon).trim().toLowerCase()] = line.slice(colon + 1)
+      .trim();
+  }
+
+  const disposition = headers["content-disposition"] ?? "";
+  if (!/^form-data\s*(;|$)/i.test(disposition)) {
+    throw parseError();
+  }
+  const parameters = {};
+  for (const match of disposition.matchAll(HEADER_PARAMETER)) {
+    const value = match[2]?.replace(/\\(.)/g, "$1") ?? match[3].trim();
+    parameters[match[1].toLowerCase()] = unescapeName(value);
+  }
+  if (parameters.name === undefined) {
+    throw parseError();
+  }
+  return {
+    name: parameters.name,
+    filename: parameters.filename,
+    type: headers["content-type"],
+  };
+}
+
+function parseMultipart(bytes, boundary) {
+  const formData = new FormData();
+  const delimiter = encode(`--${boundary}`);
+  const nextDelimiter = encode(`\r\n--${boundary}`);
+  const headersEnd = encode("\r\n\r\n");
+
+  let position = indexOf(bytes, delimiter, 0);
+  if (position === -1) {
+    throw parseError();
+  }
+  position += delimiter.byteLength;
+
+  while (true) {
+    // "--" after a delimiter ends the body
+    if (bytes[position] === 0x2d && bytes[position + 1] === 0x2d) {
+      return formData;
+    }
+    while (bytes[position] === 0x20 || bytes[position] === 0x09) {
+      position++;
+    }
+    if (bytes[position] !== 0x0d || bytes[position + 1] !== 0x0a) {
+      throw parseError();
+    }
+
+    // The CRLF ending the delimiter line also ends an empty header block
+    const end = indexOf(bytes, headersEnd, position);
+    if (end === -1) {
+      throw parseError();
+    }
+    const { name, filename, type } = parsePartHeaders(
+      decode(bytes.subarray(position + 2, end)),
+    );
+
+    const start = end + headersEnd.byteLength;
+    const next = indexOf(bytes, nextDelimiter, start);
+    if (next === -1) {
+      throw parseError();
+    }
+    const content = bytes.slice(start, next);
+    if (filename === undefined) {
+      formData.append(name, decode(content));
+    } else {
+      formData.append(
+        name,
+        new File([content], filename, { type: type ?? "text/plain" }),
+      );
+    }
+    position = next + nextDelimiter.byteLength;
+  }
+}
+
+// Parse a multipart/form-data or application/x-www-form-urlencoded body
+function parseFormData(bytes, contentType) {
+  const essence = (contentType ?? "").split(";")[0].trim().toLowerCase();
+  if (essence === "multipart/form-data") {
+    const match = /;\s*boundary\s*=\s*(?:"([^"]*)"|([^;\s]*))/i.exec(
+      contentType,
+    );
+    const boundary = match?.[1] ?? match?.[2];
+    if (!boundary) {
+      throw parseError();
+    }
+    return parseMultipart(bytes, boundary);
+  }
+  if (essence === "application/x-www-form-urlencoded") {
+    const formData = new FormData();
+    for (const [name, value] of new URLSearchParams(decode(bytes))) {
+      formData.append(name, value);
+    }
+    return formData;
+  }
+  throw parseError();
+}
+
+__internal.fetch.encodeFormData = encodeFormData;
+__internal.fetch.parseFormData = parseFormData;
+
+globalThis.FormData = FormData;
@@ -1,28 +1,117 @@
-use rquickjs::{Ctx, Module};
+use bytes::Bytes;
+use deno_net::tls::{self, TlsOptions};
+use futures_util::{Stream, StreamExt, TryStreamExt};
+use http_body_util::combinators::{BoxBody, UnsyncBoxBody};
+use http_body_util::{BodyDataStream, BodyExt, Full, StreamBody};
+use hyper::body::Frame;
+use rquickjs::{Ctx, FromJs, Module, TypedArray, Value};
 use std::collections::HashMap;
 use std::error::Error;
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use utils::add_internal_function;
 
+mod cache;
+mod eventsource;
+mod fixture;
+mod local;
+mod pool;
+mod proxy;
+mod server;
+mod websocket;
+
 pub fn init(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
-    #[cfg(feature = "rustls")]
-    {
-        // Initialize rustls crypto provider
-        use rustls::crypto::ring::default_provider;
-        let _ = default_provider().install_default();
-    }
+    tls::install_provider();
 
     setup_internal(ctx).map_err(|_| rquickjs::Error::Unknown)?;
+    let module = Module::evaluate(ctx.clone(), "web_fetch_blob", include_str!("blob.js"))?;
+    module.finish::<()>()?;
+    let module = Module::evaluate(
+        ctx.clone(),
+        "web_fetch_form_data",
+        include_str!("form_data.js"),
+    )?;
+    module.finish::<()>()?;
     let module = Module::evaluate(ctx.clone(), "web_fetch", include_str!("fetch.js"))?;
     module.finish::<()>()?;
+    let module = Module::evaluate(ctx.clone(), "web_fetch_cache", include_str!("cache.js"))?;
+    module.finish::<()>()?;
+    let module = Module::evaluate(ctx.clone(), "web_fetch_serve", include_str!("serve.js"))?;
+    module.finish::<()>()?;
+    let module = Module::evaluate(
+        ctx.clone(),
+        "web_fetch_websocket",
+        include_str!("websocket.js"),
+    )?;
+    module.finish::<()>()?;
+    let module = Module::evaluate(
+        ctx.clone(),
+        "web_fetch_eventsource",
+        include_str!("eventsource.js"),
+    )?;
+    module.finish::<()>()?;
     Ok(())
 }
 
-type FetchResult = Option<Result<String, String>>;
+struct FetchResponse {
+    // JSON with the status, status text and headers
+    meta: String,
+    body: ResponseBody,
+}
+
+/// A response body being streamed to JS, decoded when it was compressed. Dropping an
+/// unfinished body closes its HTTP/1.1 connection or resets its HTTP/2 stream.
+type ResponseBody = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+// Content codings decoded by fetch, advertised to servers
+const ACCEPT_ENCODING: &str = "gzip, deflate, br";
+
+// Size of the chunks decoded and file bodies are read in
+const READ_CHUNK_SIZE: usize = 16 * 1024;
+
+type FetchResult = Option<Result<FetchResponse, String>>;
+
+/// Request body as handed over by JS. Buffered bodies can be sent again when a
+/// redirect is followed, streamed ones only once.
+enum RequestSource {
+    Buffered(Bytes),
+    Streamed(Option<RequestBody>),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RedirectMode {
+    Follow,
+    Manual,
+    Error,
+}
+
+// Redirects followed before fetch fails, as in browsers
+const MAX_REDIRECTS: usize = 20;
+
+// Next chunk of a response body, or None at its end
+type ReadResult = Option<Result<Option<Bytes>, String>>;
+
+type RequestBody = BoxBody<Bytes, std::io::Error>;
+// Body of a response from the network, or from a fixture being replayed
+type ReceivedBody = UnsyncBoxBody<Bytes, std::io::Error>;
+type BodyChunk = Result<Frame<Bytes>, std::io::Error>;
+
+// Number of streamed request body chunks buffered before JS has to wait
+const BODY_CHUNK_BUFFER: usize = 16;
 
 struct FetchState {
     next_id: u64,
     pending: HashMap<u64, Arc<Mutex<FetchResult>>>,
+    // Dropping a task cancels the request and closes its connection
+    tasks: HashMap<u64, smol::Task<()>>,
+    // Senders feeding streamed request bodies
+    uploads: HashMap<u64, smol::channel::Sender<BodyChunk>>,
+    // Response bodies being read by JS, keyed by the request id
+    responses: HashMap<u64, Arc<smol::lock::Mutex<ResponseBody>>>,
+    // Outstanding reads of response body chunks
+    reads: HashMap<u64, Arc<Mutex<ReadResult>>>,
+    // Clients created with Deno.createHttpClient
+    clients: HashMap<u64, Arc<pool::Client>>,
 }
 
 // Use smol's global executor instead of maintaining our own runtime
@@ -31,42 +120,232 @@ static FETCH_STATE: once_cell::sync::Lazy<Mutex<FetchState>> = once_cell::sync::
     Mutex::new(FetchState {
         next_id: 0,
         pending: HashMap::new(),
+        tasks: HashMap::new(),
+        uploads: HashMap::new(),
+        responses: HashMap::new(),
+        reads: HashMap::new(),
+        clients: HashMap::new(),
     })
 });
 
+fn next_id() -> u64 {
+    let mut state = FETCH_STATE.lock().unwrap();
+    let id = state.next_id;
+    state.next_id += 1;
+    id
+}
+
 fn setup_internal(ctx: &Ctx) -> Result<(), Box<dyn Error>> {
     ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].fetch = {};")?;
 
-    add_internal_function!(ctx, "fetch.start", |url: String,
-                                                method: String,
-                                                headers: String,
-                                                body: String|
-     -> u64 {
-        fetch_start(url, method, headers, body)
+    add_internal_function!(ctx, "fetch.start", fetch_start);
+
+    add_internal_function!(ctx, "fetch.write", fetch_write);
+
+    add_internal_function!(ctx, "fetch.writing", |id: u64| -> bool {
+        FETCH_STATE.lock().unwrap().uploads.contains_key(&id)
+    });
+
+    add_internal_function!(ctx, "fetch.close", |id: u64, error: Option<String>| {
+        fetch_close(id, error)
     });
 
     add_internal_function!(ctx, "fetch.poll", |id: u64| -> String { fetch_poll(id) });
 
+    add_internal_function!(ctx, "fetch.cancel", |id: u64| { fetch_cancel(id) });
+
+    add_internal_function!(ctx, "fetch.read", |id: u64| -> u64 { fetch_read(id) });
+
+    add_internal_function!(ctx, "fetch.pollRead", fetch_poll_read);
+
+    add_internal_function!(ctx, "fetch.createClient", fetch_create_client);
+
+    add_internal_function!(ctx, "fetch.closeClient", |id: u64| {
+        fetch_close_client(id)
+    });
+
+    add_internal_function!(ctx, "fetch.encode", encode_utf8);
+
+    add_internal_function!(ctx, "fetch.decode", decode_text);
+
+    cache::setup_internal(ctx)?;
+
+    server::setup_internal(ctx)?;
+
+    websocket::setup_internal(ctx)?;
+
+    eventsource::setup_internal(ctx)?;
+
     Ok(())
 }
 
-fn fetch_start(url: String, method: String, headers: String, body: String) -> u64 {
+/// Start a request. `body` is a string or `Uint8Array`, or null when there is no body
+/// or when `stream` is set and chunks will follow through `fetch.write`.
+/// `redirect` is the fetch redirect mode: "follow", "manual" or "error". `client` is
+/// the id of a client from `fetch.createClient`, or null for the default client.
+fn fetch_start(
+    url: String,
+    method: String,
+    headers: String,
+    body: Option<Value<'_>>,
+    stream: bool,
+    redirect: String,
+    client: Option<u64>,
+) -> u64 {
+    let id = next_id();
     let mut state = FETCH_STATE.lock().unwrap();
-    let id = state.next_id;
-    state.next_id += 1;
 
     let result = Arc::new(Mutex::new(None));
     state.pending.insert(id, result.clone());
 
-    smol::spawn(async move {
-        let res = fetch_request(url, method, headers, body).await;
+    let client = match client {
+        Some(client) => {
+            (state.clients.get(&client).cloned()).ok_or_else(|| "HTTP client is closed".to_string())
+        }
+        None => pool::DEFAULT_CLIENT.clone(),
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            *result.lock().unwrap() = Some(Err(e));
+            return id;
+        }
+    };
+
+    let source = if stream {
+        let (sender, receiver) = smol::channel::bounded(BODY_CHUNK_BUFFER);
+        state.uploads.insert(id, sender);
+        RequestSource::Streamed(Some(RequestBody::new(StreamBody::new(receiver))))
+    } else {
+        let bytes = body.map(|value| body_bytes(&value));
+        RequestSource::Buffered(bytes.unwrap_or_default())
+    };
+    let redirect = match redirect.as_str() {
+        "manual" => RedirectMode::Manual,
+        "error" => RedirectMode::Error,
+        _ => RedirectMode::Follow,
+    };
+
+    let task = smol::spawn(async move {
+        let res = fetch_request(&client, url, method, headers, source, redirect).await;
         *result.lock().unwrap() = Some(res);
-    })
-    .detach();
+    });
+    state.tasks.insert(id, task);
 
     id
 }
 
+fn body_bytes(value: &Value<'_>) -> Bytes {
+    if let Some(text) = value.as_string() {
+        return text.to_string().map(Bytes::from).unwrap_or_default();
+    }
+    TypedArray::<u8>::from_js(value.ctx(), value.clone())
+        .ok()
+        .and_then(|array| array.as_bytes().map(Bytes::copy_from_slice))
+        .unwrap_or_default()
+}
+
+/// Queue a chunk of a streamed request body. Returns false when the buffer is full
+/// and the chunk should be retried later.
+fn fetch_write(id: u64, chunk: TypedArray<'_, u8>) -> bool {
+    let mut state = FETCH_STATE.lock().unwrap();
+    let Some(sender) = state.uploads.get(&id) else {
+        return true;
+    };
+    let bytes = Bytes::copy_from_slice(chunk.as_bytes().unwrap_or_default());
+
+    match sender.try_send(Ok(Frame::data(bytes))) {
+        Ok(()) => true,
+        Err(smol::channel::TrySendError::Full(_)) => false,
+        // The request already finished or failed, drop the rest of the body
+        Err(smol::channel::TrySendError::Closed(_)) => {
+            state.uploads.remove(&id);
+            true
+        }
+    }
+}
+
+/// End a streamed request body, aborting the request when the stream errored.
+fn fetch_close(id: u64, error: Option<String>) {
+    let mut state = FETCH_STATE.lock().unwrap();
+    if let Some(sender) = state.uploads.remove(&id)
+        && let Some(error) = error
+    {
+        let _ = sender.force_send(Err(std::io::Error::other(error)));
+    }
+}
+
+/// Create an HTTP client from JSON options. Returns the id to pass to `fetch.start`.
+fn fetch_create_client(ctx: Ctx<'_>, options: String) -> rquickjs::Result<u64> {
+    let options: serde_json::Value = serde_json::from_str(&options).unwrap_or_default();
+
+    let proxy = match options.get("proxy").filter(|proxy| !proxy.is_null()) {
+        Some(options) => {
+            let url = options.get("url").and_then(|url| url.as_str());
+            let url =
+                url.ok_or_else(|| rquickjs::Exception::throw_type(&ctx, "Proxy URL is required"))?;
+            let mut proxy =
+                proxy::Proxy::parse(url).map_err(|e| rquickjs::Exception::throw_type(&ctx, &e))?;
+            if let Some(auth) = options.get("basicAuth").filter(|auth| !auth.is_null()) {
+                let field = |name| auth.get(name).and_then(|value| value.as_str());
+                proxy.set_basic_auth(
+                    field("username").unwrap_or_default(),
+                    field("password").unwrap_or_default(),
+                );
+            }
+            Some(proxy)
+        }
+        None => None,
+    };
+
+    let string = |name| options.get(name).and_then(|value| value.as_str());
+    let tls = TlsOptions {
+        ca_certs: options
+            .get("caCerts")
+            .and_then(|certs| certs.as_array())
+            .map(|certs| {
+                let certs = certs.iter().filter_map(|cert| cert.as_str());
+                certs.map(str::to_string).collect()
+            })
+            .unwrap_or_default(),
+        cert: string("cert").map(str::to_string),
+        key: string("key").map(str::to_string),
+        alpn_protocols: http_alpn_protocols(
+            options.get("http2").and_then(|http2| http2.as_bool()) != Some(false),
+        ),
+    };
+    let client =
+        pool::Client::new(proxy, &tls).map_err(|e| rquickjs::Exception::throw_type(&ctx, &e))?;
+
+    let id = next_id();
+    let mut state = FETCH_STATE.lock().unwrap();
+    state.clients.insert(id, Arc::new(client));
+    Ok(id)
+}
+
+/// Close a client. Requests already made keep their connections until they finish.
+fn fetch_close_client(id: u64) {
+    let client = FETCH_STATE.lock().unwrap().clients.remove(&id);
+    if let Some(client) = client {
+        client.close();
+    }
+}
+
+/// Cancel a request or a body read, dropping its response body and connection.
+fn fetch_cancel(id: u64) {
+    let mut state = FETCH_STATE.lock().unwrap();
+    state.pending.remove(&id);
+    state.uploads.remove(&id);
+    state.reads.remove(&id);
+    let task = state.tasks.remove(&id);
+    let body = state.responses.remove(&id);
+    drop(state);
+
+    // Dropping the task outside the lock cancels the in-flight request
+    drop(task);
+    drop(body);
+}
+
 fn fetch_poll(id: u64) -> String {
     let mut state = FETCH_STATE.lock().unwrap();
     if let Some(result_arc) = state.pending.get(&id) {
@@ -75,10 +354,16 @@ fn fetch_poll(id: u64) -> String {
             // Remove from pending map
             drop(result);
             state.pending.remove(&id);
+            state.tasks.remove(&id);
+            state.uploads.remove(&id);
 
             match res {
-                Ok(json) => json,
-                Err(e) => format!(r#"{{"error":"{}"}}"#, e.replace('"', "\\\"")),
+                Ok(response) => {
+                    let body = Arc::new(smol::lock::Mutex::new(response.body));
+                    state.responses.insert(id, body);
+                    response.meta
+                }
+                Err(e) => serde_json::json!({ "error": e }).to_string(),
             }
         } else {
             String::new()
@@ -88,302 +373,381 @@ fn fetch_poll(id: u64) -> String {
     }
 }
 
-async fn fetch_request(
-    url: String,
-    method: String,
-    _headers: String,
-    _body: String,
-) -> Result<String, String> {
-    // Parse URL
-    let uri: hyper::Uri = url.parse().map_err(|e| format!("Invalid URL: {}", e))?;
-
-    // Get host for Host header
-    let host = uri.host().ok_or("Missing host in URL")?;
-    let host_header = if let Some(port) = uri.port_u16() {
-        format!("{}:{}", host, port)
-    } else {
-        host.to_string()
-    };
+/// Start reading the next chunk of the body of response `id`. Returns the id of the
+/// read to pass to `fetch.pollRead`.
+fn fetch_read(id: u64) -> u64 {
+    let read_id = next_id();
+    let mut state = FETCH_STATE.lock().unwrap();
 
-    // Create request
-    let req = hyper::Request::builder()
-        .method(method.as_str())
-        .uri(&uri)
-        .header("Host", host_header)
-        .header("User-Agent", "mdeno/0.1")
-        .body(http_body_util::Empty::<bytes::Bytes>::new())
-        .map_err(|e| format!("Failed to build request: {}", e))?;
+    let result = Arc::new(Mutex::new(None));
+    state.reads.insert(read_id, result.clone());
 
-    // Connect and send request
-    let response = fetch_impl(req).await?;
+    let Some(body) = state.responses.get(&id).cloned() else {
+        *result.lock().unwrap() = Some(Ok(None));
+        return read_id;
+    };
 
-    // Read response body
-    let status = response.status().as_u16();
-    let headers = format!("{:?}", response.headers());
+    let task = smol::spawn(async move {
+        let chunk = read_chunk(&mut *body.lock().await).await;
 
-    use http_body_util::BodyExt;
+        // The body is finished, release it so its connection can be reused
+        if !matches!(chunk, Ok(Some(_))) {
+            FETCH_STATE.lock().unwrap().responses.remove(&id);
+        }
+        *result.lock().unwrap() = Some(chunk);
+    });
+    state.tasks.insert(read_id, task);
 
-    let body_bytes = response
-        .into_body()
-        .collect()
-        .await
-        .map_err(|e| format!("Failed to read body: {}", e))?
-        .to_bytes();
-
-    let body_text = String::from_utf8_lossy(&body_bytes).to_string();
-
-    // Properly escape the body text for JSON
-    let escaped_body = body_text
-        .replace('\\', "\\\\")
-        .replace('"', "\\\"")
-        .replace('\n', "\\n")
-        .replace('\r', "\\r")
-        .replace('\t', "\\t")
-        .chars()
-        .map(|c| {
-            if c.is_control() && c != '\n' && c != '\r' && c != '\t' {
-                format!("\\u{:04x}", c as u32)
-            } else {
-                c.to_string()
-            }
-        })
-        .collect::<String>();
-
-    Ok(format!(
-        r#"{{"status":{},"headers":{},"body":"{}"}}"#,
-        status,
-        serde_json::to_string(&headers).unwrap(),
-        escaped_body
-    ))
+    read_id
 }
 
-#[cfg(feature = "native-tls")]
-mod tls {
-    use futures_io::{AsyncRead, AsyncWrite};
-    use smol::net::TcpStream;
-
-    pub enum IoStream {
-        Plain(TcpStream),
-        Tls(async_native_tls::TlsStream<TcpStream>),
-    }
-
-    impl AsyncRead for IoStream {
-        fn poll_read(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-            buf: &mut [u8],
-        ) -> std::task::Poll<std::io::Result<usize>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_read(cx, buf),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_read(cx, buf),
-            }
+async fn read_chunk(body: &mut ResponseBody) -> Result<Option<Bytes>, String> {
+    loop {
+        match body.next().await {
+            // Skip empty chunks
+            Some(Ok(data)) if data.is_empty() => continue,
+            Some(Ok(data)) => return Ok(Some(data)),
+            Some(Err(e)) => return Err(format!("Failed to read body: {}", e)),
+            None => return Ok(None),
         }
     }
+}
 
-    impl AsyncWrite for IoStream {
-        fn poll_write(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-            buf: &[u8],
-        ) -> std::task::Poll<std::io::Result<usize>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_write(cx, buf),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_write(cx, buf),
-            }
-        }
-
-        fn poll_flush(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<std::io::Result<()>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_flush(cx),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_flush(cx),
-            }
+/// Stream the body of a response, decoding the content codings in
+/// `ACCEPT_ENCODING`. The Content-Encoding and Content-Length of a decoded body no
+/// longer apply and are removed from `headers`.
+fn response_body(headers: &mut hyper::HeaderMap, body: ReceivedBody) -> ResponseBody {
+    use async_compression::futures::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+    use futures_util::io::AsyncRead;
+
+    // Trailers are dropped
+    let stream = BodyDataStream::new(body);
+
+    let encoding = headers
+        .get(hyper::header::CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase());
+    let reader: Pin<Box<dyn AsyncRead + Send>> = match encoding.as_deref() {
+        Some("gzip" | "x-gzip") => {
+            let mut decoder = GzipDecoder::new(stream.into_async_read());
+            decoder.multiple_members(true);
+            Box::pin(decoder)
         }
+        // HTTP's deflate coding is zlib-wrapped
+        Some("deflate") => Box::pin(ZlibDecoder::new(stream.into_async_read())),
+        Some("br") => Box::pin(BrotliDecoder::new(stream.into_async_read())),
+        _ => return Box::pin(stream),
+    };
+    headers.remove(hyper::header::CONTENT_ENCODING);
+    headers.remove(hyper::header::CONTENT_LENGTH);
+    reader_body(reader)
+}
 
-        fn poll_close(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<std::io::Result<()>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_close(cx),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_close(cx),
-            }
+/// Stream the bytes of `reader` as a body.
+fn reader_body<R>(reader: R) -> ResponseBody
+where
+    R: futures_util::io::AsyncRead + Send + Unpin + 'static,
+{
+    use futures_util::io::AsyncReadExt;
+
+    let chunks = futures_util::stream::try_unfold(reader, |mut reader| async move {
+        let mut chunk = vec![0; READ_CHUNK_SIZE];
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            return Ok(None);
         }
-    }
-
-    pub async fn create_tls_stream(host: &str, port: u16) -> Result<IoStream, String> {
-        let stream = TcpStream::connect(format!("{}:{}", host, port))
-            .await
-            .map_err(|e| format!("Connection failed: {}", e))?;
-
-        let connector = async_native_tls::TlsConnector::new();
-        let tls_stream = connector
-            .connect(host, stream)
-            .await
-            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+        chunk.truncate(read);
+        Ok(Some((Bytes::from(chunk), reader)))
+    });
+    Box::pin(chunks)
+}
 
-        Ok(IoStream::Tls(tls_stream))
+/// Poll a body read. Returns undefined while pending, a `Uint8Array` chunk, or null
+/// at the end of the body. Throws a TypeError when reading failed.
+fn fetch_poll_read<'js>(ctx: Ctx<'js>, read_id: u64) -> rquickjs::Result<Value<'js>> {
+    let mut state = FETCH_STATE.lock().unwrap();
+    let Some(result) = state.reads.get(&read_id) else {
+        return Ok(Value::new_null(ctx));
+    };
+    let Some(chunk) = result.lock().unwrap().take() else {
+        return Ok(Value::new_undefined(ctx));
+    };
+    state.reads.remove(&read_id);
+    state.tasks.remove(&read_id);
+    drop(state);
+
+    match chunk {
+        Ok(Some(bytes)) => Ok(TypedArray::<u8>::new_copy(ctx, &bytes)?.into_value()),
+        Ok(None) => Ok(Value::new_null(ctx)),
+        Err(e) => Err(rquickjs::Exception::throw_type(&ctx, &e)),
     }
+}
 
-    pub async fn create_plain_stream(host: &str, port: u16) -> Result<IoStream, String> {
-        let stream = TcpStream::connect(format!("{}:{}", host, port))
-            .await
-            .map_err(|e| format!("Connection failed: {}", e))?;
-        Ok(IoStream::Plain(stream))
-    }
+fn encode_utf8<'js>(ctx: Ctx<'js>, text: String) -> rquickjs::Result<TypedArray<'js, u8>> {
+    TypedArray::new(ctx, text.into_bytes())
 }
 
-#[cfg(feature = "rustls")]
-mod tls {
-    use futures_io::{AsyncRead, AsyncWrite};
-    use futures_rustls::TlsConnector;
-    use smol::net::TcpStream;
-    use std::sync::Arc;
+/// Decode bytes with the encoding named by a charset label, falling back to UTF-8
+/// for unknown labels. A byte order mark takes precedence over the label.
+fn decode_text(bytes: TypedArray<'_, u8>, label: Option<String>) -> String {
+    let encoding = label
+        .and_then(|label| encoding_rs::Encoding::for_label(label.as_bytes()))
+        .unwrap_or(encoding_rs::UTF_8);
+    let (text, _, _) = encoding.decode(bytes.as_bytes().unwrap_or_default());
+    text.into_owned()
+}
 
-    pub enum IoStream {
-        Plain(TcpStream),
-        Tls(futures_rustls::client::TlsStream<TcpStream>),
+async fn fetch_request(
+    client: &pool::Client,
+    url: String,
+    mut method: String,
+    headers: String,
+    mut source: RequestSource,
+    redirect: RedirectMode,
+) -> Result<FetchResponse, String> {
+    let mut url = url::Url::parse(&url).map_err(|e| format!("Invalid URL: {}", e))?;
+    match url.scheme() {
+        "file" => return local::fetch_file(&url, &method).await,
+        "data" => return local::fetch_data(&url),
+        _ => {}
     }
 
-    impl AsyncRead for IoStream {
-        fn poll_read(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-            buf: &mut [u8],
-        ) -> std::task::Poll<std::io::Result<usize>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_read(cx, buf),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_read(cx, buf),
+    // Headers arrive as JSON name/value pairs
+    let mut headers: Vec<(String, String)> =
+        serde_json::from_str(&headers).map_err(|e| format!("Invalid headers: {}", e))?;
+
+    let mut redirects = 0;
+    loop {
+        let (body, content_length) = match &mut source {
+            RequestSource::Buffered(bytes) => {
+                let body = Full::new(bytes.clone())
+                    .map_err(|never| match never {})
+                    .boxed();
+                (body, Some(bytes.len() as u64))
             }
-        }
-    }
+            RequestSource::Streamed(body) => {
+                let body = body
+                    .take()
+                    .ok_or("Cannot follow redirect with a streamed request body")?;
+                (body, None)
+            }
+        };
 
-    impl AsyncWrite for IoStream {
-        fn poll_write(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-            buf: &[u8],
-        ) -> std::task::Poll<std::io::Result<usize>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_write(cx, buf),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_write(cx, buf),
+        // The fragment is never sent and not part of the response URL
+        let mut target = url.clone();
+        target.set_fragment(None);
+        let uri: hyper::Uri = target
+            .as_str()
+            .parse()
+            .map_err(|e| format!("Invalid URL: {}", e))?;
+
+        let req = build_request(&uri, &method, &headers, body, content_length)?;
+
+        // Connect and send request
+        let response = fetch_impl(client, &uri, req).await?;
+        let status = response.status();
+
+        let location = response
+            .headers()
+            .get(hyper::header::LOCATION)
+            .and_then(|value| value.to_str().ok());
+        if let Some(location) = location
+            && matches!(status.as_u16(), 301 | 302 | 303 | 307 | 308)
+            && redirect != RedirectMode::Manual
+        {
+            if redirect == RedirectMode::Error {
+                return Err("Redirect was blocked by redirect mode \"error\"".to_string());
             }
-        }
+            if redirects == MAX_REDIRECTS {
+                return Err("Too many redirects".to_string());
+            }
+            redirects += 1;
 
-        fn poll_flush(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<std::io::Result<()>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_flush(cx),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_flush(cx),
+            let mut next = url
+                .join(location)
+                .map_err(|e| format!("Invalid redirect location: {}", e))?;
+            if !matches!(next.scheme(), "http" | "https") {
+                return Err(format!("Cannot redirect to {} URL", next.scheme()));
+            }
+            if next.fragment().is_none() {
+                next.set_fragment(url.fragment());
             }
-        }
 
-        fn poll_close(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<std::io::Result<()>> {
-            match &mut *self {
-                IoStream::Plain(s) => std::pin::Pin::new(s).poll_close(cx),
-                IoStream::Tls(s) => std::pin::Pin::new(s).poll_close(cx),
+            // https://fetch.spec.whatwg.org/#http-redirect-fetch
+            let status = status.as_u16();
+            if (matches!(status, 301 | 302) && method == "POST")
+                || (status == 303 && !matches!(method.as_str(), "GET" | "HEAD"))
+            {
+                method = "GET".to_string();
+                source = RequestSource::Buffered(Bytes::new());
+                headers.retain(|(name, _)| !is_request_body_header(name));
+            }
+            if next.origin() != url.origin() {
+                headers.retain(|(name, _)| !is_credentials_header(name));
             }
-        }
-    }
 
-    pub async fn create_tls_stream(host: &str, port: u16) -> Result<IoStream, String> {
-        use rustls::pki_types::ServerName;
+            // Dropping the redirect response frees its connection for the next hop
+            url = next;
+            continue;
+        }
 
-        let stream = TcpStream::connect(format!("{}:{}", host, port))
-            .await
-            .map_err(|e| format!("Connection failed: {}", e))?;
+        // The body is streamed to JS afterwards
+        let status_text = status_text(&response);
+        let (mut parts, body) = response.into_parts();
+        let body = response_body(&mut parts.headers, body);
+        let response_headers = header_pairs(&parts.headers);
+
+        let meta = serde_json::json!({
+            "status": status.as_u16(),
+            "statusText": status_text,
+            "headers": response_headers,
+            "url": target.as_str(),
+            "redirected": redirects > 0,
+        });
+
+        return Ok(FetchResponse {
+            meta: meta.to_string(),
+            body,
+        });
+    }
+}
 
-        let root_store = rustls::RootCertStore {
-            roots: webpki_roots::TLS_SERVER_ROOTS.iter().cloned().collect(),
-        };
+fn build_request(
+    uri: &hyper::Uri,
+    method: &str,
+    headers: &[(String, String)],
+    body: RequestBody,
+    content_length: Option<u64>,
+) -> Result<hyper::Request<RequestBody>, String> {
+    // Get host for Host header
+    let host = uri.host().ok_or("Missing host in URL")?;
+    let host_header = if let Some(port) = uri.port_u16() {
+        format!("{}:{}", host, port)
+    } else {
+        host.to_string()
+    };
 
-        let config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+    // Create request
+    let mut req = hyper::Request::builder()
+        .method(method)
+        .uri(uri.path_and_query().map_or("/", |path| path.as_str()))
+        .header(hyper::header::HOST, host_header);
+
+    for (name, value) in headers {
+        let name = hyper::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| format!("Invalid header name: {}", name))?;
+
+        // Framing headers are derived from the body
+        if name == hyper::header::HOST
+            || name == hyper::header::CONTENT_LENGTH
+            || name == hyper::header::TRANSFER_ENCODING
+        {
+            continue;
+        }
 
-        let connector = TlsConnector::from(Arc::new(config));
-        let server_name = ServerName::try_from(host.to_string())
-            .map_err(|e| format!("Invalid server name: {}", e))?;
+        let value = hyper::header::HeaderValue::from_str(value)
+            .map_err(|_| format!("Invalid header value for {}", name))?;
+        req = req.header(name, value);
+    }
 
-        let tls_stream = connector
-            .connect(server_name, stream)
-            .await
-            .map_err(|e| format!("TLS handshake failed: {}", e))?;
+    if !headers
+        .iter()
+        .any(|(name, _)| name.eq_ignore_ascii_case("user-agent"))
+    {
+        req = req.header(hyper::header::USER_AGENT, "mdeno/0.1");
+    }
 
-        Ok(IoStream::Tls(tls_stream))
+    // Range requests ask for bytes of the unencoded representation
+    if !headers.iter().any(|(name, _)| {
+        name.eq_ignore_ascii_case("accept-encoding") || name.eq_ignore_ascii_case("range")
+    }) {
+        req = req.header(hyper::header::ACCEPT_ENCODING, ACCEPT_ENCODING);
     }
 
-    pub async fn create_plain_stream(host: &str, port: u16) -> Result<IoStream, String> {
-        let addr = format!("{}:{}", host, port);
-        let stream = TcpStream::connect(&addr)
-            .await
-            .map_err(|e| format!("Connection failed: {}", e))?;
-        Ok(IoStream::Plain(stream))
+    // Streamed bodies have no known length and are sent chunked
+    if let Some(length) = content_length
+        && (length > 0 || !matches!(method, "GET" | "HEAD"))
+    {
+        req = req.header(hyper::header::CONTENT_LENGTH, length);
     }
+
+    req.body(body)
+        .map_err(|e| format!("Failed to build request: {}", e))
 }
 
-async fn fetch_impl(
-    req: hyper::Request<http_body_util::Empty<bytes::Bytes>>,
-) -> Result<hyper::Response<hyper::body::Incoming>, String> {
-    use smol_hyper::rt::FuturesIo;
-
-    let host = req.uri().host().ok_or("Missing host")?;
-    let scheme = req.uri().scheme_str().unwrap_or("http");
-
-    // Determine port and create appropriate stream
-    let io_stream = match scheme {
-        "https" => {
-            let port = req.uri().port_u16().unwrap_or(443);
-
-            // Resolve host to avoid IPv6 fallback delays
-            let tls_host = if host == "localhost" {
-                "127.0.0.1"
-            } else {
-                host
-            };
-
-            tls::create_tls_stream(tls_host, port).await?
-        }
-        "http" => {
-            let port = req.uri().port_u16().unwrap_or(80);
+/// Headers describing a request body, dropped when a redirect turns the request
+/// into a GET.
+fn is_request_body_header(name: &str) -> bool {
+    [
+        "content-encoding",
+        "content-language",
+        "content-location",
+        "content-type",
+    ]
+    .iter()
+    .any(|header| name.eq_ignore_ascii_case(header))
+}
 
-            // Resolve host to avoid IPv6 fallback delays
-            let http_host = if host == "localhost" {
-                "127.0.0.1"
-            } else {
-                host
-            };
+/// Headers that must not be forwarded to another origin.
+fn is_credentials_header(name: &str) -> bool {
+    ["authorization", "cookie", "proxy-authorization"]
+        .iter()
+        .any(|header| name.eq_ignore_ascii_case(header))
+}
 
-            tls::create_plain_stream(http_host, port).await?
-        }
-        scheme => return Err(format!("Unsupported scheme: {}", scheme)),
-    };
+/// The reason phrase sent by the server, or the canonical one when there was none
+/// (HTTP/2 has no reason phrases).
+pub(crate) fn status_text<B>(response: &hyper::Response<B>) -> String {
+    match response.extensions().get::<hyper::ext::ReasonPhrase>() {
+        Some(reason) => latin1(reason.as_bytes()),
+        None => response
+            .status()
+            .canonical_reason()
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Header name/value pairs, with each value of a repeated header kept separate.
+fn header_pairs(headers: &hyper::HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .map(|(name, value)| (name.as_str().to_string(), latin1(value.as_bytes())))
+        .collect()
+}
 
-    let io = FuturesIo::new(io_stream);
+/// Protocols fetch offers through ALPN. HTTP/2 is only used when the server
+/// selects it.
+fn http_alpn_protocols(http2: bool) -> Vec<String> {
+    let protocols: &[&str] = if http2 {
+        &["h2", "http/1.1"]
+    } else {
+        &["http/1.1"]
+    };
+    protocols
+        .iter()
+        .map(|protocol| protocol.to_string())
+        .collect()
+}
 
-    // Establish HTTP connection
-    let (mut sender, conn) = hyper::client::conn::http1::handshake(io)
-        .await
-        .map_err(|e| format!("Handshake failed: {}", e))?;
+/// Decode header bytes the way the fetch spec maps them to a ByteString.
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
 
-    // Spawn connection task
-    smol::spawn(async move {
-        if let Err(e) = conn.await {
-            eprintln!("Connection error: {:?}", e);
+/// Send a request to the origin of `uri` over a pooled connection of `client`, or
+/// record or replay it when a fixture is configured.
+async fn fetch_impl(
+    client: &pool::Client,
+    uri: &hyper::Uri,
+    req: hyper::Request<RequestBody>,
+) -> Result<hyper::Response<ReceivedBody>, String> {
+    match &*fixture::MODE {
+        fixture::Mode::Network => {
+            let response = pool::send_request(client, uri, req).await?;
+            Ok(response.map(|body| body.map_err(std::io::Error::other).boxed_unsync()))
         }
-    })
-    .detach();
-
-    // Send request
-    sender
-        .send_request(req)
-        .await
-        .map_err(|e| format!("Request failed: {}", e))
+        fixture::Mode::Record(recorder) => recorder.send(client, uri, req).await,
+        fixture::Mode::Replay(replayer) => replayer.send(uri, req),
+    }
 }
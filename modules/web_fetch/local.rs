@@ -0,0 +1,133 @@
+//! Responses to file: and data: URLs, which are answered without a network request.
+
+use crate::{FetchResponse, reader_body};
+use base64::Engine;
+use base64::alphabet::STANDARD;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use bytes::Bytes;
+use url::Url;
+
+// Content type of data: URLs that leave it out or give an invalid one
+const DEFAULT_DATA_TYPE: &str = "text/plain;charset=US-ASCII";
+
+/// Stream a local file, with a content type guessed from its extension.
+pub async fn fetch_file(url: &Url, method: &str) -> Result<FetchResponse, String> {
+    if !matches!(method, "GET" | "HEAD") {
+        return Err(format!(
+            "Fetching files only supports the GET method, received {}",
+            method
+        ));
+    }
+    let path = url
+        .to_file_path()
+        .map_err(|_| format!("Invalid file URL: {}", url))?;
+
+    let file = smol::fs::File::open(&path)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let metadata = file
+        .metadata()
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    if metadata.is_dir() {
+        return Err(format!("Failed to open {}: Is a directory", path.display()));
+    }
+
+    let content_type = mime_guess::from_path(&path).first_or_octet_stream();
+    let headers = [
+        ("content-length", metadata.len().to_string()),
+        ("content-type", content_type.to_string()),
+    ];
+    Ok(FetchResponse {
+        meta: meta(url, &headers),
+        body: reader_body(file),
+    })
+}
+
+/// Decode the body of a data: URL.
+/// https://fetch.spec.whatwg.org/#data-url-processor
+pub fn fetch_data(url: &Url) -> Result<FetchResponse, String> {
+    let input = &url[url::Position::AfterScheme..url::Position::AfterQuery];
+    let input = input.strip_prefix(':').unwrap_or(input);
+    let (mime_type, data) = input
+        .split_once(',')
+        .ok_or("Invalid data URL: missing comma")?;
+
+    let mut mime_type = mime_type.trim_matches(|c: char| c.is_ascii_whitespace());
+    let mut bytes: Vec<u8> = percent_encoding::percent_decode_str(data).collect();
+    if let Some(rest) = strip_base64(mime_type) {
+        mime_type = rest.trim_end_matches(|c: char| c.is_ascii_whitespace());
+        bytes = forgiving_base64(&bytes)?;
+    }
+
+    let content_type = if mime_type.starts_with(';') {
+        format!("text/plain{}", mime_type)
+    } else {
+        mime_type.to_string()
+    };
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    let content_type = match essence.split_once('/') {
+        Some((kind, subtype)) if !kind.is_empty() && !subtype.is_empty() => content_type,
+        _ => DEFAULT_DATA_TYPE.to_string(),
+    };
+
+    let body = futures_util::stream::once(async move { Ok(Bytes::from(bytes)) });
+    Ok(FetchResponse {
+        meta: meta(url, &[("content-type", content_type)]),
+        body: Box::pin(body),
+    })
+}
+
+fn meta(url: &Url, headers: &[(&str, String)]) -> String {
+    let mut url = url.clone();
+    url.set_fragment(None);
+
+    let meta = serde_json::json!({
+        "status": 200,
+        "statusText": "OK",
+        "headers": headers,
+        "url": url.as_str(),
+        "redirected": false,
+    });
+    meta.to_string()
+}
+
+/// Strip a ";base64" suffix, matched case-insensitively and with optional spaces
+/// before "base64".
+fn strip_base64(mime_type: &str) -> Option<&str> {
+    let split = mime_type.len().checked_sub("base64".len())?;
+    let (head, suffix) = mime_type.split_at_checked(split)?;
+    if !suffix.eq_ignore_ascii_case("base64") {
+        return None;
+    }
+    head.trim_end_matches(' ').strip_suffix(';')
+}
+
+/// https://infra.spec.whatwg.org/#forgiving-base64-decode
+fn forgiving_base64(data: &[u8]) -> Result<Vec<u8>, String> {
+    const ENGINE: GeneralPurpose = GeneralPurpose::new(
+        &STANDARD,
+        GeneralPurposeConfig::new()
+            .with_decode_padding_mode(DecodePaddingMode::Indifferent)
+            .with_decode_allow_trailing_bits(true),
+    );
+
+    let mut data: Vec<u8> = data
+        .iter()
+        .copied()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    if data.len().is_multiple_of(4) {
+        for _ in 0..2 {
+            if data.last() == Some(&b'=') {
+                data.pop();
+            }
+        }
+    }
+    if data.len() % 4 == 1 || data.contains(&b'=') {
+        return Err("Invalid data URL: invalid base64".to_string());
+    }
+    ENGINE
+        .decode(&data)
+        .map_err(|_| "Invalid data URL: invalid base64".to_string())
+}
@@ -0,0 +1,354 @@
+//! Connections kept alive between requests, shared by every fetch of the runtime.
+//!
+//! HTTP/1.1 connections carry one request at a time: they leave the pool while in
+//! use and return once their response body has been read. HTTP/2 connections stay
+//! in the pool and multiplex concurrent requests. Every client has its own
+//! connections.
+
+use crate::proxy::{self, Proxy};
+use crate::{RequestBody, TlsOptions, http_alpn_protocols, tls};
+use hyper::body::Incoming;
+use hyper::client::conn::{TrySendError, http1, http2};
+use once_cell::sync::Lazy;
+use smol_hyper::rt::FuturesIo;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Idle connections are closed after this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+// Idle HTTP/1.1 connections kept per origin
+const MAX_IDLE_PER_ORIGIN: usize = 32;
+
+type Request = hyper::Request<RequestBody>;
+type Response = hyper::Response<Incoming>;
+
+static POOL: Lazy<Mutex<Pool>> = Lazy::new(|| {
+    Mutex::new(Pool {
+        idle: HashMap::new(),
+        reaping: false,
+    })
+});
+
+/// Connection settings shared by the requests of an HTTP client.
+pub struct Client {
+    id: u64,
+    // Used instead of the proxy environment variables
+    proxy: Option<Proxy>,
+    tls: tls::TlsConfig,
+}
+
+impl Client {
+    pub fn new(proxy: Option<Proxy>, tls: &TlsOptions) -> Result<Client, String> {
+        // The default client has id 0
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        Ok(Client {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            proxy,
+            tls: tls::TlsConfig::new(tls)?,
+        })
+    }
+
+    /// Close the idle connections of the client.
+    pub fn close(&self) {
+        let mut pool = POOL.lock().unwrap();
+        pool.idle.retain(|(client, _), _| *client != self.id);
+    }
+}
+
+/// The client used when fetch is not given one. Fails to build when a certificate
+/// file from the environment cannot be loaded.
+pub static DEFAULT_CLIENT: Lazy<Result<Arc<Client>, String>> = Lazy::new(|| {
+    let client = Client {
+        id: 0,
+        proxy: None,
+        tls: tls::TlsConfig::new(&TlsOptions {
+            alpn_protocols: http_alpn_protocols(true),
+            ..TlsOptions::default()
+        })?,
+    };
+    Ok(Arc::new(client))
+});
+
+// Connections are pooled by client and by origin ("scheme://host:port"), or by
+// proxy for plain HTTP requests sent through one
+type PoolKey = (u64, String);
+
+struct Pool {
+    idle: HashMap<PoolKey, Vec<Idle>>,
+    // Whether the task closing expired connections is running
+    reaping: bool,
+}
+
+struct Idle {
+    sender: Sender,
+    since: Instant,
+}
+
+enum Sender {
+    Http1(http1::SendRequest<RequestBody>),
+    Http2(http2::SendRequest<RequestBody>),
+}
+
+impl Sender {
+    fn is_closed(&self) -> bool {
+        match self {
+            Sender::Http1(sender) => sender.is_closed(),
+            Sender::Http2(sender) => sender.is_closed(),
+        }
+    }
+
+    /// Send `req` to `uri`. HTTP/1.1 requests keep their URI, HTTP/2 ones are sent
+    /// in absolute form since `:scheme` and `:authority` come from the URI.
+    async fn send(
+        &mut self,
+        mut req: Request,
+        uri: &hyper::Uri,
+    ) -> Result<Response, TrySendError<Request>> {
+        match self {
+            Sender::Http1(sender) => sender.try_send_request(req).await,
+            Sender::Http2(sender) => {
+                if req.uri().scheme().is_none() {
+                    *req.uri_mut() = absolute_uri(uri, &mut req);
+                }
+                sender.try_send_request(req).await
+            }
+        }
+    }
+}
+
+impl Pool {
+    /// Take a connection to `origin` that can send a request right away.
+    fn checkout(&mut self, key: &PoolKey) -> Option<Sender> {
+        let connections = self.idle.get_mut(key)?;
+        connections.retain(|idle| !idle.sender.is_closed() && idle.since.elapsed() < IDLE_TIMEOUT);
+
+        let index = connections.iter().rposition(|idle| match &idle.sender {
+            Sender::Http1(sender) => sender.is_ready(),
+            Sender::Http2(sender) => sender.is_ready(),
+        });
+        let sender = match index {
+            Some(index) => match &mut connections[index] {
+                Idle {
+                    sender: Sender::Http2(sender),
+                    since,
+                } => {
+                    *since = Instant::now();
+                    Some(Sender::Http2(sender.clone()))
+                }
+                Idle {
+                    sender: Sender::Http1(_),
+                    ..
+                } => Some(connections.remove(index).sender),
+            },
+            None => None,
+        };
+
+        if connections.is_empty() {
+            self.idle.remove(key);
+        }
+        sender
+    }
+
+    fn insert(&mut self, key: PoolKey, sender: Sender) {
+        let connections = self.idle.entry(key).or_default();
+        let http1 = connections
+            .iter()
+            .filter(|idle| matches!(idle.sender, Sender::Http1(_)))
+            .count();
+        if matches!(sender, Sender::Http1(_)) && http1 >= MAX_IDLE_PER_ORIGIN {
+            return;
+        }
+        connections.push(Idle {
+            sender,
+            since: Instant::now(),
+        });
+
+        if !self.reaping {
+            self.reaping = true;
+            smol::spawn(reap()).detach();
+        }
+    }
+}
+
+/// Close idle connections as they expire, until the pool is empty.
+async fn reap() {
+    loop {
+        smol::Timer::after(IDLE_TIMEOUT / 3).await;
+
+        // Dropping a sender lets its connection task shut the connection down
+        let mut pool = POOL.lock().unwrap();
+        pool.idle.retain(|_, connections| {
+            connections
+                .retain(|idle| !idle.sender.is_closed() && idle.since.elapsed() < IDLE_TIMEOUT);
+            !connections.is_empty()
+        });
+        if pool.idle.is_empty() {
+            pool.reaping = false;
+            return;
+        }
+    }
+}
+
+/// Send a request to the origin of `uri`, reusing a pooled connection of `client`
+/// when one is available and opening a new one otherwise.
+pub async fn send_request(
+    client: &Client,
+    uri: &hyper::Uri,
+    mut req: Request,
+) -> Result<Response, String> {
+    let scheme = uri.scheme_str().unwrap_or("http");
+    let host = uri.host().ok_or("Missing host")?;
+    let port = match (uri.port_u16(), scheme) {
+        (Some(port), _) => port,
+        (None, "https") => 443,
+        (None, "http") => 80,
+        (None, scheme) => return Err(format!("Unsupported scheme: {}", scheme)),
+    };
+
+    let proxy = match &client.proxy {
+        Some(proxy) => Some(proxy),
+        None => proxy::from_env(scheme, host, port),
+    };
+    let key = match proxy {
+        // Plain HTTP requests go to the proxy in absolute form, so its connections
+        // serve every origin
+        Some(proxy) if scheme == "http" => {
+            *req.uri_mut() = uri.clone();
+            if let Some(authorization) = proxy.authorization() {
+                let value = hyper::header::HeaderValue::from_str(authorization)
+                    .map_err(|_| "Invalid proxy credentials")?;
+                req.headers_mut()
+                    .insert(hyper::header::PROXY_AUTHORIZATION, value);
+            }
+            (client.id, proxy.origin())
+        }
+        _ => (client.id, format!("{}://{}:{}", scheme, host, port)),
+    };
+
+    // A pooled connection may have been closed by the server in the meantime. The
+    // request is handed back when it was not sent, so it can go out on another one.
+    loop {
+        let sender = POOL.lock().unwrap().checkout(&key);
+        let Some(mut sender) = sender else { break };
+        match sender.send(req, uri).await {
+            Ok(response) => {
+                release(key, sender);
+                return Ok(response);
+            }
+            Err(mut error) => match error.take_message() {
+                Some(message) => req = message,
+                None => return Err(format!("Request failed: {}", error.into_error())),
+            },
+        }
+    }
+
+    let mut sender = connect(&client.tls, scheme, host, port, proxy).await?;
+    if let Sender::Http2(sender) = &sender {
+        POOL.lock()
+            .unwrap()
+            .insert(key.clone(), Sender::Http2(sender.clone()));
+    }
+    let response = sender
+        .send(req, uri)
+        .await
+        .map_err(|e| format!("Request failed: {}", e.into_error()))?;
+    release(key, sender);
+    Ok(response)
+}
+
+/// The URI of `req` in absolute form, with the scheme of `uri`. `:authority`
+/// replaces the Host header, so the authority is taken from it when it is set.
+fn absolute_uri(uri: &hyper::Uri, req: &mut Request) -> hyper::Uri {
+    let authority = req
+        .headers_mut()
+        .remove(hyper::header::HOST)
+        .and_then(|host| hyper::http::uri::Authority::try_from(host.as_bytes()).ok())
+        .or_else(|| uri.authority().cloned());
+
+    let mut parts = req.uri().clone().into_parts();
+    parts.scheme = uri.scheme().cloned();
+    parts.authority = authority;
+    hyper::Uri::from_parts(parts).unwrap_or_else(|_| uri.clone())
+}
+
+/// Return an HTTP/1.1 connection to the pool once its response has been read.
+/// HTTP/2 connections never leave the pool.
+fn release(key: PoolKey, sender: Sender) {
+    let Sender::Http1(mut sender) = sender else {
+        return;
+    };
+    smol::spawn(async move {
+        // Fails when the connection closed, e.g. because the body was dropped unread
+        if sender.ready().await.is_ok() {
+            POOL.lock().unwrap().insert(key, Sender::Http1(sender));
+        }
+    })
+    .detach();
+}
+
+async fn connect(
+    tls: &tls::TlsConfig,
+    scheme: &str,
+    host: &str,
+    port: u16,
+    proxy: Option<&Proxy>,
+) -> Result<Sender, String> {
+    // IPv6 addresses are in brackets in URLs, but not in server names
+    let server_name = host.trim_start_matches('[').trim_end_matches(']');
+
+    let io_stream = match (scheme, proxy) {
+        ("https", Some(proxy)) => {
+            let mut stream = proxy.connect().await?;
+            proxy.tunnel(&mut stream, host, port).await?;
+            tls::connect_tls(tls, stream, server_name).await?
+        }
+        ("https", None) => tls::create_tls_stream(tls, server_name, port).await?,
+        (_, Some(proxy)) => tls::IoStream::Plain(proxy.connect().await?),
+        (_, None) => tls::create_plain_stream(host, port).await?,
+    };
+
+    // HTTP/2 is only used when the server picked it through ALPN
+    if io_stream.alpn_protocol().as_deref() == Some(b"h2") {
+        let (sender, conn) = http2::handshake(Executor, FuturesIo::new(io_stream))
+            .await
+            .map_err(|e| format!("Handshake failed: {}", e))?;
+        smol::spawn(async move {
+            if let Err(e) = conn.await {
+                eprintln!("Connection error: {:?}", e);
+            }
+        })
+        .detach();
+        return Ok(Sender::Http2(sender));
+    }
+
+    let (sender, conn) = http1::handshake(FuturesIo::new(io_stream))
+        .await
+        .map_err(|e| format!("Handshake failed: {}", e))?;
+
+    // The connection task ends once the connection is closed, or when it is idle
+    // and its sender was dropped
+    smol::spawn(async move {
+        if let Err(e) = conn.await {
+            eprintln!("Connection error: {:?}", e);
+        }
+    })
+    .detach();
+    Ok(Sender::Http1(sender))
+}
+
+/// Runs the background tasks of HTTP/2 connections on the smol executor.
+#[derive(Clone, Copy)]
+pub struct Executor;
+
+impl<F> hyper::rt::Executor<F> for Executor
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    fn execute(&self, future: F) {
+        smol::spawn(future).detach();
+    }
+}
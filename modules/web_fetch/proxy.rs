@@ -0,0 +1,195 @@
+//! HTTP proxies, configured per client or through the http_proxy, HTTPS_PROXY and
+//! NO_PROXY environment variables.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use once_cell::sync::Lazy;
+use smol::io::{AsyncReadExt, AsyncWriteExt};
+use smol::net::TcpStream;
+
+pub struct Proxy {
+    host: String,
+    port: u16,
+    // Value of the Proxy-Authorization header
+    authorization: Option<String>,
+}
+
+impl Proxy {
+    /// Parse a proxy URL. The scheme may be left out, as is common in environment
+    /// variables; credentials in the URL are sent with basic auth.
+    pub fn parse(url: &str) -> Result<Proxy, String> {
+        let url = if url.contains("://") {
+            url::Url::parse(url)
+        } else {
+            url::Url::parse(&format!("http://{}", url))
+        }
+        .map_err(|e| format!("Invalid proxy URL: {}", e))?;
+
+        if url.scheme() != "http" {
+            return Err(format!("Unsupported proxy scheme: {}", url.scheme()));
+        }
+        let host = url.host_str().ok_or("Missing host in proxy URL")?;
+
+        let mut proxy = Proxy {
+            host: host.to_string(),
+            port: url.port().unwrap_or(80),
+            authorization: None,
+        };
+        if !url.username().is_empty() {
+            let decode = |part| {
+                percent_encoding::percent_decode_str(part)
+                    .decode_utf8_lossy()
+                    .into_owned()
+            };
+            let password = url.password().unwrap_or_default();
+            proxy.set_basic_auth(&decode(url.username()), &decode(password));
+        }
+        Ok(proxy)
+    }
+
+    pub fn set_basic_auth(&mut self, username: &str, password: &str) {
+        let credentials = STANDARD.encode(format!("{}:{}", username, password));
+        self.authorization = Some(format!("Basic {}", credentials));
+    }
+
+    pub fn authorization(&self) -> Option<&str> {
+        self.authorization.as_deref()
+    }
+
+    /// Identifies the proxy in the connection pool.
+    pub fn origin(&self) -> String {
+        format!("http://{}:{}", self.host, self.port)
+    }
+
+    pub async fn connect(&self) -> Result<TcpStream, String> {
+        deno_net::tcp::connect(&self.host, self.port)
+            .await
+            .map_err(|e| format!("Proxy connection failed: {}", e))
+    }
+
+    /// Ask the proxy for a tunnel to `host:port` with CONNECT.
+    pub async fn tunnel(
+        &self,
+        stream: &mut TcpStream,
+        host: &str,
+        port: u16,
+    ) -> Result<(), String> {
+        let mut request = format!(
+            "CONNECT {host}:{port} HTTP/1.1\r\nHost: {host}:{port}\r\nUser-Agent: mdeno/0.1\r\n"
+        );
+        if let Some(authorization) = &self.authorization {
+            request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+        }
+        request.push_str("\r\n");
+        stream
+            .write_all(request.as_bytes())
+            .await
+            .map_err(|e| format!("Proxy connection failed: {}", e))?;
+
+        // Read the response head byte by byte, the tunnel starts right after it
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            if head.len() > MAX_RESPONSE_HEAD {
+                return Err("Proxy response head is too large".to_string());
+            }
+            let mut byte = [0];
+            let read = stream
+                .read(&mut byte)
+                .await
+                .map_err(|e| format!("Proxy connection failed: {}", e))?;
+            if read == 0 {
+                return Err("Proxy closed the connection".to_string());
+            }
+            head.push(byte[0]);
+        }
+
+        let head = String::from_utf8_lossy(&head);
+        let status_line = head.lines().next().unwrap_or_default();
+        match status_line.split_whitespace().nth(1) {
+            Some(status) if status.starts_with('2') => Ok(()),
+            _ => Err(format!("Proxy refused the tunnel: {}", status_line)),
+        }
+    }
+}
+
+// Longest CONNECT response head accepted from a proxy
+const MAX_RESPONSE_HEAD: usize = 64 * 1024;
+
+struct EnvProxies {
+    http: Option<Proxy>,
+    https: Option<Proxy>,
+    no_proxy: Vec<String>,
+}
+
+static ENV_PROXIES: Lazy<EnvProxies> = Lazy::new(|| {
+    let proxy = |url: Option<String>| url.and_then(|url| Proxy::parse(&url).ok());
+    EnvProxies {
+        // Like curl, only in lower case: CGI servers set HTTP_PROXY from the Proxy
+        // header of the request they handle (httpoxy)
+        http: proxy(
+            std::env::var("http_proxy")
+                .ok()
+                .filter(|value| !value.is_empty()),
+        ),
+        https: proxy(env_var("HTTPS_PROXY")),
+        no_proxy: env_var("NO_PROXY")
+            .unwrap_or_default()
+            .split(',')
+            .map(|entry| entry.trim().to_ascii_lowercase())
+            .filter(|entry| !entry.is_empty())
+            .collect(),
+    }
+});
+
+// Variables are looked up in upper case, then in lower case
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_ascii_lowercase()))
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
+/// The proxy the environment configures for a request, if any.
+pub fn from_env(scheme: &str, host: &str, port: u16) -> Option<&'static Proxy> {
+    let proxies = &*ENV_PROXIES;
+    let proxy = match scheme {
+        "https" => proxies.https.as_ref(),
+        _ => proxies.http.as_ref(),
+    };
+    proxy.filter(|_| !bypass(&proxies.no_proxy, host, port))
+}
+
+/// Whether NO_PROXY excludes `host:port`. Entries are "*", host names that also
+/// match their subdomains, or IP addresses, each optionally with a port.
+fn bypass(no_proxy: &[String], host: &str, port: u16) -> bool {
+    let host = host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_ascii_lowercase();
+
+    no_proxy.iter().any(|entry| {
+        if entry == "*" {
+            return true;
+        }
+
+        // A bare IPv6 address has colons but no port
+        let (name, entry_port) = match entry.rsplit_once(':') {
+            Some((name, entry_port))
+                if !entry.ends_with(']') && (entry.starts_with('[') || !name.contains(':')) =>
+            {
+                (name, entry_port.parse::<u16>().ok())
+            }
+            _ => (entry.as_str(), None),
+        };
+        if entry_port.is_some_and(|entry_port| entry_port != port) {
+            return false;
+        }
+
+        let name = name
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .trim_start_matches('*')
+            .trim_start_matches('.');
+        host == name || host.ends_with(&format!(".{}", name))
+    })
+}
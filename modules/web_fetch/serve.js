@@ -0,0 +1,191 @@
+// https://docs.deno.com/api/deno/~/Deno.serve
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const NULL_BODY_METHODS = ["GET", "HEAD"];
+
+function defaultOnError(error) {
+  console.error("Error in serve handler:", error);
+  return new Response("Internal Server Error", { status: 500 });
+}
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+// https://docs.deno.com/api/deno/~/Deno.HttpServer
+class HttpServer {
+  #id;
+  #addr;
+  #finished;
+
+  constructor(key = null, id = undefined, addr = undefined, finished = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+    this.#id = id;
+    this.#addr = addr;
+    this.#finished = finished;
+  }
+
+  get addr() {
+    return this.#addr;
+  }
+
+  // Resolves once the server has shut down
+  get finished() {
+    return this.#finished;
+  }
+
+  // Stop accepting connections, and close the open ones once the requests in
+  // flight are answered
+  shutdown() {
+    __internal.serve.shutdown(this.#id);
+    return this.#finished;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "HttpServer";
+  }
+}
+
+// Write a streamed response body, cancelling the stream when the client goes
+// away
+async function writeBody(upload, stream) {
+  const reader = stream.getReader();
+  try {
+    while (true) {
+      const { done, value } = await reader.read();
+      if (done) break;
+      if (!(value instanceof Uint8Array)) {
+        throw new TypeError("Response body stream chunks must be Uint8Array");
+      }
+      while (!__internal.fetch.write(upload, value)) {
+        await Promise.resolve();
+      }
+      if (!__internal.fetch.writing(upload)) {
+        await reader.cancel();
+        return;
+      }
+    }
+    __internal.fetch.close(upload, null);
+  } catch (error) {
+    __internal.fetch.close(upload, String(error));
+    reader.cancel(error).catch(() => {});
+  }
+}
+
+async function respond(meta, handler, onError) {
+  const { id, method, url, headers, remoteAddr } = meta;
+
+  let response;
+  try {
+    const request = new Request(url, {
+      method,
+      headers,
+      body: NULL_BODY_METHODS.includes(method)
+        ? null
+        : __internal.fetch.bodyStream(id),
+    });
+    response = await handler(request, {
+      remoteAddr: { transport: "tcp", ...remoteAddr },
+    });
+    if (!(response instanceof Response)) {
+      throw new TypeError(
+        "Return value from serve handler must be a response or a promise resolving to a response",
+      );
+    }
+  } catch (error) {
+    try {
+      response = await onError(error);
+      if (!(response instanceof Response)) {
+        throw new TypeError(
+          "Return value from onError handler must be a response or a promise resolving to a response",
+        );
+      }
+    } catch (error) {
+      response = defaultOnError(error);
+    }
+  }
+
+  const { status, headers: responseHeaders, body } = __internal.fetch
+    .responseState(response);
+  const source = body?.bytes ?? null;
+  const stream = body !== null && source === null ? body.stream : null;
+
+  const upload = __internal.serve.respond(
+    id,
+    status,
+    JSON.stringify([...responseHeaders]),
+    source,
+    stream !== null,
+  );
+  if (upload !== null && upload !== undefined) {
+    await writeBody(upload, stream);
+  } else if (stream !== null) {
+    // The client went away before the response was ready
+    stream.cancel();
+  }
+
+  // The request body can no longer be read once the response is sent
+  __internal.fetch.cancel(id);
+}
+
+async function acceptRequests(id, handler, onError) {
+  while (true) {
+    // Waits briefly for a request when there is nothing to answer
+    const request = __internal.serve.next(id);
+    if (request === null) return;
+    if (request !== undefined) {
+      // A failure to answer one request must not take down the server
+      respond(JSON.parse(request), handler, onError).catch((error) => {
+        console.error("Error in serve handler:", error);
+      });
+    }
+
+    // Yield to event loop
+    await Promise.resolve();
+  }
+}
+
+// Takes a handler, options and a handler, or options with a handler
+function serve(options, handler = undefined) {
+  if (typeof options === "function") {
+    handler = options;
+    options = {};
+  }
+  options ??= {};
+  handler ??= options.handler;
+  if (typeof handler !== "function") {
+    throw new TypeError("A handler function must be provided.");
+  }
+
+  const { id, hostname, port } = JSON.parse(__internal.serve.listen(
+    JSON.stringify({
+      hostname: options.hostname,
+      port: options.port,
+      cert: options.cert,
+      key: options.key,
+    }),
+  ));
+  const addr = { transport: "tcp", hostname, port };
+
+  if (options.onListen) {
+    options.onListen(addr);
+  } else {
+    const scheme = options.cert === undefined ? "http" : "https";
+    const host = hostname.includes(":") ? `[${hostname}]` : hostname;
+    console.log(`Listening on ${scheme}://${host}:${port}/`);
+  }
+
+  const onError = options.onError ?? defaultOnError;
+  const finished = acceptRequests(id, handler, onError);
+  const server = new HttpServer(illegalConstructorKey, id, addr, finished);
+
+  const signal = options.signal;
+  if (signal?.aborted) {
+    server.shutdown();
+  } else {
+    signal?.addEventListener("abort", () => server.shutdown(), { once: true });
+  }
+  return server;
+}
+
+Object.assign(globalThis.__mdeno__.fetch, { serve });
@@ -0,0 +1,343 @@
+//! HTTP server behind Deno.serve. Connections are served by hyper on the smol
+//! executor, and requests are queued for JS, which answers them with
+//! `serve.respond`. Request bodies are read like fetch response bodies, and
+//! streamed responses are written like fetch request bodies.
+
+use crate::pool::Executor;
+use crate::{BODY_CHUNK_BUFFER, FETCH_STATE, RequestBody, ResponseBody, body_bytes, next_id, tls};
+use futures_util::TryStreamExt;
+use futures_util::future::{Either, select};
+use http_body_util::{BodyDataStream, BodyExt, Full, StreamBody};
+use hyper::body::Incoming;
+use hyper::server::conn::{http1, http2};
+use once_cell::sync::Lazy;
+use rquickjs::{Ctx, Value};
+use smol::net::{TcpListener, TcpStream};
+use smol_hyper::rt::FuturesIo;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use utils::{add_internal_function, poll_receiver};
+
+struct Server {
+    // Requests waiting for JS, as JSON
+    requests: Arc<Mutex<mpsc::Receiver<String>>>,
+    // Dropped to stop accepting and to close connections once their requests
+    // are answered
+    shutdown: Option<smol::channel::Sender<()>>,
+    connections: Arc<AtomicUsize>,
+}
+
+/// State shared by the connections of a server.
+struct Listener {
+    scheme: &'static str,
+    local_addr: SocketAddr,
+    acceptor: Option<tls::TlsAcceptor>,
+    queue: mpsc::Sender<String>,
+    shutdown: smol::channel::Receiver<()>,
+    connections: Arc<AtomicUsize>,
+}
+
+/// A request handed to JS, answered by sending its response.
+type Responder = smol::channel::Sender<hyper::Response<RequestBody>>;
+
+static SERVERS: Lazy<Mutex<HashMap<u64, Server>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// Keyed by request id
+static RESPONDERS: Lazy<Mutex<HashMap<u64, Responder>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].serve = {};")?;
+
+    add_internal_function!(ctx, "serve.listen", serve_listen);
+
+    add_internal_function!(ctx, "serve.next", serve_next);
+
+    add_internal_function!(ctx, "serve.respond", serve_respond);
+
+    add_internal_function!(ctx, "serve.shutdown", |id: u64| { serve_shutdown(id) });
+
+    Ok(())
+}
+
+/// Start listening with JSON options `{hostname, port, cert, key}`. Returns JSON
+/// with the server id and the address it listens on.
+fn serve_listen(ctx: Ctx<'_>, options: String) -> rquickjs::Result<String> {
+    listen(&options).map_err(|e| rquickjs::Exception::throw_type(&ctx, &e))
+}
+
+fn listen(options: &str) -> Result<String, String> {
+    let options: serde_json::Value = serde_json::from_str(options).unwrap_or_default();
+    let string = |name| options.get(name).and_then(|value| value.as_str());
+    let hostname = string("hostname").unwrap_or("0.0.0.0");
+    let port = options.get("port").and_then(|port| port.as_u64());
+    let port = u16::try_from(port.unwrap_or(8000)).map_err(|_| "Invalid port")?;
+
+    let acceptor = match (string("cert"), string("key")) {
+        (Some(cert), Some(key)) => {
+            let protocols = crate::http_alpn_protocols(true);
+            Some(tls::TlsAcceptor::new(cert, key, &protocols)?)
+        }
+        (None, None) => None,
+        _ => return Err("Both cert and key are required to serve HTTPS".to_string()),
+    };
+
+    let listener = std::net::TcpListener::bind((hostname, port))
+        .map_err(|e| format!("Failed to listen on {}:{}: {}", hostname, port, e))?;
+    let local_addr = listener.local_addr().map_err(|e| e.to_string())?;
+    let listener = TcpListener::try_from(listener).map_err(|e| e.to_string())?;
+
+    let id = next_id();
+    let (queue, requests) = mpsc::channel();
+    let (shutdown, shutdown_signal) = smol::channel::bounded(1);
+    let connections = Arc::new(AtomicUsize::new(0));
+    let shared = Arc::new(Listener {
+        scheme: if acceptor.is_some() { "https" } else { "http" },
+        local_addr,
+        acceptor,
+        queue,
+        shutdown: shutdown_signal,
+        connections: connections.clone(),
+    });
+    smol::spawn(accept(listener, shared)).detach();
+
+    SERVERS.lock().unwrap().insert(
+        id,
+        Server {
+            requests: Arc::new(Mutex::new(requests)),
+            shutdown: Some(shutdown),
+            connections,
+        },
+    );
+
+    let addr = serde_json::json!({
+        "id": id,
+        "hostname": local_addr.ip().to_string(),
+        "port": local_addr.port(),
+    });
+    Ok(addr.to_string())
+}
+
+async fn accept(listener: TcpListener, shared: Arc<Listener>) {
+    loop {
+        // The listener is closed once the server shuts down
+        let accepted = smol::future::or(async { Some(listener.accept().await) }, async {
+            let _ = shared.shutdown.recv().await;
+            None
+        })
+        .await;
+        let (stream, remote_addr) = match accepted {
+            Some(Ok(accepted)) => accepted,
+            Some(Err(e)) => {
+                eprintln!("Accept error: {}", e);
+                continue;
+            }
+            None => return,
+        };
+
+        shared.connections.fetch_add(1, Ordering::SeqCst);
+        let connection = shared.clone();
+        smol::spawn(async move {
+            serve_connection(&connection, stream, remote_addr).await;
+            connection.connections.fetch_sub(1, Ordering::SeqCst);
+        })
+        .detach();
+    }
+}
+
+/// Serve the requests of a connection until it closes, or until the server shuts
+/// down and the requests in flight are answered.
+async fn serve_connection(shared: &Arc<Listener>, stream: TcpStream, remote_addr: SocketAddr) {
+    let io = match &shared.acceptor {
+        Some(acceptor) => match tls::accept_tls(acceptor, stream).await {
+            Ok(io) => io,
+            // Failed handshakes are the client's problem
+            Err(_) => return,
+        },
+        None => tls::IoStream::Plain(stream),
+    };
+
+    let handler = shared.clone();
+    let service = hyper::service::service_fn(move |req| {
+        let handler = handler.clone();
+        async move { Ok::<_, Infallible>(handle(&handler, remote_addr, req).await) }
+    });
+    let shutdown = pin!(shared.shutdown.recv());
+
+    // Errors are client disconnects and protocol violations
+    if io.alpn_protocol().as_deref() == Some(b"h2") {
+        let conn = http2::Builder::new(Executor).serve_connection(FuturesIo::new(io), service);
+        let mut conn = pin!(conn);
+        if let Either::Right(_) = select(conn.as_mut(), shutdown).await {
+            conn.as_mut().graceful_shutdown();
+            let _ = conn.await;
+        }
+    } else {
+        let conn = http1::Builder::new().serve_connection(FuturesIo::new(io), service);
+        let mut conn = pin!(conn);
+        if let Either::Right(_) = select(conn.as_mut(), shutdown).await {
+            conn.as_mut().graceful_shutdown();
+            let _ = conn.await;
+        }
+    }
+}
+
+/// Queue a request for JS and wait for its response.
+async fn handle(
+    shared: &Listener,
+    remote_addr: SocketAddr,
+    req: hyper::Request<Incoming>,
+) -> hyper::Response<RequestBody> {
+    let (parts, body) = req.into_parts();
+
+    // HTTP/2 requests carry the authority in the URI, HTTP/1.1 ones in Host
+    let authority = match parts.uri.authority() {
+        Some(authority) => authority.to_string(),
+        None => parts
+            .headers
+            .get(hyper::header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .map(str::to_string)
+            .unwrap_or_else(|| shared.local_addr.to_string()),
+    };
+    let path = parts.uri.path_and_query().map_or("/", |path| path.as_str());
+
+    // The body is read by JS like a fetch response body
+    let id = next_id();
+    let body: ResponseBody = Box::pin(BodyDataStream::new(body).map_err(std::io::Error::other));
+    FETCH_STATE
+        .lock()
+        .unwrap()
+        .responses
+        .insert(id, Arc::new(smol::lock::Mutex::new(body)));
+
+    let (sender, receiver) = smol::channel::bounded(1);
+    RESPONDERS.lock().unwrap().insert(id, sender);
+    // Forgets the request when the client goes away before it is answered
+    let _pending = Pending(id);
+
+    let meta = serde_json::json!({
+        "id": id,
+        "method": parts.method.as_str(),
+        "url": format!("{}://{}{}", shared.scheme, authority, path),
+        "headers": crate::header_pairs(&parts.headers),
+        "remoteAddr": {
+            "hostname": remote_addr.ip().to_string(),
+            "port": remote_addr.port(),
+        },
+    });
+    if shared.queue.send(meta.to_string()).is_err() {
+        return empty_response(hyper::StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    match receiver.recv().await {
+        Ok(response) => response,
+        Err(_) => empty_response(hyper::StatusCode::SERVICE_UNAVAILABLE),
+    }
+}
+
+struct Pending(u64);
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        RESPONDERS.lock().unwrap().remove(&self.0);
+    }
+}
+
+fn empty_response(status: hyper::StatusCode) -> hyper::Response<RequestBody> {
+    let body = Full::new(bytes::Bytes::new())
+        .map_err(|never| match never {})
+        .boxed();
+    let mut response = hyper::Response::new(body);
+    *response.status_mut() = status;
+    response
+}
+
+/// Take the next request of server `id`. Returns JSON with the request, undefined
+/// when there is none yet, or null once the server has shut down.
+fn serve_next<'js>(ctx: Ctx<'js>, id: u64) -> rquickjs::Result<Value<'js>> {
+    let Some(requests) = SERVERS
+        .lock()
+        .unwrap()
+        .get(&id)
+        .map(|server| server.requests.clone())
+    else {
+        return Ok(Value::new_null(ctx));
+    };
+
+    // Wait without holding SERVERS, which other servers and shutdown need
+    let request = poll_receiver(&ctx, &requests.lock().unwrap());
+    if let Some(request) = request {
+        return rquickjs::String::from_str(ctx, &request).map(Value::from);
+    }
+
+    let mut servers = SERVERS.lock().unwrap();
+    match servers.get(&id) {
+        Some(server)
+            if server.shutdown.is_some() || server.connections.load(Ordering::SeqCst) > 0 =>
+        {
+            Ok(Value::new_undefined(ctx))
+        }
+        _ => {
+            servers.remove(&id);
+            Ok(Value::new_null(ctx))
+        }
+    }
+}
+
+/// Answer request `id`. `body` is a string or `Uint8Array`, or null when there is
+/// no body or when `stream` is set. Returns the id to write a streamed body to
+/// with `fetch.write`, or null when the body was sent at once.
+fn serve_respond(
+    id: u64,
+    status: u16,
+    headers: String,
+    body: Option<Value<'_>>,
+    stream: bool,
+) -> Option<u64> {
+    let responder = RESPONDERS.lock().unwrap().remove(&id)?;
+
+    let headers: Vec<(String, String)> = serde_json::from_str(&headers).unwrap_or_default();
+    let mut builder = hyper::Response::builder().status(status);
+    for (name, value) in &headers {
+        // hyper frames the body itself
+        if matches!(
+            name.to_ascii_lowercase().as_str(),
+            "connection" | "keep-alive" | "transfer-encoding"
+        ) || (!stream && name.eq_ignore_ascii_case("content-length"))
+        {
+            continue;
+        }
+        builder = builder.header(name, value);
+    }
+
+    let (body, upload) = if stream {
+        let (sender, receiver) = smol::channel::bounded(BODY_CHUNK_BUFFER);
+        let upload = next_id();
+        FETCH_STATE.lock().unwrap().uploads.insert(upload, sender);
+        (RequestBody::new(StreamBody::new(receiver)), Some(upload))
+    } else {
+        let bytes = body.map(|value| body_bytes(&value)).unwrap_or_default();
+        let body = Full::new(bytes).map_err(|never| match never {}).boxed();
+        (body, None)
+    };
+
+    let response = builder
+        .body(body)
+        .unwrap_or_else(|_| empty_response(hyper::StatusCode::INTERNAL_SERVER_ERROR));
+    let _ = responder.try_send(response);
+    upload
+}
+
+/// Stop accepting connections. Requests in flight are still answered, then the
+/// connections are closed.
+fn serve_shutdown(id: u64) {
+    let mut servers = SERVERS.lock().unwrap();
+    if let Some(server) = servers.get_mut(&id) {
+        server.shutdown = None;
+    }
+}
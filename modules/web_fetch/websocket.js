@@ -0,0 +1,278 @@
+// https://websockets.spec.whatwg.org/
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const CONNECTING = 0;
+const OPEN = 1;
+const CLOSING = 2;
+const CLOSED = 3;
+
+// Characters of an HTTP token, which subprotocol names must be
+const TOKEN = /^[!#$%&'*+\-.^_`|~0-9A-Za-z]+$/;
+
+// https://websockets.spec.whatwg.org/#the-closeevent-interface
+class CloseEvent extends Event {
+  #wasClean;
+  #code;
+  #reason;
+
+  constructor(type, eventInitDict = {}) {
+    super(type, eventInitDict);
+    this.#wasClean = Boolean(eventInitDict?.wasClean ?? false);
+    this.#code = Number(eventInitDict?.code ?? 0);
+    this.#reason = String(eventInitDict?.reason ?? "");
+  }
+
+  get wasClean() {
+    return this.#wasClean;
+  }
+
+  get code() {
+    return this.#code;
+  }
+
+  get reason() {
+    return this.#reason;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "CloseEvent";
+  }
+}
+
+function syntaxError(message) {
+  return new DOMException(
+    `Failed to construct 'WebSocket': ${message}`,
+    "SyntaxError",
+  );
+}
+
+function parseURL(url) {
+  let parsed;
+  try {
+    parsed = new URL(url);
+  } catch {
+    throw syntaxError(`The URL '${url}' is invalid.`);
+  }
+  if (parsed.protocol === "http:") parsed.protocol = "ws:";
+  if (parsed.protocol === "https:") parsed.protocol = "wss:";
+  if (parsed.protocol !== "ws:" && parsed.protocol !== "wss:") {
+    throw syntaxError(
+      `The URL's scheme must be either 'http', 'https', 'ws', or 'wss'. '${parsed.protocol}' is not allowed.`,
+    );
+  }
+  if (parsed.hash !== "" || parsed.href.endsWith("#")) {
+    throw syntaxError(
+      `The URL contains a fragment identifier ('${parsed.hash}'). Fragment identifiers are not allowed in WebSocket URLs.`,
+    );
+  }
+  return parsed;
+}
+
+function parseProtocols(protocols) {
+  if (protocols === undefined) return [];
+  protocols = typeof protocols === "string"
+    ? [protocols]
+    : Array.from(protocols, String);
+  for (const [index, protocol] of protocols.entries()) {
+    if (!TOKEN.test(protocol)) {
+      throw syntaxError(`The subprotocol '${protocol}' is invalid.`);
+    }
+    if (protocols.indexOf(protocol) !== index) {
+      throw syntaxError(`The subprotocol '${protocol}' is duplicated.`);
+    }
+  }
+  return protocols;
+}
+
+// Bytes of a binary message
+function messageBytes(data) {
+  if (data instanceof Blob) {
+    return __internal.fetch.blobBytes(data);
+  }
+  if (data instanceof ArrayBuffer) {
+    return new Uint8Array(data);
+  }
+  if (ArrayBuffer.isView(data)) {
+    return new Uint8Array(data.buffer, data.byteOffset, data.byteLength);
+  }
+  return null;
+}
+
+class WebSocket extends EventTarget {
+  #id;
+  #url;
+  #readyState = CONNECTING;
+  #protocol = "";
+  #extensions = "";
+  #binaryType = "blob";
+
+  constructor(url, protocols = undefined) {
+    if (arguments.length < 1) {
+      throw new TypeError(
+        "Failed to construct 'WebSocket': 1 argument required, but only 0 present.",
+      );
+    }
+    super();
+    const parsed = parseURL(String(url));
+    protocols = parseProtocols(protocols);
+
+    this.#url = parsed.href;
+    this.#id = __internal.websocket.connect(this.#url, protocols.join(", "));
+    this.#receive(parsed.origin);
+  }
+
+  get url() {
+    return this.#url;
+  }
+
+  get readyState() {
+    return this.#readyState;
+  }
+
+  get bufferedAmount() {
+    return __internal.websocket.bufferedAmount(this.#id);
+  }
+
+  get protocol() {
+    return this.#protocol;
+  }
+
+  get extensions() {
+    return this.#extensions;
+  }
+
+  get binaryType() {
+    return this.#binaryType;
+  }
+
+  set binaryType(value) {
+    value = String(value);
+    if (value === "blob" || value === "arraybuffer") {
+      this.#binaryType = value;
+    }
+  }
+
+  send(data) {
+    if (this.#readyState === CONNECTING) {
+      throw new DOMException(
+        "Failed to execute 'send' on 'WebSocket': Still in CONNECTING state.",
+        "InvalidStateError",
+      );
+    }
+    const message = messageBytes(data) ?? String(data);
+    if (this.#readyState === OPEN) {
+      __internal.websocket.send(this.#id, message);
+    }
+  }
+
+  close(code = undefined, reason = undefined) {
+    if (code !== undefined) {
+      code = Number(code);
+      if (code !== 1000 && !(code >= 3000 && code <= 4999)) {
+        throw new DOMException(
+          `Failed to execute 'close' on 'WebSocket': The close code must be either 1000, or between 3000 and 4999. ${code} is neither.`,
+          "InvalidAccessError",
+        );
+      }
+    }
+    if (reason !== undefined) {
+      reason = String(reason);
+      if (__internal.fetch.encode(reason).byteLength > 123) {
+        throw new DOMException(
+          "Failed to execute 'close' on 'WebSocket': The close reason must not be greater than 123 UTF-8 bytes.",
+          "SyntaxError",
+        );
+      }
+    }
+
+    if (this.#readyState === CONNECTING) {
+      // Fails the connection; the error and close events follow
+      this.#readyState = CLOSING;
+      __internal.websocket.cancel(this.#id);
+    } else if (this.#readyState === OPEN) {
+      this.#readyState = CLOSING;
+      code ??= reason === undefined ? null : 1000;
+      __internal.websocket.close(this.#id, code, reason ?? "");
+    }
+  }
+
+  // Dispatch the events of the connection until it closes
+  async #receive(origin) {
+    // Events are dispatched after the constructor returns and the caller had a
+    // chance to add listeners
+    await Promise.resolve();
+    while (true) {
+      const event = __internal.websocket.next(this.#id);
+      if (event === undefined) {
+        // Yield to event loop
+        await Promise.resolve();
+        continue;
+      }
+
+      // The connection was cancelled by close() before it opened
+      if (event === null) {
+        this.#readyState = CLOSED;
+        this.#dispatch(
+          new ErrorEvent("error", {
+            message: "WebSocket is closed before the connection is established.",
+          }),
+        );
+        this.#dispatch(new CloseEvent("close", { code: 1006 }));
+        return;
+      }
+
+      switch (event.type) {
+        case "open":
+          this.#readyState = OPEN;
+          this.#protocol = event.protocol;
+          this.#extensions = event.extensions;
+          this.#dispatch(new Event("open"));
+          break;
+        case "message": {
+          let data = event.data;
+          if (typeof data !== "string") {
+            data = this.#binaryType === "blob" ? new Blob([data]) : data.buffer;
+          }
+          this.#dispatch(new MessageEvent("message", { data, origin }));
+          break;
+        }
+        case "error":
+          this.#dispatch(new ErrorEvent("error", { message: event.message }));
+          break;
+        case "close":
+          this.#readyState = CLOSED;
+          this.#dispatch(
+            new CloseEvent("close", {
+              wasClean: event.wasClean,
+              code: event.code,
+              reason: event.reason,
+            }),
+          );
+          return;
+      }
+    }
+  }
+
+  #dispatch(event) {
+    __internal.events.dispatchTrusted(this, event);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "WebSocket";
+  }
+}
+
+for (
+  const [key, value] of Object.entries({ CONNECTING, OPEN, CLOSING, CLOSED })
+) {
+  const descriptor = { value, enumerable: true };
+  Object.defineProperty(WebSocket, key, descriptor);
+  Object.defineProperty(WebSocket.prototype, key, descriptor);
+}
+
+for (const name of ["open", "message", "error", "close"]) {
+  __internal.events.defineEventHandler(WebSocket.prototype, name);
+}
+
+globalThis.WebSocket = WebSocket;
+globalThis.CloseEvent = CloseEvent;
@@ -0,0 +1,315 @@
+//! WebSocket client connections. The opening handshake and the framing are done by
+//! async-tungstenite over the same streams as fetch, and events are queued for JS,
+//! which takes them with `websocket.next`. Pings are answered as they are read.
+
+use crate::{TlsOptions, next_id, tls};
+use async_tungstenite::WebSocketStream;
+use async_tungstenite::tungstenite::client::IntoClientRequest;
+use async_tungstenite::tungstenite::handshake::client::Response;
+use async_tungstenite::tungstenite::protocol::CloseFrame;
+use async_tungstenite::tungstenite::{Error as WsError, Message};
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use rquickjs::{Ctx, Object, TypedArray, Value};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use url::Url;
+use utils::{add_internal_function, poll_receiver};
+
+struct Socket {
+    events: mpsc::Receiver<Event>,
+    commands: smol::channel::Sender<Command>,
+    // Bytes of sent messages not yet written to the connection
+    buffered: Arc<AtomicUsize>,
+    // Dropping the task closes the connection
+    _task: smol::Task<()>,
+}
+
+enum Event {
+    Open {
+        protocol: String,
+        extensions: String,
+    },
+    Message(Message),
+    Error(String),
+    Close {
+        code: u16,
+        reason: String,
+        clean: bool,
+    },
+}
+
+enum Command {
+    Send(Message),
+    Close(Option<CloseFrame>),
+}
+
+static SOCKETS: Lazy<Mutex<HashMap<u64, Socket>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// The WebSocket handshake is an HTTP/1.1 request, so HTTP/2 is not offered
+static TLS_CONFIG: Lazy<Result<tls::TlsConfig, String>> = Lazy::new(|| {
+    tls::TlsConfig::new(&TlsOptions {
+        alpn_protocols: vec!["http/1.1".to_string()],
+        ..TlsOptions::default()
+    })
+});
+
+pub fn setup_internal(ctx: &Ctx) -> rquickjs::Result<()> {
+    ctx.eval::<(), _>("globalThis[Symbol.for('mdeno.internal')].websocket = {};")?;
+
+    add_internal_function!(
+        ctx,
+        "websocket.connect",
+        |url: String, protocols: String| { websocket_connect(url, protocols) }
+    );
+
+    add_internal_function!(ctx, "websocket.next", websocket_next);
+
+    add_internal_function!(ctx, "websocket.send", websocket_send);
+
+    add_internal_function!(ctx, "websocket.close", websocket_close);
+
+    add_internal_function!(ctx, "websocket.bufferedAmount", |id: u64| -> usize {
+        let sockets = SOCKETS.lock().unwrap();
+        sockets
+            .get(&id)
+            .map_or(0, |socket| socket.buffered.load(Ordering::SeqCst))
+    });
+
+    add_internal_function!(ctx, "websocket.cancel", |id: u64| {
+        SOCKETS.lock().unwrap().remove(&id);
+    });
+
+    Ok(())
+}
+
+/// Open a connection to `url`, a ws: or wss: URL, offering the comma-separated
+/// `protocols`. Returns the id of the socket.
+fn websocket_connect(url: String, protocols: String) -> u64 {
+    let id = next_id();
+    let (events, receiver) = mpsc::channel();
+    let (commands, queue) = smol::channel::unbounded();
+    let buffered = Arc::new(AtomicUsize::new(0));
+
+    let task = smol::spawn(run(url, protocols, events, queue, buffered.clone()));
+    SOCKETS.lock().unwrap().insert(
+        id,
+        Socket {
+            events: receiver,
+            commands,
+            buffered,
+            _task: task,
+        },
+    );
+    id
+}
+
+async fn run(
+    url: String,
+    protocols: String,
+    events: mpsc::Sender<Event>,
+    commands: smol::channel::Receiver<Command>,
+    buffered: Arc<AtomicUsize>,
+) {
+    let (stream, response) = match connect(&url, &protocols).await {
+        Ok(connected) => connected,
+        Err(e) => {
+            let _ = events.send(Event::Error(e));
+            let _ = events.send(Event::Close {
+                code: 1006,
+                reason: String::new(),
+                clean: false,
+            });
+            return;
+        }
+    };
+
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let _ = events.send(Event::Open {
+        protocol: header("sec-websocket-protocol"),
+        extensions: header("sec-websocket-extensions"),
+    });
+
+    let (mut sender, mut receiver) = stream.split();
+    let write = async {
+        while let Ok(command) = commands.recv().await {
+            match command {
+                Command::Send(message) => {
+                    let size = message.len();
+                    let result = sender.send(message).await;
+                    buffered.fetch_sub(size, Ordering::SeqCst);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                Command::Close(frame) => {
+                    let _ = sender.close(frame).await;
+                    break;
+                }
+            }
+        }
+        // The connection ends when reading does
+        std::future::pending::<()>().await
+    };
+
+    let read = async {
+        let mut close = None;
+        while let Some(message) = receiver.next().await {
+            match message {
+                Ok(Message::Close(frame)) => {
+                    close = Some(match frame {
+                        Some(frame) => (frame.code.into(), frame.reason.to_string()),
+                        None => (1005, String::new()),
+                    });
+                }
+                Ok(message @ (Message::Text(_) | Message::Binary(_))) => {
+                    let _ = events.send(Event::Message(message));
+                }
+                // Pings are answered by tungstenite
+                Ok(_) => {}
+                Err(WsError::ConnectionClosed | WsError::AlreadyClosed) => break,
+                Err(e) => {
+                    let _ = events.send(Event::Error(e.to_string()));
+                    break;
+                }
+            }
+        }
+
+        let (code, reason, clean) = match close {
+            Some((code, reason)) => (code, reason, true),
+            None => (1006, String::new(), false),
+        };
+        let _ = events.send(Event::Close {
+            code,
+            reason,
+            clean,
+        });
+    };
+
+    smol::future::or(read, write).await;
+}
+
+async fn connect(
+    url: &str,
+    protocols: &str,
+) -> Result<(WebSocketStream<tls::IoStream>, Response), String> {
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    let host = parsed.host_str().ok_or("Missing host")?;
+    let port = parsed.port_or_known_default().ok_or("Missing port")?;
+
+    // IPv6 addresses are in brackets in URLs, but not in server names
+    let server_name = host.trim_start_matches('[').trim_end_matches(']');
+    let stream = match parsed.scheme() {
+        "wss" => {
+            let config = TLS_CONFIG.as_ref().map_err(|e| e.clone())?;
+            tls::create_tls_stream(config, server_name, port).await?
+        }
+        "ws" => tls::create_plain_stream(host, port).await?,
+        scheme => return Err(format!("Unsupported scheme: {}", scheme)),
+    };
+
+    let mut request = url
+        .into_client_request()
+        .map_err(|e| format!("Invalid URL: {}", e))?;
+    if !protocols.is_empty() {
+        let value = protocols
+            .parse()
+            .map_err(|_| "Invalid subprotocol".to_string())?;
+        request
+            .headers_mut()
+            .insert("sec-websocket-protocol", value);
+    }
+
+    async_tungstenite::client_async(request, stream)
+        .await
+        .map_err(|e| format!("Handshake failed: {}", e))
+}
+
+/// Take the next event of socket `id`. Returns an object with the event `type` and
+/// its fields, undefined when there is none yet, or null once the socket closed.
+fn websocket_next<'js>(ctx: Ctx<'js>, id: u64) -> rquickjs::Result<Value<'js>> {
+    let mut sockets = SOCKETS.lock().unwrap();
+    let Some(socket) = sockets.get(&id) else {
+        return Ok(Value::new_null(ctx));
+    };
+    let Some(event) = poll_receiver(&ctx, &socket.events) else {
+        return Ok(Value::new_undefined(ctx));
+    };
+
+    let object = Object::new(ctx.clone())?;
+    match event {
+        Event::Open {
+            protocol,
+            extensions,
+        } => {
+            object.set("type", "open")?;
+            object.set("protocol", protocol)?;
+            object.set("extensions", extensions)?;
+        }
+        Event::Message(Message::Binary(data)) => {
+            object.set("type", "message")?;
+            object.set("data", TypedArray::<u8>::new_copy(ctx.clone(), &data)?)?;
+        }
+        Event::Message(message) => {
+            object.set("type", "message")?;
+            object.set("data", message.to_text().unwrap_or_default())?;
+        }
+        Event::Error(message) => {
+            object.set("type", "error")?;
+            object.set("message", message)?;
+        }
+        Event::Close {
+            code,
+            reason,
+            clean,
+        } => {
+            sockets.remove(&id);
+            object.set("type", "close")?;
+            object.set("code", code)?;
+            object.set("reason", reason)?;
+            object.set("wasClean", clean)?;
+        }
+    }
+    Ok(object.into_value())
+}
+
+/// Send `data`, a string for a text message or a `Uint8Array` for a binary one.
+fn websocket_send(id: u64, data: Value<'_>) -> rquickjs::Result<()> {
+    let message = match data.as_string() {
+        Some(text) => Message::text(text.to_string()?),
+        None => Message::binary(
+            TypedArray::<u8>::from_value(data)?
+                .as_bytes()
+                .unwrap_or_default()
+                .to_vec(),
+        ),
+    };
+
+    let sockets = SOCKETS.lock().unwrap();
+    if let Some(socket) = sockets.get(&id) {
+        socket.buffered.fetch_add(message.len(), Ordering::SeqCst);
+        let _ = socket.commands.try_send(Command::Send(message));
+    }
+    Ok(())
+}
+
+/// Start the closing handshake, with a close frame carrying `code` and `reason`
+/// when a code is given.
+fn websocket_close(id: u64, code: Option<u16>, reason: String) {
+    let frame = code.map(|code| CloseFrame {
+        code: code.into(),
+        reason: reason.into(),
+    });
+    let sockets = SOCKETS.lock().unwrap();
+    if let Some(socket) = sockets.get(&id) {
+        let _ = socket.commands.try_send(Command::Close(frame));
+    }
+}
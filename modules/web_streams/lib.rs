@@ -0,0 +1,7 @@
+use rquickjs::{Ctx, Module};
+
+pub fn init(ctx: &Ctx<'_>) -> rquickjs::Result<()> {
+    let module = Module::evaluate(ctx.clone(), "web_streams", include_str!("streams.js"))?;
+    module.finish::<()>()?;
+    Ok(())
+}
@@ -0,0 +1,1626 @@
+// https://streams.spec.whatwg.org/
+// Default (non-byte) readable streams, writable streams and transform streams.
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+const illegalConstructorKey = Symbol("illegalConstructorKey");
+
+// Internal state of the public objects, shared between the classes below
+const readableState = new WeakMap();
+const readerState = new WeakMap();
+const readableControllerState = new WeakMap();
+const writableState = new WeakMap();
+const writerState = new WeakMap();
+const writableControllerState = new WeakMap();
+const transformState = new WeakMap();
+const transformControllerState = new WeakMap();
+
+// Marker queued by WritableStreamDefaultController to close the sink in order
+const CLOSE_SENTINEL = Symbol("close");
+
+function getState(map, object) {
+  const state = map.get(object);
+  if (!state) {
+    throw new TypeError("Illegal invocation");
+  }
+  return state;
+}
+
+function deferred() {
+  const result = { state: "pending" };
+  result.promise = new Promise((resolve, reject) => {
+    result.resolve = (value) => {
+      if (result.state !== "pending") return;
+      result.state = "resolved";
+      resolve(value);
+    };
+    result.reject = (reason) => {
+      if (result.state !== "pending") return;
+      result.state = "rejected";
+      reject(reason);
+    };
+  });
+  return result;
+}
+
+// Rejected promises the stream machinery hands out but does not require
+// anyone to observe
+function markHandled(promise) {
+  promise.catch(() => {});
+  return promise;
+}
+
+function rejectedDeferred(reason) {
+  const result = deferred();
+  markHandled(result.promise);
+  result.reject(reason);
+  return result;
+}
+
+function resolvedDeferred(value) {
+  const result = deferred();
+  result.resolve(value);
+  return result;
+}
+
+// Call an optional source or sink method, turning throws into rejections
+function promiseCall(method, thisArg, ...args) {
+  if (method === undefined) {
+    return Promise.resolve(undefined);
+  }
+  try {
+    return Promise.resolve(method.apply(thisArg, args));
+  } catch (error) {
+    return Promise.reject(error);
+  }
+}
+
+function extractHighWaterMark(strategy, defaultHWM) {
+  if (strategy?.highWaterMark === undefined) {
+    return defaultHWM;
+  }
+  const highWaterMark = Number(strategy.highWaterMark);
+  if (Number.isNaN(highWaterMark) || highWaterMark < 0) {
+    throw new RangeError("highWaterMark must be a non-negative number");
+  }
+  return highWaterMark;
+}
+
+function extractSizeAlgorithm(strategy) {
+  const size = strategy?.size;
+  if (size === undefined) {
+    return () => 1;
+  }
+  if (typeof size !== "function") {
+    throw new TypeError("size must be a function");
+  }
+  return (chunk) => size(chunk);
+}
+
+function validateChunkSize(size) {
+  if (!Number.isFinite(size) || size < 0) {
+    throw new RangeError("Chunk size must be a finite, non-negative number");
+  }
+  return size;
+}
+
+// Readable streams
+
+function isReadableLocked(stream) {
+  return stream.reader !== null;
+}
+
+function readableStreamCancel(stream, reason) {
+  stream.disturbed = true;
+  if (stream.state === "closed") {
+    return Promise.resolve(undefined);
+  }
+  if (stream.state === "errored") {
+    return Promise.reject(stream.storedError);
+  }
+  readableStreamClose(stream);
+
+  const controller = stream.controller;
+  controller.queue = [];
+  controller.queueTotalSize = 0;
+  const result = controller.cancelAlgorithm(reason);
+  clearReadableAlgorithms(controller);
+  return result.then(() => undefined);
+}
+
+function readableStreamClose(stream) {
+  stream.state = "closed";
+  const reader = stream.reader;
+  if (reader === null) return;
+
+  reader.closed.resolve(undefined);
+  const requests = reader.readRequests;
+  reader.readRequests = [];
+  for (const request of requests) {
+    request.resolve({ value: undefined, done: true });
+  }
+}
+
+function readableStreamError(stream, error) {
+  stream.state = "errored";
+  stream.storedError = error;
+  const reader = stream.reader;
+  if (reader === null) return;
+
+  markHandled(reader.closed.promise);
+  reader.closed.reject(error);
+  const requests = reader.readRequests;
+  reader.readRequests = [];
+  for (const request of requests) {
+    request.reject(error);
+  }
+}
+
+function readerRead(reader) {
+  const stream = reader.stream;
+  stream.disturbed = true;
+  if (stream.state === "closed") {
+    return Promise.resolve({ value: undefined, done: true });
+  }
+  if (stream.state === "errored") {
+    return Promise.reject(stream.storedError);
+  }
+
+  const controller = stream.controller;
+  if (controller.queue.length > 0) {
+    const { value, size } = controller.queue.shift();
+    controller.queueTotalSize = Math.max(0, controller.queueTotalSize - size);
+    if (controller.closeRequested && controller.queue.length === 0) {
+      clearReadableAlgorithms(controller);
+      readableStreamClose(stream);
+    } else {
+      readableCallPullIfNeeded(controller);
+    }
+    return Promise.resolve({ value, done: false });
+  }
+
+  const request = deferred();
+  reader.readRequests.push(request);
+  readableCallPullIfNeeded(controller);
+  return request.promise;
+}
+
+function readerRelease(reader) {
+  const stream = reader.stream;
+  const error = new TypeError("Reader was released");
+  if (reader.closed.state === "pending") {
+    reader.closed.reject(error);
+  } else {
+    reader.closed = rejectedDeferred(error);
+  }
+  markHandled(reader.closed.promise);
+
+  const requests = reader.readRequests;
+  reader.readRequests = [];
+  for (const request of requests) {
+    request.reject(error);
+  }
+  stream.reader = null;
+  reader.stream = null;
+}
+
+function setUpReader(reader, stream) {
+  if (isReadableLocked(stream)) {
+    throw new TypeError("ReadableStream is locked");
+  }
+  reader.stream = stream;
+  reader.readRequests = [];
+  stream.reader = reader;
+  if (stream.state === "readable") {
+    reader.closed = deferred();
+  } else if (stream.state === "closed") {
+    reader.closed = resolvedDeferred(undefined);
+  } else {
+    reader.closed = rejectedDeferred(stream.storedError);
+  }
+}
+
+function readableCanCloseOrEnqueue(controller) {
+  return !controller.closeRequested && controller.stream.state === "readable";
+}
+
+function readableDesiredSize(controller) {
+  const state = controller.stream.state;
+  if (state === "errored") return null;
+  if (state === "closed") return 0;
+  return controller.highWaterMark - controller.queueTotalSize;
+}
+
+function readableShouldCallPull(controller) {
+  if (!readableCanCloseOrEnqueue(controller) || !controller.started) {
+    return false;
+  }
+  const stream = controller.stream;
+  if (isReadableLocked(stream) && stream.reader.readRequests.length > 0) {
+    return true;
+  }
+  return readableDesiredSize(controller) > 0;
+}
+
+function readableCallPullIfNeeded(controller) {
+  if (!readableShouldCallPull(controller)) return;
+  if (controller.pulling) {
+    controller.pullAgain = true;
+    return;
+  }
+  controller.pulling = true;
+  controller.pullAlgorithm().then(() => {
+    controller.pulling = false;
+    if (controller.pullAgain) {
+      controller.pullAgain = false;
+      readableCallPullIfNeeded(controller);
+    }
+  }, (error) => readableControllerError(controller, error));
+}
+
+function clearReadableAlgorithms(controller) {
+  controller.pullAlgorithm = () => Promise.resolve(undefined);
+  controller.cancelAlgorithm = () => Promise.resolve(undefined);
+  controller.sizeAlgorithm = () => 1;
+}
+
+function readableControllerEnqueue(controller, chunk) {
+  if (!readableCanCloseOrEnqueue(controller)) {
+    throw new TypeError(
+      "ReadableStream is not in a state that permits enqueue",
+    );
+  }
+  const stream = controller.stream;
+  if (isReadableLocked(stream) && stream.reader.readRequests.length > 0) {
+    stream.reader.readRequests.shift().resolve({ value: chunk, done: false });
+  } else {
+    let size;
+    try {
+      size = validateChunkSize(controller.sizeAlgorithm(chunk));
+    } catch (error) {
+      readableControllerError(controller, error);
+      throw error;
+    }
+    controller.queue.push({ value: chunk, size });
+    controller.queueTotalSize += size;
+  }
+  readableCallPullIfNeeded(controller);
+}
+
+function readableControllerClose(controller) {
+  if (!readableCanCloseOrEnqueue(controller)) {
+    throw new TypeError("ReadableStream is not in a state that permits close");
+  }
+  controller.closeRequested = true;
+  if (controller.queue.length === 0) {
+    clearReadableAlgorithms(controller);
+    readableStreamClose(controller.stream);
+  }
+}
+
+function readableControllerError(controller, error) {
+  if (controller.stream.state !== "readable") return;
+  controller.queue = [];
+  controller.queueTotalSize = 0;
+  clearReadableAlgorithms(controller);
+  readableStreamError(controller.stream, error);
+}
+
+function setUpReadableController(stream, source, highWaterMark, sizeAlgorithm) {
+  const controller = new ReadableStreamDefaultController(illegalConstructorKey);
+  const state = {
+    stream,
+    queue: [],
+    queueTotalSize: 0,
+    started: false,
+    closeRequested: false,
+    pulling: false,
+    pullAgain: false,
+    highWaterMark,
+    sizeAlgorithm,
+    pullAlgorithm: () => promiseCall(source.pull, source, controller),
+    cancelAlgorithm: (reason) => promiseCall(source.cancel, source, reason),
+  };
+  readableControllerState.set(controller, state);
+  stream.controller = state;
+
+  promiseCall(source.start, source, controller).then(() => {
+    state.started = true;
+    readableCallPullIfNeeded(state);
+  }, (error) => readableControllerError(state, error));
+}
+
+function createReadableStream(source, highWaterMark = 1, sizeAlgorithm) {
+  const stream = Object.create(ReadableStream.prototype);
+  const state = initReadableState();
+  readableState.set(stream, state);
+  setUpReadableController(
+    state,
+    source,
+    highWaterMark,
+    sizeAlgorithm ?? (() => 1),
+  );
+  return stream;
+}
+
+function initReadableState() {
+  return {
+    state: "readable",
+    storedError: undefined,
+    reader: null,
+    disturbed: false,
+    controller: null,
+  };
+}
+
+class ReadableStream {
+  constructor(underlyingSource = undefined, strategy = {}) {
+    const source = underlyingSource ?? {};
+    if (typeof source !== "object" && typeof source !== "function") {
+      throw new TypeError("underlyingSource must be an object");
+    }
+    if (source.type !== undefined && String(source.type) !== "bytes") {
+      throw new TypeError(`Invalid type: ${source.type}`);
+    }
+    const highWaterMark = extractHighWaterMark(
+      strategy,
+      source.type === undefined ? 1 : 0,
+    );
+    const sizeAlgorithm = extractSizeAlgorithm(strategy);
+
+    const state = initReadableState();
+    readableState.set(this, state);
+    setUpReadableController(state, source, highWaterMark, sizeAlgorithm);
+  }
+
+  static from(asyncIterable) {
+    const method = asyncIterable?.[Symbol.asyncIterator] ??
+      asyncIterable?.[Symbol.iterator];
+    if (typeof method !== "function") {
+      throw new TypeError("ReadableStream.from requires an iterable");
+    }
+    const iterator = method.call(asyncIterable);
+    return createReadableStream({
+      async pull(controller) {
+        const { value, done } = await iterator.next();
+        if (done) {
+          controller.close();
+        } else {
+          controller.enqueue(await value);
+        }
+      },
+      async cancel(reason) {
+        await iterator.return?.(reason);
+      },
+    }, 0);
+  }
+
+  get locked() {
+    return isReadableLocked(getState(readableState, this));
+  }
+
+  cancel(reason) {
+    let state;
+    try {
+      state = getState(readableState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (isReadableLocked(state)) {
+      return Promise.reject(new TypeError("ReadableStream is locked"));
+    }
+    return readableStreamCancel(state, reason);
+  }
+
+  getReader(options = {}) {
+    getState(readableState, this);
+    if (options?.mode !== undefined) {
+      if (String(options.mode) === "byob") {
+        throw new TypeError("BYOB readers are not supported");
+      }
+      throw new TypeError(`Invalid reader mode: ${options.mode}`);
+    }
+    return new ReadableStreamDefaultReader(this);
+  }
+
+  pipeThrough(transform, options = {}) {
+    const state = getState(readableState, this);
+    const { readable, writable } = transform ?? {};
+    if (!readableState.has(readable) || !writableState.has(writable)) {
+      throw new TypeError("pipeThrough requires a { readable, writable } pair");
+    }
+    if (isReadableLocked(state)) {
+      throw new TypeError("ReadableStream is locked");
+    }
+    if (isWritableLocked(writableState.get(writable))) {
+      throw new TypeError("WritableStream is locked");
+    }
+    markHandled(pipeTo(this, writable, options));
+    return readable;
+  }
+
+  pipeTo(destination, options = {}) {
+    try {
+      const state = getState(readableState, this);
+      if (!writableState.has(destination)) {
+        throw new TypeError("pipeTo requires a WritableStream");
+      }
+      if (isReadableLocked(state)) {
+        throw new TypeError("ReadableStream is locked");
+      }
+      if (isWritableLocked(writableState.get(destination))) {
+        throw new TypeError("WritableStream is locked");
+      }
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    return pipeTo(this, destination, options);
+  }
+
+  tee() {
+    return tee(this);
+  }
+
+  values(options = {}) {
+    const reader = this.getReader();
+    return iterate(reader, Boolean(options?.preventCancel));
+  }
+
+  [Symbol.asyncIterator](options) {
+    return this.values(options);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "ReadableStream";
+  }
+}
+
+async function* iterate(reader, preventCancel) {
+  let finished = false;
+  try {
+    while (true) {
+      const { value, done } = await reader.read();
+      if (done) {
+        finished = true;
+        return;
+      }
+      yield value;
+    }
+  } catch (error) {
+    finished = true;
+    throw error;
+  } finally {
+    // Leaving the loop early cancels the stream
+    if (!finished && !preventCancel) {
+      await reader.cancel();
+    }
+    reader.releaseLock();
+  }
+}
+
+function tee(stream) {
+  const state = getState(readableState, stream);
+  const reader = new ReadableStreamDefaultReader(stream);
+  let reading = false;
+  let readAgain = false;
+  const canceled = [false, false];
+  const reasons = [undefined, undefined];
+  const controllers = [];
+  const cancelPromise = deferred();
+
+  function pull() {
+    if (reading) {
+      readAgain = true;
+      return Promise.resolve(undefined);
+    }
+    reading = true;
+    reader.read().then(({ value, done }) => {
+      reading = false;
+      if (done) {
+        for (let i = 0; i < 2; i++) {
+          if (!canceled[i] && readableCanCloseOrEnqueue(controllers[i])) {
+            readableControllerClose(controllers[i]);
+          }
+        }
+        cancelPromise.resolve(undefined);
+        return;
+      }
+      readAgain = false;
+      for (let i = 0; i < 2; i++) {
+        if (!canceled[i] && readableCanCloseOrEnqueue(controllers[i])) {
+          readableControllerEnqueue(controllers[i], value);
+        }
+      }
+      if (readAgain) {
+        pull();
+      }
+    }, () => {
+      reading = false;
+    });
+    return Promise.resolve(undefined);
+  }
+
+  function cancel(index, reason) {
+    canceled[index] = true;
+    reasons[index] = reason;
+    if (canceled[1 - index]) {
+      cancelPromise.resolve(readableStreamCancel(state, reasons));
+    }
+    return cancelPromise.promise;
+  }
+
+  const branches = [0, 1].map((index) =>
+    createReadableStream({
+      start(controller) {
+        controllers[index] = getState(readableControllerState, controller);
+      },
+      pull,
+      cancel: (reason) => cancel(index, reason),
+    })
+  );
+
+  getState(readerState, reader).closed.promise.catch((error) => {
+    for (const controller of controllers) {
+      readableControllerError(controller, error);
+    }
+    cancelPromise.resolve(undefined);
+  });
+
+  return branches;
+}
+
+class ReadableStreamDefaultReader {
+  constructor(stream) {
+    const reader = {};
+    setUpReader(reader, getState(readableState, stream));
+    readerState.set(this, reader);
+  }
+
+  get closed() {
+    return getState(readerState, this).closed.promise;
+  }
+
+  read() {
+    let reader;
+    try {
+      reader = getState(readerState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (reader.stream === null) {
+      return Promise.reject(new TypeError("Reader has no stream"));
+    }
+    return readerRead(reader);
+  }
+
+  releaseLock() {
+    const reader = getState(readerState, this);
+    if (reader.stream !== null) {
+      readerRelease(reader);
+    }
+  }
+
+  cancel(reason) {
+    let reader;
+    try {
+      reader = getState(readerState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (reader.stream === null) {
+      return Promise.reject(new TypeError("Reader has no stream"));
+    }
+    return readableStreamCancel(reader.stream, reason);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "ReadableStreamDefaultReader";
+  }
+}
+
+class ReadableStreamDefaultController {
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+  }
+
+  get desiredSize() {
+    return readableDesiredSize(getState(readableControllerState, this));
+  }
+
+  close() {
+    readableControllerClose(getState(readableControllerState, this));
+  }
+
+  enqueue(chunk) {
+    readableControllerEnqueue(getState(readableControllerState, this), chunk);
+  }
+
+  error(error) {
+    readableControllerError(getState(readableControllerState, this), error);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "ReadableStreamDefaultController";
+  }
+}
+
+// Writable streams
+
+function isWritableLocked(stream) {
+  return stream.writer !== null;
+}
+
+function closeQueuedOrInFlight(stream) {
+  return stream.closeRequest !== null || stream.inFlightCloseRequest !== null;
+}
+
+function hasOperationMarkedInFlight(stream) {
+  return stream.inFlightWriteRequest !== null ||
+    stream.inFlightCloseRequest !== null;
+}
+
+function writableStreamAbort(stream, reason) {
+  if (stream.state === "closed" || stream.state === "errored") {
+    return Promise.resolve(undefined);
+  }
+  stream.controller.abortController?.abort(reason);
+  if (stream.state === "closed" || stream.state === "errored") {
+    return Promise.resolve(undefined);
+  }
+  if (stream.pendingAbortRequest !== null) {
+    return stream.pendingAbortRequest.promise;
+  }
+
+  const wasAlreadyErroring = stream.state === "erroring";
+  const request = deferred();
+  request.reason = wasAlreadyErroring ? undefined : reason;
+  request.wasAlreadyErroring = wasAlreadyErroring;
+  stream.pendingAbortRequest = request;
+  if (!wasAlreadyErroring) {
+    writableStartErroring(stream, reason);
+  }
+  return request.promise;
+}
+
+function writableStreamClose(stream) {
+  if (stream.state === "closed" || stream.state === "errored") {
+    return Promise.reject(
+      new TypeError("WritableStream is already closed or errored"),
+    );
+  }
+  const request = deferred();
+  stream.closeRequest = request;
+
+  const writer = stream.writer;
+  if (writer !== null && stream.backpressure && stream.state === "writable") {
+    writer.ready.resolve(undefined);
+  }
+  writableControllerEnqueue(stream.controller, CLOSE_SENTINEL, 0);
+  writableAdvanceQueueIfNeeded(stream.controller);
+  return request.promise;
+}
+
+function writableDealWithRejection(stream, error) {
+  if (stream.state === "writable") {
+    writableStartErroring(stream, error);
+  } else {
+    writableFinishErroring(stream);
+  }
+}
+
+function writableStartErroring(stream, reason) {
+  stream.state = "erroring";
+  stream.storedError = reason;
+  if (stream.writer !== null) {
+    ensureReadyRejected(stream.writer, reason);
+  }
+  if (!hasOperationMarkedInFlight(stream) && stream.controller.started) {
+    writableFinishErroring(stream);
+  }
+}
+
+function writableFinishErroring(stream) {
+  stream.state = "errored";
+  const controller = stream.controller;
+  controller.queue = [];
+  controller.queueTotalSize = 0;
+
+  const error = stream.storedError;
+  for (const request of stream.writeRequests) {
+    request.reject(error);
+  }
+  stream.writeRequests = [];
+
+  const abortRequest = stream.pendingAbortRequest;
+  if (abortRequest === null) {
+    writableRejectCloseAndClosedIfNeeded(stream);
+    return;
+  }
+  stream.pendingAbortRequest = null;
+  if (abortRequest.wasAlreadyErroring) {
+    abortRequest.reject(error);
+    writableRejectCloseAndClosedIfNeeded(stream);
+    return;
+  }
+  const result = controller.abortAlgorithm(abortRequest.reason);
+  clearWritableAlgorithms(controller);
+  result.then(() => {
+    abortRequest.resolve(undefined);
+    writableRejectCloseAndClosedIfNeeded(stream);
+  }, (reason) => {
+    abortRequest.reject(reason);
+    writableRejectCloseAndClosedIfNeeded(stream);
+  });
+}
+
+function writableRejectCloseAndClosedIfNeeded(stream) {
+  if (stream.closeRequest !== null) {
+    stream.closeRequest.reject(stream.storedError);
+    stream.closeRequest = null;
+  }
+  if (stream.writer !== null) {
+    markHandled(stream.writer.closed.promise);
+    stream.writer.closed.reject(stream.storedError);
+  }
+}
+
+function writableUpdateBackpressure(stream, backpressure) {
+  const writer = stream.writer;
+  if (writer !== null && backpressure !== stream.backpressure) {
+    if (backpressure) {
+      writer.ready = deferred();
+    } else {
+      writer.ready.resolve(undefined);
+    }
+  }
+  stream.backpressure = backpressure;
+}
+
+function ensureReadyRejected(writer, error) {
+  if (writer.ready.state === "pending") {
+    writer.ready.reject(error);
+  } else {
+    writer.ready = rejectedDeferred(error);
+  }
+  markHandled(writer.ready.promise);
+}
+
+function ensureClosedRejected(writer, error) {
+  if (writer.closed.state === "pending") {
+    writer.closed.reject(error);
+  } else {
+    writer.closed = rejectedDeferred(error);
+  }
+  markHandled(writer.closed.promise);
+}
+
+function writableDesiredSize(controller) {
+  return controller.highWaterMark - controller.queueTotalSize;
+}
+
+function writableControllerEnqueue(controller, value, size) {
+  controller.queue.push({ value, size });
+  controller.queueTotalSize += size;
+}
+
+function writableControllerErrorIfNeeded(controller, error) {
+  if (controller.stream.state === "writable") {
+    clearWritableAlgorithms(controller);
+    writableStartErroring(controller.stream, error);
+  }
+}
+
+function clearWritableAlgorithms(controller) {
+  controller.writeAlgorithm = () => Promise.resolve(undefined);
+  controller.closeAlgorithm = () => Promise.resolve(undefined);
+  controller.abortAlgorithm = () => Promise.resolve(undefined);
+  controller.sizeAlgorithm = () => 1;
+}
+
+function writableAdvanceQueueIfNeeded(controller) {
+  const stream = controller.stream;
+  if (!controller.started || stream.inFlightWriteRequest !== null) {
+    return;
+  }
+  if (stream.state === "erroring") {
+    writableFinishErroring(stream);
+    return;
+  }
+  if (controller.queue.length === 0) {
+    return;
+  }
+
+  const { value } = controller.queue[0];
+  if (value === CLOSE_SENTINEL) {
+    writableProcessClose(controller);
+  } else {
+    writableProcessWrite(controller, value);
+  }
+}
+
+function writableProcessClose(controller) {
+  const stream = controller.stream;
+  stream.inFlightCloseRequest = stream.closeRequest;
+  stream.closeRequest = null;
+  controller.queue.shift();
+
+  const result = controller.closeAlgorithm();
+  clearWritableAlgorithms(controller);
+  result.then(() => {
+    stream.inFlightCloseRequest.resolve(undefined);
+    stream.inFlightCloseRequest = null;
+    if (stream.state === "erroring") {
+      stream.storedError = undefined;
+      if (stream.pendingAbortRequest !== null) {
+        stream.pendingAbortRequest.resolve(undefined);
+        stream.pendingAbortRequest = null;
+      }
+    }
+    stream.state = "closed";
+    if (stream.writer !== null) {
+      stream.writer.closed.resolve(undefined);
+    }
+  }, (error) => {
+    stream.inFlightCloseRequest.reject(error);
+    stream.inFlightCloseRequest = null;
+    if (stream.pendingAbortRequest !== null) {
+      stream.pendingAbortRequest.reject(error);
+      stream.pendingAbortRequest = null;
+    }
+    writableDealWithRejection(stream, error);
+  });
+}
+
+function writableProcessWrite(controller, chunk) {
+  const stream = controller.stream;
+  stream.inFlightWriteRequest = stream.writeRequests.shift();
+
+  controller.writeAlgorithm(chunk).then(() => {
+    stream.inFlightWriteRequest.resolve(undefined);
+    stream.inFlightWriteRequest = null;
+
+    const { size } = controller.queue.shift();
+    controller.queueTotalSize = Math.max(0, controller.queueTotalSize - size);
+    if (!closeQueuedOrInFlight(stream) && stream.state === "writable") {
+      writableUpdateBackpressure(stream, writableDesiredSize(controller) <= 0);
+    }
+    writableAdvanceQueueIfNeeded(controller);
+  }, (reason) => {
+    if (stream.state === "writable") {
+      clearWritableAlgorithms(controller);
+    }
+    stream.inFlightWriteRequest.reject(reason);
+    stream.inFlightWriteRequest = null;
+    writableDealWithRejection(stream, reason);
+  });
+}
+
+function writerWrite(writer, chunk) {
+  const stream = writer.stream;
+  const controller = stream.controller;
+  let size;
+  try {
+    size = validateChunkSize(controller.sizeAlgorithm(chunk));
+  } catch (error) {
+    writableControllerErrorIfNeeded(controller, error);
+    size = 1;
+  }
+
+  if (stream !== writer.stream) {
+    return Promise.reject(new TypeError("Writer was released"));
+  }
+  if (stream.state === "errored") {
+    return Promise.reject(stream.storedError);
+  }
+  if (closeQueuedOrInFlight(stream) || stream.state === "closed") {
+    return Promise.reject(
+      new TypeError("WritableStream is closing or closed"),
+    );
+  }
+  if (stream.state === "erroring") {
+    return Promise.reject(stream.storedError);
+  }
+
+  const request = deferred();
+  stream.writeRequests.push(request);
+  writableControllerEnqueue(controller, chunk, size);
+  if (!closeQueuedOrInFlight(stream) && stream.state === "writable") {
+    writableUpdateBackpressure(stream, writableDesiredSize(controller) <= 0);
+  }
+  writableAdvanceQueueIfNeeded(controller);
+  return request.promise;
+}
+
+// Close through a writer, resolving immediately when already closed
+function writerCloseWithErrorPropagation(writer) {
+  const stream = writer.stream;
+  if (closeQueuedOrInFlight(stream) || stream.state === "closed") {
+    return Promise.resolve(undefined);
+  }
+  if (stream.state === "errored") {
+    return Promise.reject(stream.storedError);
+  }
+  return writableStreamClose(stream);
+}
+
+function writerRelease(writer) {
+  const error = new TypeError("Writer was released");
+  ensureReadyRejected(writer, error);
+  ensureClosedRejected(writer, error);
+  writer.stream.writer = null;
+  writer.stream = null;
+}
+
+function setUpWritableController(stream, sink, highWaterMark, sizeAlgorithm) {
+  const controller = new WritableStreamDefaultController(
+    illegalConstructorKey,
+  );
+  const state = {
+    stream,
+    queue: [],
+    queueTotalSize: 0,
+    started: false,
+    highWaterMark,
+    sizeAlgorithm,
+    abortController: globalThis.AbortController
+      ? new AbortController()
+      : null,
+    writeAlgorithm: (chunk) => promiseCall(sink.write, sink, chunk, controller),
+    closeAlgorithm: () => promiseCall(sink.close, sink),
+    abortAlgorithm: (reason) => promiseCall(sink.abort, sink, reason),
+  };
+  writableControllerState.set(controller, state);
+  stream.controller = state;
+  writableUpdateBackpressure(stream, writableDesiredSize(state) <= 0);
+
+  promiseCall(sink.start, sink, controller).then(() => {
+    state.started = true;
+    writableAdvanceQueueIfNeeded(state);
+  }, (error) => {
+    state.started = true;
+    writableDealWithRejection(stream, error);
+  });
+}
+
+function initWritableState() {
+  return {
+    state: "writable",
+    storedError: undefined,
+    writer: null,
+    controller: null,
+    writeRequests: [],
+    inFlightWriteRequest: null,
+    closeRequest: null,
+    inFlightCloseRequest: null,
+    pendingAbortRequest: null,
+    backpressure: false,
+  };
+}
+
+function createWritableStream(sink, highWaterMark, sizeAlgorithm) {
+  const stream = Object.create(WritableStream.prototype);
+  const state = initWritableState();
+  writableState.set(stream, state);
+  setUpWritableController(state, sink, highWaterMark, sizeAlgorithm);
+  return stream;
+}
+
+class WritableStream {
+  constructor(underlyingSink = undefined, strategy = {}) {
+    const sink = underlyingSink ?? {};
+    if (typeof sink !== "object" && typeof sink !== "function") {
+      throw new TypeError("underlyingSink must be an object");
+    }
+    if (sink.type !== undefined) {
+      throw new RangeError(`Invalid type: ${sink.type}`);
+    }
+    const highWaterMark = extractHighWaterMark(strategy, 1);
+    const sizeAlgorithm = extractSizeAlgorithm(strategy);
+
+    const state = initWritableState();
+    writableState.set(this, state);
+    setUpWritableController(state, sink, highWaterMark, sizeAlgorithm);
+  }
+
+  get locked() {
+    return isWritableLocked(getState(writableState, this));
+  }
+
+  abort(reason) {
+    let state;
+    try {
+      state = getState(writableState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (isWritableLocked(state)) {
+      return Promise.reject(new TypeError("WritableStream is locked"));
+    }
+    return writableStreamAbort(state, reason);
+  }
+
+  close() {
+    let state;
+    try {
+      state = getState(writableState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (isWritableLocked(state)) {
+      return Promise.reject(new TypeError("WritableStream is locked"));
+    }
+    if (closeQueuedOrInFlight(state)) {
+      return Promise.reject(new TypeError("WritableStream is closing"));
+    }
+    return writableStreamClose(state);
+  }
+
+  getWriter() {
+    return new WritableStreamDefaultWriter(this);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "WritableStream";
+  }
+}
+
+class WritableStreamDefaultWriter {
+  constructor(stream) {
+    const state = getState(writableState, stream);
+    if (isWritableLocked(state)) {
+      throw new TypeError("WritableStream is locked");
+    }
+    const writer = { stream: state };
+    state.writer = writer;
+
+    if (state.state === "writable") {
+      writer.ready = !closeQueuedOrInFlight(state) && state.backpressure
+        ? deferred()
+        : resolvedDeferred(undefined);
+      writer.closed = deferred();
+    } else if (state.state === "erroring") {
+      writer.ready = rejectedDeferred(state.storedError);
+      writer.closed = deferred();
+    } else if (state.state === "closed") {
+      writer.ready = resolvedDeferred(undefined);
+      writer.closed = resolvedDeferred(undefined);
+    } else {
+      writer.ready = rejectedDeferred(state.storedError);
+      writer.closed = rejectedDeferred(state.storedError);
+    }
+    writerState.set(this, writer);
+  }
+
+  get closed() {
+    return getState(writerState, this).closed.promise;
+  }
+
+  get ready() {
+    return getState(writerState, this).ready.promise;
+  }
+
+  get desiredSize() {
+    const writer = getState(writerState, this);
+    if (writer.stream === null) {
+      throw new TypeError("Writer was released");
+    }
+    const state = writer.stream.state;
+    if (state === "errored" || state === "erroring") return null;
+    if (state === "closed") return 0;
+    return writableDesiredSize(writer.stream.controller);
+  }
+
+  abort(reason) {
+    let writer;
+    try {
+      writer = getState(writerState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (writer.stream === null) {
+      return Promise.reject(new TypeError("Writer was released"));
+    }
+    return writableStreamAbort(writer.stream, reason);
+  }
+
+  close() {
+    let writer;
+    try {
+      writer = getState(writerState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (writer.stream === null) {
+      return Promise.reject(new TypeError("Writer was released"));
+    }
+    if (closeQueuedOrInFlight(writer.stream)) {
+      return Promise.reject(new TypeError("WritableStream is closing"));
+    }
+    return writableStreamClose(writer.stream);
+  }
+
+  write(chunk) {
+    let writer;
+    try {
+      writer = getState(writerState, this);
+    } catch (error) {
+      return Promise.reject(error);
+    }
+    if (writer.stream === null) {
+      return Promise.reject(new TypeError("Writer was released"));
+    }
+    return writerWrite(writer, chunk);
+  }
+
+  releaseLock() {
+    const writer = getState(writerState, this);
+    if (writer.stream !== null) {
+      writerRelease(writer);
+    }
+  }
+
+  get [Symbol.toStringTag]() {
+    return "WritableStreamDefaultWriter";
+  }
+}
+
+class WritableStreamDefaultController {
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+  }
+
+  get signal() {
+    return getState(writableControllerState, this).abortController?.signal;
+  }
+
+  error(error) {
+    const controller = getState(writableControllerState, this);
+    if (controller.stream.state === "writable") {
+      writableControllerErrorIfNeeded(controller, error);
+    }
+  }
+
+  get [Symbol.toStringTag]() {
+    return "WritableStreamDefaultController";
+  }
+}
+
+// Piping
+
+function pipeTo(source, destination, options) {
+  const preventClose = Boolean(options?.preventClose);
+  const preventAbort = Boolean(options?.preventAbort);
+  const preventCancel = Boolean(options?.preventCancel);
+  const signal = options?.signal;
+
+  const readable = getState(readableState, source);
+  const writable = getState(writableState, destination);
+  const readerObject = new ReadableStreamDefaultReader(source);
+  const writerObject = new WritableStreamDefaultWriter(destination);
+  const reader = getState(readerState, readerObject);
+  const writer = getState(writerState, writerObject);
+  readable.disturbed = true;
+
+  const result = deferred();
+  let shuttingDown = false;
+  let currentWrite = Promise.resolve(undefined);
+
+  function finalize(isError, error) {
+    writerRelease(writer);
+    readerRelease(reader);
+    signal?.removeEventListener("abort", abort);
+    if (isError) {
+      result.reject(error);
+    } else {
+      result.resolve(undefined);
+    }
+  }
+
+  // Wait for writes that were already started, even ones started meanwhile
+  function waitForWrites() {
+    const write = currentWrite;
+    return write.then(() =>
+      write === currentWrite ? undefined : waitForWrites()
+    );
+  }
+
+  function shutdown(action, isError, error) {
+    if (shuttingDown) return;
+    shuttingDown = true;
+    const run = () => {
+      if (action === null) {
+        finalize(isError, error);
+        return;
+      }
+      action().then(
+        () => finalize(isError, error),
+        (newError) => finalize(true, newError),
+      );
+    };
+    if (writable.state === "writable" && !closeQueuedOrInFlight(writable)) {
+      waitForWrites().then(run);
+    } else {
+      run();
+    }
+  }
+
+  function abort() {
+    const error = signal.reason;
+    const actions = [];
+    if (!preventAbort && writable.state === "writable") {
+      actions.push(() => writableStreamAbort(writable, error));
+    }
+    if (!preventCancel && readable.state === "readable") {
+      actions.push(() => readableStreamCancel(readable, error));
+    }
+    shutdown(
+      () => Promise.all(actions.map((action) => action())),
+      true,
+      error,
+    );
+  }
+
+  function sourceErrored(error) {
+    const action = preventAbort
+      ? null
+      : () => writableStreamAbort(writable, error);
+    shutdown(action, true, error);
+  }
+
+  function destinationErrored(error) {
+    const action = preventCancel
+      ? null
+      : () => readableStreamCancel(readable, error);
+    shutdown(action, true, error);
+  }
+
+  function sourceClosed() {
+    const action = preventClose
+      ? null
+      : () => writerCloseWithErrorPropagation(writer);
+    shutdown(action, false);
+  }
+
+  if (signal !== undefined) {
+    if (signal.aborted) {
+      abort();
+      return result.promise;
+    }
+    signal.addEventListener("abort", abort, { once: true });
+  }
+
+  // Errors and closing are propagated in this order
+  if (readable.state === "errored") {
+    sourceErrored(readable.storedError);
+  } else {
+    reader.closed.promise.catch((error) => {
+      if (readable.state === "errored") sourceErrored(error);
+    });
+  }
+  if (writable.state === "errored") {
+    destinationErrored(writable.storedError);
+  } else {
+    writer.closed.promise.catch((error) => {
+      if (writable.state === "errored") destinationErrored(error);
+    });
+  }
+  if (readable.state === "closed") {
+    sourceClosed();
+  } else {
+    reader.closed.promise.then(sourceClosed, () => {});
+  }
+  if (closeQueuedOrInFlight(writable) || writable.state === "closed") {
+    const error = new TypeError("The destination stream is closed");
+    const action = preventCancel
+      ? null
+      : () => readableStreamCancel(readable, error);
+    shutdown(action, true, error);
+  }
+
+  (async () => {
+    while (!shuttingDown) {
+      try {
+        await writer.ready.promise;
+      } catch {
+        return;
+      }
+      if (shuttingDown) return;
+
+      let chunk;
+      try {
+        chunk = await readerRead(reader);
+      } catch {
+        return;
+      }
+      if (chunk.done || shuttingDown) return;
+      currentWrite = markHandled(writerWrite(writer, chunk.value));
+    }
+  })();
+
+  return result.promise;
+}
+
+// Transform streams
+
+function transformSetBackpressure(stream, backpressure) {
+  stream.backpressureChange?.resolve(undefined);
+  stream.backpressureChange = deferred();
+  stream.backpressure = backpressure;
+}
+
+function transformError(stream, error) {
+  readableControllerError(stream.readable.controller, error);
+  transformErrorWritableAndUnblockWrite(stream, error);
+}
+
+function transformErrorWritableAndUnblockWrite(stream, error) {
+  const controller = stream.controller;
+  controller.transformAlgorithm = () => Promise.resolve(undefined);
+  controller.flushAlgorithm = () => Promise.resolve(undefined);
+  writableControllerErrorIfNeeded(stream.writable.controller, error);
+  if (stream.backpressure) {
+    transformSetBackpressure(stream, false);
+  }
+}
+
+function transformPerform(stream, chunk) {
+  return stream.controller.transformAlgorithm(chunk).catch((error) => {
+    transformError(stream, error);
+    throw error;
+  });
+}
+
+function transformControllerEnqueue(stream, chunk) {
+  const readableController = stream.readable.controller;
+  if (!readableCanCloseOrEnqueue(readableController)) {
+    throw new TypeError("Readable side is not in a state that permits enqueue");
+  }
+  try {
+    readableControllerEnqueue(readableController, chunk);
+  } catch (error) {
+    transformErrorWritableAndUnblockWrite(stream, error);
+    throw stream.readable.storedError;
+  }
+  const backpressure = !readableShouldCallPull(readableController);
+  if (backpressure !== stream.backpressure) {
+    transformSetBackpressure(stream, true);
+  }
+}
+
+function transformTerminate(stream) {
+  const readableController = stream.readable.controller;
+  if (readableCanCloseOrEnqueue(readableController)) {
+    readableControllerClose(readableController);
+  }
+  transformErrorWritableAndUnblockWrite(
+    stream,
+    new TypeError("TransformStream terminated"),
+  );
+}
+
+class TransformStream {
+  constructor(
+    transformer = undefined,
+    writableStrategy = {},
+    readableStrategy = {},
+  ) {
+    transformer ??= {};
+    if (
+      transformer.readableType !== undefined ||
+      transformer.writableType !== undefined
+    ) {
+      throw new RangeError("Invalid readableType or writableType");
+    }
+    const readableHighWaterMark = extractHighWaterMark(readableStrategy, 0);
+    const readableSizeAlgorithm = extractSizeAlgorithm(readableStrategy);
+    const writableHighWaterMark = extractHighWaterMark(writableStrategy, 1);
+    const writableSizeAlgorithm = extractSizeAlgorithm(writableStrategy);
+
+    const stream = {
+      readable: null,
+      writable: null,
+      controller: null,
+      backpressure: undefined,
+      backpressureChange: null,
+    };
+    const startPromise = deferred();
+
+    const writableObject = createWritableStream({
+      start: () => startPromise.promise,
+      write(chunk) {
+        if (!stream.backpressure) {
+          return transformPerform(stream, chunk);
+        }
+        return stream.backpressureChange.promise.then(() => {
+          if (stream.writable.state === "erroring") {
+            throw stream.writable.storedError;
+          }
+          return transformPerform(stream, chunk);
+        });
+      },
+      abort(reason) {
+        transformError(stream, reason);
+        return promiseCall(transformer.cancel, transformer, reason);
+      },
+      close() {
+        return stream.controller.flushAlgorithm().then(() => {
+          if (stream.readable.state === "errored") {
+            throw stream.readable.storedError;
+          }
+          const readableController = stream.readable.controller;
+          if (readableCanCloseOrEnqueue(readableController)) {
+            readableControllerClose(readableController);
+          }
+        }, (error) => {
+          transformError(stream, error);
+          throw stream.readable.storedError;
+        });
+      },
+    }, writableHighWaterMark, writableSizeAlgorithm);
+
+    const readableObject = createReadableStream({
+      start: () => startPromise.promise,
+      pull() {
+        transformSetBackpressure(stream, false);
+        return stream.backpressureChange.promise;
+      },
+      cancel(reason) {
+        transformErrorWritableAndUnblockWrite(stream, reason);
+        return promiseCall(transformer.cancel, transformer, reason);
+      },
+    }, readableHighWaterMark, readableSizeAlgorithm);
+
+    stream.readable = readableState.get(readableObject);
+    stream.writable = writableState.get(writableObject);
+    transformSetBackpressure(stream, true);
+
+    const controller = new TransformStreamDefaultController(
+      illegalConstructorKey,
+    );
+    stream.controller = {
+      stream,
+      transformAlgorithm: transformer.transform === undefined
+        ? (chunk) => {
+          try {
+            transformControllerEnqueue(stream, chunk);
+            return Promise.resolve(undefined);
+          } catch (error) {
+            return Promise.reject(error);
+          }
+        }
+        : (chunk) =>
+          promiseCall(transformer.transform, transformer, chunk, controller),
+      flushAlgorithm: () =>
+        promiseCall(transformer.flush, transformer, controller),
+    };
+    transformControllerState.set(controller, stream.controller);
+    transformState.set(this, {
+      readable: readableObject,
+      writable: writableObject,
+    });
+
+    promiseCall(transformer.start, transformer, controller).then(
+      startPromise.resolve,
+      startPromise.reject,
+    );
+  }
+
+  get readable() {
+    return getState(transformState, this).readable;
+  }
+
+  get writable() {
+    return getState(transformState, this).writable;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "TransformStream";
+  }
+}
+
+class TransformStreamDefaultController {
+  constructor(key = null) {
+    if (key !== illegalConstructorKey) {
+      throw new TypeError("Illegal constructor.");
+    }
+  }
+
+  get desiredSize() {
+    const { stream } = getState(transformControllerState, this);
+    return readableDesiredSize(stream.readable.controller);
+  }
+
+  enqueue(chunk) {
+    const { stream } = getState(transformControllerState, this);
+    transformControllerEnqueue(stream, chunk);
+  }
+
+  error(reason) {
+    const { stream } = getState(transformControllerState, this);
+    transformError(stream, reason);
+  }
+
+  terminate() {
+    const { stream } = getState(transformControllerState, this);
+    transformTerminate(stream);
+  }
+
+  get [Symbol.toStringTag]() {
+    return "TransformStreamDefaultController";
+  }
+}
+
+// Queuing strategies
+
+class ByteLengthQueuingStrategy {
+  #highWaterMark;
+
+  constructor(init) {
+    if (init?.highWaterMark === undefined) {
+      throw new TypeError("highWaterMark is required");
+    }
+    this.#highWaterMark = Number(init.highWaterMark);
+  }
+
+  get highWaterMark() {
+    return this.#highWaterMark;
+  }
+
+  get size() {
+    return (chunk) => chunk.byteLength;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "ByteLengthQueuingStrategy";
+  }
+}
+
+class CountQueuingStrategy {
+  #highWaterMark;
+
+  constructor(init) {
+    if (init?.highWaterMark === undefined) {
+      throw new TypeError("highWaterMark is required");
+    }
+    this.#highWaterMark = Number(init.highWaterMark);
+  }
+
+  get highWaterMark() {
+    return this.#highWaterMark;
+  }
+
+  get size() {
+    return () => 1;
+  }
+
+  get [Symbol.toStringTag]() {
+    return "CountQueuingStrategy";
+  }
+}
+
+__internal.streams = {
+  // Create a readable stream without the public constructor's validation
+  createReadableStream,
+
+  isReadableStream(value) {
+    return readableState.has(value);
+  },
+
+  isDisturbed(stream) {
+    return getState(readableState, stream).disturbed;
+  },
+};
+
+globalThis.ReadableStream = ReadableStream;
+globalThis.ReadableStreamDefaultReader = ReadableStreamDefaultReader;
+globalThis.ReadableStreamDefaultController = ReadableStreamDefaultController;
+globalThis.WritableStream = WritableStream;
+globalThis.WritableStreamDefaultWriter = WritableStreamDefaultWriter;
+globalThis.WritableStreamDefaultController = WritableStreamDefaultController;
+globalThis.TransformStream = TransformStream;
+globalThis.TransformStreamDefaultController = TransformStreamDefaultController;
+globalThis.ByteLengthQueuingStrategy = ByteLengthQueuingStrategy;
+globalThis.CountQueuingStrategy = CountQueuingStrategy;
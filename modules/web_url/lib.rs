@@ -28,9 +28,35 @@ fn setup_internal(ctx: &Ctx) -> Result<(), Box<dyn Error>> {
             .unwrap_or_else(|e| format!(r#"{{"error":"{}"}}"#, e.replace('"', "\\\"")))
     });
 
+    add_internal_function!(ctx, "url.parseSearchParams", |query: String| -> String {
+        parse_search_params(query)
+    });
+
+    add_internal_function!(
+        ctx,
+        "url.serializeSearchParams",
+        |pairs: String| -> String { serialize_search_params(pairs) }
+    );
+
     Ok(())
 }
 
+/// Parse an application/x-www-form-urlencoded string into JSON name/value pairs.
+pub fn parse_search_params(query: String) -> String {
+    let pairs: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    serde_json::to_string(&pairs).unwrap()
+}
+
+/// Serialize JSON name/value pairs as application/x-www-form-urlencoded.
+pub fn serialize_search_params(pairs: String) -> String {
+    let pairs: Vec<(String, String)> = serde_json::from_str(&pairs).unwrap_or_default();
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(pairs)
+        .finish()
+}
+
 pub fn parse_url(url_str: String, base: String) -> Result<String, String> {
     let parsed = if base.is_empty() {
         Url::parse(&url_str).map_err(|e| e.to_string())?
@@ -41,7 +67,7 @@ pub fn parse_url(url_str: String, base: String) -> Result<String, String> {
 
     let json = serde_json::json!({
         "href": parsed.as_str(),
-        "origin": format!("{}://{}", parsed.scheme(), parsed.host_str().unwrap_or("")),
+        "origin": parsed.origin().ascii_serialization(),
         "protocol": format!("{}:", parsed.scheme()),
         "username": parsed.username(),
         "password": parsed.password().unwrap_or(""),
@@ -128,7 +154,7 @@ pub fn set_url_component(
 
     let json = serde_json::json!({
         "href": parsed.as_str(),
-        "origin": format!("{}://{}", parsed.scheme(), parsed.host_str().unwrap_or("")),
+        "origin": parsed.origin().ascii_serialization(),
         "protocol": format!("{}:", parsed.scheme()),
         "username": parsed.username(),
         "password": parsed.password().unwrap_or(""),
@@ -1,5 +1,10 @@
 const __internal = globalThis[Symbol.for("mdeno.internal")];
 
+// Helpers assigned inside the classes to reach their private state
+let setURLSearch;
+let attachURL;
+let resetList;
+
 class URL {
   #href;
   #origin;
@@ -12,12 +17,16 @@ class URL {
   #pathname;
   #search;
   #hash;
+  #searchParams = null;
 
   constructor(url, base) {
     const parseURL = __internal.url.parse;
 
     try {
       const result = JSON.parse(parseURL(url, base || ""));
+      if (result.error !== undefined) {
+        throw new Error(result.error);
+      }
 
       this.#href = result.href;
       this.#origin = result.origin;
@@ -54,6 +63,7 @@ class URL {
     this.#pathname = result.pathname;
     this.#search = result.search;
     this.#hash = result.hash;
+    this.#updateSearchParams();
   }
 
   get origin() {
@@ -174,6 +184,21 @@ class URL {
     );
     this.#href = result.href;
     this.#search = result.search;
+    this.#updateSearchParams();
+  }
+
+  get searchParams() {
+    if (this.#searchParams === null) {
+      this.#searchParams = new URLSearchParams(this.#search);
+      attachURL(this.#searchParams, this);
+    }
+    return this.#searchParams;
+  }
+
+  #updateSearchParams() {
+    if (this.#searchParams !== null) {
+      resetList(this.#searchParams, this.#search);
+    }
   }
 
   get hash() {
@@ -214,6 +239,161 @@ class URL {
       return false;
     }
   }
+
+  static {
+    setURLSearch = (url, query) => {
+      const setComponent = __internal.url.setComponent;
+
+      const result = JSON.parse(setComponent(url.#href, "search", query));
+      url.#href = result.href;
+      url.#search = result.search;
+    };
+  }
+}
+
+// https://url.spec.whatwg.org/#interface-urlsearchparams
+class URLSearchParams {
+  #list = [];
+  #url = null;
+
+  constructor(init = "") {
+    if (typeof init === "object" && init !== null) {
+      if (typeof init[Symbol.iterator] === "function") {
+        for (const pair of init) {
+          const entry = [...pair];
+          if (entry.length !== 2) {
+            throw new TypeError(
+              "Failed to construct 'URLSearchParams': Each query pair must be an iterable [name, value] tuple",
+            );
+          }
+          this.#list.push([String(entry[0]), String(entry[1])]);
+        }
+      } else {
+        for (const [name, value] of Object.entries(init)) {
+          this.#list.push([name, String(value)]);
+        }
+      }
+    } else {
+      this.#list = parseQuery(String(init));
+    }
+  }
+
+  get size() {
+    return this.#list.length;
+  }
+
+  append(name, value) {
+    this.#list.push([String(name), String(value)]);
+    this.#update();
+  }
+
+  delete(name, value) {
+    name = String(name);
+    value = value === undefined ? undefined : String(value);
+    this.#list = this.#list.filter(([n, v]) =>
+      n !== name || (value !== undefined && v !== value)
+    );
+    this.#update();
+  }
+
+  get(name) {
+    name = String(name);
+    const entry = this.#list.find(([n]) => n === name);
+    return entry ? entry[1] : null;
+  }
+
+  getAll(name) {
+    name = String(name);
+    return this.#list.filter(([n]) => n === name).map(([, v]) => v);
+  }
+
+  has(name, value) {
+    name = String(name);
+    value = value === undefined ? undefined : String(value);
+    return this.#list.some(([n, v]) =>
+      n === name && (value === undefined || v === value)
+    );
+  }
+
+  set(name, value) {
+    name = String(name);
+    value = String(value);
+    const index = this.#list.findIndex(([n]) => n === name);
+    if (index === -1) {
+      this.#list.push([name, value]);
+    } else {
+      this.#list[index][1] = value;
+      this.#list = this.#list.filter(([n], i) => n !== name || i <= index);
+    }
+    this.#update();
+  }
+
+  sort() {
+    // Array.prototype.sort is stable and compares UTF-16 code units
+    this.#list.sort(([a], [b]) => (a < b ? -1 : a > b ? 1 : 0));
+    this.#update();
+  }
+
+  forEach(callback, thisArg) {
+    for (const [name, value] of this.#list) {
+      callback.call(thisArg, value, name, this);
+    }
+  }
+
+  *entries() {
+    for (const [name, value] of this.#list) {
+      yield [name, value];
+    }
+  }
+
+  *keys() {
+    for (const [name] of this.#list) {
+      yield name;
+    }
+  }
+
+  *values() {
+    for (const [, value] of this.#list) {
+      yield value;
+    }
+  }
+
+  [Symbol.iterator]() {
+    return this.entries();
+  }
+
+  toString() {
+    return __internal.url.serializeSearchParams(JSON.stringify(this.#list));
+  }
+
+  get [Symbol.toStringTag]() {
+    return "URLSearchParams";
+  }
+
+  // Keep the owning URL's query in sync
+  #update() {
+    if (this.#url !== null) {
+      const query = this.toString();
+      setURLSearch(this.#url, query);
+    }
+  }
+
+  static {
+    attachURL = (params, url) => {
+      params.#url = url;
+    };
+    resetList = (params, search) => {
+      params.#list = parseQuery(search);
+    };
+  }
+}
+
+function parseQuery(query) {
+  if (query.startsWith("?")) {
+    query = query.slice(1);
+  }
+  return JSON.parse(__internal.url.parseSearchParams(query));
 }
 
 globalThis.URL = URL;
+globalThis.URLSearchParams = URLSearchParams;
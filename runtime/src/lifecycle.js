@@ -0,0 +1,114 @@
+// Process lifecycle events dispatched on globalThis
+// load, beforeunload, unload, error and unhandledrejection
+const __internal = globalThis[Symbol.for("mdeno.internal")];
+
+// Events are only dispatched when web_events is enabled
+const events = __internal.events;
+
+if (events) {
+  Object.setPrototypeOf(globalThis, EventTarget.prototype);
+  events.setEventTargetData(globalThis);
+  for (
+    const name of [
+      "load",
+      "beforeunload",
+      "unload",
+      "error",
+      "unhandledrejection",
+    ]
+  ) {
+    events.defineEventHandler(globalThis, name);
+  }
+}
+
+// Returns false when a listener called preventDefault()
+function dispatch(EventClass, type, init = {}) {
+  if (!events) return true;
+  return events.dispatchTrusted(globalThis, new EventClass(type, init));
+}
+
+function formatError(error) {
+  if (error instanceof Error) {
+    const header = `${error.name}: ${error.message}`;
+    return error.stack ? `${header}\n${error.stack}` : header;
+  }
+  try {
+    return typeof error === "string" ? error : JSON.stringify(error);
+  } catch {
+    return String(error);
+  }
+}
+
+// Promises rejected without a handler, in rejection order
+const pendingRejections = new Map();
+let unloaded = false;
+
+__internal.lifecycle = {
+  dispatchLoad() {
+    dispatch(globalThis.Event, "load");
+  },
+
+  // Returns true when a listener asked to keep the event loop alive
+  dispatchBeforeUnload() {
+    return !dispatch(globalThis.Event, "beforeunload", { cancelable: true });
+  },
+
+  dispatchUnload() {
+    if (unloaded) return;
+    unloaded = true;
+    dispatch(globalThis.Event, "unload");
+  },
+
+  // Returns true when the error was handled by a listener
+  dispatchError(error) {
+    // A top-level error also rejects the module evaluation promise; report it once
+    for (const [promise, reason] of pendingRejections) {
+      if (reason === error) {
+        pendingRejections.delete(promise);
+      }
+    }
+
+    return !dispatch(globalThis.ErrorEvent, "error", {
+      cancelable: true,
+      error,
+      message: error instanceof Error ? error.message : String(error),
+    });
+  },
+
+  // Exceptions thrown by event listeners terminate the process unless handled
+  reportException(error) {
+    if (!this.dispatchError(error)) {
+      __internal.terminate(`Uncaught ${formatError(error)}`);
+    }
+  },
+
+  trackRejection(promise, reason, handled) {
+    if (handled) {
+      pendingRejections.delete(promise);
+    } else {
+      pendingRejections.set(promise, reason);
+    }
+  },
+
+  // Returns a report of the first rejection nobody handled, or null
+  processRejections() {
+    while (pendingRejections.size > 0) {
+      const [promise, reason] = pendingRejections.entries().next().value;
+      pendingRejections.delete(promise);
+
+      const prevented = !dispatch(
+        globalThis.PromiseRejectionEvent,
+        "unhandledrejection",
+        { cancelable: true, promise, reason },
+      );
+      if (!prevented) {
+        return `Uncaught (in promise) ${formatError(reason)}`;
+      }
+    }
+    return null;
+  },
+};
+
+globalThis.reportError = function reportError(error) {
+  __internal.lifecycle.reportException(error);
+};
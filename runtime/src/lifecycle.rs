@@ -0,0 +1,61 @@
+use rquickjs::{Ctx, Function, Module, Object, Result, Runtime, Value};
+use utils::add_internal_function;
+
+/// Route promise rejections into the lifecycle module. Must be called before the
+/// runtime is locked by a context.
+pub fn set_rejection_tracker(runtime: &Runtime) {
+    runtime.set_host_promise_rejection_tracker(Some(Box::new(
+        |ctx: Ctx<'_>, promise: Value<'_>, reason: Value<'_>, is_handled: bool| {
+            let _ = call::<()>(&ctx, "trackRejection", (promise, reason, is_handled));
+        },
+    )));
+}
+
+/// Evaluate the lifecycle event support on globalThis.
+pub fn init(ctx: &Ctx<'_>) -> Result<()> {
+    add_internal_function!(ctx, "terminate", |report: String| -> i32 {
+        eprintln!("Error: {}", report);
+        std::process::exit(1);
+    });
+
+    let module = Module::evaluate(ctx.clone(), "lifecycle", include_str!("lifecycle.js"))?;
+    module.finish::<()>()?;
+    Ok(())
+}
+
+pub fn dispatch_load(ctx: &Ctx<'_>) -> Result<()> {
+    call(ctx, "dispatchLoad", ())
+}
+
+/// Returns true when a listener called `preventDefault()` to keep the process alive.
+pub fn dispatch_before_unload(ctx: &Ctx<'_>) -> Result<bool> {
+    call(ctx, "dispatchBeforeUnload", ())
+}
+
+pub fn dispatch_unload(ctx: &Ctx<'_>) -> Result<()> {
+    call(ctx, "dispatchUnload", ())
+}
+
+/// Returns true when a listener called `preventDefault()` on the error event.
+pub fn dispatch_error<'js>(ctx: &Ctx<'js>, error: Value<'js>) -> Result<bool> {
+    call(ctx, "dispatchError", (error,))
+}
+
+/// Dispatch `unhandledrejection` for rejections still unhandled after the job queue
+/// drained. Returns a report of the first one that no listener prevented.
+pub fn process_rejections(ctx: &Ctx<'_>) -> Result<Option<String>> {
+    call(ctx, "processRejections", ())
+}
+
+fn call<'js, R>(
+    ctx: &Ctx<'js>,
+    name: &str,
+    args: impl rquickjs::function::IntoArgs<'js>,
+) -> Result<R>
+where
+    R: rquickjs::FromJs<'js>,
+{
+    let lifecycle: Object = ctx.eval("globalThis[Symbol.for('mdeno.internal')].lifecycle")?;
+    let func: Function = lifecycle.get(name)?;
+    func.call(args)
+}
@@ -1,18 +1,22 @@
 use clap_lex::RawArgs;
-use rquickjs::{CatchResultExt, CaughtError, Context, Module, Runtime};
+use rquickjs::{CatchResultExt, CaughtError, Context, Ctx, Module, Object, Promise, Runtime};
 use std::error::Error;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use url::Url;
 
+mod lifecycle;
 mod module_builder;
 
 const SECTION_NAME: &str = "mdeno_js";
 
 fn main() -> Result<(), Box<dyn Error>> {
     // Check if this executable has embedded JavaScript code and no arguments
-    if std::env::args().len() == 1 {
-        if let Ok(embedded_code) = extract_embedded_js() {
-            return run_js_code(&embedded_code);
-        }
+    if std::env::args().len() == 1
+        && let Ok(embedded_code) = extract_embedded_js()
+    {
+        return run_js_code(&embedded_code);
     }
 
     let raw = RawArgs::from_args();
@@ -22,37 +26,37 @@ fn main() -> Result<(), Box<dyn Error>> {
     let mut file_path: Option<String> = None;
     let mut is_compile = false;
 
-    if let Some(arg) = raw.next(&mut cursor) {
-        if let Ok(value) = arg.to_value() {
-            match value {
-                "compile" => {
-                    is_compile = true;
-                    if let Some(file_arg) = raw.next(&mut cursor) {
-                        if let Ok(file_value) = file_arg.to_value() {
-                            file_path = Some(file_value.to_string());
-                        }
-                    }
-                }
-                "run" => {
-                    if let Some(file_arg) = raw.next(&mut cursor) {
-                        if let Ok(file_value) = file_arg.to_value() {
-                            file_path = Some(file_value.to_string());
-                        }
-                    }
+    if let Some(arg) = raw.next(&mut cursor)
+        && let Ok(value) = arg.to_value()
+    {
+        match value {
+            "compile" => {
+                is_compile = true;
+                if let Some(file_arg) = raw.next(&mut cursor)
+                    && let Ok(file_value) = file_arg.to_value()
+                {
+                    file_path = Some(file_value.to_string());
                 }
-                _ if !value.starts_with('-') => {
-                    // No subcommand, treat as file path (run mode)
-                    file_path = Some(value.to_string());
+            }
+            "run" => {
+                if let Some(file_arg) = raw.next(&mut cursor)
+                    && let Ok(file_value) = file_arg.to_value()
+                {
+                    file_path = Some(file_value.to_string());
                 }
-                _ => {}
             }
+            _ if !value.starts_with('-') => {
+                // No subcommand, treat as file path (run mode)
+                file_path = Some(value.to_string());
+            }
+            _ => {}
         }
     }
 
     let file_path = file_path.ok_or("JavaScript file is required")?;
 
     // Convert file path to absolute path
-    let file_path_buf = std::path::Path::new(&file_path);
+    let file_path_buf = Path::new(&file_path);
     let absolute_file_path = if file_path_buf.is_absolute() {
         file_path_buf.to_path_buf()
     } else {
@@ -62,7 +66,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     // Convert to native path separator
     let absolute_file_path_str = absolute_file_path
         .components()
-        .collect::<std::path::PathBuf>()
+        .collect::<PathBuf>()
         .display()
         .to_string();
 
@@ -83,12 +87,25 @@ fn main() -> Result<(), Box<dyn Error>> {
 }
 
 fn run_js_code(js_code: &str) -> Result<(), Box<dyn Error>> {
-    run_js_code_with_path(js_code, "")
+    let script_path = embedded_script_path()?;
+    run_js_code_with_path(js_code, &script_path)
+}
+
+/// Embedded code is treated as a `<name>.js` file living next to the executable,
+/// so relative resolution and self-location work for compiled tools.
+fn embedded_script_path() -> Result<String, Box<dyn Error>> {
+    let exe_path = std::env::current_exe()?;
+    let stem = exe_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("main");
+    let dir = exe_path.parent().unwrap_or_else(|| Path::new("."));
+
+    Ok(dir.join(format!("{}.js", stem)).display().to_string())
 }
 
 fn run_js_code_with_path(js_code: &str, script_path: &str) -> Result<(), Box<dyn Error>> {
     use module_builder::ModuleBuilder;
-    use std::sync::Arc;
 
     smol::block_on(async {
         let runtime = Runtime::new()?;
@@ -102,45 +119,39 @@ fn run_js_code_with_path(js_code: &str, script_path: &str) -> Result<(), Box<dyn
             module_builder::NodeResolver::new(registry.clone()),
             module_builder::NodeLoader::new(registry.clone()),
         );
+        lifecycle::set_rejection_tracker(&runtime);
 
         let context = Context::full(&runtime)?;
 
         context.with(|ctx| -> Result<(), Box<dyn Error>> {
-            setup_extensions(&ctx, script_path)?;
-
-            let effective_path = if script_path.is_empty() {
-                "./$mdeno$eval.js"
-            } else {
-                script_path
-            };
-
-            let result = {
-                Module::evaluate(ctx.clone(), effective_path, js_code)
-                    .and_then(|m| m.finish::<()>())
-            };
-
-            if let Err(caught) = result.catch(&ctx) {
-                match caught {
-                    CaughtError::Exception(exception) => {
-                        if let Some(message) = exception.message() {
-                            eprintln!("Error: {}", message);
-                        }
-                        if let Some(stack) = exception.stack() {
-                            eprintln!("{}", stack);
-                        }
-                    }
-                    CaughtError::Value(value) => {
-                        eprintln!("Error: {:?}", value);
-                    }
-                    CaughtError::Error(error) => {
-                        eprintln!("Error: {:?}", error);
-                    }
+            let main_url = Url::from_file_path(script_path)
+                .map_err(|_| format!("Invalid script path: {}", script_path))?;
+
+            setup_extensions(&ctx, &main_url)?;
+
+            let result = Module::declare(ctx.clone(), script_path, js_code).and_then(|module| {
+                module_builder::setup_import_meta(&module.meta()?, &main_url, true, &registry)?;
+                let (_module, promise) = module.eval()?;
+                finish_module(&ctx, &promise)
+            });
+
+            handle_uncaught(&ctx, result);
+
+            let load = lifecycle::dispatch_load(&ctx);
+            handle_uncaught(&ctx, load);
+            run_event_loop(&ctx);
+
+            // Keep running while beforeunload listeners call preventDefault()
+            loop {
+                match lifecycle::dispatch_before_unload(&ctx).catch(&ctx) {
+                    Ok(true) => run_event_loop(&ctx),
+                    Ok(false) => break,
+                    Err(caught) => report_uncaught(&ctx, caught),
                 }
-                std::process::exit(1);
             }
 
-            // Execute all pending jobs (promises, microtasks)
-            while ctx.execute_pending_job() {}
+            let unload = lifecycle::dispatch_unload(&ctx);
+            handle_uncaught(&ctx, unload);
 
             Ok(())
         })?;
@@ -149,6 +160,112 @@ fn run_js_code_with_path(js_code: &str, script_path: &str) -> Result<(), Box<dyn
     })
 }
 
+/// Execute all pending jobs (promises, microtasks) and expired timers, then report
+/// rejections that are still unhandled once the queue has drained.
+fn run_event_loop(ctx: &Ctx) {
+    loop {
+        loop {
+            run_timers(ctx);
+            if !ctx.execute_pending_job() {
+                break;
+            }
+        }
+
+        match lifecycle::process_rejections(ctx).catch(ctx) {
+            Ok(Some(report)) => {
+                eprintln!("Error: {}", report);
+                std::process::exit(1);
+            }
+            Ok(None) => {}
+            Err(caught) => report_uncaught(ctx, caught),
+        }
+
+        if ctx.execute_pending_job() {
+            continue;
+        }
+        match next_timer(ctx) {
+            Some(wait) => std::thread::sleep(wait),
+            None => break,
+        }
+    }
+}
+
+/// Run jobs and timers until the evaluation `promise` of a module settles, so
+/// top-level await can wait for timers.
+fn finish_module<'js>(ctx: &Ctx<'js>, promise: &Promise<'js>) -> rquickjs::Result<()> {
+    loop {
+        if let Some(result) = promise.result() {
+            return result;
+        }
+        run_timers(ctx);
+        if ctx.execute_pending_job() {
+            continue;
+        }
+        match next_timer(ctx) {
+            Some(wait) => std::thread::sleep(wait),
+            None => return Err(rquickjs::Error::WouldBlock),
+        }
+    }
+}
+
+#[cfg(feature = "events")]
+fn run_timers(ctx: &Ctx) {
+    handle_uncaught(ctx, web_events::run_timers(ctx));
+}
+
+#[cfg(not(feature = "events"))]
+fn run_timers(_ctx: &Ctx) {}
+
+/// Time until the next timer that keeps the process alive.
+#[cfg(feature = "events")]
+fn next_timer(ctx: &Ctx) -> Option<std::time::Duration> {
+    web_events::next_timer(ctx).catch(ctx).ok().flatten()
+}
+
+#[cfg(not(feature = "events"))]
+fn next_timer(_ctx: &Ctx) -> Option<std::time::Duration> {
+    None
+}
+
+fn handle_uncaught<T>(ctx: &Ctx<'_>, result: rquickjs::Result<T>) {
+    if let Err(caught) = result.catch(ctx) {
+        report_uncaught(ctx, caught);
+    }
+}
+
+/// Dispatch an `error` event for an uncaught exception and terminate the process
+/// unless a listener called `preventDefault()`.
+fn report_uncaught<'js>(ctx: &Ctx<'js>, caught: CaughtError<'js>) {
+    let error = match &caught {
+        CaughtError::Exception(exception) => Some(exception.clone().into_object().into_value()),
+        CaughtError::Value(value) => Some(value.clone()),
+        CaughtError::Error(_) => None,
+    };
+    if let Some(error) = error
+        && let Ok(true) = lifecycle::dispatch_error(ctx, error)
+    {
+        return;
+    }
+
+    match caught {
+        CaughtError::Exception(exception) => {
+            if let Some(message) = exception.message() {
+                eprintln!("Error: {}", message);
+            }
+            if let Some(stack) = exception.stack() {
+                eprintln!("{}", stack);
+            }
+        }
+        CaughtError::Value(value) => {
+            eprintln!("Error: {:?}", value);
+        }
+        CaughtError::Error(error) => {
+            eprintln!("Error: {:?}", error);
+        }
+    }
+    std::process::exit(1);
+}
+
 fn compile_js_to_executable(js_file: &str, output_name: &str) -> Result<(), Box<dyn Error>> {
     let js_code = fs::read_to_string(js_file)?;
 
@@ -182,20 +299,31 @@ fn compile_js_to_executable(js_file: &str, output_name: &str) -> Result<(), Box<
             .build(&mut output_file)?;
     }
 
+    // libsui locates the ELF payload through a trailer at the very end of the file,
+    // so the magic string has to go in front of the appended section
     #[cfg(target_os = "linux")]
     {
         use libsui::Elf;
+        let mut exe_bytes = exe_bytes;
+        exe_bytes.extend_from_slice(b"md3n04cl1");
         let elf = Elf::new(&exe_bytes);
         elf.append(SECTION_NAME, js_code.as_bytes(), &mut output_file)?;
     }
 
     // Append magic string "md3n04cl1" to mark this as a standalone binary
+    #[cfg(not(target_os = "linux"))]
     {
         use std::io::Write;
         let mut output_file = fs::OpenOptions::new().append(true).open(&output_exe)?;
         output_file.write_all(b"md3n04cl1")?;
     }
 
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&output_exe, fs::Permissions::from_mode(0o755))?;
+    }
+
     let file_size = fs::metadata(&output_exe)?.len();
     let size_mb = file_size as f64 / 1024.0 / 1024.0;
 
@@ -214,9 +342,8 @@ fn extract_embedded_js() -> Result<String, Box<dyn Error>> {
     Ok(js_code)
 }
 
-fn setup_extensions(ctx: &rquickjs::Ctx, _script_path: &str) -> Result<(), Box<dyn Error>> {
+fn setup_extensions(ctx: &Ctx, main_url: &Url) -> Result<(), Box<dyn Error>> {
     use module_builder::ModuleBuilder;
-    use rquickjs::Module;
 
     // Initialize mdeno namespace with internal object as a module
     let module = Module::evaluate(
@@ -227,16 +354,36 @@ fn setup_extensions(ctx: &rquickjs::Ctx, _script_path: &str) -> Result<(), Box<d
         globalThis.__mdeno__ = {
             fs: {},
             os: {},
+            fetch: {},
+            net: {},
         };
         "#,
     )
     .map_err(|e| format!("Failed to create __mdeno__ namespace: {:?}", e))?;
     module.finish::<()>()?;
 
+    // Runtime metadata exposed as Deno.version and Deno.mainModule
+    let internal: Object = ctx.eval("globalThis[Symbol.for('mdeno.internal')]")?;
+    let version = Object::new(ctx.clone())?;
+    version.set("mdeno", env!("CARGO_PKG_VERSION"))?;
+    version.set("quickjs", quickjs_version())?;
+    internal.set("version", version)?;
+    internal.set("mainModule", main_url.as_str())?;
+
     // Build module configuration using default (feature-based)
     let builder = ModuleBuilder::default();
     let (global_attachment, _module_registry) = builder.build();
     global_attachment.attach(ctx)?;
 
+    // Lifecycle events are dispatched through web_events, so they come last
+    lifecycle::init(ctx)?;
+
     Ok(())
 }
+
+fn quickjs_version() -> String {
+    // SAFETY: JS_GetVersion returns a pointer to a static NUL-terminated string
+    unsafe { std::ffi::CStr::from_ptr(rquickjs::qjs::JS_GetVersion()) }
+        .to_string_lossy()
+        .into_owned()
+}
@@ -1,11 +1,14 @@
 use rquickjs::loader::{Loader, Resolver};
-use rquickjs::{Ctx, Error, Module, Result};
+use rquickjs::{Ctx, Error, Function, Module, Object, Result};
 use std::collections::HashMap;
 use std::sync::Arc;
+use url::Url;
 use utils::ModuleDef;
 
+type GlobalInit = Box<dyn Fn(&Ctx<'_>) -> Result<()>>;
+
 pub struct ModuleBuilder {
-    globals: Vec<Box<dyn Fn(&Ctx<'_>) -> Result<()>>>,
+    globals: Vec<GlobalInit>,
     module_sources: HashMap<&'static str, fn() -> &'static str>,
 }
 
@@ -44,6 +47,15 @@ impl Default for ModuleBuilder {
     fn default() -> Self {
         let mut builder = Self::new();
 
+        // Initialize events first, other modules build on EventTarget and DOMException
+        #[cfg(feature = "events")]
+        {
+            builder = builder.with_global(web_events::init);
+        }
+        #[cfg(feature = "streams")]
+        {
+            builder = builder.with_global(web_streams::init);
+        }
         #[cfg(feature = "console")]
         {
             builder = builder.with_global(web_console::init);
@@ -56,6 +68,10 @@ impl Default for ModuleBuilder {
         {
             builder = builder.with_global(web_encoding::init);
         }
+        #[cfg(feature = "crypto-core")]
+        {
+            builder = builder.with_global(web_crypto::init);
+        }
         #[cfg(any(feature = "fetch", feature = "fetch-rustls"))]
         {
             builder = builder.with_global(web_fetch::init);
@@ -67,17 +83,21 @@ impl Default for ModuleBuilder {
             builder = builder.with_global(web_navigator::init);
         }
 
-        // Initialize file system and OS modules
+        // Initialize file system, network and OS modules
         #[cfg(feature = "deno_fs")]
         {
             builder = builder.with_global(deno_fs::init);
         }
+        #[cfg(feature = "deno_net")]
+        {
+            builder = builder.with_global(deno_net::init);
+        }
         #[cfg(feature = "deno_os")]
         {
             builder = builder.with_global(deno_os::init);
         }
 
-        // Initialize Deno namespace (depends on deno_fs and deno_os)
+        // Initialize Deno namespace (depends on deno_fs, deno_net and deno_os)
         #[cfg(feature = "deno_ns")]
         {
             builder = builder.with_global(deno_ns::init);
@@ -88,7 +108,7 @@ impl Default for ModuleBuilder {
 }
 
 pub struct GlobalAttachment {
-    globals: Vec<Box<dyn Fn(&Ctx<'_>) -> Result<()>>>,
+    globals: Vec<GlobalInit>,
 }
 
 impl GlobalAttachment {
@@ -151,6 +171,70 @@ impl Loader for NodeLoader {
             .get_source(name)
             .ok_or_else(|| Error::new_loading(name))?;
 
-        Module::declare(ctx.clone(), name, source)
+        let module = Module::declare(ctx.clone(), name, source)?;
+        if let Ok(url) = Url::parse(name).or_else(|_| Url::from_file_path(name)) {
+            setup_import_meta(&module.meta()?, &url, false, &self.registry)?;
+        }
+        Ok(module)
     }
 }
+
+/// Populate `import.meta` of the module at `url`.
+pub fn setup_import_meta<'js>(
+    meta: &Object<'js>,
+    url: &Url,
+    main: bool,
+    registry: &Arc<ModuleRegistry>,
+) -> Result<()> {
+    meta.set("url", url.as_str())?;
+    meta.set("main", main)?;
+
+    // Only local modules have a file name
+    if let Ok(path) = url.to_file_path() {
+        let dirname = path
+            .parent()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        meta.set("filename", path.display().to_string())?;
+        meta.set("dirname", dirname)?;
+    }
+
+    let base = url.clone();
+    let registry = registry.clone();
+    let resolve = Function::new(
+        meta.ctx().clone(),
+        move |ctx: Ctx<'js>, specifier: String| {
+            resolve_specifier(&base, &specifier, &registry)
+                .map_err(|message| rquickjs::Exception::throw_type(&ctx, &message))
+        },
+    )?
+    .with_name("resolve")?;
+    meta.set("resolve", resolve)?;
+
+    Ok(())
+}
+
+/// Resolve a module specifier against the URL of the importing module.
+fn resolve_specifier(
+    base: &Url,
+    specifier: &str,
+    registry: &ModuleRegistry,
+) -> std::result::Result<String, String> {
+    if registry.has_module(specifier) {
+        return Ok(specifier.to_string());
+    }
+
+    if specifier.starts_with("./") || specifier.starts_with("../") || specifier.starts_with('/') {
+        return base
+            .join(specifier)
+            .map(String::from)
+            .map_err(|e| format!("Invalid module specifier \"{}\": {}", specifier, e));
+    }
+
+    Url::parse(specifier).map(String::from).map_err(|_| {
+        format!(
+            "Relative import path \"{}\" not prefixed with / or ./ or ../",
+            specifier
+        )
+    })
+}
@@ -0,0 +1,191 @@
+//! AbortController and AbortSignal, and fetch cancellation.
+
+mod common;
+
+use common::{TIMEOUT, mdeno, run, serve};
+use std::io::Write;
+use std::time::Instant;
+
+#[test]
+fn abort_controller() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const controller = new AbortController();
+        const { signal } = controller;
+        let events = 0;
+        signal.onabort = (event) => {
+          events++;
+          console.log(event.type, event.isTrusted, signal.aborted);
+        };
+        console.log(signal.aborted, signal.reason);
+        controller.abort();
+        controller.abort("again");
+        console.log(events, signal.reason.name, signal.reason instanceof DOMException);
+        try {
+          signal.throwIfAborted();
+        } catch (error) {
+          console.log("thrown", error === signal.reason);
+        }
+
+        const custom = AbortSignal.abort("custom");
+        console.log(custom.aborted, custom.reason);
+        try {
+          new AbortSignal();
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "false undefined",
+            "abort true true",
+            "1 AbortError true",
+            "thrown true",
+            "true custom",
+            "TypeError"
+        ]
+    );
+}
+
+#[test]
+fn abort_signal_timeout() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const started = Date.now();
+        const signal = AbortSignal.timeout(50);
+        console.log(signal.aborted);
+        await new Promise((resolve) => signal.addEventListener("abort", resolve));
+        console.log(signal.reason.name, signal.reason.code, Date.now() - started >= 50);
+        try {
+          AbortSignal.timeout(-1);
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["false", "TimeoutError 23 true", "TypeError"]
+    );
+}
+
+#[test]
+fn unobserved_timeout_does_not_keep_process_alive() {
+    let started = Instant::now();
+    let stdout = run(&mut mdeno(
+        r#"
+        const signal = AbortSignal.timeout(60_000);
+        console.log("done", signal.aborted);
+        "#,
+    ));
+
+    assert_eq!(stdout.trim(), "done false");
+    assert!(started.elapsed() < TIMEOUT);
+}
+
+#[test]
+fn abort_signal_any() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const first = new AbortController();
+        const second = new AbortController();
+        const any = AbortSignal.any([first.signal, second.signal]);
+        const nested = AbortSignal.any([any]);
+        any.onabort = () => console.log("any", any.reason, nested.aborted);
+        nested.onabort = () => console.log("nested", nested.reason);
+        second.abort("second");
+        first.abort("first");
+        console.log(any.reason);
+
+        const aborted = AbortSignal.any([new AbortController().signal, AbortSignal.abort("early")]);
+        console.log(aborted.aborted, aborted.reason);
+
+        const timeout = AbortSignal.any([AbortSignal.timeout(20)]);
+        await new Promise((resolve) => timeout.onabort = resolve);
+        console.log(timeout.reason.name);
+        try {
+          AbortSignal.any([{}]);
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "any second true",
+            "nested second",
+            "second",
+            "true early",
+            "TimeoutError",
+            "TypeError"
+        ]
+    );
+}
+
+#[test]
+fn fetch_aborted_by_timeout() {
+    // The server never answers
+    let (port, _) = serve(|_, _| std::thread::sleep(TIMEOUT));
+    let started = Instant::now();
+    let stdout = run(mdeno(
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}/`;
+        try {
+          await fetch(url, { signal: AbortSignal.timeout(100) });
+          console.log("fetched");
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    )
+    .env("PORT", port.to_string()));
+
+    assert_eq!(stdout.trim(), "TimeoutError");
+    assert!(started.elapsed() < TIMEOUT);
+}
+
+#[test]
+fn fetch_body_aborted_by_controller() {
+    // The body starts, then stalls
+    let (port, _) = serve(|_, stream| {
+        let _ = stream.write_all(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n5\r\nhello\r\n",
+        );
+        let _ = stream.flush();
+        std::thread::sleep(TIMEOUT);
+    });
+    let stdout = run(mdeno(
+        r#"
+        const controller = new AbortController();
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}/`;
+        const res = await fetch(url, { signal: controller.signal });
+        const reader = res.body.getReader();
+        const { value } = await reader.read();
+        console.log(new TextDecoder().decode(value));
+        controller.abort();
+        try {
+          await reader.read();
+          console.log("read");
+        } catch (error) {
+          console.log(error.name);
+        }
+        try {
+          await fetch(url, { signal: controller.signal });
+        } catch (error) {
+          console.log("already", error.name);
+        }
+        "#,
+    )
+    .env("PORT", port.to_string()));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["hello", "AbortError", "already AbortError"]
+    );
+}
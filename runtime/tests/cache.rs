@@ -0,0 +1,237 @@
+//! Cache Storage and the HTTP cache of fetch, stored in a fresh DENO_DIR.
+
+mod common;
+
+use common::{header, mdeno, run, serve};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A command running `source` with its own empty DENO_DIR, removed when dropped.
+struct Isolated {
+    command: Command,
+    dir: PathBuf,
+}
+
+impl Isolated {
+    fn new(source: &str, test: &str) -> Isolated {
+        let dir = std::env::temp_dir().join(format!(
+            "mdeno-test-{}-{}-deno-dir",
+            std::process::id(),
+            test
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut command = mdeno(source);
+        command.env("DENO_DIR", &dir);
+        Isolated { command, dir }
+    }
+
+    fn env(mut self, key: &str, value: String) -> Isolated {
+        self.command.env(key, value);
+        self
+    }
+
+    fn run(&mut self) -> Vec<String> {
+        run(&mut self.command).lines().map(str::to_string).collect()
+    }
+}
+
+impl Drop for Isolated {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn cache_storage() {
+    let output = Isolated::new(
+        r#"
+        // Names of any length are kept as given
+        const name = "é".repeat(200);
+        const cache = await caches.open(name);
+        await caches.open("other");
+        console.log(JSON.stringify((await caches.keys()).map((key) => key.length)));
+        console.log(await caches.has(name), await caches.has("missing"));
+
+        const url = "https://example.com/page?x=1";
+        await cache.put(url, new Response("stored", { headers: { "x-a": "1" } }));
+        const match = await cache.match(url);
+        console.log(match.status, match.headers.get("x-a"), await match.text());
+        console.log(await cache.match("https://example.com/page"));
+        console.log((await cache.match("https://example.com/page", { ignoreSearch: true }))?.status);
+
+        // A new response for the URL replaces the stored one
+        await cache.put(new Request(url), new Response("replaced"));
+        console.log(await (await cache.match(url)).text());
+        await cache.put("https://example.com/second", new Response("second"));
+        console.log((await cache.keys()).map((request) => request.url).join(" "));
+
+        console.log(await cache.delete(url), await cache.delete(url));
+        console.log((await cache.keys()).length);
+        for (const options of [undefined, { method: "POST" }]) {
+          try {
+            await cache.put(new Request(url, options), new Response(""));
+          } catch (error) {
+            console.log(error.name);
+          }
+        }
+
+        console.log(await caches.delete(name), await caches.delete(name));
+        console.log(JSON.stringify(await caches.keys()));
+        "#,
+        "storage",
+    )
+    .run();
+
+    assert_eq!(
+        output,
+        [
+            "[5,200]",
+            "true false",
+            "200 1 stored",
+            "undefined",
+            "200",
+            "replaced",
+            "https://example.com/page?x=1 https://example.com/second",
+            "true false",
+            "1",
+            "TypeError",
+            "true false",
+            r#"["other"]"#
+        ]
+    );
+}
+
+#[test]
+fn cache_storage_vary() {
+    let output = Isolated::new(
+        r#"
+        const cache = await caches.open("vary");
+        const url = "https://example.com/";
+        const request = (language) =>
+          new Request(url, { headers: { "accept-language": language } });
+        const response = (body) =>
+          new Response(body, { headers: { vary: "Accept-Language" } });
+
+        // Entries are kept per value of the headers the response varies on
+        await cache.put(request("en"), response("english"));
+        await cache.put(request("fr"), response("french"));
+        console.log(await (await cache.match(request("en"))).text());
+        console.log(await (await cache.match(request("fr"))).text());
+        console.log(await cache.match(request("de")));
+        console.log((await cache.match(request("de"), { ignoreVary: true }))?.status);
+        const keys = await cache.keys();
+        console.log(keys.map((key) => key.headers.get("accept-language")).join(","));
+
+        await cache.put(request("en"), response("english again"));
+        console.log(await (await cache.match(request("en"))).text());
+        console.log((await cache.keys()).length);
+        console.log(await cache.delete(request("fr")), (await cache.keys()).length);
+        "#,
+        "vary",
+    )
+    .run();
+
+    assert_eq!(
+        output,
+        [
+            "english",
+            "french",
+            "undefined",
+            "200",
+            "en,fr",
+            "english again",
+            "2",
+            "true 1"
+        ]
+    );
+}
+
+#[test]
+fn http_cache() {
+    let requests = AtomicUsize::new(0);
+    let (port, heads) = serve(move |head, stream| {
+        let count = requests.fetch_add(1, Ordering::SeqCst) + 1;
+        let path = head.split(' ').nth(1).unwrap_or_default();
+        let reply = match path {
+            "/fresh" => format!("Cache-Control: max-age=60\r\n\r\n{}", count),
+            "/etag" if header(head, "if-none-match") == Some("\"v1\"") => {
+                let head =
+                    "HTTP/1.1 304 Not Modified\r\nX-Updated: yes\r\nConnection: close\r\n\r\n";
+                let _ = stream.write_all(head.as_bytes());
+                return;
+            }
+            "/etag" => "Cache-Control: no-cache\r\nETag: \"v1\"\r\n\r\netag body".to_string(),
+            _ => format!(
+                "Cache-Control: max-age=60\r\nVary: Accept-Language\r\n\r\n{}",
+                header(head, "accept-language").unwrap_or_default()
+            ),
+        };
+        let (headers, body) = reply.split_once("\r\n\r\n").unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\n{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            headers,
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes());
+    });
+
+    let output = Isolated::new(
+        r#"
+        const base = `http://127.0.0.1:${Deno.env.get("PORT")}`;
+        const get = (path, init = {}) => fetch(base + path, { cache: "default", ...init });
+
+        // Fresh responses are served from the cache
+        for (let i = 0; i < 2; i++) console.log(await (await get("/fresh")).text());
+        console.log(await (await get("/fresh", { cache: "reload" })).text());
+
+        // Stale responses are revalidated, and a 304 updates their headers
+        await (await get("/etag")).text();
+        const revalidated = await get("/etag");
+        console.log(revalidated.status, revalidated.headers.get("x-updated"));
+        console.log(await revalidated.text());
+
+        for (const language of ["en", "fr", "en"]) {
+          const init = { headers: { "accept-language": language } };
+          console.log(await (await get("/vary", init)).text());
+        }
+
+        try {
+          await get("/missing", { cache: "only-if-cached" });
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+        "http",
+    )
+    .env("PORT", port.to_string())
+    .run();
+
+    assert_eq!(
+        output,
+        [
+            "1",
+            "1",
+            "2",
+            "200 yes",
+            "etag body",
+            "en",
+            "fr",
+            "en",
+            "TypeError"
+        ]
+    );
+    let heads: Vec<_> = heads.try_iter().collect();
+    let paths: Vec<_> = heads
+        .iter()
+        .map(|head| head.split(' ').nth(1).unwrap_or_default())
+        .collect();
+    assert_eq!(
+        paths,
+        ["/fresh", "/fresh", "/etag", "/etag", "/vary", "/vary"]
+    );
+    assert_eq!(header(&heads[2], "if-none-match"), None);
+    assert_eq!(header(&heads[3], "if-none-match"), Some("\"v1\""));
+}
@@ -0,0 +1,203 @@
+//! Helpers shared by the integration tests, which run the mdeno binary against
+//! fixtures listening on loopback.
+#![allow(dead_code)]
+
+pub mod tls;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Longest a test waits for mdeno to answer
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Write `source` to a fresh script file and return its path.
+pub fn script(source: &str) -> PathBuf {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let path = std::env::temp_dir().join(format!(
+        "mdeno-test-{}-{}.js",
+        std::process::id(),
+        NEXT.fetch_add(1, Ordering::SeqCst)
+    ));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+/// A command running `source` with mdeno, without the proxy and fixture
+/// variables of the environment the tests run in.
+pub fn mdeno(source: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_mdeno"));
+    command.arg(script(source));
+    for name in [
+        "HTTP_PROXY",
+        "http_proxy",
+        "HTTPS_PROXY",
+        "https_proxy",
+        "NO_PROXY",
+        "no_proxy",
+        "MDENO_FETCH_RECORD",
+        "MDENO_FETCH_REPLAY",
+    ] {
+        command.env_remove(name);
+    }
+    command
+}
+
+/// Run `command` and return its standard output, failing the test with its
+/// standard error when it does not succeed.
+pub fn run(command: &mut Command) -> String {
+    let output = command.output().unwrap();
+    assert_success(&output);
+    String::from_utf8(output.stdout).unwrap()
+}
+
+pub fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "mdeno failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// A running mdeno, killed when dropped so that a failing test cannot leave it
+/// behind.
+pub struct Running {
+    child: Child,
+    stdout: Option<BufReader<ChildStdout>>,
+}
+
+impl Running {
+    /// Wait for the next line the process prints.
+    pub fn read_line(&mut self) -> String {
+        let mut line = String::new();
+        self.stdout.as_mut().unwrap().read_line(&mut line).unwrap();
+        line.trim_end().to_string()
+    }
+
+    /// Wait for the process to exit. Returns the rest of its standard output.
+    pub fn finish(mut self) -> String {
+        let mut stdout = self.stdout.take().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut rest = String::new();
+            let _ = stdout.read_to_string(&mut rest);
+            let _ = sender.send(rest);
+        });
+        let rest = receiver
+            .recv_timeout(TIMEOUT)
+            .expect("mdeno did not exit in time");
+        assert!(self.child.wait().unwrap().success());
+        rest
+    }
+}
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+    }
+}
+
+/// Start `command` with piped standard output and wait for the first line it
+/// prints.
+pub fn spawn(command: &mut Command) -> (Running, String) {
+    let mut child = command.stdout(Stdio::piped()).spawn().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    (
+        Running {
+            child,
+            stdout: Some(stdout),
+        },
+        line.trim_end().to_string(),
+    )
+}
+
+/// Read a request or response head, up to and including the empty line.
+pub fn read_head(stream: &mut impl Read) -> String {
+    let mut head = Vec::new();
+    let mut byte = [0u8];
+    while !head.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).unwrap() == 0 {
+            break;
+        }
+        head.push(byte[0]);
+    }
+    String::from_utf8_lossy(&head).into_owned()
+}
+
+/// Value of header `name` in `head`.
+pub fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Read the body following request `head`, sent with a Content-Length or chunked.
+pub fn read_body(head: &str, stream: &mut impl Read) -> Vec<u8> {
+    let mut body = Vec::new();
+    if let Some(length) = header(head, "content-length") {
+        body.resize(length.parse().unwrap(), 0);
+        stream.read_exact(&mut body).unwrap();
+    } else if header(head, "transfer-encoding") == Some("chunked") {
+        loop {
+            let size = read_line(stream);
+            let size = usize::from_str_radix(size.trim_end(), 16).unwrap();
+            let mut chunk = vec![0; size + 2];
+            stream.read_exact(&mut chunk).unwrap();
+            if size == 0 {
+                break;
+            }
+            body.extend_from_slice(&chunk[..size]);
+        }
+    }
+    body
+}
+
+fn read_line(stream: &mut impl Read) -> String {
+    let mut line = Vec::new();
+    let mut byte = [0u8];
+    while !line.ends_with(b"\r\n") && stream.read(&mut byte).unwrap() == 1 {
+        line.push(byte[0]);
+    }
+    String::from_utf8_lossy(&line).into_owned()
+}
+
+/// An HTTP/1.1 response closing the connection.
+pub fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+/// Serve each connection on loopback with `handle`, which gets the request head
+/// and the stream. Returns the port, and the request heads as they arrive.
+pub fn serve(
+    handle: impl Fn(&str, &mut TcpStream) + Send + 'static,
+) -> (u16, mpsc::Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (heads, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let head = read_head(&mut stream);
+            handle(&head, &mut stream);
+            let _ = stream.flush();
+            // The heads may not be wanted
+            let _ = heads.send(head);
+        }
+    });
+    (port, receiver)
+}
@@ -0,0 +1,73 @@
+//! TLS fixtures: a test CA, a server certificate for localhost and 127.0.0.1 and
+//! a client certificate, all issued by the CA.
+
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+
+/// Path of fixture `name`.
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/tls")
+        .join(name)
+}
+
+/// Contents of fixture `name`.
+pub fn pem(name: &str) -> String {
+    std::fs::read_to_string(path(name)).unwrap()
+}
+
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+/// Serve each connection on loopback over TLS with `handle`, which gets the
+/// stream once the handshake succeeded. With `client_auth`, clients must present
+/// a certificate issued by the test CA. Returns the port.
+pub fn serve(client_auth: bool, handle: impl Fn(&mut TlsStream) + Send + 'static) -> u16 {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .unwrap();
+    let builder = if client_auth {
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(CertificateDer::from_pem_file(path("ca.pem")).unwrap())
+            .unwrap();
+        let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+            .build()
+            .unwrap();
+        builder.with_client_cert_verifier(verifier)
+    } else {
+        builder.with_no_client_auth()
+    };
+    let config = Arc::new(
+        builder
+            .with_single_cert(
+                vec![CertificateDer::from_pem_file(path("server.pem")).unwrap()],
+                PrivateKeyDer::from_pem_file(path("server-key.pem")).unwrap(),
+            )
+            .unwrap(),
+    );
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut connection = ServerConnection::new(config.clone()).unwrap();
+            // Clients refused by the handshake are skipped
+            let mut handshake = Ok((0, 0));
+            while handshake.is_ok() && connection.is_handshaking() {
+                handshake = connection.complete_io(&mut stream);
+            }
+            if handshake.is_ok() {
+                handle(&mut StreamOwned::new(connection, stream));
+            }
+        }
+    });
+    port
+}
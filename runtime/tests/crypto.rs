@@ -0,0 +1,377 @@
+//! Web Crypto against published known answers, and key round-trips.
+
+mod common;
+
+use common::{mdeno, run};
+use std::path::PathBuf;
+
+// Helpers for scripts: hex conversions, and the SubtleCrypto
+const PRELUDE: &str = r#"
+const subtle = crypto.subtle;
+const hex = (buffer) =>
+  [...new Uint8Array(buffer)].map((b) => b.toString(16).padStart(2, "0")).join("");
+const bytes = (hex) => new Uint8Array(hex.match(/../g)?.map((b) => parseInt(b, 16)) ?? []);
+const utf8 = (text) => new TextEncoder().encode(text);
+"#;
+
+fn lines(source: &str) -> Vec<String> {
+    run(&mut mdeno(&format!("{}{}", PRELUDE, source)))
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn digests() {
+    // FIPS 180-2, appendix B
+    let output = lines(
+        r#"
+        for (const hash of ["SHA-1", "SHA-256", "SHA-384", "SHA-512"]) {
+          console.log(hex(await subtle.digest(hash, utf8("abc"))));
+        }
+        console.log(hex(await subtle.digest("SHA-256", new Uint8Array())));
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "cb00753f45a35e8bb5a03d699ac65007272c32ab0eded1631a8b605a43ff5bed8086072ba1e7cc2358baeca134c825a7",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        ]
+    );
+}
+
+#[test]
+fn hmac() {
+    // RFC 4231, test cases 1 and 2
+    let output = lines(
+        r#"
+        const cases = [
+          ["0b".repeat(20), "Hi There", "SHA-256"],
+          ["0b".repeat(20), "Hi There", "SHA-384"],
+          ["4a656665", "what do ya want for nothing?", "SHA-256"],
+          ["4a656665", "what do ya want for nothing?", "SHA-512"],
+        ];
+        for (const [key, data, hash] of cases) {
+          const algorithm = { name: "HMAC", hash };
+          const imported = await subtle.importKey("raw", bytes(key), algorithm, false, [
+            "sign",
+            "verify",
+          ]);
+          const signature = await subtle.sign("HMAC", imported, utf8(data));
+          console.log(hex(signature));
+          console.log(
+            await subtle.verify("HMAC", imported, signature, utf8(data)),
+            await subtle.verify("HMAC", imported, signature, utf8(data + ".")),
+          );
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7",
+            "true false",
+            "afd03944d84895626b0825f4ab46907f15f9dadbe4101ec682aa034c7cebc59cfaea9ea9076ede7f4af152e8b2fa9cb6",
+            "true false",
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843",
+            "true false",
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737",
+            "true false"
+        ]
+    );
+}
+
+#[test]
+fn aes() {
+    let output = lines(
+        r#"
+        const key = async (hex, name) =>
+          await subtle.importKey("raw", bytes(hex), name, false, ["encrypt", "decrypt"]);
+        const roundTrip = async (algorithm, key, data) => {
+          const encrypted = await subtle.encrypt(algorithm, key, data);
+          const decrypted = await subtle.decrypt(algorithm, key, encrypted);
+          console.log(hex(encrypted), hex(decrypted) === hex(data));
+          return encrypted;
+        };
+
+        // The GCM specification of McGrew and Viega, test cases 1 and 2, the tag
+        // following the ciphertext
+        const gcm = await key("00".repeat(16), "AES-GCM");
+        const iv = new Uint8Array(12);
+        await roundTrip({ name: "AES-GCM", iv }, gcm, new Uint8Array());
+        const sealed = await roundTrip({ name: "AES-GCM", iv }, gcm, new Uint8Array(16));
+        new Uint8Array(sealed)[0] ^= 1;
+        try {
+          await subtle.decrypt({ name: "AES-GCM", iv }, gcm, sealed);
+        } catch (error) {
+          console.log(error.name);
+        }
+
+        // NIST SP 800-38A, F.2.1 and F.5.1
+        const nist = "2b7e151628aed2a6abf7158809cf4f3c";
+        const plaintext = bytes(
+          "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51",
+        );
+        const cbc = await key(nist, "AES-CBC");
+        const cbcIv = bytes("000102030405060708090a0b0c0d0e0f");
+        // PKCS#7 padding adds a block
+        const padded = await roundTrip({ name: "AES-CBC", iv: cbcIv }, cbc, plaintext);
+        console.log(padded.byteLength);
+        const ctr = await key(nist, "AES-CTR");
+        const counter = bytes("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+        await roundTrip({ name: "AES-CTR", counter, length: 64 }, ctr, plaintext);
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "58e2fccefa7e3061367f1d57a4e7455a true",
+            "0388dace60b6a392f328c2b971b2fe78ab6e47d42cec13bdf53a67b21257bddf true",
+            "OperationError",
+            // The last block is the padding
+            &format!(
+                "7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2{} true",
+                "55e21d7100b988ffec32feeafaf23538"
+            ),
+            "48",
+            "874d6191b620e3261bef6864990db6ce9806f66b7970fdff8617187bb9fffdff true"
+        ]
+    );
+}
+
+#[test]
+fn key_derivation() {
+    let output = lines(
+        r#"
+        // RFC 6070, and the SHA-256 counterpart of its first case
+        const password = await subtle.importKey("raw", utf8("password"), "PBKDF2", false, [
+          "deriveBits",
+        ]);
+        for (const [hash, iterations, length] of [
+          ["SHA-1", 1, 160],
+          ["SHA-1", 2, 160],
+          ["SHA-1", 4096, 160],
+          ["SHA-256", 1, 256],
+        ]) {
+          const algorithm = { name: "PBKDF2", hash, salt: utf8("salt"), iterations };
+          console.log(hex(await subtle.deriveBits(algorithm, password, length)));
+        }
+
+        // RFC 5869, test case 1
+        const ikm = await subtle.importKey("raw", bytes("0b".repeat(22)), "HKDF", false, [
+          "deriveBits",
+        ]);
+        const algorithm = {
+          name: "HKDF",
+          hash: "SHA-256",
+          salt: bytes("000102030405060708090a0b0c"),
+          info: bytes("f0f1f2f3f4f5f6f7f8f9"),
+        };
+        console.log(hex(await subtle.deriveBits(algorithm, ikm, 42 * 8)));
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "0c60c80f961f0e71f3a9b524af6012062fe037a6",
+            "ea6c014dc72d6f8ccd1ed92ace1d41f0d8de8957",
+            "4b007901b765489abead49d926f721d065a429c1",
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b",
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        ]
+    );
+}
+
+#[test]
+fn curves() {
+    let output = lines(
+        r#"
+        // Private keys in PKCS#8 as in RFC 8410, followed by the 32 key bytes
+        const pkcs8 = (oid, key) => bytes(`302e020100300506032b65${oid}04220420${key}`);
+
+        // RFC 8032, section 7.1, test 1: signatures are deterministic
+        const ed = await subtle.importKey(
+          "pkcs8",
+          pkcs8("70", "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+          "Ed25519",
+          false,
+          ["sign"],
+        );
+        console.log(hex(await subtle.sign("Ed25519", ed, new Uint8Array())));
+        const edPublic = await subtle.importKey(
+          "raw",
+          bytes("d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a"),
+          "Ed25519",
+          true,
+          ["verify"],
+        );
+        const signature = await subtle.sign("Ed25519", ed, utf8("message"));
+        console.log(
+          await subtle.verify("Ed25519", edPublic, signature, utf8("message")),
+          await subtle.verify("Ed25519", edPublic, signature, utf8("massage")),
+        );
+
+        // RFC 7748, section 6.1
+        const alice = await subtle.importKey(
+          "pkcs8",
+          pkcs8("6e", "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a"),
+          "X25519",
+          false,
+          ["deriveBits"],
+        );
+        const bob = await subtle.importKey(
+          "raw",
+          bytes("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f"),
+          "X25519",
+          true,
+          [],
+        );
+        console.log(hex(await subtle.deriveBits({ name: "X25519", public: bob }, alice, 256)));
+
+        // ECDSA signatures are randomized, so they are checked by verifying
+        for (const [namedCurve, hash] of [["P-256", "SHA-256"], ["P-384", "SHA-384"]]) {
+          const pair = await subtle.generateKey({ name: "ECDSA", namedCurve }, false, [
+            "sign",
+            "verify",
+          ]);
+          const algorithm = { name: "ECDSA", hash };
+          const signature = await subtle.sign(algorithm, pair.privateKey, utf8("data"));
+          console.log(
+            namedCurve,
+            signature.byteLength,
+            await subtle.verify(algorithm, pair.publicKey, signature, utf8("data")),
+            await subtle.verify(algorithm, pair.publicKey, signature, utf8("date")),
+          );
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e065224901555fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+            "true false",
+            "4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742",
+            "P-256 64 true false",
+            "P-384 96 true false"
+        ]
+    );
+}
+
+#[test]
+fn key_formats_round_trip() {
+    let output = lines(
+        r#"
+        // Keys exported as JWK and imported again export the same SPKI and PKCS#8
+        const algorithms = [
+          [{ name: "ECDSA", namedCurve: "P-256" }, ["sign", "verify"]],
+          [{ name: "ECDSA", namedCurve: "P-384" }, ["sign", "verify"]],
+          [{ name: "Ed25519" }, ["sign", "verify"]],
+          [{ name: "X25519" }, ["deriveBits"]],
+        ];
+        for (const [algorithm, usages] of algorithms) {
+          const pair = await subtle.generateKey(algorithm, true, usages);
+          const publicUsages = usages.filter((usage) => usage === "verify");
+          const privateUsages = usages.filter((usage) => usage !== "verify");
+          const same = async (format, key, usages) => {
+            const exported = await subtle.exportKey(format, key);
+            const jwk = await subtle.exportKey("jwk", key);
+            const imported = await subtle.importKey("jwk", jwk, algorithm, true, usages);
+            const again = await subtle.importKey(format, exported, algorithm, true, usages);
+            return hex(await subtle.exportKey(format, imported)) === hex(exported) &&
+              JSON.stringify(await subtle.exportKey("jwk", again)) === JSON.stringify(jwk);
+          };
+          console.log(
+            algorithm.namedCurve ?? algorithm.name,
+            await same("spki", pair.publicKey, publicUsages),
+            await same("pkcs8", pair.privateKey, privateUsages),
+          );
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "P-256 true true",
+            "P-384 true true",
+            "Ed25519 true true",
+            "X25519 true true"
+        ]
+    );
+}
+
+#[test]
+fn rsa() {
+    let fixture =
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/crypto/rsa-2048.json");
+    let output = run(mdeno(&format!(
+        "{}{}",
+        PRELUDE,
+        r#"
+        // A key, an RSA-OAEP ciphertext and an RSA-PSS signature made by another
+        // implementation
+        const fixture = JSON.parse(Deno.readTextFileSync(Deno.env.get("FIXTURE")));
+        const oaep = { name: "RSA-OAEP", hash: "SHA-256" };
+        const pss = { name: "RSA-PSS", hash: "SHA-256" };
+        const decryptKey = await subtle.importKey("pkcs8", bytes(fixture.pkcs8), oaep, true, [
+          "decrypt",
+        ]);
+        const label = utf8(fixture.oaepLabel);
+        const message = await subtle.decrypt(
+          { name: "RSA-OAEP", label },
+          decryptKey,
+          bytes(fixture.oaepSha256),
+        );
+        console.log(new TextDecoder().decode(message));
+        console.log(decryptKey.algorithm.modulusLength);
+
+        const verifyKey = await subtle.importKey("spki", bytes(fixture.spki), pss, true, [
+          "verify",
+        ]);
+        const signature = bytes(fixture.pssSha256Salt32);
+        const algorithm = { name: "RSA-PSS", saltLength: 32 };
+        console.log(await subtle.verify(algorithm, verifyKey, signature, utf8(fixture.message)));
+
+        // The private key as JWK gives back the same PKCS#8, when imported again
+        // without the usages and algorithm of the decryption key
+        const { key_ops: _, alg: __, ...jwk } = await subtle.exportKey("jwk", decryptKey);
+        const signKey = await subtle.importKey("jwk", jwk, pss, true, ["sign"]);
+        console.log(hex(await subtle.exportKey("pkcs8", signKey)) === hex(bytes(fixture.pkcs8)));
+        const signed = await subtle.sign(algorithm, signKey, utf8("signed here"));
+        console.log(await subtle.verify(algorithm, verifyKey, signed, utf8("signed here")));
+
+        // Keys shorter than 2048 bits are not generated
+        try {
+          await subtle.generateKey(
+            { ...oaep, modulusLength: 1024, publicExponent: new Uint8Array([1, 0, 1]) },
+            true,
+            ["encrypt", "decrypt"],
+          );
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#
+    ))
+    .env("FIXTURE", fixture));
+
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "RSA known answer",
+            "2048",
+            "true",
+            "true",
+            "true",
+            "OperationError"
+        ]
+    );
+}
@@ -0,0 +1,187 @@
+//! Deno.resolveDns, against a stub name server on loopback answering canned
+//! records over UDP and TCP.
+
+mod common;
+
+use common::{mdeno, run};
+use std::io::{Read, Write};
+use std::net::{TcpListener, UdpSocket};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const TYPE_A: u16 = 1;
+const TYPE_MX: u16 = 15;
+const TYPE_TXT: u16 = 16;
+
+// Response flags: recursion desired and available
+const FLAGS: u16 = 0x8180;
+const TRUNCATED: u16 = 0x0200;
+const NXDOMAIN: u16 = 3;
+
+/// Name and type of the question of `query`, and where the question ends.
+fn question(query: &[u8]) -> (String, u16, usize) {
+    let mut labels = Vec::new();
+    let mut at = 12;
+    while query[at] != 0 {
+        let len = query[at] as usize;
+        labels.push(String::from_utf8_lossy(&query[at + 1..at + 1 + len]).into_owned());
+        at += 1 + len;
+    }
+    let record_type = u16::from_be_bytes([query[at + 1], query[at + 2]]);
+    (labels.join("."), record_type, at + 5)
+}
+
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    for label in name.split('.') {
+        bytes.push(label.len() as u8);
+        bytes.extend_from_slice(label.as_bytes());
+    }
+    bytes.push(0);
+    bytes
+}
+
+/// The answer to `query`, with `flags` and the records as type and data.
+fn answer(query: &[u8], flags: u16, records: &[(u16, Vec<u8>)]) -> Vec<u8> {
+    let (_, _, end) = question(query);
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&query[..2]);
+    bytes.extend_from_slice(&flags.to_be_bytes());
+    bytes.extend_from_slice(&1u16.to_be_bytes());
+    bytes.extend_from_slice(&(records.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&[0, 0, 0, 0]);
+    bytes.extend_from_slice(&query[12..end]);
+    for (record_type, data) in records {
+        // The name points to the one of the question
+        bytes.extend_from_slice(&[0xc0, 12]);
+        bytes.extend_from_slice(&record_type.to_be_bytes());
+        bytes.extend_from_slice(&1u16.to_be_bytes());
+        bytes.extend_from_slice(&300u32.to_be_bytes());
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(data);
+    }
+    bytes
+}
+
+fn txt(parts: &[&str]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for part in parts {
+        data.push(part.len() as u8);
+        data.extend_from_slice(part.as_bytes());
+    }
+    data
+}
+
+/// The canned answer to `query`. Over UDP, big.test is answered truncated.
+fn respond(query: &[u8], tcp: bool) -> Vec<u8> {
+    let (name, record_type, _) = question(query);
+    match (name.as_str(), record_type) {
+        ("a.test", TYPE_A) => answer(
+            query,
+            FLAGS,
+            &[(TYPE_A, vec![192, 0, 2, 1]), (TYPE_A, vec![192, 0, 2, 2])],
+        ),
+        ("mx.test", TYPE_MX) => {
+            let mut data = 10u16.to_be_bytes().to_vec();
+            data.extend(encode_name("mail.test"));
+            answer(query, FLAGS, &[(TYPE_MX, data)])
+        }
+        ("txt.test", TYPE_TXT) => answer(query, FLAGS, &[(TYPE_TXT, txt(&["v=spf1", " -all"]))]),
+        ("big.test", TYPE_TXT) if !tcp => answer(query, FLAGS | TRUNCATED, &[]),
+        ("big.test", TYPE_TXT) => answer(query, FLAGS, &[(TYPE_TXT, txt(&["over tcp"]))]),
+        _ => answer(query, FLAGS | NXDOMAIN, &[]),
+    }
+}
+
+/// Start the stub on a port free for both UDP and TCP. Returns the port and the
+/// count of queries received over TCP.
+fn name_server() -> (u16, Arc<AtomicUsize>) {
+    let (udp, tcp) = loop {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = tcp.local_addr().unwrap().port();
+        if let Ok(udp) = UdpSocket::bind(("127.0.0.1", port)) {
+            break (udp, tcp);
+        }
+    };
+    let port = tcp.local_addr().unwrap().port();
+
+    thread::spawn(move || {
+        let mut buffer = [0; 512];
+        while let Ok((n, peer)) = udp.recv_from(&mut buffer) {
+            let _ = udp.send_to(&respond(&buffer[..n], false), peer);
+        }
+    });
+
+    let tcp_queries = Arc::new(AtomicUsize::new(0));
+    let count = tcp_queries.clone();
+    thread::spawn(move || {
+        for stream in tcp.incoming() {
+            let Ok(mut stream) = stream else { break };
+            count.fetch_add(1, Ordering::SeqCst);
+            let mut length = [0; 2];
+            stream.read_exact(&mut length).unwrap();
+            let mut query = vec![0; u16::from_be_bytes(length).into()];
+            stream.read_exact(&mut query).unwrap();
+            let response = respond(&query, true);
+            stream
+                .write_all(&(response.len() as u16).to_be_bytes())
+                .unwrap();
+            stream.write_all(&response).unwrap();
+        }
+    });
+
+    (port, tcp_queries)
+}
+
+fn resolve(port: u16, query: &str, record_type: &str) -> String {
+    let stdout = run(mdeno(
+        r#"
+        const nameServer = { ipAddr: "127.0.0.1", port: Number(Deno.env.get("PORT")) };
+        try {
+          const records = await Deno.resolveDns(
+            Deno.env.get("QUERY"),
+            Deno.env.get("TYPE"),
+            { nameServer },
+          );
+          console.log(JSON.stringify(records));
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    )
+    .env("PORT", port.to_string())
+    .env("QUERY", query)
+    .env("TYPE", record_type));
+    stdout.trim().to_string()
+}
+
+#[test]
+fn resolve_a_mx_and_txt() {
+    let (port, tcp_queries) = name_server();
+
+    assert_eq!(resolve(port, "a.test", "A"), r#"["192.0.2.1","192.0.2.2"]"#);
+    assert_eq!(
+        resolve(port, "mx.test", "MX"),
+        r#"[{"exchange":"mail.test.","preference":10}]"#
+    );
+    assert_eq!(resolve(port, "txt.test", "TXT"), r#"[["v=spf1"," -all"]]"#);
+    assert_eq!(tcp_queries.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn truncated_answers_are_retried_over_tcp() {
+    let (port, tcp_queries) = name_server();
+
+    assert_eq!(resolve(port, "big.test", "TXT"), r#"[["over tcp"]]"#);
+    assert_eq!(tcp_queries.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn unknown_names_are_not_found() {
+    let (port, _) = name_server();
+
+    assert_eq!(resolve(port, "missing.test", "A"), "NotFound");
+    // The name exists, but not with records of that type
+    assert_eq!(resolve(port, "a.test", "MX"), "NotFound");
+}
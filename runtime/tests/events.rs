@@ -0,0 +1,144 @@
+//! EventTarget, Event, CustomEvent and DOMException.
+
+mod common;
+
+use common::{mdeno, run};
+
+fn lines(source: &str) -> Vec<String> {
+    run(&mut mdeno(source))
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn listeners_run_in_order_with_their_options() {
+    let output = lines(
+        r#"
+        class Emitter extends EventTarget {}
+        const target = new Emitter();
+        const log = [];
+        const listener = () => log.push("duplicate");
+        target.addEventListener("ping", listener);
+        target.addEventListener("ping", listener);
+        target.addEventListener("ping", () => log.push("once"), { once: true });
+        target.addEventListener("ping", { handleEvent: () => log.push("object") });
+        target.addEventListener("ping", () => log.push("capture"), true);
+        const controller = new AbortController();
+        target.addEventListener("ping", () => log.push("aborted"), {
+          signal: controller.signal,
+        });
+        controller.abort();
+
+        target.dispatchEvent(new Event("ping"));
+        console.log(log.join(","));
+        log.length = 0;
+        target.removeEventListener("ping", listener);
+        target.dispatchEvent(new Event("ping"));
+        console.log(log.join(","));
+        console.log(target instanceof EventTarget, String(target));
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "capture,duplicate,once,object",
+            "capture,object",
+            "true [object EventTarget]"
+        ]
+    );
+}
+
+#[test]
+fn dispatch_state_and_cancelation() {
+    let output = lines(
+        r#"
+        const target = new EventTarget();
+        target.addEventListener("ping", (event) => {
+          console.log(
+            event.target === target,
+            event.currentTarget === target,
+            event.eventPhase,
+            event.isTrusted,
+          );
+          event.preventDefault();
+          event.stopImmediatePropagation();
+        });
+        target.addEventListener("ping", () => console.log("not reached"));
+        target.addEventListener("passive", (event) => event.preventDefault(), {
+          passive: true,
+        });
+
+        const event = new Event("ping", { cancelable: true });
+        console.log(target.dispatchEvent(event), event.defaultPrevented);
+        console.log(event.eventPhase, event.currentTarget);
+        // Events that are not cancelable cannot be canceled
+        console.log(target.dispatchEvent(new Event("ping")));
+        const passive = new Event("passive", { cancelable: true });
+        console.log(target.dispatchEvent(passive), passive.defaultPrevented);
+        try {
+          target.dispatchEvent({ type: "ping" });
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "true true 2 false",
+            "false true",
+            "0 null",
+            "true true 2 false",
+            "true",
+            "true false",
+            "TypeError"
+        ]
+    );
+}
+
+#[test]
+fn custom_event_detail() {
+    let output = lines(
+        r#"
+        const event = new CustomEvent("data", { detail: { value: 1 }, bubbles: true });
+        console.log(event.type, event.detail.value, event.bubbles, event.cancelable);
+        console.log(event instanceof Event, String(event));
+        console.log(new CustomEvent("empty").detail);
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        ["data 1 true false", "true [object CustomEvent]", "null"]
+    );
+}
+
+#[test]
+fn dom_exception_names_and_codes() {
+    let output = lines(
+        r#"
+        const error = new DOMException("gone", "NotFoundError");
+        console.log(error.name, error.message, error.code);
+        console.log(error instanceof Error, error instanceof DOMException);
+        console.log(DOMException.ABORT_ERR, error.NOT_FOUND_ERR);
+        const cause = new Error("cause");
+        const wrapped = new DOMException("wrapped", { name: "DataError", cause });
+        console.log(wrapped.name, wrapped.code, wrapped.cause === cause);
+        console.log(new DOMException().name, Object.prototype.toString.call(error));
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "NotFoundError gone 8",
+            "true true",
+            "20 8",
+            "DataError 0 true",
+            "Error [object DOMException]"
+        ]
+    );
+}
@@ -0,0 +1,83 @@
+//! EventSource reconnection and fatal responses against a server on loopback.
+
+mod common;
+
+use common::{header, mdeno, response, run, serve};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// An event stream response with `body`, closing the connection after it.
+fn event_stream(body: &str) -> String {
+    format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n{}",
+        body
+    )
+}
+
+// Logs the events of a source until it closes for good
+const LOG_EVENTS: &str = r#"
+const source = new EventSource(`http://127.0.0.1:${Deno.env.get("PORT")}/events`);
+source.onopen = () => console.log("open", source.readyState);
+source.onmessage = (event) => console.log("message", event.data, event.lastEventId);
+await new Promise((resolve) => {
+  source.onerror = () => {
+    console.log("error", source.readyState);
+    if (source.readyState === EventSource.CLOSED) resolve();
+  };
+});
+"#;
+
+#[test]
+fn reconnects_after_the_retry_delay() {
+    let connections = AtomicUsize::new(0);
+    let (port, heads) = serve(move |_, stream| {
+        let body = match connections.fetch_add(1, Ordering::SeqCst) {
+            0 => event_stream("retry: 50\nid: 1\ndata: one\n\n"),
+            1 => event_stream("data: two\n\n"),
+            _ => response("204 No Content", ""),
+        };
+        let _ = stream.write_all(body.as_bytes());
+    });
+
+    let started = Instant::now();
+    let stdout = run(mdeno(LOG_EVENTS).env("PORT", port.to_string()));
+    // Far below the default delay of 3 seconds
+    assert!(started.elapsed().as_secs() < 3);
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "open 1",
+            "message one 1",
+            "error 0",
+            "open 1",
+            "message two 1",
+            "error 0",
+            "error 2"
+        ]
+    );
+    let heads: Vec<_> = heads.iter().take(3).collect();
+    assert_eq!(header(&heads[0], "accept"), Some("text/event-stream"));
+    assert_eq!(header(&heads[0], "last-event-id"), None);
+    assert_eq!(header(&heads[1], "last-event-id"), Some("1"));
+    assert_eq!(header(&heads[2], "last-event-id"), Some("1"));
+}
+
+#[test]
+fn fatal_responses_close_the_source() {
+    for reply in [
+        response("404 Not Found", "data: no\n\n"),
+        response("200 OK", "data: no\n\n"),
+    ] {
+        let (port, heads) = serve(move |_, stream| {
+            let _ = stream.write_all(reply.as_bytes());
+        });
+        let stdout = run(mdeno(LOG_EVENTS).env("PORT", port.to_string()));
+
+        assert_eq!(stdout.trim(), "error 2");
+        // No reconnection was tried
+        heads.recv().unwrap();
+        assert!(heads.try_recv().is_err());
+    }
+}
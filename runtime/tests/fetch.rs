@@ -0,0 +1,611 @@
+//! fetch, against servers on loopback.
+
+mod common;
+
+use common::{TIMEOUT, header, mdeno, read_body, read_head, response, run, serve, spawn};
+use std::io::Write;
+
+/// A server answering each request with its method, content type, length or
+/// transfer encoding, and body. Returns the port.
+fn echo_server() -> u16 {
+    let (port, _) = serve(|head, stream| {
+        let body = read_body(head, stream);
+        let method = head.split(' ').next().unwrap_or_default();
+        let length = header(head, "content-length")
+            .or(header(head, "transfer-encoding"))
+            .unwrap_or("-");
+        let echo = format!(
+            "{} {} {} {}",
+            method,
+            header(head, "content-type").unwrap_or("-"),
+            length,
+            String::from_utf8_lossy(&body)
+        );
+        let _ = stream.write_all(response("200 OK", &echo).as_bytes());
+    });
+    port
+}
+
+fn fetch(port: u16, source: &str) -> Vec<String> {
+    run(mdeno(source).env("PORT", port.to_string()))
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn request_bodies() {
+    let output = fetch(
+        echo_server(),
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}/`;
+        const post = async (body) =>
+          console.log(await (await fetch(url, { method: "POST", body })).text());
+        await post("hello");
+        await post(new TextEncoder().encode("bytes"));
+        await post(new TextEncoder().encode("buffer").buffer);
+        await post(new Blob(["blob"], { type: "text/x-blob" }));
+        await post(new URLSearchParams({ a: "1", b: "x y" }));
+        await post(new ReadableStream({
+          start(controller) {
+            controller.enqueue(new TextEncoder().encode("one,"));
+            controller.enqueue(new TextEncoder().encode("two"));
+            controller.close();
+          },
+        }));
+        console.log(await (await fetch(url)).text());
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "POST text/plain;charset=UTF-8 5 hello",
+            "POST - 5 bytes",
+            "POST - 6 buffer",
+            "POST text/x-blob 4 blob",
+            "POST application/x-www-form-urlencoded;charset=UTF-8 9 a=1&b=x+y",
+            "POST - chunked one,two",
+            "GET - - "
+        ]
+    );
+}
+
+#[test]
+fn request_headers() {
+    let (port, heads) = serve(|head, stream| {
+        read_body(head, stream);
+        let _ = stream.write_all(response("200 OK", "").as_bytes());
+    });
+    fetch(
+        port,
+        r#"
+        const headers = new Headers({ authorization: "Bearer token" });
+        headers.append("x-multi", "a");
+        headers.append("x-multi", "b");
+        await fetch(`http://127.0.0.1:${Deno.env.get("PORT")}/path?q=1`, {
+          method: "PUT",
+          headers,
+          body: "{}",
+        });
+        "#,
+    );
+
+    let head = heads.recv().unwrap();
+    assert!(head.starts_with("PUT /path?q=1 HTTP/1.1\r\n"), "{}", head);
+    assert_eq!(header(&head, "host"), Some(&*format!("127.0.0.1:{}", port)));
+    assert_eq!(header(&head, "authorization"), Some("Bearer token"));
+    assert_eq!(header(&head, "x-multi"), Some("a, b"));
+    assert_eq!(header(&head, "content-length"), Some("2"));
+    assert!(header(&head, "user-agent").is_some());
+}
+
+#[test]
+fn response_status_and_headers() {
+    let (port, _) = serve(|_, stream| {
+        let _ = stream.write_all(
+            b"HTTP/1.1 201 Made Up\r\nSet-Cookie: a=1\r\nX-Dup: 1\r\nContent-Type: text/plain\r\nSet-Cookie: b=2\r\nX-Dup: 2\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+    });
+    let output = fetch(
+        port,
+        r#"
+        const res = await fetch(`http://127.0.0.1:${Deno.env.get("PORT")}/`);
+        console.log(res.status, res.statusText, res.ok);
+        console.log(res.headers.get("CONTENT-TYPE"), res.headers.get("x-dup"));
+        console.log(JSON.stringify(res.headers.getSetCookie()));
+        console.log(JSON.stringify([...res.headers]));
+        try {
+          res.headers.append("x-new", "1");
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "201 Made Up true",
+            "text/plain 1, 2",
+            r#"["a=1","b=2"]"#,
+            r#"[["connection","close"],["content-length","0"],["content-type","text/plain"],["set-cookie","a=1"],["set-cookie","b=2"],["x-dup","1, 2"]]"#,
+            "TypeError"
+        ]
+    );
+}
+
+#[test]
+fn headers_class() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const headers = new Headers([["B", " two "], ["a", "1"]]);
+        headers.append("a", "2");
+        headers.set("c", "3");
+        console.log(JSON.stringify([...headers]), headers.has("A"));
+        headers.delete("a");
+        console.log([...headers.keys()].join(","), headers.get("missing"));
+        for (const [name, value] of [["bad name", "x"], ["x", "a\nb"]]) {
+          try {
+            headers.set(name, value);
+          } catch (error) {
+            console.log(error.name);
+          }
+        }
+        console.log(new Response("", { headers: { "x-a": "1" } }).headers.get("x-a"));
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            r#"[["a","1, 2"],["b","two"],["c","3"]] true"#,
+            "b,c null",
+            "TypeError",
+            "TypeError",
+            "1"
+        ]
+    );
+}
+
+/// A server answering `/bin` with every byte value, and `/latin1` with text in
+/// ISO-8859-1.
+fn binary_server() -> u16 {
+    let (port, _) = serve(|head, stream| {
+        let (content_type, body): (&str, Vec<u8>) = if head.starts_with("GET /latin1 ") {
+            ("text/plain; charset=ISO-8859-1", b"caf\xe9".to_vec())
+        } else {
+            (
+                "application/octet-stream",
+                (0..=255).cycle().take(1 << 20).collect(),
+            )
+        };
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            content_type,
+            body.len()
+        );
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(&body);
+    });
+    port
+}
+
+#[test]
+fn binary_response_bodies() {
+    let output = fetch(
+        binary_server(),
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}`;
+        const bytes = await (await fetch(`${url}/bin`)).bytes();
+        const intact = bytes.every((byte, i) => byte === i % 256);
+        console.log(bytes.constructor.name, bytes.length, intact);
+
+        const res = await fetch(`${url}/bin`);
+        const buffer = await res.arrayBuffer();
+        console.log(buffer.constructor.name, buffer.byteLength, res.bodyUsed);
+        try {
+          await res.text();
+        } catch (error) {
+          console.log(error.name);
+        }
+
+        const blob = await (await fetch(`${url}/bin`)).blob();
+        const head = new Uint8Array(await blob.slice(254, 258).arrayBuffer());
+        console.log(blob.size, blob.type, head.join(","));
+
+        console.log(await (await fetch(`${url}/latin1`)).text());
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "Uint8Array 1048576 true",
+            "ArrayBuffer 1048576 true",
+            "TypeError",
+            "1048576 application/octet-stream 254,255,0,1",
+            "café"
+        ]
+    );
+}
+
+#[test]
+fn response_body_streams_as_it_arrives() {
+    // The second chunk is only sent once mdeno printed the first
+    let (send_rest, rest) = std::sync::mpsc::channel::<()>();
+    let rest = std::sync::Mutex::new(rest);
+    let (port, _) = serve(move |_, stream| {
+        let _ = stream.write_all(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n6\r\nfirst,\r\n",
+        );
+        let _ = stream.flush();
+        let _ = rest.lock().unwrap().recv_timeout(TIMEOUT);
+        let _ = stream.write_all(b"6\r\nsecond\r\n0\r\n\r\n");
+    });
+    let (mut running, first) = spawn(
+        mdeno(
+            r#"
+            const res = await fetch(`http://127.0.0.1:${Deno.env.get("PORT")}/`);
+            console.log(res.body instanceof ReadableStream, res.bodyUsed);
+            const decoder = new TextDecoder();
+            for await (const chunk of res.body) {
+              console.log(chunk instanceof Uint8Array, decoder.decode(chunk));
+            }
+            console.log(res.bodyUsed);
+            "#,
+        )
+        .env("PORT", port.to_string()),
+    );
+    assert_eq!(first, "true false");
+
+    assert_eq!(running.read_line(), "true first,");
+    send_rest.send(()).unwrap();
+    assert_eq!(
+        running.finish().lines().collect::<Vec<_>>(),
+        ["true second", "true"]
+    );
+}
+
+/// Echo the method, body headers, credentials and body of a request.
+fn echo_request(head: &str, stream: &mut std::net::TcpStream) {
+    let body = read_body(head, stream);
+    let echo = [
+        head.split(' ').next().unwrap_or_default(),
+        header(head, "content-type").unwrap_or("-"),
+        header(head, "authorization").unwrap_or("-"),
+        header(head, "cookie").unwrap_or("-"),
+        header(head, "x-custom").unwrap_or("-"),
+        &String::from_utf8_lossy(&body),
+    ]
+    .join(" ");
+    let _ = stream.write_all(response("200 OK", echo.trim_end()).as_bytes());
+}
+
+/// A server redirecting `/<status>` to `/echo` with that status, `/cross` to
+/// `/echo` of another origin and `/loop` to itself. Returns the port.
+fn redirect_server() -> u16 {
+    let (other_port, _) = serve(echo_request);
+    let (port, _) = serve(move |head, stream| {
+        let path = head.split(' ').nth(1).unwrap_or_default();
+        let (status, location) = match path {
+            "/echo" => return echo_request(head, stream),
+            "/cross" => ("302 Found", format!("http://127.0.0.1:{}/echo", other_port)),
+            "/loop" => ("302 Found", "/loop".to_string()),
+            status => (&status[1..], "/echo".to_string()),
+        };
+        read_body(head, stream);
+        let _ = stream.write_all(
+            format!(
+                "HTTP/1.1 {} Redirect\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status, location
+            )
+            .as_bytes(),
+        );
+    });
+    port
+}
+
+#[test]
+fn redirect_rewrites_method_and_body() {
+    let output = fetch(
+        redirect_server(),
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}`;
+        for (const [method, status] of [
+          ["POST", 301],
+          ["POST", 302],
+          ["PUT", 302],
+          ["POST", 303],
+          ["PUT", 303],
+          ["POST", 307],
+          ["PUT", 308],
+        ]) {
+          const res = await fetch(`${url}/${status}`, {
+            method,
+            body: "data",
+            headers: { authorization: "Bearer token" },
+          });
+          console.log(method, status, await res.text());
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "POST 301 GET - Bearer token - -",
+            "POST 302 GET - Bearer token - -",
+            "PUT 302 PUT text/plain;charset=UTF-8 Bearer token - - data",
+            "POST 303 GET - Bearer token - -",
+            "PUT 303 GET - Bearer token - -",
+            "POST 307 POST text/plain;charset=UTF-8 Bearer token - - data",
+            "PUT 308 PUT text/plain;charset=UTF-8 Bearer token - - data"
+        ]
+    );
+}
+
+#[test]
+fn redirect_strips_credentials_across_origins() {
+    let output = fetch(
+        redirect_server(),
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}`;
+        const res = await fetch(`${url}/cross#part`, {
+          headers: {
+            authorization: "Bearer token",
+            cookie: "session=1",
+            "x-custom": "kept",
+          },
+        });
+        console.log(await res.text());
+        console.log(res.redirected, res.url.endsWith("/echo"), res.url.startsWith(url));
+
+        const direct = await fetch(`${url}/echo`);
+        console.log(direct.redirected, direct.url === `${url}/echo`);
+        "#,
+    );
+
+    assert_eq!(output, ["GET - - - kept", "true true false", "false true"]);
+}
+
+#[test]
+fn redirect_modes() {
+    let output = fetch(
+        redirect_server(),
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}`;
+        const manual = await fetch(`${url}/302`, { redirect: "manual" });
+        console.log(manual.status, manual.headers.get("location"), manual.redirected);
+        for (const [path, redirect] of [["/302", "error"], ["/loop", "follow"]]) {
+          try {
+            await fetch(`${url}${path}`, { redirect });
+            console.log("fetched");
+          } catch (error) {
+            console.log(error.name, error.message);
+          }
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "302 /echo false",
+            "TypeError Failed to fetch: Redirect was blocked by redirect mode \"error\"",
+            "TypeError Failed to fetch: Too many redirects"
+        ]
+    );
+}
+
+/// `text` in the content coding `encoding`.
+fn encode(encoding: &str, text: &[u8]) -> Vec<u8> {
+    use flate2::Compression;
+    use flate2::write::{GzEncoder, ZlibEncoder};
+
+    match encoding {
+        "gzip" => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(text).unwrap();
+            encoder.finish().unwrap()
+        }
+        "deflate" => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(text).unwrap();
+            encoder.finish().unwrap()
+        }
+        "br" => {
+            let mut encoded = Vec::new();
+            let mut encoder = brotli::CompressorWriter::new(&mut encoded, 4096, 5, 22);
+            encoder.write_all(text).unwrap();
+            drop(encoder);
+            encoded
+        }
+        _ => text.to_vec(),
+    }
+}
+
+#[test]
+fn compressed_response_bodies() {
+    let (port, heads) = serve(|head, stream| {
+        let path = head.split(' ').nth(1).unwrap_or_default();
+        let text = "compressed text ".repeat(4096);
+        let (encoding, body) = match path {
+            // Gzip bodies may have several members
+            "/gzip-members" => {
+                let (first, second) = text.split_at(1000);
+                let members = [
+                    encode("gzip", first.as_bytes()),
+                    encode("gzip", second.as_bytes()),
+                ];
+                ("gzip", members.concat())
+            }
+            _ => (&path[1..], encode(&path[1..], text.as_bytes())),
+        };
+        let head = format!(
+            "HTTP/1.1 200 OK\r\nContent-Encoding: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            encoding,
+            body.len()
+        );
+        let _ = stream.write_all(head.as_bytes());
+        let _ = stream.write_all(&body);
+    });
+    let output = fetch(
+        port,
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}`;
+        const expected = "compressed text ".repeat(4096);
+        for (const encoding of ["gzip", "deflate", "br", "gzip-members", "identity"]) {
+          const res = await fetch(`${url}/${encoding}`);
+          const text = await res.text();
+          console.log(
+            encoding,
+            text === expected,
+            res.headers.get("content-encoding"),
+            res.headers.get("content-length"),
+          );
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "gzip true null null",
+            "deflate true null null",
+            "br true null null",
+            "gzip-members true null null",
+            "identity true identity 65536"
+        ]
+    );
+    assert_eq!(
+        header(&heads.recv().unwrap(), "accept-encoding"),
+        Some("gzip, deflate, br")
+    );
+}
+
+#[test]
+fn ipv6_literals_and_localhost() {
+    let Ok(listener) = std::net::TcpListener::bind("[::1]:0") else {
+        // No IPv6 loopback
+        return;
+    };
+    let v6_port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let head = read_head(&mut stream);
+            let host = header(&head, "host").unwrap_or_default().to_string();
+            let _ = stream.write_all(response("200 OK", &host).as_bytes());
+        }
+    });
+    let (v4_port, _) = serve(|_, stream| {
+        let _ = stream.write_all(response("200 OK", "v4").as_bytes());
+    });
+
+    let stdout = run(mdeno(
+        r#"
+        const v6 = await fetch(`http://[::1]:${Deno.env.get("V6_PORT")}/`);
+        console.log(await v6.text());
+        const v4 = await fetch(`http://localhost:${Deno.env.get("V4_PORT")}/`);
+        console.log(await v4.text());
+        "#,
+    )
+    .env("V6_PORT", v6_port.to_string())
+    .env("V4_PORT", v4_port.to_string()));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [format!("[::1]:{}", v6_port), "v4".to_string()]
+    );
+}
+
+#[test]
+fn file_urls() {
+    let name = format!("mdeno-test-{}-data.json", std::process::id());
+    let path = std::env::temp_dir().join(&name);
+    std::fs::write(&path, r#"{"local":true}"#).unwrap();
+    let stdout = run(mdeno(
+        r#"
+        const url = new URL(Deno.env.get("NAME"), import.meta.url);
+        const res = await fetch(url);
+        console.log(
+          res.status,
+          res.headers.get("content-type"),
+          res.headers.get("content-length"),
+          res.url === url.href,
+        );
+        console.log(JSON.stringify(await res.json()));
+        for (const [target, method] of [
+          [new URL("./missing.json", url.href), "GET"],
+          [new URL("./", url.href), "GET"],
+          [url, "POST"],
+        ]) {
+          try {
+            await fetch(target, { method });
+            console.log("fetched");
+          } catch (error) {
+            console.log(error.name);
+          }
+        }
+        "#,
+    )
+    .env("NAME", &name));
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "200 application/json 14 true",
+            r#"{"local":true}"#,
+            "TypeError",
+            "TypeError",
+            "TypeError"
+        ]
+    );
+}
+
+#[test]
+fn data_urls() {
+    let stdout = run(&mut mdeno(
+        r#"
+        for (const url of [
+          "data:,Hello%2C%20World%21",
+          "data:text/html;charset=utf-8,%3Cb%3Ehi%3C%2Fb%3E",
+          "data:application/octet-stream;base64,AAH/",
+          "data:;BASE64,aGk#fragment",
+          "data:text/plain; base64 ,aG k=",
+          "data:nonsense,x",
+        ]) {
+          const res = await fetch(url);
+          const bytes = await res.bytes();
+          console.log(res.headers.get("content-type"), bytes.join(","));
+        }
+        console.log((await fetch("data:,x#y")).url);
+        for (const url of ["data:;base64,a", "data:no-comma"]) {
+          try {
+            await fetch(url);
+            console.log("fetched");
+          } catch (error) {
+            console.log(error.name);
+          }
+        }
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "text/plain;charset=US-ASCII 72,101,108,108,111,44,32,87,111,114,108,100,33",
+            "text/html;charset=utf-8 60,98,62,104,105,60,47,98,62",
+            "application/octet-stream 0,1,255",
+            // No type is left once ";base64" is removed
+            "text/plain;charset=US-ASCII 104,105",
+            "text/plain 104,105",
+            "text/plain;charset=US-ASCII 120",
+            "data:,x",
+            "TypeError",
+            "TypeError"
+        ]
+    );
+}
@@ -0,0 +1,167 @@
+//! Recording and replay of fetch traffic with MDENO_FETCH_RECORD and
+//! MDENO_FETCH_REPLAY.
+
+mod common;
+
+use common::{header, mdeno, run, serve};
+use std::io::Write;
+
+const HAR: &str = r#"{
+  "log": {
+    "version": "1.2",
+    "entries": [
+      {
+        "request": { "method": "GET", "url": "http://example.test/count" },
+        "response": {
+          "status": 200,
+          "statusText": "OK",
+          "headers": [{ "name": "x-count", "value": "1" }],
+          "content": { "text": "first" }
+        }
+      },
+      {
+        "request": { "method": "GET", "url": "http://example.test/count" },
+        "response": {
+          "status": 201,
+          "statusText": "Made It",
+          "headers": [{ "name": "x-count", "value": "2" }],
+          "content": { "text": "second" }
+        }
+      },
+      {
+        "request": { "method": "POST", "url": "http://example.test/bytes" },
+        "response": {
+          "status": 200,
+          "statusText": "OK",
+          "headers": [],
+          "content": { "text": "AAH/", "encoding": "base64" }
+        }
+      }
+    ]
+  }
+}"#;
+
+#[test]
+fn replay_serves_recorded_responses_in_turn() {
+    let har = std::env::temp_dir().join(format!("mdeno-test-{}.har", std::process::id()));
+    std::fs::write(&har, HAR).unwrap();
+    let stdout = run(mdeno(
+        r#"
+        for (let i = 0; i < 3; i++) {
+          const res = await fetch("http://example.test/count");
+          console.log(
+            res.status,
+            res.statusText,
+            res.headers.get("x-count"),
+            await res.text(),
+          );
+        }
+        const res = await fetch("http://example.test/bytes", {
+          method: "POST",
+          body: "ignored",
+        });
+        console.log([...new Uint8Array(await res.arrayBuffer())].join(","));
+        try {
+          await fetch("http://example.test/missing");
+          console.log("fetched");
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    )
+    .env("MDENO_FETCH_REPLAY", &har));
+    let _ = std::fs::remove_file(&har);
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "200 OK 1 first",
+            "201 Made It 2 second",
+            // The last matching entry is served again
+            "201 Made It 2 second",
+            "0,1,255",
+            "TypeError",
+        ]
+    );
+}
+
+#[test]
+fn record_redacts_credentials_and_keeps_reason_phrase() {
+    let (port, heads) = serve(|_, stream| {
+        let _ = stream.write_all(
+            b"HTTP/1.1 200 Fine\r\nSet-Cookie: session=secret\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        );
+    });
+    let har = std::env::temp_dir().join(format!("mdeno-test-{}-record.har", std::process::id()));
+    let stdout = run(mdeno(
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}/?page=2&API_KEY=secret`;
+        const res = await fetch(url, {
+          headers: { authorization: "Bearer secret", "x-kept": "yes" },
+        });
+        console.log(res.statusText, await res.text());
+        const har = JSON.parse(
+          Deno.readTextFileSync(Deno.env.get("MDENO_FETCH_RECORD")),
+        );
+        const [entry] = har.log.entries;
+        const headers = (message) =>
+          Object.fromEntries(message.headers.map((h) => [h.name, h.value]));
+        const request = headers(entry.request);
+        const response = headers(entry.response);
+        console.log(request["authorization"], request["x-kept"]);
+        console.log(response["set-cookie"]);
+        console.log(entry.response.statusText, entry.response.content.text);
+        console.log(new URL(entry.request.url).search);
+        "#,
+    )
+    .env("MDENO_FETCH_RECORD", &har)
+    .env("PORT", port.to_string()));
+    let recorded = std::fs::read_to_string(&har).unwrap();
+    let _ = std::fs::remove_file(&har);
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "Fine hello",
+            "[REDACTED] yes",
+            "[REDACTED]",
+            "Fine hello",
+            "?page=2&API_KEY=[REDACTED]"
+        ]
+    );
+    assert!(!recorded.contains("secret"), "{}", recorded);
+    // The server itself got the real credentials
+    let head = heads.recv().unwrap();
+    assert!(head.starts_with("GET /?page=2&API_KEY=secret "), "{}", head);
+    assert_eq!(header(&head, "authorization"), Some("Bearer secret"));
+}
+
+#[test]
+fn replay_matches_redacted_urls() {
+    let har = std::env::temp_dir().join(format!("mdeno-test-{}-redacted.har", std::process::id()));
+    let fixture = HAR.replace(
+        "http://example.test/count",
+        "http://example.test/count?token=[REDACTED]&page=1",
+    );
+    std::fs::write(&har, fixture).unwrap();
+    let stdout = run(mdeno(
+        r#"
+        // Any token matches the redacted one, other parameters must be the same
+        for (const query of ["token=abc&page=1", "token=def&page=1", "token=abc&page=2"]) {
+          try {
+            const res = await fetch(`http://example.test/count?${query}`);
+            console.log(await res.text());
+          } catch (error) {
+            console.log(error.name);
+          }
+        }
+        "#,
+    )
+    .env("MDENO_FETCH_REPLAY", &har));
+    let _ = std::fs::remove_file(&har);
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["first", "second", "TypeError"]
+    );
+}
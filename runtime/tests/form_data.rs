@@ -0,0 +1,183 @@
+//! Request, FormData, Blob and File, and multipart/form-data uploads.
+
+mod common;
+
+use common::{header, mdeno, read_body, response, run, serve};
+use std::io::Write;
+
+fn lines(source: &str) -> Vec<String> {
+    run(&mut mdeno(source))
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn multipart_upload() {
+    let (port, heads) = serve(|head, stream| {
+        let body = read_body(head, stream);
+        // The body is sent back to be checked with the head
+        let _ = stream.write_all(response("200 OK", &String::from_utf8_lossy(&body)).as_bytes());
+    });
+    let stdout = run(mdeno(
+        r#"
+        const form = new FormData();
+        form.append("text", "line\nbreak");
+        form.append('quoted"name', "value");
+        form.append("file", new File([new Uint8Array([104, 105, 33])], "hi.txt", {
+          type: "text/plain",
+        }));
+        form.append("blob", new Blob(["raw"]));
+        const res = await fetch(`http://127.0.0.1:${Deno.env.get("PORT")}/`, {
+          method: "POST",
+          body: form,
+        });
+        console.log(await res.text());
+        "#,
+    )
+    .env("PORT", port.to_string()));
+
+    let head = heads.recv().unwrap();
+    let content_type = header(&head, "content-type").unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/form-data; boundary=")
+        .unwrap();
+    let expected = [
+        "--B\r\nContent-Disposition: form-data; name=\"text\"\r\n\r\nline\r\nbreak\r\n",
+        "--B\r\nContent-Disposition: form-data; name=\"quoted%22name\"\r\n\r\nvalue\r\n",
+        "--B\r\nContent-Disposition: form-data; name=\"file\"; filename=\"hi.txt\"\r\nContent-Type: text/plain\r\n\r\nhi!\r\n",
+        "--B\r\nContent-Disposition: form-data; name=\"blob\"; filename=\"blob\"\r\nContent-Type: application/octet-stream\r\n\r\nraw\r\n",
+        "--B--\r\n",
+    ]
+    .concat()
+    .replace("--B", &format!("--{}", boundary));
+    // console.log adds a line break
+    assert_eq!(stdout, format!("{}\n", expected));
+}
+
+#[test]
+fn form_data_round_trip() {
+    let output = lines(
+        r#"
+        const form = new FormData();
+        form.append("a", "1");
+        form.append("a", "2");
+        form.set("b", new Blob([new Uint8Array([0, 255])], { type: "image/x-test" }), "b.bin");
+        console.log(form.getAll("a").join(","), form.has("b"), form.get("missing"));
+
+        const parsed = await new Response(form).formData();
+        const file = parsed.get("b");
+        const bytes = new Uint8Array(await file.arrayBuffer());
+        console.log(parsed.getAll("a").join(","), file instanceof File);
+        console.log(file.name, file.type, bytes.join(","));
+
+        const urlencoded = await new Response("x=1&y=a+b", {
+          headers: { "content-type": "application/x-www-form-urlencoded" },
+        }).formData();
+        console.log(JSON.stringify([...urlencoded]));
+        try {
+          await new Response("not a form").formData();
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "1,2 true null",
+            "1,2 true",
+            "b.bin image/x-test 0,255",
+            r#"[["x","1"],["y","a b"]]"#,
+            "TypeError"
+        ]
+    );
+}
+
+#[test]
+fn blob_and_file() {
+    let output = lines(
+        r#"
+        const blob = new Blob(["abc", new Uint8Array([100, 101]), new Blob(["f"])], {
+          type: "Text/Plain",
+        });
+        console.log(blob.size, blob.type, await blob.text());
+        console.log(await blob.slice(1, -1).text(), await blob.slice(-2).text());
+        console.log(blob.slice(0, 1, "x/y").type, blob.slice(4, 2).size);
+
+        const chunks = [];
+        for await (const chunk of blob.stream()) chunks.push(...chunk);
+        console.log(new TextDecoder().decode(new Uint8Array(chunks)));
+        console.log((await blob.arrayBuffer()).byteLength);
+
+        const file = new File(["x"], "name.txt", { lastModified: 42 });
+        console.log(file instanceof Blob, file.name, file.lastModified, file.type === "");
+        console.log(String(file), String(blob));
+        "#,
+    );
+
+    assert_eq!(
+        output,
+        [
+            "6 text/plain abcdef",
+            "bcde ef",
+            "x/y 0",
+            "abcdef",
+            "6",
+            "true name.txt 42 true",
+            "[object File] [object Blob]"
+        ]
+    );
+}
+
+#[test]
+fn request_class() {
+    let (port, heads) = serve(|head, stream| {
+        let body = read_body(head, stream);
+        let _ = stream.write_all(response("200 OK", &String::from_utf8_lossy(&body)).as_bytes());
+    });
+    let stdout = run(mdeno(
+        r#"
+        const url = `http://127.0.0.1:${Deno.env.get("PORT")}/path`;
+        const request = new Request(url, {
+          method: "post",
+          headers: { "x-custom": "1" },
+          body: "payload",
+        });
+        console.log(request.method, request.url === url, request.headers.get("x-custom"));
+        console.log(request.headers.get("content-type"), request.redirect);
+
+        const clone = request.clone();
+        console.log(await clone.text(), clone.bodyUsed, request.bodyUsed);
+
+        const copy = new Request(request, { headers: { "x-custom": "2" } });
+        const res = await fetch(copy);
+        console.log(await res.text(), request.bodyUsed);
+
+        for (const init of [{ method: "GET", body: "x" }, { method: "CONNECT" }]) {
+          try {
+            new Request(url, init);
+          } catch (error) {
+            console.log(error.name);
+          }
+        }
+        "#,
+    )
+    .env("PORT", port.to_string()));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "POST true 1",
+            "text/plain;charset=UTF-8 follow",
+            "payload true false",
+            "payload true",
+            "TypeError",
+            "TypeError"
+        ]
+    );
+    let head = heads.recv().unwrap();
+    assert!(head.starts_with("POST /path HTTP/1.1\r\n"), "{}", head);
+    assert_eq!(header(&head, "x-custom"), Some("2"));
+}
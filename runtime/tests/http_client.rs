@@ -0,0 +1,117 @@
+//! Deno.createHttpClient with custom CA and client certificates, and the CA
+//! certificates of the environment, against a TLS server on loopback.
+
+mod common;
+
+use common::{mdeno, read_head, response, run, tls};
+use std::io::Write;
+
+/// A TLS server answering whether the client presented a certificate. Returns
+/// the port.
+fn tls_server(client_auth: bool) -> u16 {
+    tls::serve(client_auth, |stream| {
+        read_head(stream);
+        let presented = stream.conn.peer_certificates().is_some();
+        let body = if presented {
+            "with cert"
+        } else {
+            "without cert"
+        };
+        let _ = stream.write_all(response("200 OK", body).as_bytes());
+        let _ = stream.flush();
+    })
+}
+
+const FETCH: &str = r#"
+const read = (name) => Deno.readTextFileSync(Deno.env.get(name));
+const options = {};
+if (Deno.env.get("CA")) options.caCerts = [read("CA")];
+if (Deno.env.get("CERT")) {
+  options.cert = read("CERT");
+  options.key = read("KEY");
+}
+const client = Deno.createHttpClient(options);
+try {
+  const res = await fetch(`https://127.0.0.1:${Deno.env.get("PORT")}/`, { client });
+  console.log(await res.text());
+} catch (error) {
+  console.log(error.name);
+}
+"#;
+
+#[test]
+fn server_certificates_need_a_trusted_ca() {
+    let port = tls_server(false);
+
+    let untrusted = run(mdeno(FETCH).env("PORT", port.to_string()));
+    assert_eq!(untrusted.trim(), "TypeError");
+
+    let trusted = run(mdeno(FETCH)
+        .env("PORT", port.to_string())
+        .env("CA", tls::path("ca.pem")));
+    assert_eq!(trusted.trim(), "without cert");
+}
+
+#[test]
+fn client_certificates() {
+    let port = tls_server(true);
+
+    let stdout = run(mdeno(FETCH)
+        .env("PORT", port.to_string())
+        .env("CA", tls::path("ca.pem"))
+        .env("CERT", tls::path("client.pem"))
+        .env("KEY", tls::path("client-key.pem")));
+    assert_eq!(stdout.trim(), "with cert");
+
+    // The server refuses clients without a certificate
+    let stdout = run(mdeno(FETCH)
+        .env("PORT", port.to_string())
+        .env("CA", tls::path("ca.pem")));
+    assert_eq!(stdout.trim(), "TypeError");
+}
+
+#[test]
+fn ca_certificates_from_environment() {
+    let port = tls_server(false);
+    for variable in ["DENO_CERT", "SSL_CERT_FILE"] {
+        let stdout = run(mdeno(
+            r#"
+            const res = await fetch(`https://127.0.0.1:${Deno.env.get("PORT")}/`);
+            console.log(await res.text());
+            "#,
+        )
+        .env("PORT", port.to_string())
+        .env(variable, tls::path("ca.pem")));
+        assert_eq!(stdout.trim(), "without cert", "{}", variable);
+    }
+}
+
+#[test]
+fn closed_client() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const client = Deno.createHttpClient({});
+        console.log(client instanceof Deno.HttpClient);
+        client.close();
+        try {
+          await fetch("http://127.0.0.1:1/", { client });
+        } catch (error) {
+          console.log(error.name, error.message);
+        }
+        try {
+          Deno.createHttpClient({ cert: "only a certificate" });
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "true",
+            "TypeError Failed to fetch: HTTP client is closed",
+            "TypeError"
+        ]
+    );
+}
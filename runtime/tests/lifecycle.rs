@@ -0,0 +1,139 @@
+//! Lifecycle events dispatched on globalThis, and how they decide the exit code.
+
+mod common;
+
+use common::{mdeno, run};
+
+#[test]
+fn lifecycle_events_in_order() {
+    let stdout = run(&mut mdeno(
+        r#"
+        for (const type of ["load", "beforeunload", "unload"]) {
+          addEventListener(type, (event) => {
+            console.log(type, event instanceof Event, event.cancelable);
+          });
+        }
+        console.log("main");
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "main",
+            "load true false",
+            "beforeunload true true",
+            "unload true false"
+        ]
+    );
+}
+
+#[test]
+fn beforeunload_prevent_default_keeps_running() {
+    let stdout = run(&mut mdeno(
+        r#"
+        let extra = 0;
+        addEventListener("beforeunload", (event) => {
+          if (extra++ < 2) {
+            event.preventDefault();
+            Promise.resolve().then(() => console.log("job", extra));
+          }
+        });
+        onunload = () => console.log("unload");
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["job 1", "job 2", "unload"]
+    );
+}
+
+#[test]
+fn exit_dispatches_unload() {
+    let output = mdeno(
+        r#"
+        addEventListener("unload", () => console.log("unload"));
+        Deno.exit(3);
+        "#,
+    )
+    .output()
+    .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "unload");
+}
+
+#[test]
+fn unhandled_rejection_exits_non_zero() {
+    let output = mdeno(
+        r#"
+        addEventListener("unhandledrejection", (event) => {
+          console.log("unhandledrejection", event.reason.message);
+        });
+        Promise.reject(new Error("boom"));
+        "#,
+    )
+    .output()
+    .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "unhandledrejection boom"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Uncaught (in promise) Error: boom"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn unhandled_rejection_prevent_default() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const rejected = Promise.reject(new Error("boom"));
+        addEventListener("unhandledrejection", (event) => {
+          console.log(event.promise === rejected, event.reason.message);
+          event.preventDefault();
+        });
+        // Rejections handled before the job queue drains are not reported
+        Promise.reject(new Error("late")).catch(() => console.log("caught"));
+        addEventListener("unload", () => console.log("unload"));
+        "#,
+    ));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["caught", "true boom", "unload"]
+    );
+}
+
+#[test]
+fn uncaught_error_event() {
+    let stdout = run(&mut mdeno(
+        r#"
+        addEventListener("error", (event) => {
+          console.log("error", event.message, event.error instanceof TypeError);
+          event.preventDefault();
+        });
+        const target = new EventTarget();
+        target.addEventListener("ping", () => {
+          throw new TypeError("from a listener");
+        });
+        target.dispatchEvent(new Event("ping"));
+        reportError(new TypeError("reported"));
+        "#,
+    ));
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        ["error from a listener true", "error reported true"]
+    );
+
+    let output = mdeno("throw new Error(\"top level\");").output().unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("top level"), "{}", stderr);
+}
@@ -0,0 +1,288 @@
+//! Deno.connect, Deno.listen, Deno.startTls and Deno.listenDatagram on loopback.
+
+mod common;
+
+use common::{mdeno, run, tls};
+use std::io::{Read, Write};
+
+fn lines(stdout: String) -> Vec<String> {
+    stdout.lines().map(str::to_string).collect()
+}
+
+#[test]
+fn tcp_echo() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const listener = Deno.listen({ hostname: "127.0.0.1", port: 0 });
+        const { port } = listener.addr;
+        const served = (async () => {
+          const conn = await listener.accept();
+          const buffer = new Uint8Array(1024);
+          let n;
+          while ((n = await conn.read(buffer)) !== null) {
+            await conn.write(buffer.subarray(0, n));
+          }
+          conn.close();
+        })();
+
+        const conn = await Deno.connect({ hostname: "127.0.0.1", port });
+        console.log(String(conn), conn.remoteAddr.port === port, conn.localAddr.transport);
+        conn.setNoDelay(true);
+        conn.setNoDelay(false);
+        await conn.write(new TextEncoder().encode("hello"));
+        // The echo ends once the peer reads the end of the stream
+        await conn.closeWrite();
+        const echoed = await new Response(conn.readable).text();
+        console.log(echoed);
+        await served;
+        conn.close();
+        try {
+          conn.setNoDelay();
+        } catch (error) {
+          console.log(error.name);
+        }
+        listener.close();
+        "#,
+    ));
+
+    assert_eq!(
+        lines(stdout),
+        ["[object TcpConn] true tcp", "hello", "BadResource"]
+    );
+}
+
+#[test]
+fn listener_iteration_and_refused_connections() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const listener = Deno.listen({ hostname: "127.0.0.1", port: 0 });
+        const { port } = listener.addr;
+        const accepted = (async () => {
+          let count = 0;
+          for await (const conn of listener) {
+            conn.close();
+            count++;
+          }
+          return count;
+        })();
+        for (let i = 0; i < 2; i++) {
+          const conn = await Deno.connect({ hostname: "127.0.0.1", port });
+          console.log(await conn.read(new Uint8Array(1)));
+          conn.close();
+        }
+        listener.close();
+        console.log(await accepted);
+        try {
+          await Deno.connect({ hostname: "127.0.0.1", port });
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    ));
+
+    assert_eq!(lines(stdout), ["null", "null", "2", "ConnectionRefused"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn unix_echo() {
+    let path = std::env::temp_dir().join(format!("mdeno-test-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let stdout = run(mdeno(
+        r#"
+        const path = Deno.env.get("SOCKET");
+        const listener = Deno.listen({ transport: "unix", path });
+        const served = (async () => {
+          const conn = await listener.accept();
+          await conn.readable.pipeTo(conn.writable);
+        })();
+
+        const conn = await Deno.connect({ transport: "unix", path });
+        console.log(String(conn), conn.remoteAddr.transport, conn.remoteAddr.path === path);
+        await conn.write(new TextEncoder().encode("over unix"));
+        await conn.closeWrite();
+        console.log(await new Response(conn.readable).text());
+        await served;
+        listener.close();
+        "#,
+    )
+    .env("SOCKET", &path));
+    let _ = std::fs::remove_file(&path);
+
+    assert_eq!(lines(stdout), ["[object UnixConn] unix true", "over unix"]);
+}
+
+/// A TLS echo server on loopback. Returns the port.
+fn tls_echo_server() -> u16 {
+    tls::serve(false, |stream| {
+        let mut buffer = [0; 1024];
+        while let Ok(n @ 1..) = stream.read(&mut buffer) {
+            if stream.write_all(&buffer[..n]).is_err() {
+                break;
+            }
+        }
+        stream.conn.send_close_notify();
+        let _ = stream.flush();
+    })
+}
+
+#[test]
+fn start_tls() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const listener = Deno.listen({ hostname: "127.0.0.1", port: 0 });
+        const accepted = listener.accept();
+        const conn = await Deno.connect(listener.addr);
+        const peer = await accepted;
+
+        // A pending read keeps the connection from being upgraded, and the
+        // connection stays usable
+        const pending = conn.read(new Uint8Array(1));
+        try {
+          await Deno.startTls(conn, { hostname: "localhost" });
+        } catch (error) {
+          console.log(error.name);
+        }
+        await peer.write(new Uint8Array([1]));
+        console.log(await pending);
+        await conn.write(new Uint8Array([2]));
+        console.log(await peer.read(new Uint8Array(1)));
+        conn.close();
+        peer.close();
+        listener.close();
+        "#,
+    ));
+    assert_eq!(lines(stdout), ["Busy", "1", "1"]);
+
+    let port = tls_echo_server();
+    let stdout = run(mdeno(
+        r#"
+        const caCerts = [Deno.readTextFileSync(Deno.env.get("CA"))];
+        const port = Number(Deno.env.get("PORT"));
+        const plain = await Deno.connect({ hostname: "127.0.0.1", port });
+        const conn = await Deno.startTls(plain, { hostname: "localhost", caCerts });
+        console.log(String(conn));
+        await conn.write(new TextEncoder().encode("secret"));
+        const buffer = new Uint8Array(16);
+        const n = await conn.read(buffer);
+        console.log(new TextDecoder().decode(buffer.subarray(0, n)));
+        conn.close();
+
+        // The plain connection was replaced
+        try {
+          await plain.write(new Uint8Array(1));
+        } catch (error) {
+          console.log(error.name);
+        }
+
+        // Without the test CA, the server is not trusted
+        const untrusted = await Deno.connect({ hostname: "127.0.0.1", port });
+        try {
+          await Deno.startTls(untrusted, { hostname: "localhost" });
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    )
+    .env("PORT", port.to_string())
+    .env("CA", tls::path("ca.pem")));
+    assert_eq!(
+        lines(stdout),
+        ["[object TlsConn]", "secret", "BadResource", "InvalidData"]
+    );
+}
+
+#[test]
+fn tls_listener() {
+    let stdout = run(mdeno(
+        r#"
+        const read = (name) => Deno.readTextFileSync(Deno.env.get(name));
+        const listener = Deno.listenTls({
+          hostname: "127.0.0.1",
+          port: 0,
+          cert: read("CERT"),
+          key: read("KEY"),
+        });
+        const served = (async () => {
+          const conn = await listener.accept();
+          const buffer = new Uint8Array(16);
+          const n = await conn.read(buffer);
+          await conn.write(buffer.subarray(0, n));
+          conn.close();
+        })();
+
+        const conn = await Deno.connectTls({
+          hostname: "localhost",
+          port: listener.addr.port,
+          caCerts: [read("CA")],
+        });
+        await conn.write(new TextEncoder().encode("both ends"));
+        const buffer = new Uint8Array(16);
+        const n = await conn.read(buffer);
+        console.log(new TextDecoder().decode(buffer.subarray(0, n)));
+        await served;
+        conn.close();
+        listener.close();
+        "#,
+    )
+    .env("CA", tls::path("ca.pem"))
+    .env("CERT", tls::path("server.pem"))
+    .env("KEY", tls::path("server-key.pem")));
+
+    assert_eq!(lines(stdout), ["both ends"]);
+}
+
+#[test]
+fn datagrams() {
+    let stdout = run(&mut mdeno(
+        r#"
+        const a = Deno.listenDatagram({ transport: "udp", hostname: "127.0.0.1", port: 0 });
+        const b = Deno.listenDatagram({ transport: "udp", hostname: "127.0.0.1", port: 0 });
+        console.log(String(a), a.addr.transport, a.addr.port > 0);
+
+        const sent = await a.send(new TextEncoder().encode("ping"), b.addr);
+        const [data, from] = await b.receive();
+        console.log(sent, new TextDecoder().decode(data), from.port === a.addr.port);
+
+        // Datagrams larger than the buffer are truncated
+        await b.send(new Uint8Array([1, 2, 3]), a.addr);
+        const [head] = await a.receive(new Uint8Array(2));
+        console.log(head.join(","));
+
+        let resolveThree;
+        const three = new Promise((resolve) => resolveThree = resolve);
+        const received = (async () => {
+          const payloads = [];
+          for await (const [data] of b) {
+            payloads.push(data[0]);
+            if (payloads.length === 3) resolveThree();
+          }
+          return payloads;
+        })();
+        for (const byte of [1, 2, 3]) await a.send(new Uint8Array([byte]), b.addr);
+        await three;
+        // Closing interrupts the pending receive, which ends the iteration
+        b.close();
+        console.log((await received).join(","));
+
+        const pending = a.receive();
+        a.close();
+        try {
+          await pending;
+        } catch (error) {
+          console.log(error.name);
+        }
+        "#,
+    ));
+
+    assert_eq!(
+        lines(stdout),
+        [
+            "[object DatagramConn] udp true",
+            "4 ping true",
+            "1,2",
+            "1,2,3",
+            "Interrupted"
+        ]
+    );
+}
@@ -0,0 +1,135 @@
+//! Deno.serve, driven by plain HTTP/1.1 requests over loopback.
+
+mod common;
+
+use common::{TIMEOUT, mdeno, read_head, spawn};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+const SERVER: &str = r#"
+const server = Deno.serve({
+  port: 0,
+  onListen({ port }) {
+    console.log(port);
+  },
+  onError(error) {
+    if (error.message === "handled") {
+      return new Response("handled", { status: 418 });
+    }
+    return "not a response";
+  },
+}, async (req) => {
+  const { pathname } = new URL(req.url);
+  switch (pathname) {
+    case "/echo":
+      return new Response(await req.text(), {
+        headers: { "x-method": req.method },
+      });
+    case "/stream":
+      return new Response(new ReadableStream({
+        start(controller) {
+          controller.enqueue(new TextEncoder().encode("one,"));
+          controller.enqueue(new TextEncoder().encode("two"));
+          controller.close();
+        },
+      }));
+    case "/handled":
+      throw new Error("handled");
+    case "/unhandled":
+      throw new Error("unhandled");
+    case "/shutdown":
+      queueMicrotask(() => server.shutdown());
+      return new Response("bye");
+    default:
+      return new Response(`hello ${pathname}`);
+  }
+});
+await server.finished;
+console.log("finished");
+"#;
+
+/// Send `request` and read the response up to the end of the connection.
+/// Returns its head and its body.
+fn request(port: u16, request: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    stream.write_all(request.as_bytes()).unwrap();
+    let head = read_head(&mut stream);
+    let mut body = String::new();
+    stream.read_to_string(&mut body).unwrap();
+    (head, body)
+}
+
+fn get(port: u16, path: &str) -> (String, String) {
+    request(
+        port,
+        &format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        ),
+    )
+}
+
+/// Decode a chunked body.
+fn dechunk(mut body: &str) -> String {
+    let mut decoded = String::new();
+    while let Some((size, rest)) = body.split_once("\r\n") {
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        decoded.push_str(&rest[..size]);
+        body = &rest[size + 2..];
+    }
+    decoded
+}
+
+#[test]
+fn serve_answers_requests_until_shutdown() {
+    let (server, port) = spawn(&mut mdeno(SERVER));
+    let port: u16 = port.parse().unwrap();
+
+    let (head, body) = get(port, "/hi");
+    assert!(head.starts_with("HTTP/1.1 200 OK\r\n"), "{}", head);
+    assert_eq!(body, "hello /hi");
+
+    let (head, body) = request(
+        port,
+        "POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+    );
+    assert!(head.contains("x-method: POST\r\n"), "{}", head);
+    assert_eq!(body, "hello");
+
+    let (head, body) = get(port, "/stream");
+    assert!(head.contains("transfer-encoding: chunked\r\n"), "{}", head);
+    assert_eq!(dechunk(&body), "one,two");
+
+    let (_, body) = get(port, "/shutdown");
+    assert_eq!(body, "bye");
+
+    assert_eq!(server.finish().trim(), "finished");
+}
+
+#[test]
+fn serve_survives_handler_errors() {
+    let (server, port) = spawn(&mut mdeno(SERVER));
+    let port: u16 = port.parse().unwrap();
+
+    let (head, body) = get(port, "/handled");
+    assert!(head.starts_with("HTTP/1.1 418 "), "{}", head);
+    assert_eq!(body, "handled");
+
+    // onError returning something else than a Response falls back to a 500
+    for _ in 0..2 {
+        let (head, body) = get(port, "/unhandled");
+        assert!(head.starts_with("HTTP/1.1 500 "), "{}", head);
+        assert_eq!(body, "Internal Server Error");
+    }
+
+    let (_, body) = get(port, "/after");
+    assert_eq!(body, "hello /after");
+
+    get(port, "/shutdown");
+    // Errors are logged before
+    assert_eq!(server.finish().lines().last(), Some("finished"));
+}
@@ -0,0 +1,111 @@
+//! WebSocket, against an echo server on loopback.
+
+mod common;
+
+use common::{mdeno, run};
+use std::net::TcpListener;
+use std::thread;
+use tungstenite::handshake::server::{Request, Response};
+
+/// Echo the messages of one WebSocket connection, agreeing to the first
+/// subprotocol offered. Returns the port.
+fn echo_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut socket =
+            tungstenite::accept_hdr(stream, |request: &Request, mut response: Response| {
+                let protocol = request
+                    .headers()
+                    .get("sec-websocket-protocol")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.split(',').next())
+                    .map(|protocol| protocol.trim().parse().unwrap());
+                if let Some(protocol) = protocol {
+                    response
+                        .headers_mut()
+                        .insert("sec-websocket-protocol", protocol);
+                }
+                Ok(response)
+            })
+            .unwrap();
+        // Close frames are answered by tungstenite, which then ends the loop
+        while let Ok(message) = socket.read() {
+            if message.is_text() || message.is_binary() {
+                socket.send(message).unwrap();
+            }
+        }
+    });
+    port
+}
+
+#[test]
+fn websocket_echo() {
+    let port = echo_server();
+    let stdout = run(mdeno(
+        r#"
+        const ws = new WebSocket(
+          `ws://127.0.0.1:${Deno.env.get("PORT")}/echo`,
+          ["chat", "superchat"],
+        );
+        ws.binaryType = "arraybuffer";
+        const messages = [];
+        const closed = new Promise((resolve) => {
+          ws.onclose = (event) => resolve(event);
+        });
+        ws.onmessage = (event) => {
+          messages.push(event.data);
+          if (messages.length === 2) ws.close(4000, "done");
+        };
+        await new Promise((resolve) => ws.onopen = resolve);
+        console.log("open", ws.protocol, ws.readyState);
+        ws.send("hello");
+        ws.send(new Uint8Array([1, 2, 3]));
+        const event = await closed;
+        console.log("text", messages[0]);
+        console.log("binary", [...new Uint8Array(messages[1])].join(","));
+        console.log("close", event.code, event.reason, event.wasClean);
+        console.log("state", ws.readyState);
+        "#,
+    )
+    .env("PORT", port.to_string()));
+
+    assert_eq!(
+        stdout.lines().collect::<Vec<_>>(),
+        [
+            "open chat 1",
+            "text hello",
+            "binary 1,2,3",
+            "close 4000 done true",
+            "state 3",
+        ]
+    );
+}
+
+#[test]
+fn websocket_connection_refused() {
+    // Nothing listens on the port once the listener is dropped
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let stdout = run(mdeno(
+        r#"
+        const ws = new WebSocket(`ws://127.0.0.1:${Deno.env.get("PORT")}/`);
+        const events = [];
+        ws.onerror = (event) => events.push(event.type);
+        await new Promise((resolve) => {
+          ws.onclose = (event) => {
+            events.push(`close ${event.code} ${event.wasClean}`);
+            resolve();
+          };
+        });
+        console.log(events.join(", "));
+        "#,
+    )
+    .env("PORT", port.to_string()));
+
+    assert_eq!(stdout.trim(), "error, close 1006 false");
+}